@@ -1,30 +1,166 @@
 #!/bin/bash
 
+# Inputs may contain globs for meow-coverage, never expand them in the shell
+set -f
+
 echo MEOWCOV_SOURCE_PREFIX = $MEOWCOV_SOURCE_PREFIX
 echo MEOWCOV_REPO_NAME = $MEOWCOV_REPO_NAME
 echo MEOWCOV_COMMIT_ID = $MEOWCOV_COMMIT_ID
 echo MEOWCOV_NEW_LCOV = $MEOWCOV_NEW_LCOV
 echo MEOWCOV_OLD_LCOV = $MEOWCOV_OLD_LCOV
+echo MEOWCOV_BASELINE_ARTIFACT = $MEOWCOV_BASELINE_ARTIFACT
+echo MEOWCOV_UPLOAD_ARTIFACT = $MEOWCOV_UPLOAD_ARTIFACT
+echo MEOWCOV_FLAG = $MEOWCOV_FLAG
+echo MEOWCOV_OUTPUT_JSON = $MEOWCOV_OUTPUT_JSON
+echo MEOWCOV_UNINSTRUMENTED_PATHS = $MEOWCOV_UNINSTRUMENTED_PATHS
+echo MEOWCOV_COUNT_UNINSTRUMENTED = $MEOWCOV_COUNT_UNINSTRUMENTED
+echo MEOWCOV_REQUIRE_PATCH_COVERAGE = $MEOWCOV_REQUIRE_PATCH_COVERAGE
+echo MEOWCOV_CARRYFORWARD_FROM = $MEOWCOV_CARRYFORWARD_FROM
+echo MEOWCOV_TREND_FROM = $MEOWCOV_TREND_FROM
+echo MEOWCOV_LINKS = $MEOWCOV_LINKS
+echo MEOWCOV_CACHE_DIR = $MEOWCOV_CACHE_DIR
 echo MEOWCOV_PR_NUMBER = $MEOWCOV_PR_NUMBER
 echo MEOWCOV_BRANCH = $MEOWCOV_BRANCH
 echo MEOWCOV_COVERAGE_REPO = $MEOWCOV_COVERAGE_REPO
 echo MEOWCOV_COVERAGE_TEAM = $MEOWCOV_COVERAGE_TEAM
+echo MEOWCOV_TEAM_SOURCE = $MEOWCOV_TEAM_SOURCE
+echo MEOWCOV_PUBLISH_PAYLOAD = $MEOWCOV_PUBLISH_PAYLOAD
+echo MEOWCOV_PUBLISH_FROM_ARTIFACT = $MEOWCOV_PUBLISH_FROM_ARTIFACT
 echo MEOWCOV_REBUILD_RECORDS = $MEOWCOV_REBUILD_RECORDS
+echo MEOWCOV_PROVIDER = $MEOWCOV_PROVIDER
+echo MEOWCOV_BASE_URL = $MEOWCOV_BASE_URL
+echo MEOWCOV_PERCENTAGE_PRECISION = $MEOWCOV_PERCENTAGE_PRECISION
+echo MEOWCOV_PERCENTAGE_ROUNDING = $MEOWCOV_PERCENTAGE_ROUNDING
 
+# Anything not passed explicitly is read from the GitHub Actions environment
+MEOWCOV_GLOBAL_ARGS="--auto-context --github-token $MEOWCOV_GITHUB_TOKEN"
+if [[ -n $MEOWCOV_REPO_NAME ]]
+then
+    MEOWCOV_GLOBAL_ARGS="$MEOWCOV_GLOBAL_ARGS --repo-name $MEOWCOV_REPO_NAME"
+fi
+if [[ -n $MEOWCOV_COVERAGE_REPO_TOKEN ]]
+then
+    MEOWCOV_GLOBAL_ARGS="$MEOWCOV_GLOBAL_ARGS --coverage-repo-token $MEOWCOV_COVERAGE_REPO_TOKEN"
+fi
+if [[ -n $MEOWCOV_PROVIDER ]]
+then
+    MEOWCOV_GLOBAL_ARGS="$MEOWCOV_GLOBAL_ARGS --provider $MEOWCOV_PROVIDER"
+fi
+if [[ -n $MEOWCOV_BASE_URL ]]
+then
+    MEOWCOV_GLOBAL_ARGS="$MEOWCOV_GLOBAL_ARGS --base-url $MEOWCOV_BASE_URL"
+fi
+if [[ -n $MEOWCOV_PERCENTAGE_PRECISION ]]
+then
+    MEOWCOV_GLOBAL_ARGS="$MEOWCOV_GLOBAL_ARGS --percentage-precision $MEOWCOV_PERCENTAGE_PRECISION"
+fi
+if [[ -n $MEOWCOV_PERCENTAGE_ROUNDING ]]
+then
+    MEOWCOV_GLOBAL_ARGS="$MEOWCOV_GLOBAL_ARGS --percentage-rounding $MEOWCOV_PERCENTAGE_ROUNDING"
+fi
 
+MEOWCOV_RUN_ARGS=""
+for MEOWCOV_LCOV in $MEOWCOV_NEW_LCOV
+do
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --new-lcov-file $MEOWCOV_LCOV"
+done
+if [[ -n $MEOWCOV_SOURCE_PREFIX ]]
+then
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --source-prefix $MEOWCOV_SOURCE_PREFIX"
+fi
+if [[ -n $MEOWCOV_COMMIT_ID ]]
+then
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --commit-id $MEOWCOV_COMMIT_ID"
+fi
+if [[ $MEOWCOV_UPLOAD_ARTIFACT == true ]]
+then
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --upload-artifact"
+fi
+if [[ -n $MEOWCOV_FLAG ]]
+then
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --flag $MEOWCOV_FLAG"
+fi
+if [[ -n $MEOWCOV_OUTPUT_JSON ]]
+then
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --output-json $MEOWCOV_OUTPUT_JSON"
+fi
+for MEOWCOV_GLOB in $MEOWCOV_UNINSTRUMENTED_PATHS
+do
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --uninstrumented-paths $MEOWCOV_GLOB"
+done
+if [[ $MEOWCOV_COUNT_UNINSTRUMENTED == true ]]
+then
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --count-uninstrumented"
+fi
+if [[ -n $MEOWCOV_LINKS ]]
+then
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --links $MEOWCOV_LINKS"
+fi
+if [[ $MEOWCOV_CHANGED_ONLY == true ]]
+then
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --changed-only"
+fi
+if [[ -n $MEOWCOV_PUBLISH_PAYLOAD ]]
+then
+    MEOWCOV_RUN_ARGS="$MEOWCOV_RUN_ARGS --publish-payload $MEOWCOV_PUBLISH_PAYLOAD"
+fi
+MEOWCOV_PR_ARGS=""
+if [[ -n $MEOWCOV_PR_NUMBER ]]
+then
+    MEOWCOV_PR_ARGS="--pr-number $MEOWCOV_PR_NUMBER"
+fi
+if [[ $MEOWCOV_BASELINE_ARTIFACT == true ]]
+then
+    MEOWCOV_PR_ARGS="$MEOWCOV_PR_ARGS --baseline-artifact"
+fi
+if [[ -n $MEOWCOV_REQUIRE_PATCH_COVERAGE ]]
+then
+    MEOWCOV_PR_ARGS="$MEOWCOV_PR_ARGS --require-patch-coverage $MEOWCOV_REQUIRE_PATCH_COVERAGE"
+fi
+if [[ -n $MEOWCOV_CARRYFORWARD_FROM ]]
+then
+    MEOWCOV_PR_ARGS="$MEOWCOV_PR_ARGS --carryforward-from $MEOWCOV_CARRYFORWARD_FROM"
+fi
+if [[ -n $MEOWCOV_TREND_FROM ]]
+then
+    MEOWCOV_PR_ARGS="$MEOWCOV_PR_ARGS --trend-from $MEOWCOV_TREND_FROM"
+fi
+if [[ -n $MEOWCOV_CACHE_DIR ]]
+then
+    MEOWCOV_PR_ARGS="$MEOWCOV_PR_ARGS --cache-dir $MEOWCOV_CACHE_DIR"
+fi
+MEOWCOV_BRANCH_ARGS=""
+if [[ -n $MEOWCOV_BRANCH ]]
+then
+    MEOWCOV_BRANCH_ARGS="--branch $MEOWCOV_BRANCH"
+fi
+MEOWCOV_TEAM_ARGS=""
+if [[ -n $MEOWCOV_COVERAGE_TEAM ]]
+then
+    MEOWCOV_TEAM_ARGS="--coverage-team $MEOWCOV_COVERAGE_TEAM"
+fi
+if [[ -n $MEOWCOV_TEAM_SOURCE ]]
+then
+    MEOWCOV_TEAM_ARGS="$MEOWCOV_TEAM_ARGS --team-source $MEOWCOV_TEAM_SOURCE"
+fi
+
+# If a payload is provided, post what a pull request run from a fork computed
+if [[ -n $MEOWCOV_PUBLISH_FROM_ARTIFACT ]]
+then
+    meow-coverage $MEOWCOV_GLOBAL_ARGS publish-from-artifact --payload $MEOWCOV_PUBLISH_FROM_ARTIFACT
 # If a value is provided for the records directory, assume we want to rebuild records
-if [[ -n $MEOWCOV_REBUILD_RECORDS ]]
+elif [[ -n $MEOWCOV_REBUILD_RECORDS ]]
 then
-    meow-coverage --github-token $MEOWCOV_GITHUB_TOKEN --repo-name $MEOWCOV_REPO_NAME tracking --coverage-repo-name $MEOWCOV_COVERAGE_REPO rebuild --records $MEOWCOV_REBUILD_RECORDS --branch $MEOWCOV_BRANCH
-elif [[ -n $MEOWCOV_COVERAGE_REPO ]] || [[ -n $MEOWCOV_COVERAGE_TEAM ]] # Otherwise if both the coverage repo and coverage team are provided assume we are running on a commit where the report will be gathered
+    meow-coverage $MEOWCOV_GLOBAL_ARGS tracking --coverage-repo-name $MEOWCOV_COVERAGE_REPO rebuild --records $MEOWCOV_REBUILD_RECORDS --branch $MEOWCOV_BRANCH
+elif [[ -n $MEOWCOV_COVERAGE_REPO ]] # Otherwise if the coverage repo is provided assume we are running on a commit where the report will be gathered
 then
-    meow-coverage --github-token $MEOWCOV_GITHUB_TOKEN --repo-name $MEOWCOV_REPO_NAME coverage-run --source-prefix $MEOWCOV_SOURCE_PREFIX --commit-id $MEOWCOV_COMMIT_ID --new-lcov-file $MEOWCOV_NEW_LCOV push-with-report --coverage-repo $MEOWCOV_COVERAGE_REPO --coverage-team $MEOWCOV_COVERAGE_TEAM --branch $MEOWCOV_BRANCH
-elif [[ -z $MEOWCOV_PR_NUMBER ]] # Otherwise if no PR number is specified assume we will just run on a commit without gathering the report
+    meow-coverage $MEOWCOV_GLOBAL_ARGS coverage-run $MEOWCOV_RUN_ARGS push-with-report --coverage-repo $MEOWCOV_COVERAGE_REPO $MEOWCOV_TEAM_ARGS $MEOWCOV_BRANCH_ARGS
+elif [[ -z $MEOWCOV_PR_NUMBER ]] && [[ $GITHUB_EVENT_NAME != pull_request* ]] # Otherwise if not running for a PR assume we will just run on a commit without gathering the report
 then
-    meow-coverage --github-token $MEOWCOV_GITHUB_TOKEN --repo-name $MEOWCOV_REPO_NAME coverage-run --source-prefix $MEOWCOV_SOURCE_PREFIX --commit-id $MEOWCOV_COMMIT_ID --new-lcov-file $MEOWCOV_NEW_LCOV push
-elif [[ -z $MEOWCOV_OLD_LCOV ]] # Otherwise as a PR number was specified we are running on a pull request, check if we have an old LCOV file to do a comparison with
+    meow-coverage $MEOWCOV_GLOBAL_ARGS coverage-run $MEOWCOV_RUN_ARGS push
+elif [[ -z $MEOWCOV_OLD_LCOV ]] # Otherwise we are running on a pull request, check if we have an old LCOV file to do a comparison with
 then
-    meow-coverage --github-token $MEOWCOV_GITHUB_TOKEN --repo-name $MEOWCOV_REPO_NAME coverage-run --source-prefix $MEOWCOV_SOURCE_PREFIX --commit-id $MEOWCOV_COMMIT_ID --new-lcov-file $MEOWCOV_NEW_LCOV pull-request --pr-number $MEOWCOV_PR_NUMBER
+    meow-coverage $MEOWCOV_GLOBAL_ARGS coverage-run $MEOWCOV_RUN_ARGS pull-request $MEOWCOV_PR_ARGS
 else
-    meow-coverage --github-token $MEOWCOV_GITHUB_TOKEN --repo-name $MEOWCOV_REPO_NAME coverage-run --source-prefix $MEOWCOV_SOURCE_PREFIX --commit-id $MEOWCOV_COMMIT_ID --new-lcov-file $MEOWCOV_NEW_LCOV pull-request --pr-number $MEOWCOV_PR_NUMBER --old-lcov-file $MEOWCOV_OLD_LCOV
+    meow-coverage $MEOWCOV_GLOBAL_ARGS coverage-run $MEOWCOV_RUN_ARGS pull-request $MEOWCOV_PR_ARGS --old-lcov-file $MEOWCOV_OLD_LCOV
 fi
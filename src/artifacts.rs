@@ -0,0 +1,337 @@
+//! Exchange Lcov reports between runs via GitHub Actions artifacts: push runs
+//! upload their report as the artifact of the branch, so pull request runs
+//! against that branch can download it as the baseline
+
+use std::io::{Cursor, Read, Write};
+
+use base64::Engine;
+use octocrab::{models::workflows::WorkflowListArtifact, params::actions::ArchiveFormat, Octocrab};
+use reqwest::header::AUTHORIZATION;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+use crate::MeowCoverageError;
+
+/// Name of the Lcov file inside the artifact
+const ARTIFACT_FILE_NAME: &str = "lcov.info";
+
+/// Service of the artifact API used by `actions/upload-artifact@v4`
+const ARTIFACT_SERVICE: &str = "twirp/github.actions.results.api.v1.ArtifactService";
+
+/// Response of the list artifacts API
+#[derive(Debug, Deserialize)]
+struct ArtifactList {
+	/// Artifacts, newest first
+	artifacts: Vec<WorkflowListArtifact>,
+}
+
+/// An artifact in the response of the list artifacts API, with the workflow
+/// run that uploaded it
+#[derive(Debug, Deserialize)]
+struct RunArtifact {
+	/// Artifact ID
+	id: u64,
+	/// Whether the artifact expired and can no longer be downloaded
+	expired: bool,
+	/// Creation time in RFC 3339 format
+	created_at: String,
+	/// Workflow run that uploaded the artifact
+	workflow_run: Option<ArtifactWorkflowRun>,
+}
+
+/// Workflow run of a [RunArtifact]
+#[derive(Debug, Deserialize)]
+struct ArtifactWorkflowRun {
+	/// Workflow run ID
+	id: u64,
+	/// Commit the run was triggered for
+	head_sha: String,
+}
+
+/// Response of the list artifacts API including the workflow runs
+#[derive(Debug, Deserialize)]
+struct RunArtifactList {
+	/// Artifacts, newest first
+	artifacts: Vec<RunArtifact>,
+}
+
+/// Lcov report uploaded as the artifact of a branch by a past workflow run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoricalLcov {
+	/// Commit the workflow run was triggered for
+	pub commit: String,
+	/// ID of the workflow run
+	pub run_id: u64,
+	/// Unix timestamp the artifact was uploaded at
+	pub timestamp: i64,
+	/// Lcov report
+	pub lcov: Vec<u8>,
+}
+
+/// Claims of the `ACTIONS_RUNTIME_TOKEN`
+#[derive(Debug, Deserialize)]
+struct RuntimeClaims {
+	/// Space separated scopes, including
+	/// `Actions.Results:<run backend ID>:<job backend ID>`
+	scp: String,
+}
+
+/// Response of `CreateArtifact`
+#[derive(Debug, Deserialize)]
+struct CreateArtifactResponse {
+	/// Whether the artifact was created
+	ok: bool,
+	/// URL to upload the artifact archive to
+	#[serde(alias = "signedUploadUrl")]
+	signed_upload_url: String,
+}
+
+/// Response of `FinalizeArtifact`
+#[derive(Debug, Deserialize)]
+struct FinalizeArtifactResponse {
+	/// Whether the artifact was finalized
+	ok: bool,
+}
+
+/// Name of the artifact holding the Lcov report of a branch
+#[must_use]
+pub fn artifact_name(branch: &str) -> String {
+	format!("meow-coverage-{}", branch.trim_start_matches("refs/heads/").replace('/', "-"))
+}
+
+/// Download the Lcov report of the newest unexpired artifact of a branch,
+/// returns [None] if there is none
+pub async fn download_lcov_artifact(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+) -> Result<Option<Vec<u8>>, MeowCoverageError> {
+	let route = format!("/repos/{}/{}/actions/artifacts", owner, repo);
+	let list: ArtifactList = client
+		.get(route, Some(&[("name", artifact_name(branch).as_str()), ("per_page", "10")]))
+		.await?;
+
+	let Some(artifact) = list.artifacts.into_iter().find(|artifact| !artifact.expired) else {
+		return Ok(None);
+	};
+
+	let archive =
+		client.actions().download_artifact(owner, repo, artifact.id, ArchiveFormat::Zip).await?;
+	Ok(Some(read_lcov_archive(archive.to_vec())?))
+}
+
+/// Download the Lcov reports of all unexpired artifacts of a branch uploaded
+/// since the unix timestamp `since`, oldest first
+pub async fn download_lcov_artifacts_since(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+	since: i64,
+) -> Result<Vec<HistoricalLcov>, MeowCoverageError> {
+	let route = format!("/repos/{}/{}/actions/artifacts", owner, repo);
+	let name = artifact_name(branch);
+
+	let mut reports = Vec::new();
+	for page in 1_u32.. {
+		let page = page.to_string();
+		let list: RunArtifactList = client
+			.get(
+				&route,
+				Some(&[("name", name.as_str()), ("per_page", "100"), ("page", page.as_str())]),
+			)
+			.await?;
+		let count = list.artifacts.len();
+
+		let mut reached_since = false;
+		for artifact in list.artifacts {
+			let Some(timestamp) = parse_timestamp(&artifact.created_at) else {
+				continue;
+			};
+			if timestamp < since {
+				reached_since = true;
+				break;
+			}
+			let Some(run) = artifact.workflow_run.filter(|_| !artifact.expired) else {
+				continue;
+			};
+
+			let archive = client
+				.actions()
+				.download_artifact(owner, repo, artifact.id.into(), ArchiveFormat::Zip)
+				.await?;
+			reports.push(HistoricalLcov {
+				commit: run.head_sha,
+				run_id: run.id,
+				timestamp,
+				lcov: read_lcov_archive(archive.to_vec())?,
+			});
+		}
+
+		if reached_since || count < 100 {
+			break;
+		}
+	}
+
+	reports.reverse();
+	Ok(reports)
+}
+
+/// Read the Lcov report out of the zip archive of an artifact
+fn read_lcov_archive(archive: Vec<u8>) -> Result<Vec<u8>, MeowCoverageError> {
+	let mut archive = ZipArchive::new(Cursor::new(archive))?;
+	let mut file = archive.by_name(ARTIFACT_FILE_NAME)?;
+
+	let mut lcov = Vec::new();
+	file.read_to_end(&mut lcov)?;
+
+	Ok(lcov)
+}
+
+/// Parse a UTC date (`YYYY-MM-DD`) or time in RFC 3339 format
+/// (`YYYY-MM-DDTHH:MM:SSZ`, as returned by the GitHub API) into a unix
+/// timestamp
+#[must_use]
+pub fn parse_timestamp(value: &str) -> Option<i64> {
+	let (date, time) = value.split_once('T').unwrap_or((value, "00:00:00Z"));
+
+	let mut date = date.splitn(3, '-').map(str::parse::<u16>);
+	let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+	let date = time::Date::from_calendar_date(
+		year.into(),
+		time::Month::try_from(u8::try_from(month).ok()?).ok()?,
+		u8::try_from(day).ok()?,
+	)
+	.ok()?;
+
+	let mut time = time.strip_suffix('Z')?.splitn(3, ':').map(str::parse::<u8>);
+	let time =
+		time::Time::from_hms(time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?).ok()?;
+
+	Some(time::PrimitiveDateTime::new(date, time).assume_utc().unix_timestamp())
+}
+
+/// Upload an Lcov report as the artifact of a branch, this requires the
+/// `ACTIONS_RUNTIME_TOKEN` and `ACTIONS_RESULTS_URL` which GitHub Actions only
+/// passes to actions
+pub async fn upload_lcov_artifact(branch: &str, lcov: &[u8]) -> Result<(), MeowCoverageError> {
+	let (Ok(token), Ok(results_url)) =
+		(std::env::var("ACTIONS_RUNTIME_TOKEN"), std::env::var("ACTIONS_RESULTS_URL"))
+	else {
+		return Err(MeowCoverageError::MissingActionsRuntime);
+	};
+	let (run_id, job_id) = backend_ids(&token)?;
+	let service = format!("{}/{}", results_url.trim_end_matches('/'), ARTIFACT_SERVICE);
+	let name = artifact_name(branch);
+	let client = reqwest::Client::new();
+
+	let created: CreateArtifactResponse = client
+		.post(format!("{}/CreateArtifact", service))
+		.header(AUTHORIZATION, format!("Bearer {}", token))
+		.json(&serde_json::json!({
+			"workflow_run_backend_id": run_id,
+			"workflow_job_run_backend_id": job_id,
+			"name": name,
+			"version": 4,
+		}))
+		.send()
+		.await?
+		.error_for_status()?
+		.json()
+		.await?;
+	if !created.ok {
+		return Err(MeowCoverageError::Provider(format!("Failed to create artifact {}", name)));
+	}
+
+	let archive = {
+		let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+		writer.start_file(ARTIFACT_FILE_NAME, FileOptions::default())?;
+		writer.write_all(lcov)?;
+		writer.finish()?.into_inner()
+	};
+	let hash = hex::encode(Sha256::digest(&archive));
+	let size = archive.len();
+
+	client
+		.put(created.signed_upload_url)
+		.header("x-ms-blob-type", "BlockBlob")
+		.body(archive)
+		.send()
+		.await?
+		.error_for_status()?;
+
+	let finalized: FinalizeArtifactResponse = client
+		.post(format!("{}/FinalizeArtifact", service))
+		.header(AUTHORIZATION, format!("Bearer {}", token))
+		.json(&serde_json::json!({
+			"workflow_run_backend_id": run_id,
+			"workflow_job_run_backend_id": job_id,
+			"name": name,
+			"size": size.to_string(),
+			"hash": format!("sha256:{}", hash),
+		}))
+		.send()
+		.await?
+		.error_for_status()?
+		.json()
+		.await?;
+	match finalized.ok {
+		true => Ok(()),
+		false => Err(MeowCoverageError::Provider(format!("Failed to finalize artifact {}", name))),
+	}
+}
+
+/// Workflow run and job backend IDs from the scopes of an
+/// `ACTIONS_RUNTIME_TOKEN`
+fn backend_ids(token: &str) -> Result<(String, String), MeowCoverageError> {
+	let invalid = || MeowCoverageError::Provider(String::from("Invalid ACTIONS_RUNTIME_TOKEN"));
+
+	let payload = token.split('.').nth(1).ok_or_else(invalid)?;
+	let payload =
+		base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(payload).map_err(|_| invalid())?;
+	let claims: RuntimeClaims = serde_json::from_slice(&payload)?;
+
+	claims
+		.scp
+		.split(' ')
+		.find_map(|scope| {
+			let mut parts = scope.strip_prefix("Actions.Results:")?.split(':');
+			Some((String::from(parts.next()?), String::from(parts.next()?)))
+		})
+		.ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+	use base64::Engine;
+
+	use super::{artifact_name, backend_ids, parse_timestamp};
+
+	#[test]
+	fn test_artifact_name() {
+		assert_eq!(artifact_name("main"), "meow-coverage-main");
+		assert_eq!(artifact_name("refs/heads/release/1.0"), "meow-coverage-release-1.0");
+	}
+
+	#[test]
+	fn test_backend_ids() {
+		let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+			.encode(r#"{"scp":"Actions.ExampleScope Actions.Results:run-id:job-id"}"#);
+		assert_eq!(
+			backend_ids(&format!("header.{}.signature", payload)).unwrap(),
+			(String::from("run-id"), String::from("job-id"))
+		);
+		assert!(backend_ids("header.e30.signature").is_err());
+	}
+
+	#[test]
+	fn test_parse_timestamp() {
+		assert_eq!(parse_timestamp("2023-11-14"), Some(1_699_920_000));
+		assert_eq!(parse_timestamp("2023-11-14T22:13:20Z"), Some(1_700_000_000));
+		assert_eq!(parse_timestamp("2023-13-14"), None);
+		assert_eq!(parse_timestamp("2023-11-14T22:13:20+01:00"), None);
+		assert_eq!(parse_timestamp("yesterday"), None);
+	}
+}
@@ -0,0 +1,161 @@
+//! Configuration file of the analysed repository, `.meow-coverage.toml` in the
+//! working directory by default or fetched from the analysed commit, for
+//! settings that belong with the code rather than in workflow arguments
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{
+	coverage::{helpers::PathStrategy, options::LinkMode, owners::OwnerRule},
+	provider::VcsProvider,
+	tracking::Team,
+	MeowCoverageError,
+};
+
+/// Default path of the configuration file
+pub const CONFIG_FILE: &str = ".meow-coverage.toml";
+
+/// Configuration of the analysed repository
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepositoryConfig {
+	/// How Lcov paths are mapped to repository paths, for example
+	/// `paths = { strip_components = 3 }`. Command line arguments take
+	/// precedence
+	#[serde(default)]
+	pub paths: Option<PathStrategy>,
+	/// What the files and lines in the summary comments link to, for example
+	/// `links = "blob"`. Command line arguments take precedence
+	#[serde(default)]
+	pub links: Option<LinkMode>,
+	/// Owners of paths, as `[[owners]]` tables with the `paths` globs and
+	/// their `owners`
+	#[serde(default)]
+	pub owners: Vec<OwnerRule>,
+	/// Mention the [RepositoryConfig::owners] of files with untested changes
+	/// in the summary comments of pull requests
+	#[serde(default)]
+	pub mention_owners: bool,
+	/// Fail pull request runs if less than this percentage of the changed
+	/// lines is tested. Command line arguments take precedence
+	#[serde(default)]
+	pub require_patch_coverage: Option<f64>,
+	/// Globs of repository paths to drop from the reports, for example
+	/// `ignore = ["src/generated/**"]`
+	#[serde(default)]
+	pub ignore: Vec<String>,
+	/// Team the tracking records of the repository belong to, unless
+	/// `--coverage-team` or `--team-source` are passed
+	#[serde(default)]
+	pub team: Option<Team>,
+	/// Report solely via the commit status, without posting any comments
+	#[serde(default)]
+	pub status_only: bool,
+	/// Maximum number of review comments. Command line arguments take
+	/// precedence
+	#[serde(default)]
+	pub max_review_comments: Option<usize>,
+	/// Include suggested-test hints in review comments
+	#[serde(default)]
+	pub review_comment_hints: bool,
+}
+
+impl RepositoryConfig {
+	/// Parse the configuration from TOML
+	pub fn parse(config: &str) -> Result<Self, MeowCoverageError> {
+		Ok(toml::from_str(config)?)
+	}
+
+	/// Read the configuration file at `path`, an explicitly passed file must
+	/// exist while a missing default file is an empty configuration
+	pub fn load(path: Option<&Path>) -> Result<Self, MeowCoverageError> {
+		match path {
+			Some(path) => Self::parse(&std::fs::read_to_string(path)?),
+			None => match Path::new(CONFIG_FILE).exists() {
+				true => Self::parse(&std::fs::read_to_string(CONFIG_FILE)?),
+				false => Ok(Self::default()),
+			},
+		}
+	}
+
+	/// Read the configuration file at `path` like [RepositoryConfig::load],
+	/// or if no file is passed and there is no default file in the working
+	/// directory (for example without a checkout), fetch the default file of
+	/// `repo_name` (`OWNER/REPO`) at the analysed commit
+	pub async fn load_or_fetch(
+		path: Option<&Path>,
+		provider: &dyn VcsProvider,
+		repo_name: &str,
+		commit_sha: &str,
+	) -> Result<Self, MeowCoverageError> {
+		if path.is_some() || Path::new(CONFIG_FILE).exists() {
+			return Self::load(path);
+		}
+
+		let (owner, repo) =
+			repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+		match provider.get_file_at_commit(owner, repo, commit_sha, CONFIG_FILE).await? {
+			Some(content) => Self::parse(&String::from_utf8_lossy(&content)),
+			None => Ok(Self::default()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{RepositoryConfig, CONFIG_FILE};
+	use crate::{
+		coverage::{helpers::PathStrategy, options::LinkMode},
+		provider::{fake::FakeProvider, RepoFile},
+		tracking::Team,
+	};
+
+	#[test]
+	fn test_parse_config() {
+		let config = RepositoryConfig::parse(
+			"[paths.rewrite]\npattern = '^github\\.com/famedly/meow/'\nreplacement = ''\n",
+		)
+		.unwrap();
+		assert!(matches!(config.paths, Some(PathStrategy::Rewrite { .. })));
+
+		assert!(RepositoryConfig::parse("").unwrap().paths.is_none());
+		assert_eq!(
+			RepositoryConfig::parse("links = 'blob'\n").unwrap().links,
+			Some(LinkMode::Blob)
+		);
+		assert!(RepositoryConfig::parse("links = 'raw'\n").is_err());
+		assert!(RepositoryConfig::parse("[paths]\nsuffix = 'rs'\n").is_err());
+		assert!(RepositoryConfig::parse("unknown = true\n").is_err());
+
+		let config = RepositoryConfig::parse(
+			"mention_owners = true\n[[owners]]\npaths = ['crates/auth/**']\nowners = ['famedly/auth']\n",
+		)
+		.unwrap();
+		assert!(config.mention_owners);
+		assert_eq!(config.owners[0].owners, ["famedly/auth"]);
+	}
+
+	#[tokio::test]
+	async fn test_fetch_config() {
+		let provider = FakeProvider::default();
+		let config =
+			RepositoryConfig::load_or_fetch(None, &provider, "famedly/meow", "abc").await.unwrap();
+		assert!(config.ignore.is_empty());
+
+		provider.files.lock().unwrap().insert(
+			format!("famedly/meow/abc/{}", CONFIG_FILE),
+			RepoFile {
+				content: b"require_patch_coverage = 80.0\nignore = ['src/generated/**']\nteam = 'Workflow'\nstatus_only = true\nmax_review_comments = 5\nreview_comment_hints = true\n".to_vec(),
+				sha: String::new(),
+			},
+		);
+		let config =
+			RepositoryConfig::load_or_fetch(None, &provider, "famedly/meow", "abc").await.unwrap();
+		assert_eq!(config.require_patch_coverage, Some(80_f64));
+		assert_eq!(config.ignore, ["src/generated/**"]);
+		assert_eq!(config.team, Some(Team::Workflow));
+		assert!(config.status_only && config.review_comment_hints);
+		assert_eq!(config.max_review_comments, Some(5));
+	}
+}
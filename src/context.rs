@@ -0,0 +1,281 @@
+//! Run context read from the environment of a GitHub Actions job, so the
+//! repository, commit, pull request, and token don't have to be passed
+//! explicitly
+
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+
+use crate::MeowCoverageError;
+
+/// Head of a pull request in the event payload
+#[derive(Debug, Deserialize)]
+struct PullRequestHead {
+	/// Head commit SHA
+	sha: String,
+}
+
+/// Pull request in the event payload
+#[derive(Debug, Deserialize)]
+struct PullRequestPayload {
+	/// Pull request number
+	number: u64,
+	/// Head of the pull request
+	head: PullRequestHead,
+}
+
+/// Workflow run in the event payload
+#[derive(Debug, Deserialize)]
+struct WorkflowRunPayload {
+	/// Head commit SHA of the run
+	head_sha: String,
+}
+
+/// Repository in the event payload
+#[derive(Debug, Deserialize)]
+struct RepositoryPayload {
+	/// Default branch of the repository
+	default_branch: Option<String>,
+}
+
+/// Subset of the event payload at `GITHUB_EVENT_PATH`
+#[derive(Debug, Default, Deserialize)]
+struct EventPayload {
+	/// Pull request, for `pull_request` and `pull_request_target` events
+	pull_request: Option<PullRequestPayload>,
+	/// Commit the branch pointed to before a `push` event, all zeros for
+	/// new branches
+	before: Option<String>,
+	/// Repository the event happened in
+	repository: Option<RepositoryPayload>,
+	/// Run that triggered a `workflow_run` event
+	workflow_run: Option<WorkflowRunPayload>,
+	/// Inputs of `workflow_dispatch` events
+	#[serde(default)]
+	inputs: HashMap<String, serde_json::Value>,
+}
+
+/// Event that triggered a GitHub Actions run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitHubEvent {
+	/// Push to a branch
+	Push,
+	/// Pull request (`pull_request` or `pull_request_target`)
+	PullRequest,
+	/// Manually or API dispatched workflow, with its string inputs
+	WorkflowDispatch(HashMap<String, String>),
+	/// Any other event, by name
+	Other(String),
+}
+
+/// Context of a GitHub Actions run, every field is [None] if unavailable
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitHubContext {
+	/// Repository name in format `OWNER/REPO`
+	pub repo_name: Option<String>,
+	/// Commit being analysed, the head commit for pull requests rather than
+	/// the merge commit in `GITHUB_SHA`
+	pub commit_id: Option<String>,
+	/// Pull request number
+	pub pr_number: Option<u64>,
+	/// Branch the run is for
+	pub branch: Option<String>,
+	/// Base branch of the pull request
+	pub base_branch: Option<String>,
+	/// API token
+	pub token: Option<String>,
+	/// Event that triggered the run
+	pub event: Option<GitHubEvent>,
+	/// Commit the default branch pointed to before the push the run is for,
+	/// to detect renamed default branches, [None] for other branches, new
+	/// branches, and other events
+	pub default_branch_before: Option<String>,
+	/// Commit the branch pointed to before the push the run is for, [None]
+	/// for new branches and other events
+	pub push_before: Option<String>,
+	/// Head commit of the run that triggered a `workflow_run` event, [None]
+	/// for other events
+	pub workflow_run_head_sha: Option<String>,
+}
+
+impl GitHubContext {
+	/// Read the context from the environment of the process
+	pub fn from_env() -> Result<Self, MeowCoverageError> {
+		Self::from_vars(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+	}
+
+	/// Read the context from environment variables looked up with `var`
+	fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, MeowCoverageError> {
+		let payload = match var("GITHUB_EVENT_PATH") {
+			Some(path) if Path::new(&path).exists() => {
+				serde_json::from_slice::<EventPayload>(&std::fs::read(path)?)?
+			}
+			_ => EventPayload::default(),
+		};
+		let git_ref = var("GITHUB_REF");
+
+		let pr_number =
+			payload.pull_request.as_ref().map(|pull_request| pull_request.number).or_else(|| {
+				git_ref
+					.as_deref()?
+					.strip_prefix("refs/pull/")?
+					.split_once('/')
+					.and_then(|(number, _)| number.parse().ok())
+			});
+		let branch = var("GITHUB_HEAD_REF")
+			.or_else(|| git_ref.as_deref()?.strip_prefix("refs/heads/").map(String::from));
+
+		let push_before = payload
+			.before
+			.filter(|before| before.chars().any(|char| char != '0'))
+			.filter(|_| var("GITHUB_EVENT_NAME").as_deref() == Some("push"));
+		let default_branch_before = push_before.clone().filter(|_| {
+			let default_branch =
+				payload.repository.as_ref().and_then(|repo| repo.default_branch.as_deref());
+			default_branch.is_some() && default_branch == branch.as_deref()
+		});
+
+		let event = var("GITHUB_EVENT_NAME").map(|name| match name.as_str() {
+			"push" => GitHubEvent::Push,
+			"pull_request" | "pull_request_target" => GitHubEvent::PullRequest,
+			"workflow_dispatch" => GitHubEvent::WorkflowDispatch(
+				payload
+					.inputs
+					.iter()
+					.filter_map(|(name, value)| {
+						value.as_str().map(|value| (name.clone(), String::from(value)))
+					})
+					.collect(),
+			),
+			_ => GitHubEvent::Other(name),
+		});
+
+		Ok(Self {
+			repo_name: var("GITHUB_REPOSITORY"),
+			commit_id: payload
+				.pull_request
+				.map(|pull_request| pull_request.head.sha)
+				.or_else(|| var("GITHUB_SHA")),
+			pr_number,
+			branch,
+			base_branch: var("GITHUB_BASE_REF"),
+			token: var("GITHUB_TOKEN"),
+			event,
+			default_branch_before,
+			push_before,
+			workflow_run_head_sha: payload.workflow_run.map(|workflow_run| workflow_run.head_sha),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use super::{GitHubContext, GitHubEvent};
+
+	#[test]
+	fn test_context_from_vars() {
+		let event_path = std::env::temp_dir().join("meow-coverage-test-event.json");
+		std::fs::write(&event_path, r#"{"pull_request":{"number":42,"head":{"sha":"abcdef"}}}"#)
+			.unwrap();
+
+		let vars = HashMap::from([
+			("GITHUB_REPOSITORY", String::from("famedly/meow")),
+			("GITHUB_SHA", String::from("fedcba")),
+			("GITHUB_REF", String::from("refs/pull/42/merge")),
+			("GITHUB_HEAD_REF", String::from("feature")),
+			("GITHUB_BASE_REF", String::from("main")),
+			("GITHUB_EVENT_PATH", String::from(event_path.to_str().unwrap())),
+			("GITHUB_EVENT_NAME", String::from("pull_request")),
+		]);
+		assert_eq!(
+			GitHubContext::from_vars(|name| vars.get(name).cloned()).unwrap(),
+			GitHubContext {
+				repo_name: Some(String::from("famedly/meow")),
+				commit_id: Some(String::from("abcdef")),
+				pr_number: Some(42),
+				branch: Some(String::from("feature")),
+				base_branch: Some(String::from("main")),
+				token: None,
+				event: Some(GitHubEvent::PullRequest),
+				default_branch_before: None,
+				push_before: None,
+				workflow_run_head_sha: None,
+			}
+		);
+
+		let vars = HashMap::from([
+			("GITHUB_SHA", String::from("fedcba")),
+			("GITHUB_REF", String::from("refs/heads/main")),
+			("GITHUB_TOKEN", String::from("token")),
+		]);
+		assert_eq!(
+			GitHubContext::from_vars(|name| vars.get(name).cloned()).unwrap(),
+			GitHubContext {
+				repo_name: None,
+				commit_id: Some(String::from("fedcba")),
+				pr_number: None,
+				branch: Some(String::from("main")),
+				base_branch: None,
+				token: Some(String::from("token")),
+				event: None,
+				default_branch_before: None,
+				push_before: None,
+				workflow_run_head_sha: None,
+			}
+		);
+
+		let event_path = std::env::temp_dir().join("meow-coverage-test-dispatch-event.json");
+		std::fs::write(&event_path, r#"{"inputs":{"repo-name":"famedly/meow","branch":"main"}}"#)
+			.unwrap();
+		let vars = HashMap::from([
+			("GITHUB_EVENT_NAME", String::from("workflow_dispatch")),
+			("GITHUB_EVENT_PATH", String::from(event_path.to_str().unwrap())),
+		]);
+		assert_eq!(
+			GitHubContext::from_vars(|name| vars.get(name).cloned()).unwrap().event,
+			Some(GitHubEvent::WorkflowDispatch(HashMap::from([
+				(String::from("repo-name"), String::from("famedly/meow")),
+				(String::from("branch"), String::from("main")),
+			])))
+		);
+
+		let event_path = std::env::temp_dir().join("meow-coverage-test-push-event.json");
+		std::fs::write(
+			&event_path,
+			r#"{"before":"abcdef","repository":{"default_branch":"main"}}"#,
+		)
+		.unwrap();
+		let push_vars = |git_ref: &str| {
+			HashMap::from([
+				("GITHUB_EVENT_NAME", String::from("push")),
+				("GITHUB_EVENT_PATH", String::from(event_path.to_str().unwrap())),
+				("GITHUB_REF", String::from(git_ref)),
+			])
+		};
+		let vars = push_vars("refs/heads/main");
+		assert_eq!(
+			GitHubContext::from_vars(|name| vars.get(name).cloned()).unwrap().default_branch_before,
+			Some(String::from("abcdef"))
+		);
+		let vars = push_vars("refs/heads/feature");
+		let context = GitHubContext::from_vars(|name| vars.get(name).cloned()).unwrap();
+		assert_eq!(context.default_branch_before, None);
+		assert_eq!(context.push_before.as_deref(), Some("abcdef"));
+
+		let event_path = std::env::temp_dir().join("meow-coverage-test-workflow-run-event.json");
+		std::fs::write(&event_path, r#"{"workflow_run":{"id":7,"head_sha":"abcdef"}}"#).unwrap();
+		let vars = HashMap::from([
+			("GITHUB_EVENT_NAME", String::from("workflow_run")),
+			("GITHUB_EVENT_PATH", String::from(event_path.to_str().unwrap())),
+		]);
+		assert_eq!(
+			GitHubContext::from_vars(|name| vars.get(name).cloned())
+				.unwrap()
+				.workflow_run_head_sha
+				.as_deref(),
+			Some("abcdef")
+		);
+	}
+}
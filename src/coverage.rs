@@ -1,10 +1,25 @@
 //! This module groups everything needed for coverage analysis of a single run
 
-mod helpers;
-mod html;
-mod lcov;
+pub mod carryforward;
+pub mod changes;
+pub mod diff;
+pub mod exclusion;
+pub mod heatmap;
+pub mod helpers;
+pub mod html;
+pub mod issues;
+pub mod lcov;
+pub mod llvm_cov;
+pub mod options;
+pub mod output;
+pub mod owners;
+pub mod publish;
 mod pull;
 mod push;
+pub mod rust_exclusion;
+pub mod summary;
+pub mod trend;
+pub mod uninstrumented;
 
 pub use pull::*;
 pub use push::*;
@@ -0,0 +1,173 @@
+//! Carrying forward the coverage of files missing from a pull request report
+//! from the tracking records of the base branch, so that skipped test jobs do
+//! not make the total coverage crater
+
+use std::collections::HashSet;
+
+use super::{
+	helpers::{path_split, PathStrategy},
+	lcov::LcovStatistics,
+};
+use crate::{
+	metrics,
+	provider::VcsProvider,
+	tracking::{self, BranchCoverageRecordCollection},
+	MeowCoverageError,
+};
+
+/// Where the coverage of files missing from a report is carried forward from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Carryforward {
+	/// Tracking repository in format `OWNER/REPO`
+	pub coverage_repo: String,
+	/// Branch of the analysed repository whose latest record is carried
+	/// forward, usually the base branch of the PR
+	pub base_branch: String,
+}
+
+/// Coverage of the files carried forward into a report
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CarriedFiles {
+	/// Paths of the carried files, sorted
+	pub paths: Vec<String>,
+	/// Number of instrumented lines of the carried files
+	pub lines_found: u64,
+	/// Number of tested lines of the carried files
+	pub lines_hit: u64,
+}
+
+impl CarriedFiles {
+	/// Pick the files of the latest record of `collection` that are missing
+	/// from `statistics`. Files recorded without their number of instrumented
+	/// lines cannot be weighted and are skipped
+	#[must_use]
+	pub fn collect(
+		collection: &BranchCoverageRecordCollection,
+		statistics: &LcovStatistics,
+		path_strategy: &PathStrategy,
+	) -> Self {
+		let Some(files) = collection.latest().and_then(|record| record.files.as_ref()) else {
+			return Self::default();
+		};
+		let present = statistics
+			.files
+			.iter()
+			.map(|file| path_split(&file.filename, path_strategy))
+			.collect::<HashSet<_>>();
+
+		let mut carried = Self::default();
+		let mut files = files
+			.iter()
+			.filter(|(path, _)| !present.contains(*path))
+			.filter_map(|(path, record)| Some((path, record.lines?, record.untested_lines.len())))
+			.collect::<Vec<_>>();
+		files.sort_by(|l, r| l.0.cmp(r.0));
+
+		for (path, lines_found, untested) in files {
+			carried.paths.push(path.clone());
+			carried.lines_found += lines_found;
+			carried.lines_hit += lines_found.saturating_sub(untested as u64);
+		}
+
+		carried
+	}
+
+	/// Total percentage coverage of `statistics` combined with the carried
+	/// files, [None] if no lines are instrumented
+	#[must_use]
+	pub fn percentage(&self, statistics: &LcovStatistics) -> Option<f64> {
+		metrics::percentage(
+			statistics.lines_hit + self.lines_hit,
+			statistics.lines_found + self.lines_found,
+		)
+	}
+}
+
+impl Carryforward {
+	/// Fetch the latest record of [Carryforward::base_branch] and carry
+	/// forward its files missing from `statistics`, nothing is carried if the
+	/// branch is not tracked
+	pub async fn load(
+		&self,
+		provider: &dyn VcsProvider,
+		owner: &str,
+		repo: &str,
+		statistics: &LcovStatistics,
+		path_strategy: &PathStrategy,
+	) -> Result<CarriedFiles, MeowCoverageError> {
+		let storage = tracking::open_storage(provider, &self.coverage_repo).await?;
+		let collection =
+			tracking::fetch_stored_records(storage.as_ref(), owner, repo, &self.base_branch)
+				.await?;
+
+		Ok(collection
+			.map(|collection| CarriedFiles::collect(&collection, statistics, path_strategy))
+			.unwrap_or_default())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Carryforward;
+	use crate::{
+		coverage::{helpers::PathStrategy, lcov::LcovWrapper},
+		provider::{fake::FakeProvider, RepoFile},
+	};
+
+	#[tokio::test]
+	async fn test_carryforward() {
+		let lcov = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\n".as_bytes(),
+		)
+		.unwrap();
+		let carryforward = Carryforward {
+			coverage_repo: String::from("famedly/coverage"),
+			base_branch: String::from("main"),
+		};
+		let provider = FakeProvider::default();
+
+		let carried = carryforward
+			.load(
+				&provider,
+				"famedly",
+				"meow",
+				lcov.statistics(),
+				&PathStrategy::Prefix(String::from("src/")),
+			)
+			.await
+			.unwrap();
+		assert!(carried.paths.is_empty());
+		assert_eq!(carried.percentage(lcov.statistics()), Some(50_f64));
+
+		let records = serde_json::json!({
+			"team": "Other",
+			"records": [{
+				"timestamp": 1,
+				"percentage": 7500,
+				"files": {
+					"src/lib.rs": { "percentage": 10000, "lines": 2 },
+					"src/docs.rs": { "percentage": 7500, "lines": 4, "untested_lines": [3] },
+					"src/old.rs": { "percentage": 0, "untested_lines": [1] },
+				},
+			}],
+		});
+		provider.files.lock().unwrap().insert(
+			String::from("famedly/coverage/records/famedly/meow/main.meowcov.json"),
+			RepoFile { content: serde_json::to_vec(&records).unwrap(), sha: String::from("0") },
+		);
+
+		let carried = carryforward
+			.load(
+				&provider,
+				"famedly",
+				"meow",
+				lcov.statistics(),
+				&PathStrategy::Prefix(String::from("src/")),
+			)
+			.await
+			.unwrap();
+		assert_eq!(carried.paths, ["src/docs.rs"]);
+		assert_eq!((carried.lines_found, carried.lines_hit), (4, 3));
+		assert_eq!(carried.percentage(lcov.statistics()), Some(100_f64 * 4.0 / 6.0));
+	}
+}
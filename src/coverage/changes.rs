@@ -0,0 +1,264 @@
+//! Intersection of Lcov reports with the unified diffs of changed files,
+//! independent of where the diffs come from
+
+use std::collections::HashMap;
+
+use super::{
+	helpers::{line_changed_in_hunk, lines_in_same_hunk, path_split, PathStrategy},
+	lcov::{FunctionCoverage, LcovFileCoverage, LcovWrapper},
+};
+use crate::{percentage::Percentage, provider::PullRequestFile};
+
+/// Coverage of the changed lines of a file in the Lcov report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangedFileCoverage {
+	/// The patch of the file was analysed
+	Analysed {
+		/// Path of the file after the change
+		path: String,
+		/// Percentage coverage of the whole file
+		percentage: Percentage,
+		/// Number of instrumented lines changed by the patch
+		changed_lines_found: u32,
+		/// Untested lines changed by the patch
+		untested_lines: Vec<u32>,
+		/// [ChangedFileCoverage::Analysed::untested_lines] grouped into
+		/// `(first, final)` ranges that do not span hunks
+		untested_ranges: Vec<(u32, u32)>,
+		/// Functions of the file as `(start_line, name)`, sorted by start line
+		functions: Vec<(u32, String)>,
+		/// Coverage of the functions containing untested changed lines,
+		/// sorted by start line
+		untested_functions: Vec<FunctionCoverage>,
+	},
+	/// The patch of the file was unavailable (binary or too large) or could
+	/// not be parsed
+	Unanalysed {
+		/// Path of the file after the change
+		path: String,
+	},
+}
+
+/// Index the changed files by every path an Lcov file may be reported under:
+/// the new path, the previous path of renamed files, and both of these mapped
+/// with `path_strategy`. Earlier keys take precedence, so a file matching the
+/// new path of one changed file and the previous path of another is matched
+/// to the former
+fn match_changed_files<'a>(
+	changed_files: &'a [PullRequestFile],
+	path_strategy: &PathStrategy,
+) -> HashMap<String, &'a PullRequestFile> {
+	let mut matched = HashMap::new();
+
+	for split in [false, true] {
+		for previous in [false, true] {
+			for file in changed_files {
+				let path = match previous {
+					true => file.previous_filename.as_deref(),
+					false => Some(file.filename.as_str()),
+				};
+				if let Some(path) = path {
+					let key = match split {
+						true => path_split(path, path_strategy),
+						false => String::from(path),
+					};
+					matched.entry(key).or_insert(file);
+				}
+			}
+		}
+	}
+
+	matched
+}
+
+/// Group untested changed lines into `(first, final)` ranges that do not span
+/// hunks
+fn group_by_hunk(hunks: &[patch::Hunk<'_>], lines: &[u32]) -> Vec<(u32, u32)> {
+	lines.iter().copied().fold(Vec::new(), |mut ranges, line| {
+		if let Some(last) = ranges.last_mut() {
+			if lines_in_same_hunk(hunks, u64::from(last.1), u64::from(line)) {
+				last.1 = line;
+				return ranges;
+			}
+		}
+
+		ranges.push((line, line));
+		ranges
+	})
+}
+
+/// Intersect the untested lines of an Lcov file with the patch of the changed
+/// file it was matched to
+fn changed_file_coverage(
+	changed_file: &PullRequestFile,
+	coverage: &LcovFileCoverage,
+	instrumented_lines: &[(u32, u64)],
+) -> ChangedFileCoverage {
+	let path = changed_file.filename.clone();
+	let Some(patch) = changed_file.patch.as_deref() else {
+		return ChangedFileCoverage::Unanalysed { path };
+	};
+	let patch_str = format!(
+		"--- a/{}\n+++ b/{}\n{}{}",
+		changed_file.previous_filename.as_deref().unwrap_or(&path),
+		path,
+		patch,
+		match patch.ends_with('\n') {
+			true => "",
+			false => "\n",
+		}
+	);
+
+	#[allow(clippy::print_stderr)]
+	let patch = match patch::Patch::from_single(&patch_str) {
+		Ok(patch) => patch,
+		Err(why) => {
+			eprintln!("Error parsing patch, continuing with next (why: {})", why);
+			return ChangedFileCoverage::Unanalysed { path };
+		}
+	};
+
+	let changed =
+		|line: u32| patch.hunks.iter().any(|hunk| line_changed_in_hunk(hunk, u64::from(line)));
+	let changed_lines_found =
+		instrumented_lines.iter().filter(|(line, _)| changed(*line)).count() as u32;
+	let untested_lines: Vec<_> =
+		coverage.lines.iter().copied().filter(|line| changed(*line)).collect();
+
+	let mut untested_functions: Vec<_> = untested_lines
+		.iter()
+		.filter_map(|line| coverage.enclosing_function_coverage(*line))
+		.cloned()
+		.collect();
+	untested_functions.dedup();
+
+	ChangedFileCoverage::Analysed {
+		untested_ranges: group_by_hunk(&patch.hunks, &untested_lines),
+		changed_lines_found,
+		untested_lines,
+		path,
+		percentage: Percentage::from_f64(coverage.percentage * 100_f64),
+		functions: coverage.functions.clone(),
+		untested_functions,
+	}
+}
+
+/// Intersect the coverage of `report` with the patches of `changed_files`
+///
+/// Files of the report are matched to the changed files by their new and
+/// previous (if renamed) path, optionally mapped with `path_strategy`. Files of
+/// the report that were not changed are skipped, the changed ones are returned
+/// in the order of the report
+#[must_use]
+pub fn diff_coverage(
+	report: &LcovWrapper,
+	path_strategy: &PathStrategy,
+	changed_files: &[PullRequestFile],
+) -> Vec<ChangedFileCoverage> {
+	let changed_files = match_changed_files(changed_files, path_strategy);
+	let details: HashMap<_, _> =
+		report.detailed_data().into_iter().map(|file| (file.filename, file.lines)).collect();
+
+	report
+		.statistics()
+		.files
+		.iter()
+		.filter_map(|coverage| {
+			let changed_file =
+				changed_files.get(&path_split(coverage.filename.as_str(), path_strategy))?;
+			Some(changed_file_coverage(
+				changed_file,
+				coverage,
+				details.get(&coverage.filename).map_or(&[], Vec::as_slice),
+			))
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{diff_coverage, ChangedFileCoverage};
+	use crate::{
+		coverage::{
+			helpers::PathStrategy,
+			lcov::{FunctionCoverage, LcovWrapper},
+		},
+		percentage::Percentage,
+		provider::PullRequestFile,
+	};
+
+	#[test]
+	fn test_diff_coverage() {
+		let report = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nFN:1,a\nFN:20,f\nDA:1,0\nDA:2,0\nDA:3,1\nDA:20,0\nDA:21,0\nLF:5\nLH:1\nend_of_record\nSF:/build/src/new.rs\nDA:1,0\nLF:1\nLH:0\nend_of_record\nSF:/build/src/tested.rs\nDA:1,1\nLF:1\nLH:1\nend_of_record\nSF:/build/src/image.rs\nDA:1,0\nLF:1\nLH:0\nend_of_record\nSF:/build/src/unchanged.rs\nDA:1,0\nLF:1\nLH:0\nend_of_record\n"
+				.as_bytes(),
+		)
+		.unwrap();
+		let changed_files = [
+			PullRequestFile {
+				filename: String::from("src/lib.rs"),
+				previous_filename: None,
+				patch: Some(String::from(
+					"@@ -1,0 +1,3 @@\n+fn a() {}\n+fn b() {}\n+fn c() {}\n@@ -15,1 +20,2 @@\n-fn d() {}\n+fn e() {}\n+fn f() {}",
+				)),
+			},
+			PullRequestFile {
+				filename: String::from("src/new.rs"),
+				previous_filename: Some(String::from("src/old.rs")),
+				patch: Some(String::from("@@ -1,1 +1,1 @@\n-fn a() {}\n+fn b() {}\n")),
+			},
+			PullRequestFile {
+				filename: String::from("src/tested.rs"),
+				previous_filename: None,
+				patch: Some(String::from("@@ -1,1 +1,1 @@\n-fn a() {}\n+fn b() {}\n")),
+			},
+			PullRequestFile {
+				filename: String::from("src/image.rs"),
+				previous_filename: None,
+				patch: None,
+			},
+		];
+
+		let changes =
+			diff_coverage(&report, &PathStrategy::Prefix(String::from("src/")), &changed_files);
+		assert_eq!(changes.len(), 4);
+		assert_eq!(
+			changes[0],
+			ChangedFileCoverage::Analysed {
+				path: String::from("src/lib.rs"),
+				percentage: Percentage::from_basis_points(2000),
+				changed_lines_found: 5,
+				untested_lines: vec![1, 2, 20, 21],
+				untested_ranges: vec![(1, 2), (20, 21)],
+				functions: vec![(1, String::from("a")), (20, String::from("f"))],
+				untested_functions: vec![
+					FunctionCoverage {
+						name: String::from("a"),
+						start_line: 1,
+						lines_found: 3,
+						lines_untested: 2,
+					},
+					FunctionCoverage {
+						name: String::from("f"),
+						start_line: 20,
+						lines_found: 2,
+						lines_untested: 2,
+					},
+				],
+			}
+		);
+		assert!(matches!(
+			&changes[1],
+			ChangedFileCoverage::Analysed { path, untested_lines, .. }
+				if path == "src/new.rs" && untested_lines == &[1]
+		));
+		assert!(matches!(
+			&changes[2],
+			ChangedFileCoverage::Analysed { untested_lines, .. } if untested_lines.is_empty()
+		));
+		assert_eq!(
+			changes[3],
+			ChangedFileCoverage::Unanalysed { path: String::from("src/image.rs") }
+		);
+	}
+}
@@ -0,0 +1,387 @@
+//! Coverage differences between two arbitrary Lcov reports or tracking
+//! records, independent of any hosting provider
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use super::{
+	helpers::{format_percentage, gather_lines},
+	lcov::LcovWrapper,
+};
+use crate::{
+	metrics,
+	percentage::{Percentage, PercentageFormat},
+	tracking::{BranchCoverageRecord, FileCoverageRecord},
+};
+
+/// Coverage difference of a single file
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FileCoverageDiff {
+	/// File name
+	pub filename: String,
+	/// Percentage coverage in the old report, [None] if the file is missing
+	/// from it
+	pub old_percentage: Option<f64>,
+	/// Percentage coverage in the new report, [None] if the file is missing
+	/// from it
+	pub new_percentage: Option<f64>,
+	/// Lines untested in the new report that were tested or not instrumented
+	/// in the old report
+	pub newly_uncovered: Vec<u32>,
+	/// Lines tested in the new report that were untested in the old report
+	pub newly_covered: Vec<u32>,
+}
+
+impl FileCoverageDiff {
+	/// Change of the percentage coverage, [None] if the file is missing from
+	/// either report
+	#[must_use]
+	pub fn delta(&self) -> Option<f64> {
+		metrics::delta(self.new_percentage, self.old_percentage)
+	}
+
+	/// Whether the coverage of the file changed at all
+	fn changed(&self) -> bool {
+		!self.newly_uncovered.is_empty()
+			|| !self.newly_covered.is_empty()
+			|| self.old_percentage != self.new_percentage
+	}
+}
+
+/// Coverage difference between two Lcov reports
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CoverageDiff {
+	/// Total percentage coverage of the old report
+	pub old_percentage: Option<f64>,
+	/// Total percentage coverage of the new report
+	pub new_percentage: Option<f64>,
+	/// Files whose coverage changed, sorted by file name
+	pub files: Vec<FileCoverageDiff>,
+}
+
+/// Output format of a [CoverageDiff]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffFormat {
+	/// Markdown tables and lists
+	Markdown,
+	/// JSON serialisation of the [CoverageDiff]
+	Json,
+	/// Plain text for terminals
+	Terminal,
+}
+
+/// Tested and untested lines of every file in a report
+fn line_sets(lcov: &LcovWrapper) -> BTreeMap<String, (BTreeSet<u32>, BTreeSet<u32>)> {
+	lcov.detailed_data()
+		.into_iter()
+		.map(|file| {
+			let (tested, untested) =
+				file.lines.iter().partition::<Vec<_>, _>(|(_, hits)| *hits > 0);
+			(
+				file.filename,
+				(
+					tested.into_iter().map(|(line, _)| line).collect(),
+					untested.into_iter().map(|(line, _)| line).collect(),
+				),
+			)
+		})
+		.collect()
+}
+
+/// Format lines as comma separated ranges, for example `1-3, 7`
+fn format_lines(lines: &[u32]) -> String {
+	gather_lines(lines)
+		.into_iter()
+		.map(|(start, end)| match start == end {
+			true => start.to_string(),
+			false => format!("{}-{}", start, end),
+		})
+		.join(", ")
+}
+
+/// Format an optional change of percentage coverage in `percentage_format`
+fn format_delta(delta: Option<f64>, percentage_format: PercentageFormat) -> String {
+	match delta {
+		Some(delta) => {
+			format!("{:+}%", Percentage::from_f64(delta).display_with(percentage_format))
+		}
+		None => String::from("N/A"),
+	}
+}
+
+impl CoverageDiff {
+	/// Compare the `old` and `new` Lcov reports
+	#[must_use]
+	pub fn new(old: &LcovWrapper, new: &LcovWrapper) -> Self {
+		let old_lines = line_sets(old);
+		let new_lines = line_sets(new);
+		let old_percentages = old
+			.statistics()
+			.files
+			.iter()
+			.map(|file| (file.filename.as_str(), file.percentage * 100_f64))
+			.collect::<BTreeMap<_, _>>();
+		let new_percentages = new
+			.statistics()
+			.files
+			.iter()
+			.map(|file| (file.filename.as_str(), file.percentage * 100_f64))
+			.collect::<BTreeMap<_, _>>();
+		let empty = (BTreeSet::new(), BTreeSet::new());
+
+		let files = old_lines
+			.keys()
+			.chain(new_lines.keys())
+			.collect::<BTreeSet<_>>()
+			.into_iter()
+			.map(|filename| {
+				let (_, old_untested) = old_lines.get(filename).unwrap_or(&empty);
+				let (new_tested, new_untested) = new_lines.get(filename).unwrap_or(&empty);
+
+				FileCoverageDiff {
+					filename: filename.clone(),
+					old_percentage: old_percentages.get(filename.as_str()).copied(),
+					new_percentage: new_percentages.get(filename.as_str()).copied(),
+					newly_uncovered: new_untested.difference(old_untested).copied().collect(),
+					newly_covered: new_tested.intersection(old_untested).copied().collect(),
+				}
+			})
+			.filter(FileCoverageDiff::changed)
+			.collect();
+
+		Self {
+			old_percentage: old.statistics().percentage(),
+			new_percentage: new.statistics().percentage(),
+			files,
+		}
+	}
+
+	/// Compare the `old` and `new` tracking records. Records don't keep the
+	/// tested lines, so the newly covered lines are the lines no longer
+	/// untested, including removed ones. Only the totals of records without
+	/// files (older records, see
+	/// [RecordOptions::file_history](crate::tracking::RecordOptions::file_history))
+	/// are compared
+	#[must_use]
+	pub fn from_records(old: &BranchCoverageRecord, new: &BranchCoverageRecord) -> Self {
+		let empty = HashMap::new();
+		let old_files = old.files.as_ref().unwrap_or(&empty);
+		let new_files = new.files.as_ref().unwrap_or(&empty);
+		let untested = |file: Option<&FileCoverageRecord>| {
+			file.map(|file| file.untested_lines.iter().copied().collect::<BTreeSet<_>>())
+				.unwrap_or_default()
+		};
+
+		let files = old_files
+			.keys()
+			.chain(new_files.keys())
+			.collect::<BTreeSet<_>>()
+			.into_iter()
+			.map(|filename| {
+				let (old_file, new_file) = (old_files.get(filename), new_files.get(filename));
+				let (old_untested, new_untested) = (untested(old_file), untested(new_file));
+
+				FileCoverageDiff {
+					filename: filename.clone(),
+					old_percentage: old_file.map(|file| file.percentage.as_f64()),
+					new_percentage: new_file.map(|file| file.percentage.as_f64()),
+					newly_uncovered: new_untested.difference(&old_untested).copied().collect(),
+					newly_covered: match new_file {
+						Some(_) => old_untested.difference(&new_untested).copied().collect(),
+						None => Vec::new(),
+					},
+				}
+			})
+			.filter(FileCoverageDiff::changed)
+			.collect();
+
+		Self {
+			old_percentage: Some(old.percentage.as_f64()),
+			new_percentage: Some(new.percentage.as_f64()),
+			files,
+		}
+	}
+
+	/// Change of the total percentage coverage, [None] if either report has
+	/// no instrumented lines
+	#[must_use]
+	pub fn delta(&self) -> Option<f64> {
+		metrics::delta(self.new_percentage, self.old_percentage)
+	}
+
+	/// Render the difference in the given format, with the percentages in
+	/// `percentage_format`
+	pub fn render(
+		&self,
+		format: DiffFormat,
+		percentage_format: PercentageFormat,
+	) -> Result<String, serde_json::Error> {
+		match format {
+			DiffFormat::Markdown => Ok(self.markdown(percentage_format)),
+			DiffFormat::Json => serde_json::to_string_pretty(self),
+			DiffFormat::Terminal => Ok(self.terminal(percentage_format)),
+		}
+	}
+
+	/// Render the difference as markdown
+	#[must_use]
+	pub fn markdown(&self, percentage_format: PercentageFormat) -> String {
+		self.markdown_titled("Coverage Diff", percentage_format)
+	}
+
+	/// Render the difference as markdown under the heading `title`
+	#[must_use]
+	pub fn markdown_titled(&self, title: &str, percentage_format: PercentageFormat) -> String {
+		let mut markdown = format!(
+			"# {}\n\nTotal coverage: {} → {} ({})\n",
+			title,
+			format_percentage(self.old_percentage, percentage_format),
+			format_percentage(self.new_percentage, percentage_format),
+			format_delta(self.delta(), percentage_format)
+		);
+		if self.files.is_empty() {
+			markdown.push_str("\nNo coverage changes.\n");
+			return markdown;
+		}
+
+		markdown.push_str("\n| File | Old | New | Delta |\n| --- | --- | --- | --- |\n");
+		for file in &self.files {
+			markdown.push_str(&format!(
+				"| {} | {} | {} | {} |\n",
+				file.filename,
+				format_optional_percentage(file.old_percentage, percentage_format),
+				format_optional_percentage(file.new_percentage, percentage_format),
+				format_delta(file.delta(), percentage_format)
+			));
+		}
+
+		for (title, lines) in [
+			(
+				"Newly Uncovered Lines",
+				Self::lines_of(&self.files, |file| &file.newly_uncovered[..]),
+			),
+			("Newly Covered Lines", Self::lines_of(&self.files, |file| &file.newly_covered[..])),
+		] {
+			markdown.push_str(&format!("\n## {}\n\n", title));
+			match lines.is_empty() {
+				true => markdown.push_str("None\n"),
+				false => {
+					for (filename, lines) in lines {
+						markdown.push_str(&format!("- `{}`: {}\n", filename, format_lines(lines)));
+					}
+				}
+			}
+		}
+
+		markdown
+	}
+
+	/// Render the difference as plain text for terminals
+	#[must_use]
+	pub fn terminal(&self, percentage_format: PercentageFormat) -> String {
+		let mut text = format!(
+			"Total coverage: {} -> {} ({})\n",
+			format_percentage(self.old_percentage, percentage_format),
+			format_percentage(self.new_percentage, percentage_format),
+			format_delta(self.delta(), percentage_format)
+		);
+
+		for file in &self.files {
+			text.push_str(&format!(
+				"\n{}: {} -> {} ({})\n",
+				file.filename,
+				format_optional_percentage(file.old_percentage, percentage_format),
+				format_optional_percentage(file.new_percentage, percentage_format),
+				format_delta(file.delta(), percentage_format)
+			));
+			if !file.newly_uncovered.is_empty() {
+				text.push_str(&format!("  - uncovered: {}\n", format_lines(&file.newly_uncovered)));
+			}
+			if !file.newly_covered.is_empty() {
+				text.push_str(&format!("  + covered: {}\n", format_lines(&file.newly_covered)));
+			}
+		}
+
+		text
+	}
+
+	/// Files with non-empty line lists selected by `lines`
+	fn lines_of(
+		files: &[FileCoverageDiff],
+		lines: fn(&FileCoverageDiff) -> &[u32],
+	) -> Vec<(&str, &[u32])> {
+		files
+			.iter()
+			.map(|file| (file.filename.as_str(), lines(file)))
+			.filter(|(_, lines)| !lines.is_empty())
+			.collect()
+	}
+}
+
+/// Format the percentage coverage of a file in `percentage_format`, `-` if it
+/// is missing from the report
+fn format_optional_percentage(
+	percentage: Option<f64>,
+	percentage_format: PercentageFormat,
+) -> String {
+	match percentage {
+		Some(percentage) => {
+			format!("{}%", Percentage::from_f64(percentage).display_with(percentage_format))
+		}
+		None => String::from("-"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{format_lines, CoverageDiff, DiffFormat};
+	use crate::{coverage::lcov::LcovWrapper, percentage::PercentageFormat};
+
+	#[test]
+	fn test_coverage_diff() {
+		let old = LcovWrapper::from_reader(
+			"SF:src/lib.rs\nDA:1,1\nDA:2,0\nDA:3,0\nDA:4,1\nLF:4\nLH:2\nend_of_record\nSF:src/old.rs\nDA:1,1\nLF:1\nLH:1\nend_of_record\nSF:src/same.rs\nDA:1,1\nLF:1\nLH:1\nend_of_record\n"
+				.as_bytes(),
+		)
+		.unwrap();
+		let new = LcovWrapper::from_reader(
+			"SF:src/lib.rs\nDA:1,1\nDA:2,3\nDA:3,0\nDA:4,0\nDA:5,0\nLF:5\nLH:2\nend_of_record\nSF:src/same.rs\nDA:1,1\nLF:1\nLH:1\nend_of_record\n"
+				.as_bytes(),
+		)
+		.unwrap();
+
+		let diff = CoverageDiff::new(&old, &new);
+		assert_eq!(format!("{:.2}", diff.old_percentage.unwrap()), "66.67");
+		assert_eq!(diff.new_percentage, Some(50_f64));
+		assert_eq!(diff.files.len(), 2);
+
+		let lib = &diff.files[0];
+		assert_eq!(lib.filename, "src/lib.rs");
+		assert_eq!(lib.newly_uncovered, [4, 5]);
+		assert_eq!(lib.newly_covered, [2]);
+		assert_eq!(lib.delta(), Some(-10_f64));
+
+		let removed = &diff.files[1];
+		assert_eq!(removed.filename, "src/old.rs");
+		assert_eq!(removed.new_percentage, None);
+		assert_eq!(removed.delta(), None);
+
+		let markdown = diff.render(DiffFormat::Markdown, PercentageFormat::default()).unwrap();
+		assert!(markdown.contains("| src/lib.rs | 50.00% | 40.00% | -10.00% |\n"));
+		assert!(markdown.contains("| src/old.rs | 100.00% | - | N/A |\n"));
+		assert!(markdown.contains("## Newly Uncovered Lines\n\n- `src/lib.rs`: 4-5\n"));
+		assert!(markdown.contains("## Newly Covered Lines\n\n- `src/lib.rs`: 2\n"));
+		assert!(diff
+			.render(DiffFormat::Terminal, PercentageFormat::default())
+			.unwrap()
+			.contains("  - uncovered: 4-5\n"));
+		let json: serde_json::Value = serde_json::from_str(
+			&diff.render(DiffFormat::Json, PercentageFormat::default()).unwrap(),
+		)
+		.unwrap();
+		assert_eq!(json["files"][0]["newly_covered"], serde_json::json!([2]));
+
+		assert_eq!(format_lines(&[1, 2, 3, 7, 9, 10]), "1-3, 7, 9-10");
+	}
+}
@@ -0,0 +1,117 @@
+//! Support for excluding lines from coverage with markers in the source code,
+//! compatible with the `LCOV_EXCL_*` markers understood by `genhtml`
+use std::{collections::HashSet, path::Path};
+
+use super::{
+	helpers::{path_split, PathStrategy},
+	rust_exclusion::rust_excluded_lines,
+};
+
+/// Markers excluding lines from coverage
+#[derive(Debug, Clone)]
+pub struct ExclusionMarkers {
+	/// Excludes the line it is on
+	pub line: String,
+	/// Excludes all lines up to the next `stop` marker, including both marker
+	/// lines
+	pub start: String,
+	/// Ends a block started by `start`
+	pub stop: String,
+	/// Additional marker excluding the line it is on (for example
+	/// `// meow: ignore`)
+	pub custom: Option<String>,
+	/// Also exclude the items of Rust files in `#[cfg(test)]` modules and
+	/// functions, and the items annotated with `#[coverage(off)]`, see
+	/// [rust_excluded_lines]
+	pub rust_attributes: bool,
+}
+
+impl Default for ExclusionMarkers {
+	fn default() -> Self {
+		Self {
+			line: String::from("LCOV_EXCL_LINE"),
+			start: String::from("LCOV_EXCL_START"),
+			stop: String::from("LCOV_EXCL_STOP"),
+			custom: None,
+			rust_attributes: false,
+		}
+	}
+}
+
+impl ExclusionMarkers {
+	/// Collect the excluded lines of a source file
+	#[must_use]
+	pub fn excluded_lines(&self, source: &str) -> HashSet<u32> {
+		let mut excluded = HashSet::new();
+		let mut in_block = false;
+
+		for (line, content) in (1..).zip(source.lines()) {
+			if content.contains(self.start.as_str()) {
+				in_block = true;
+			}
+
+			if in_block
+				|| content.contains(self.line.as_str())
+				|| self.custom.as_deref().is_some_and(|custom| content.contains(custom))
+			{
+				excluded.insert(line);
+			}
+
+			if content.contains(self.stop.as_str()) {
+				in_block = false;
+			}
+		}
+
+		excluded
+	}
+
+	/// Read a source file referenced by an Lcov report and collect its excluded
+	/// lines
+	///
+	/// The file is looked up at the path from the report first, then relative
+	/// to the working directory after mapping with `path_strategy`. Files that
+	/// can't be read have no excluded lines.
+	#[must_use]
+	pub fn read_excluded_lines(
+		&self,
+		filename: &str,
+		path_strategy: &PathStrategy,
+	) -> HashSet<u32> {
+		let relative = path_split(filename, path_strategy);
+
+		let source = [Path::new(filename), Path::new(relative.as_str())]
+			.into_iter()
+			.find_map(|path| std::fs::read_to_string(path).ok());
+
+		let Some(source) = source else {
+			return HashSet::new();
+		};
+		let mut excluded = self.excluded_lines(&source);
+		if self.rust_attributes && filename.ends_with(".rs") {
+			excluded.extend(rust_excluded_lines(&source));
+		}
+
+		excluded
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ExclusionMarkers;
+
+	#[test]
+	fn test_excluded_lines() {
+		let source = "fn main() {\n\tunreachable!(); // LCOV_EXCL_LINE\n\t// LCOV_EXCL_START\n\tdebug();\n\t// LCOV_EXCL_STOP\n\trun(); // meow: ignore\n}\n";
+
+		let mut excluded =
+			ExclusionMarkers::default().excluded_lines(source).into_iter().collect::<Vec<_>>();
+		excluded.sort_unstable();
+		assert_eq!(excluded, &[2, 3, 4, 5]);
+
+		let markers =
+			ExclusionMarkers { custom: Some(String::from("meow: ignore")), ..Default::default() };
+		let mut excluded = markers.excluded_lines(source).into_iter().collect::<Vec<_>>();
+		excluded.sort_unstable();
+		assert_eq!(excluded, &[2, 3, 4, 5, 6]);
+	}
+}
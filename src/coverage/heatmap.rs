@@ -0,0 +1,357 @@
+//! Treemap heatmaps of the coverage of the files of a report, the area of a
+//! file is its number of instrumented lines and its colour its coverage
+use std::{fmt::Write as _, io::Write as _};
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use super::lcov::LcovStatistics;
+use crate::{
+	percentage::{Percentage, PercentageFormat},
+	tracking::BranchCoverageRecord,
+	MeowCoverageError,
+};
+
+/// Coverage of a file in a [Heatmap]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeatmapFile {
+	/// File path
+	pub path: String,
+	/// Number of instrumented lines, the area of the file
+	pub lines: u64,
+	/// Coverage of the file
+	pub percentage: Percentage,
+}
+
+/// A rectangle of the treemap
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+	/// Left edge
+	x: f64,
+	/// Top edge
+	y: f64,
+	/// Width
+	width: f64,
+	/// Height
+	height: f64,
+}
+
+/// Heatmap of the coverage of the files of a report
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Heatmap {
+	/// Files with instrumented lines, largest first
+	files: Vec<HeatmapFile>,
+}
+
+impl Heatmap {
+	/// Build a heatmap of `files`, dropping files without instrumented lines
+	#[must_use]
+	pub fn new(mut files: Vec<HeatmapFile>) -> Self {
+		files.retain(|file| file.lines > 0);
+		files.sort_by(|l, r| r.lines.cmp(&l.lines).then_with(|| l.path.cmp(&r.path)));
+		Self { files }
+	}
+
+	/// Build a heatmap of the files of an Lcov report
+	#[must_use]
+	pub fn from_statistics(statistics: &LcovStatistics) -> Self {
+		Self::new(
+			statistics
+				.files
+				.iter()
+				.map(|file| HeatmapFile {
+					path: file.filename.clone(),
+					lines: file.lines_found,
+					percentage: Percentage::from_f64(file.percentage * 100_f64),
+				})
+				.collect(),
+		)
+	}
+
+	/// Build a heatmap of the files of a tracking record, files of records
+	/// produced before line counts were recorded get the same area
+	#[must_use]
+	pub fn from_record(record: &BranchCoverageRecord) -> Self {
+		Self::new(
+			record
+				.files
+				.iter()
+				.flatten()
+				.map(|(path, file)| HeatmapFile {
+					path: path.clone(),
+					lines: file.lines.unwrap_or(1),
+					percentage: file.percentage,
+				})
+				.collect(),
+		)
+	}
+
+	/// Whether the heatmap has no files
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.files.is_empty()
+	}
+
+	/// Lay the files out in a squarified treemap of `width` by `height`
+	fn layout(&self, width: f64, height: f64) -> Vec<(&HeatmapFile, Rect)> {
+		let total = self.files.iter().map(|file| file.lines as f64).sum::<f64>();
+		let scale = width * height / total.max(1_f64);
+		let areas = self.files.iter().map(|file| file.lines as f64 * scale).collect::<Vec<_>>();
+
+		let mut placed = Vec::with_capacity(self.files.len());
+		let mut free = Rect { x: 0_f64, y: 0_f64, width, height };
+		let mut start = 0;
+		while start < areas.len() {
+			let side = free.width.min(free.height);
+			let mut end = start + 1;
+			while end < areas.len()
+				&& worst_ratio(&areas[start..=end], side) <= worst_ratio(&areas[start..end], side)
+			{
+				end += 1;
+			}
+
+			let row_area = areas[start..end].iter().sum::<f64>();
+			let mut offset = 0_f64;
+			match free.width >= free.height {
+				// Stack the row as a column on the left
+				true => {
+					let row_width = row_area / free.height;
+					for (file, area) in self.files[start..end].iter().zip(&areas[start..end]) {
+						let item_height = area / row_width;
+						placed.push((
+							file,
+							Rect {
+								x: free.x,
+								y: free.y + offset,
+								width: row_width,
+								height: item_height,
+							},
+						));
+						offset += item_height;
+					}
+					free.x += row_width;
+					free.width -= row_width;
+				}
+				// Lay the row out along the top
+				false => {
+					let row_height = row_area / free.width;
+					for (file, area) in self.files[start..end].iter().zip(&areas[start..end]) {
+						let item_width = area / row_height;
+						placed.push((
+							file,
+							Rect {
+								x: free.x + offset,
+								y: free.y,
+								width: item_width,
+								height: row_height,
+							},
+						));
+						offset += item_width;
+					}
+					free.y += row_height;
+					free.height -= row_height;
+				}
+			}
+			start = end;
+		}
+
+		placed
+	}
+
+	/// Render the heatmap as an SVG of `width` by `height` pixels, with the
+	/// path and coverage (in `format`) of every file as its tooltip
+	pub fn render_svg(
+		&self,
+		width: u32,
+		height: u32,
+		format: PercentageFormat,
+	) -> Result<String, MeowCoverageError> {
+		check_size(width, height)?;
+		let mut rects = String::new();
+		for (file, rect) in self.layout(f64::from(width), f64::from(height)) {
+			let (red, green, blue) = colour(file.percentage);
+			let _ = write!(
+				rects,
+				"<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#{:02x}{:02x}{:02x}\" stroke=\"#fff\"><title>{}: {}% ({} lines)</title></rect>",
+				rect.x,
+				rect.y,
+				rect.width,
+				rect.height,
+				red,
+				green,
+				blue,
+				escape_xml(&file.path),
+				file.percentage.display_with(format),
+				file.lines
+			);
+		}
+
+		Ok(format!(
+			"<svg xmlns=\"http://www.w3.org/2000/svg\" class=\"heatmap\" viewBox=\"0 0 {width} {height}\" width=\"{width}\" height=\"{height}\" role=\"img\" aria-label=\"Coverage heatmap\">{rects}</svg>\n",
+			width = width,
+			height = height,
+			rects = rects
+		))
+	}
+
+	/// Render the heatmap as a PNG of `width` by `height` pixels
+	pub fn render_png(&self, width: u32, height: u32) -> Result<Vec<u8>, MeowCoverageError> {
+		check_size(width, height)?;
+		let (columns, rows) = (width as usize, height as usize);
+		let mut pixels = vec![0xff_u8; columns * rows * 3];
+
+		for (file, rect) in self.layout(f64::from(width), f64::from(height)) {
+			let (red, green, blue) = colour(file.percentage);
+			// Leave a white border of one pixel on the right and bottom edges
+			let (left, top) = (rect.x.round() as usize, rect.y.round() as usize);
+			let right = ((rect.x + rect.width).round() as usize).min(columns);
+			let bottom = ((rect.y + rect.height).round() as usize).min(rows);
+			for row in top..bottom.saturating_sub(1) {
+				for column in left..right.saturating_sub(1) {
+					let pixel = (row * columns + column) * 3;
+					pixels[pixel..pixel + 3].copy_from_slice(&[red, green, blue]);
+				}
+			}
+		}
+
+		encode_png(width, height, &pixels)
+	}
+}
+
+/// Reject images without pixels
+fn check_size(width: u32, height: u32) -> Result<(), MeowCoverageError> {
+	match width == 0 || height == 0 {
+		true => Err(MeowCoverageError::InvalidImageSize(width, height)),
+		false => Ok(()),
+	}
+}
+
+/// Worst aspect ratio of the rectangles of `areas` laid out in a row along
+/// `side`
+fn worst_ratio(areas: &[f64], side: f64) -> f64 {
+	let sum = areas.iter().sum::<f64>();
+	let (min, max) = areas
+		.iter()
+		.fold((f64::INFINITY, 0_f64), |(min, max), area| (min.min(*area), max.max(*area)));
+	let (side, sum) = (side * side, sum * sum);
+
+	(side * max / sum).max(sum / (side * min))
+}
+
+/// Colour of a coverage percentage, from red (0%) over yellow (50%) to green
+/// (100%)
+#[must_use]
+fn colour(percentage: Percentage) -> (u8, u8, u8) {
+	let fraction = percentage.as_f64().clamp(0_f64, 100_f64) / 100_f64;
+	match fraction < 0.5 {
+		true => (0xe0, (fraction * 2_f64 * 192_f64) as u8, 0x40),
+		false => (((1_f64 - fraction) * 2_f64 * 224_f64) as u8, 0xc0, 0x40),
+	}
+}
+
+/// Escape text for SVG
+fn escape_xml(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// CRC-32 of a PNG chunk
+fn crc32(bytes: &[u8]) -> u32 {
+	!bytes.iter().fold(!0_u32, |crc, byte| {
+		(0..8).fold(crc ^ u32::from(*byte), |crc, _| match crc & 1 {
+			1 => (crc >> 1) ^ 0xedb8_8320,
+			_ => crc >> 1,
+		})
+	})
+}
+
+/// Encode RGB `pixels` as a PNG
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Result<Vec<u8>, MeowCoverageError> {
+	let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+	let mut chunk = |kind: &[u8; 4], data: &[u8]| {
+		png.extend((data.len() as u32).to_be_bytes());
+		let start = png.len();
+		png.extend(kind);
+		png.extend(data);
+		let crc = crc32(&png[start..]);
+		png.extend(crc.to_be_bytes());
+	};
+
+	let mut header = Vec::with_capacity(13);
+	header.extend(width.to_be_bytes());
+	header.extend(height.to_be_bytes());
+	// 8 bits per channel, RGB, default compression, filtering, and no
+	// interlacing
+	header.extend([8, 2, 0, 0, 0]);
+	chunk(b"IHDR", &header);
+
+	let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+	for row in pixels.chunks(width as usize * 3) {
+		// No filter
+		encoder.write_all(&[0])?;
+		encoder.write_all(row)?;
+	}
+	chunk(b"IDAT", &encoder.finish()?);
+	chunk(b"IEND", &[]);
+
+	Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{crc32, Heatmap, HeatmapFile};
+	use crate::percentage::{Percentage, PercentageFormat};
+
+	fn heatmap() -> Heatmap {
+		Heatmap::new(vec![
+			HeatmapFile {
+				path: String::from("src/lib.rs"),
+				lines: 60,
+				percentage: Percentage::from_basis_points(9000),
+			},
+			HeatmapFile {
+				path: String::from("src/main.rs"),
+				lines: 30,
+				percentage: Percentage::from_basis_points(2000),
+			},
+			HeatmapFile {
+				path: String::from("src/<empty>.rs"),
+				lines: 10,
+				percentage: Percentage::from_basis_points(0),
+			},
+			HeatmapFile {
+				path: String::from("src/uninstrumented.rs"),
+				lines: 0,
+				percentage: Percentage::ZERO,
+			},
+		])
+	}
+
+	#[test]
+	fn test_heatmap_layout() {
+		let heatmap = heatmap();
+		let layout = heatmap.layout(100_f64, 50_f64);
+		assert_eq!(layout.len(), 3);
+
+		let area = layout.iter().map(|(_, rect)| rect.width * rect.height).sum::<f64>();
+		assert!((area - 5000_f64).abs() < 0.001);
+		for ((file, rect), expected) in layout.iter().zip([3000_f64, 1500_f64, 500_f64]) {
+			assert!((rect.width * rect.height - expected).abs() < 0.001, "{}", file.path);
+			assert!(rect.x >= 0_f64 && rect.x + rect.width <= 100.001);
+			assert!(rect.y >= 0_f64 && rect.y + rect.height <= 50.001);
+		}
+	}
+
+	#[test]
+	fn test_heatmap_render() {
+		let svg = heatmap().render_svg(100, 50, PercentageFormat::default()).unwrap();
+		assert_eq!(svg.matches("<rect").count(), 3);
+		assert!(svg.contains("<title>src/&lt;empty&gt;.rs: 0.00% (10 lines)</title>"));
+
+		let png = heatmap().render_png(100, 50).unwrap();
+		assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+		assert!(png.ends_with(&[0xae, 0x42, 0x60, 0x82]));
+		assert_eq!(crc32(b"IEND"), 0xae42_6082);
+
+		assert!(heatmap().render_svg(0, 50, PercentageFormat::default()).is_err());
+		assert!(heatmap().render_png(100, 0).is_err());
+	}
+}
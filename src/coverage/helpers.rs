@@ -1,13 +1,202 @@
 //! General helper utils
 
-/// Split a path by `source_prefix`, retaining the splitter in the right-paw
-/// side
-pub fn path_split(path: &str, source_prefix: &str) -> String {
-	path.split_once(source_prefix)
-		.map_or_else(|| String::from(path), |(_, val)| format!("{}{}", source_prefix, val))
+use regex::Regex;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::lcov::LabelTotal;
+use crate::percentage::{Percentage, PercentageFormat};
+
+/// How the paths of Lcov reports are mapped to paths relative to the root of
+/// the repository
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "PathStrategySpec")]
+pub enum PathStrategy {
+	/// Split by a prefix (for example `src/`), retaining the prefix. Paths
+	/// without the prefix are kept as is
+	Prefix(String),
+	/// Strip this many leading components, like `tar --strip-components`, for
+	/// module paths such as `github.com/org/repo/pkg/file.go`. Paths with no
+	/// more components than that are kept as is
+	StripComponents(usize),
+	/// Replace the first match of a regex, the replacement may refer to
+	/// capture groups as `$1` or `$name`
+	Rewrite {
+		/// Regex matched against the path
+		pattern: Regex,
+		/// Replacement of the match
+		replacement: String,
+	},
+}
+
+/// Serialised form of a [PathStrategy], as in `{ "strip_components": 3 }`
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PathStrategySpec {
+	/// [PathStrategy::Prefix]
+	Prefix(String),
+	/// [PathStrategy::StripComponents]
+	StripComponents(usize),
+	/// [PathStrategy::Rewrite]
+	Rewrite {
+		/// Regex matched against the path
+		pattern: String,
+		/// Replacement of the match
+		replacement: String,
+	},
+}
+
+impl TryFrom<PathStrategySpec> for PathStrategy {
+	type Error = regex::Error;
+
+	fn try_from(spec: PathStrategySpec) -> Result<Self, Self::Error> {
+		match spec {
+			PathStrategySpec::Prefix(prefix) => Ok(Self::Prefix(prefix)),
+			PathStrategySpec::StripComponents(count) => Ok(Self::StripComponents(count)),
+			PathStrategySpec::Rewrite { pattern, replacement } => {
+				Self::rewrite(&pattern, replacement)
+			}
+		}
+	}
+}
+
+impl PathStrategy {
+	/// Rewrite paths with a regex, failing if `pattern` is invalid
+	pub fn rewrite(pattern: &str, replacement: String) -> Result<Self, regex::Error> {
+		Ok(Self::Rewrite { pattern: Regex::new(pattern)?, replacement })
+	}
+}
+
+/// Map a path of an Lcov report to a path relative to the root of the
+/// repository according to `strategy`
+#[must_use]
+pub fn path_split(path: &str, strategy: &PathStrategy) -> String {
+	match strategy {
+		PathStrategy::Prefix(prefix) => path
+			.split_once(prefix.as_str())
+			.map_or_else(|| String::from(path), |(_, val)| format!("{}{}", prefix, val)),
+		PathStrategy::StripComponents(count) => path
+			.trim_start_matches('/')
+			.splitn(count + 1, '/')
+			.nth(*count)
+			.filter(|rest| !rest.is_empty())
+			.map_or_else(|| String::from(path), String::from),
+		PathStrategy::Rewrite { pattern, replacement } => {
+			pattern.replace(path, replacement.as_str()).into_owned()
+		}
+	}
+}
+
+/// Normalise a path from an Lcov report, which may have been produced on
+/// Windows: `\` separators become `/`, the `\\?\` verbatim prefix is dropped,
+/// and `strip_root` (for example `C:\build`) is stripped case-insensitively
+#[must_use]
+pub fn normalise_path(path: &str, strip_root: Option<&str>) -> String {
+	let path = path.replace('\\', "/");
+	let path = path.strip_prefix("//?/").map_or(path.as_str(), |path| path);
+
+	let Some(root) = strip_root else {
+		return String::from(path);
+	};
+	let root = root.replace('\\', "/");
+	let root = root.trim_end_matches('/');
+
+	match path.get(..root.len()).zip(path.get(root.len()..)) {
+		Some((prefix, rest)) if prefix.eq_ignore_ascii_case(root) && rest.starts_with('/') => {
+			String::from(rest.trim_start_matches('/'))
+		}
+		_ => String::from(path),
+	}
+}
+
+/// Gather lines into sorted `(first, final)` ranges of consecutive lines, the
+/// lines may be unsorted and contain duplicates
+#[must_use]
+pub fn gather_lines(lines: &[u32]) -> Vec<(u32, u32)> {
+	let mut lines = lines.to_vec();
+	lines.sort_unstable();
+	lines.dedup();
+
+	lines.into_iter().fold(Vec::new(), |mut ranges, line| {
+		match ranges.last_mut() {
+			Some((_, last)) if *last + 1 == line => *last = line,
+			_ => ranges.push((line, line)),
+		}
+		ranges
+	})
+}
+
+/// Name of the function a line belongs to, going by the closest function
+/// starting at or before the line in `functions` (`(start_line, name)` sorted
+/// by start line)
+#[must_use]
+pub fn enclosing_function(functions: &[(u32, String)], line: u32) -> Option<&str> {
+	functions
+		.iter()
+		.take_while(|(start_line, _)| *start_line <= line)
+		.last()
+		.map(|(_, name)| name.as_str())
+}
+
+/// Format a total percentage coverage for comments and statuses in `format`
+#[must_use]
+pub fn format_percentage(percentage: Option<f64>, format: PercentageFormat) -> String {
+	match percentage {
+		Some(percentage) => format!("{}%", Percentage::from_f64(percentage).display_with(format)),
+		None => String::from("N/A (no instrumented lines)"),
+	}
+}
+
+/// Format the totals of the labelled reports of a run as a list for
+/// comments with the percentages in `format`, empty if the run has a single
+/// report
+#[must_use]
+pub fn format_label_totals(labels: &[LabelTotal], format: PercentageFormat) -> String {
+	labels
+		.iter()
+		.map(|label| {
+			format!("- `{}`: {}\n", label.label, format_percentage(label.percentage, format))
+		})
+		.chain((!labels.is_empty()).then(|| String::from("\n")))
+		.collect()
+}
+
+/// Format paths as a comma-separated list of code spans for comments
+#[must_use]
+pub fn format_paths(paths: &[String]) -> String {
+	paths.iter().map(|path| format!("`{}`", path)).collect::<Vec<_>>().join(", ")
+}
+
+/// Prefix of the hidden marker of summary comments
+const SUMMARY_MARKER: &str = "<!-- meow-coverage-summary:";
+
+/// Hidden marker of a summary comment, identifying everything the run posts
+/// (`content`) by its hash
+#[must_use]
+pub fn summary_marker(content: &[u8]) -> String {
+	format!("{}{} -->", SUMMARY_MARKER, hex::encode(Sha256::digest(content)))
+}
+
+/// Start of the hidden marker of review comments
+const REVIEW_MARKER: &str = "<!-- meow-coverage-review:";
+
+/// Hidden marker of a review comment, identifying the comment (`content`) by
+/// its hash
+#[must_use]
+pub fn review_marker(content: &[u8]) -> String {
+	format!("{}{} -->", REVIEW_MARKER, hex::encode(Sha256::digest(content)))
+}
+
+/// Hex encoded SHA256 of a file path, as used by GitHub for diff anchors
+#[must_use]
+pub fn path_sha256(path: &str) -> String {
+	let mut hasher = Sha256::new();
+	hasher.update(path);
+	hex::encode(hasher.finalize())
 }
 
 /// Check if a line was changed in a [patch::Hunk]
+#[must_use]
 pub fn line_changed_in_hunk(hunk: &patch::Hunk, target_line: u64) -> bool {
 	let mut current_line = hunk.new_range.start;
 
@@ -33,6 +222,7 @@ pub fn line_changed_in_hunk(hunk: &patch::Hunk, target_line: u64) -> bool {
 
 /// Collect lines per-[patch::Hunk], this is sadly required by the GH issue
 /// comment API
+#[must_use]
 pub fn lines_in_same_hunk(hunks: &[patch::Hunk], line1: u64, line2: u64) -> bool {
 	for hunk in hunks {
 		let start_line = hunk.new_range.start;
@@ -44,3 +234,87 @@ pub fn lines_in_same_hunk(hunks: &[patch::Hunk], line1: u64, line2: u64) -> bool
 
 	false
 }
+
+#[cfg(test)]
+mod tests {
+	use proptest::{collection::vec, prelude::*};
+
+	use super::{gather_lines, normalise_path, path_split, PathStrategy};
+
+	#[test]
+	fn test_gather_lines() {
+		assert_eq!(gather_lines(&[0, 1, 2, 3, 5, 6, 8, 10]), &[(0, 3), (5, 6), (8, 8), (10, 10)]);
+		assert_eq!(gather_lines(&[3, 1, 4, 5, 6, 8, 10]), &[(1, 1), (3, 6), (8, 8), (10, 10)]);
+		assert_eq!(gather_lines(&[]), &[]);
+		assert_eq!(gather_lines(&[0, 0, 1, 0]), &[(0, 1)]);
+		assert_eq!(gather_lines(&[u32::MAX, u32::MAX - 1]), &[(u32::MAX - 1, u32::MAX)]);
+	}
+
+	proptest! {
+		#[test]
+		fn prop_gather_lines(lines in vec(0_u32..200, 0..100)) {
+			let ranges = gather_lines(&lines);
+
+			// Ranges are ordered, separated by at least one line, and cover
+			// exactly the input lines
+			for window in ranges.windows(2) {
+				prop_assert!(window[0].1 + 1 < window[1].0);
+			}
+			let mut covered = Vec::new();
+			for (first, last) in &ranges {
+				prop_assert!(first <= last);
+				covered.extend(*first..=*last);
+			}
+			let mut expected = lines.clone();
+			expected.sort_unstable();
+			expected.dedup();
+			prop_assert_eq!(covered, expected);
+		}
+	}
+
+	#[test]
+	fn test_normalise_path() {
+		assert_eq!(normalise_path("C:\\build\\src\\lib.rs", None), "C:/build/src/lib.rs");
+		assert_eq!(
+			normalise_path("C:\\build/src\\nested/mod.rs", Some("c:\\BUILD\\")),
+			"src/nested/mod.rs"
+		);
+		assert_eq!(normalise_path("\\\\?\\C:\\build\\src\\lib.rs", Some("C:/build")), "src/lib.rs");
+		assert_eq!(normalise_path("/build/src/lib.rs", Some("/build")), "src/lib.rs");
+		assert_eq!(normalise_path("/builder/src/lib.rs", Some("/build")), "/builder/src/lib.rs");
+		assert_eq!(
+			path_split(
+				&normalise_path("D:\\a\\src\\lib.rs", None),
+				&PathStrategy::Prefix(String::from("src/"))
+			),
+			"src/lib.rs"
+		);
+	}
+
+	#[test]
+	fn test_path_split() {
+		let prefix = PathStrategy::Prefix(String::from("src/"));
+		assert_eq!(path_split("/build/src/lib.rs", &prefix), "src/lib.rs");
+		assert_eq!(path_split("build.rs", &prefix), "build.rs");
+
+		let go = PathStrategy::StripComponents(3);
+		assert_eq!(path_split("github.com/org/repo/pkg/file.go", &go), "pkg/file.go");
+		assert_eq!(path_split("/github.com/org/repo/main.go", &go), "main.go");
+		assert_eq!(path_split("org/repo/main.go", &go), "org/repo/main.go");
+
+		let python = PathStrategy::rewrite(r"^.*/site-packages/", String::from("src/")).unwrap();
+		assert_eq!(
+			path_split("/usr/lib/python3/site-packages/meow/cli.py", &python),
+			"src/meow/cli.py"
+		);
+		let js =
+			PathStrategy::rewrite(r"^webpack:///\./(?P<path>.*)$", String::from("$path")).unwrap();
+		assert_eq!(path_split("webpack:///./lib/index.js", &js), "lib/index.js");
+		assert_eq!(path_split("lib/index.js", &js), "lib/index.js");
+
+		let strategy: PathStrategy = toml::from_str("strip_components = 2").unwrap();
+		assert!(matches!(strategy, PathStrategy::StripComponents(2)));
+		assert!(toml::from_str::<PathStrategy>("[rewrite]\npattern = \"(\"\nreplacement = \"\"")
+			.is_err());
+	}
+}
@@ -3,9 +3,13 @@ use std::borrow::Cow;
 
 use itertools::Itertools;
 
-use super::{PullFileCoverageWrapper, PushFileCoverageWrapper};
+use super::{
+	helpers::gather_lines, PullFileCoverageWrapper, PushFileCoverageWrapper, UntestedRange,
+};
+use crate::provider::{LineRange, VcsProvider};
 
 /// Makes a file, and optionally, line specific link to a diff in a PR
+#[must_use]
 pub fn make_pull_link(
 	owner: &str,
 	repo: &str,
@@ -29,6 +33,7 @@ pub fn make_pull_link(
 }
 
 /// Makes a file, and optionally line, specific link for a commit diff
+#[must_use]
 pub fn make_commit_link(
 	owner: &str,
 	repo: &str,
@@ -51,38 +56,62 @@ pub fn make_commit_link(
 	)
 }
 
-/// Gather lines that are next to each other
-fn gather_lines(lines: &[u32]) -> Vec<(u32, u32)> {
-	lines.iter().sorted().copied().fold(Vec::<(u32, u32)>::new(), |mut acc, val| {
-		if acc.iter().any(|&(low, high)| val >= low && val <= high) {
-			return acc;
-		}
-
-		match acc.last_mut() {
-			Some(last) => {
-				if val != 0 && last.1 == val - 1 {
-					last.1 = val;
-				} else {
-					acc.push((val, val));
-				}
-			}
-			None => acc.push((val, val)),
+/// Makes a file, and optionally line, specific link to the content of a file
+/// at a commit
+#[must_use]
+pub fn make_blob_link(
+	owner: &str,
+	repo: &str,
+	commit_sha: &str,
+	path: &str,
+	line: Option<(u32, Option<u32>)>,
+) -> String {
+	format!(
+		"https://github.com/{}/{}/blob/{}/{}{}",
+		owner,
+		repo,
+		commit_sha,
+		path,
+		match line {
+			Some((start_line, Some(end_line))) =>
+				Cow::Owned(format!("#L{}-L{}", start_line, end_line)),
+			Some((line, None)) => Cow::Owned(format!("#L{}", line)),
+			None => Cow::Borrowed(""),
 		}
-
-		acc
-	})
+	)
 }
 
-#[cfg(test)]
-mod tests {
-	#[test]
-	fn test_gather_lines() {
-		use super::gather_lines;
+/// What the file and line links of a summary point to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkTarget<'a> {
+	/// The diff of a pull request
+	PullRequest(u64),
+	/// The diff of a commit
+	Commit(&'a str),
+	/// The content of the files at a commit, which resolves even if the diff
+	/// is collapsed or the file is unchanged
+	Blob(&'a str),
+}
 
-		assert_eq!(gather_lines(&[0, 1, 2, 3, 5, 6, 8, 10]), &[(0, 3), (5, 6), (8, 8), (10, 10)]);
-		assert_eq!(gather_lines(&[3, 1, 4, 5, 6, 8, 10]), &[(1, 1), (3, 6), (8, 8), (10, 10)]);
-		assert_eq!(gather_lines(&[]), &[]);
-		assert_eq!(gather_lines(&[0, 0, 1, 0]), &[(0, 1)]);
+/// Makes a file, and optionally line, specific link to the `target`
+fn file_link(
+	provider: &dyn VcsProvider,
+	owner: &str,
+	repo: &str,
+	target: LinkTarget<'_>,
+	path: &str,
+	line: LineRange,
+) -> String {
+	match target {
+		LinkTarget::PullRequest(pr_number) => {
+			provider.pull_request_file_link(owner, repo, pr_number, path, line)
+		}
+		LinkTarget::Commit(commit_sha) => {
+			provider.commit_file_link(owner, repo, commit_sha, path, line)
+		}
+		LinkTarget::Blob(commit_sha) => {
+			provider.blob_file_link(owner, repo, commit_sha, path, line)
+		}
 	}
 }
 
@@ -104,18 +133,19 @@ fn build_summary(summary: &str, table_rows: String) -> String {
 	}
 }
 
-/// Build comment summary for a commit in HTML
+/// Build comment summary for a commit in HTML, linking the files to `links`
 pub fn build_push_summary(
+	provider: &dyn VcsProvider,
 	owner: &str,
 	repo: &str,
-	commit_sha: &str,
+	links: LinkTarget<'_>,
 	report: &[PushFileCoverageWrapper],
 ) -> String {
 	build_summary("🐈‍⬛ Untested Lines 🐈‍⬛", report.iter().map(|file_cov|  {
         html_to_string_macro::html! {
             <tr>
                 <td>
-                <a href={make_commit_link(owner, repo, commit_sha, file_cov.sha.as_str(), None)}>{file_cov.realpath.as_str()}</a>
+                <a href={file_link(provider, owner, repo, links, file_cov.realpath.as_str(), None)}>{file_cov.realpath.as_str()}</a>
                 </td>
                 <td>
                     {
@@ -123,12 +153,12 @@ pub fn build_push_summary(
 							Cow::Owned(match start_line == end_line {
 								true => {
 									html_to_string_macro::html! {
-										<a href={make_commit_link(owner, repo, commit_sha, file_cov.sha.as_str(), Some((start_line, None)))}>{start_line}</a>
+										<a href={file_link(provider, owner, repo, links, file_cov.realpath.as_str(), Some((start_line, None)))}>{start_line}</a>
 									}
 								},
 								false => {
 									html_to_string_macro::html! {
-										<a href={make_commit_link(owner, repo, commit_sha, file_cov.sha.as_str(), Some((start_line, Some(end_line))))}>{start_line}"-"{end_line}</a>
+										<a href={file_link(provider, owner, repo, links, file_cov.realpath.as_str(), Some((start_line, Some(end_line))))}>{start_line}"-"{end_line}</a>
 									}
 								},
 							})
@@ -141,18 +171,19 @@ pub fn build_push_summary(
     }).collect())
 }
 
-/// Build comment summary for a PR in HTML
+/// Build comment summary for a PR in HTML, linking the files to `links`
 pub fn build_pull_summary(
+	provider: &dyn VcsProvider,
 	owner: &str,
 	repo: &str,
-	pull_id: u64,
+	links: LinkTarget<'_>,
 	report: &[PullFileCoverageWrapper],
 ) -> String {
 	build_summary("🐈‍⬛ Untested Changes 🐈‍⬛", report.iter().map(|file_cov|  {
         html_to_string_macro::html! {
             <tr>
                 <td>
-                <a href={make_pull_link(owner, repo, pull_id, file_cov.sha.as_str(), None)}>{file_cov.realpath.as_str()}</a>
+                <a href={file_link(provider, owner, repo, links, file_cov.realpath.as_str(), None)}>{file_cov.realpath.as_str()}</a>
                 </td>
                 <td>
                     {
@@ -160,12 +191,51 @@ pub fn build_pull_summary(
 							Cow::Owned(match start_line == end_line {
 								true => {
 									html_to_string_macro::html! {
-										<a href={make_pull_link(owner, repo, pull_id, file_cov.sha.as_str(), Some((start_line, None)))}>{start_line}</a>
+										<a href={file_link(provider, owner, repo, links, file_cov.realpath.as_str(), Some((start_line, None)))}>{start_line}</a>
+									}
+								},
+								false => {
+									html_to_string_macro::html! {
+										<a href={file_link(provider, owner, repo, links, file_cov.realpath.as_str(), Some((start_line, Some(end_line))))}>{start_line}"-"{end_line}</a>
+									}
+								},
+							})
+						}), Cow::Borrowed(", "))
+						.collect::<String>()
+                    }
+                </td>
+            </tr>
+        }
+    }).collect())
+}
+
+/// Build comment summary in HTML for untested ranges of a PR that did not get a
+/// review comment, linking the files to `links`
+pub fn build_pull_folded_summary(
+	provider: &dyn VcsProvider,
+	owner: &str,
+	repo: &str,
+	links: LinkTarget<'_>,
+	ranges: &[UntestedRange<'_>],
+) -> String {
+	build_summary("🐈‍⬛ Untested Changes Without Review Comments 🐈‍⬛", ranges.iter().group_by(|range| range.path).into_iter().map(|(path, ranges)| {
+        html_to_string_macro::html! {
+            <tr>
+                <td>
+                <a href={file_link(provider, owner, repo, links, path, None)}>{path}</a>
+                </td>
+                <td>
+                    {
+                        itertools::intersperse(ranges.map(|range| {
+							Cow::Owned(match range.first_line == range.final_line {
+								true => {
+									html_to_string_macro::html! {
+										<a href={file_link(provider, owner, repo, links, path, Some((range.first_line, None)))}>{range.first_line}</a>
 									}
 								},
 								false => {
 									html_to_string_macro::html! {
-										<a href={make_pull_link(owner, repo, pull_id, file_cov.sha.as_str(), Some((start_line, Some(end_line))))}>{start_line}"-"{end_line}</a>
+										<a href={file_link(provider, owner, repo, links, path, Some((range.first_line, Some(range.final_line))))}>{range.first_line}"-"{range.final_line}</a>
 									}
 								},
 							})
@@ -0,0 +1,175 @@
+//! Issues opened in the analysed repository when the coverage of a branch
+//! drops sharply, and closed once it recovered
+
+use crate::{
+	percentage::{Percentage, PercentageFormat},
+	provider::VcsProvider,
+	MeowCoverageError,
+};
+
+/// Rules for opening issues when the coverage of a tracked branch drops
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DropIssues {
+	/// Open an issue when the coverage percentage dropped by more than this
+	/// many points compared to the previous tracking record, [None] never
+	/// opens issues
+	pub drop: Option<f64>,
+	/// Labels of the opened issues
+	pub labels: Vec<String>,
+}
+
+/// Marker identifying the open drop issue of a branch
+fn issue_marker(branch: &str) -> String {
+	format!("<!-- meow-coverage-drop:{} -->", branch)
+}
+
+/// Prefix of the marker storing the coverage before the drop
+const BASELINE_MARKER: &str = "<!-- meow-coverage-baseline:";
+
+/// Parse the coverage before the drop from the body of a drop issue
+fn parse_baseline(body: &str) -> Option<f64> {
+	let (_, rest) = body.split_once(BASELINE_MARKER)?;
+	let (baseline, _) = rest.split_once(" -->")?;
+	baseline.parse().ok()
+}
+
+impl DropIssues {
+	/// Open an issue for `branch` if its coverage dropped from `old_percentage`
+	/// to `new_percentage` by more than [DropIssues::drop], or close the open
+	/// issue once the coverage is back at the coverage before the drop, with
+	/// the percentages in `format`
+	#[allow(clippy::too_many_arguments)]
+	pub async fn update(
+		&self,
+		provider: &dyn VcsProvider,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		old_percentage: Option<f64>,
+		new_percentage: f64,
+		report_link: &str,
+		format: PercentageFormat,
+	) -> Result<(), MeowCoverageError> {
+		let Some(drop) = self.drop else {
+			return Ok(());
+		};
+		let marker = issue_marker(branch);
+
+		match provider.find_open_issue(owner, repo, &marker).await? {
+			Some(issue) => {
+				let Some(baseline) = parse_baseline(&issue.body) else {
+					return Ok(());
+				};
+				if new_percentage >= baseline {
+					provider
+						.close_issue(
+							owner,
+							repo,
+							issue.number,
+							&format!(
+								"Coverage of `{}` recovered to {}% (was {}% before the drop).",
+								branch,
+								Percentage::from_f64(new_percentage).display_with(format),
+								Percentage::from_f64(baseline).display_with(format)
+							),
+						)
+						.await?;
+				}
+			}
+			None => {
+				let Some(old_percentage) = old_percentage else {
+					return Ok(());
+				};
+				if old_percentage - new_percentage > drop {
+					provider
+						.create_issue(
+							owner,
+							repo,
+							&format!(
+								"Coverage of `{}` dropped by {} points",
+								branch,
+								Percentage::from_f64(old_percentage - new_percentage).display_with(format)
+							),
+							&format!(
+								"{}\n{}{:.2} -->\n\nCoverage of `{}` dropped from {}% to {}%, more than the allowed {} points.\n\nReport: {}\n\nThis issue is closed automatically once the coverage is back at {}%.",
+								marker,
+								BASELINE_MARKER,
+								old_percentage,
+								branch,
+								Percentage::from_f64(old_percentage).display_with(format),
+								Percentage::from_f64(new_percentage).display_with(format),
+								Percentage::from_f64(drop).display_with(format),
+								report_link,
+								Percentage::from_f64(old_percentage).display_with(format)
+							),
+							&self.labels,
+						)
+						.await?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::DropIssues;
+	use crate::{percentage::PercentageFormat, provider::fake::FakeProvider};
+
+	#[tokio::test]
+	async fn test_drop_issue_lifecycle() {
+		let provider = FakeProvider::default();
+		let rules = DropIssues { drop: Some(5.0), labels: vec![String::from("coverage")] };
+		let update = |old_percentage, new_percentage| {
+			rules.update(
+				&provider,
+				"famedly",
+				"meow",
+				"main",
+				old_percentage,
+				new_percentage,
+				"https://example.com/report",
+				PercentageFormat::default(),
+			)
+		};
+
+		update(Some(80.0), 76.0).await.unwrap();
+		update(None, 70.0).await.unwrap();
+		assert!(provider.issues.lock().unwrap().is_empty());
+
+		update(Some(80.0), 70.0).await.unwrap();
+		update(Some(70.0), 60.0).await.unwrap();
+		{
+			let issues = provider.issues.lock().unwrap();
+			assert_eq!(issues.len(), 1);
+			assert_eq!(issues[0].title, "Coverage of `main` dropped by 10.00 points");
+			assert!(issues[0].body.contains("https://example.com/report"));
+			assert_eq!(issues[0].labels, ["coverage"]);
+		}
+
+		update(Some(60.0), 79.0).await.unwrap();
+		assert!(provider.issues.lock().unwrap()[0].closing_comment.is_none());
+		update(Some(79.0), 80.5).await.unwrap();
+		assert_eq!(
+			provider.issues.lock().unwrap()[0].closing_comment.as_deref(),
+			Some("Coverage of `main` recovered to 80.50% (was 80.00% before the drop).")
+		);
+
+		DropIssues::default()
+			.update(
+				&provider,
+				"famedly",
+				"meow",
+				"main",
+				Some(80.0),
+				0.0,
+				"",
+				PercentageFormat::default(),
+			)
+			.await
+			.unwrap();
+		assert_eq!(provider.issues.lock().unwrap().len(), 1);
+	}
+}
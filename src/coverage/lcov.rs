@@ -1,107 +1,679 @@
 //! Helpers for handling code coverage report in the `lcov` format
-use std::path::Path;
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+	convert::Infallible,
+	io::BufRead,
+	path::{Path, PathBuf},
+	str::FromStr,
+	sync::OnceLock,
+};
 
-use lcov::{report::ParseError, Record, Report};
+use lcov::{reader::Error as ReadError, report::ParseError, Reader, Record};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+	exclusion::ExclusionMarkers,
+	helpers::{enclosing_function, normalise_path, PathStrategy},
+};
+use crate::{metrics, MeowCoverageError};
+
+/// Paths coverage tools commonly write Lcov reports to, relative to the
+/// working directory
+const WELL_KNOWN_LCOV_PATHS: [&str; 6] = [
+	"lcov.info",
+	"coverage/lcov.info",
+	"coverage.lcov",
+	"target/lcov.info",
+	"target/coverage/lcov.info",
+	"target/llvm-cov/lcov.info",
+];
+
+/// Lcov reports near `path`: the well known paths that exist, and the
+/// `.info` and `.lcov` files in the directory of `path`, sorted
+fn lcov_candidates(path: &Path) -> Vec<String> {
+	let well_known = WELL_KNOWN_LCOV_PATHS.iter().map(PathBuf::from).filter(|path| path.is_file());
+	let siblings = path
+		.parent()
+		.map(|parent| match parent.as_os_str().is_empty() {
+			true => Path::new("."),
+			false => parent,
+		})
+		.and_then(|parent| std::fs::read_dir(parent).ok())
+		.into_iter()
+		.flatten()
+		.filter_map(|entry| Some(entry.ok()?.path()))
+		.filter(|path| {
+			path.is_file()
+				&& path
+					.extension()
+					.is_some_and(|extension| extension == "info" || extension == "lcov")
+		});
+
+	let mut candidates = well_known
+		.chain(siblings)
+		.map(|path| path.strip_prefix("./").unwrap_or(&path).to_string_lossy().into_owned())
+		.collect::<Vec<_>>();
+	candidates.sort();
+	candidates.dedup();
+	candidates
+}
+
+/// Check that the Lcov report at `path` exists before reading it, failing
+/// with [MeowCoverageError::LcovFileNotFound] listing the reports nearby
+/// otherwise
+pub fn check_lcov_file(path: &str) -> Result<(), MeowCoverageError> {
+	match Path::new(path).is_file() {
+		true => Ok(()),
+		false => Err(MeowCoverageError::LcovFileNotFound {
+			path: String::from(path),
+			candidates: lcov_candidates(Path::new(path)),
+		}),
+	}
+}
+
+/// Hex SHA-256 digest of the Lcov reports of `inputs` in order, recorded
+/// with tracking records to identify the reports they were built from
+pub fn digest_inputs(inputs: &[LcovInput]) -> Result<String, MeowCoverageError> {
+	let mut hasher = Sha256::new();
+	for input in inputs {
+		hasher.update(std::fs::read(&input.path)?);
+	}
+	Ok(hex::encode(hasher.finalize()))
+}
+
+/// An Lcov report passed as `PATH` or `LABEL=PATH`, labelled reports of one
+/// run (for example `frontend` and `backend`) are merged into its total and
+/// get their own total in comments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LcovInput {
+	/// Label of the report
+	pub label: Option<String>,
+	/// Path of the report
+	pub path: String,
+}
+
+impl LcovInput {
+	/// Input of the report at `path` without a label
+	#[must_use]
+	pub fn unlabelled(path: &str) -> Self {
+		Self { label: None, path: String::from(path) }
+	}
+
+	/// Name of the report in comments, its label or else its path
+	#[must_use]
+	pub fn name(&self) -> &str {
+		self.label.as_deref().unwrap_or(&self.path)
+	}
+}
+
+impl FromStr for LcovInput {
+	type Err = Infallible;
+
+	/// Parse `LABEL=PATH`, anything before an `=` that looks like a path
+	/// (for example `out/a=b.info`) is part of the path
+	fn from_str(input: &str) -> Result<Self, Self::Err> {
+		Ok(match input.split_once('=') {
+			Some((label, path))
+				if !label.is_empty() && !path.is_empty() && !label.contains(['/', '\\']) =>
+			{
+				Self { label: Some(String::from(label)), path: String::from(path) }
+			}
+			_ => Self::unlabelled(input),
+		})
+	}
+}
+
+/// Total of one of several reports of a run, see [LcovInput]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabelTotal {
+	/// Label (or path) of the report
+	pub label: String,
+	/// Total percentage coverage of the report, [None] if no lines are
+	/// instrumented
+	pub percentage: Option<f64>,
+}
 
 /// A per-file "coverage report" (contains only unhit lines)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LcovFileCoverage {
 	/// File name
 	pub filename: String,
 	/// Percentage file coverage
 	pub percentage: f64,
+	/// Number of instrumented lines
+	#[serde(default)]
+	pub lines_found: u64,
 	/// Untested lines
 	pub lines: Vec<u32>,
+	/// Functions as `(start_line, name)`, sorted by start line
+	#[serde(default)]
+	pub functions: Vec<(u32, String)>,
+	/// Line coverage of every function, in the order of
+	/// [LcovFileCoverage::functions]
+	#[serde(default)]
+	pub function_coverage: Vec<FunctionCoverage>,
+}
+
+/// Line coverage of a function, counting the instrumented lines from its start
+/// up to the start of the next function
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionCoverage {
+	/// Function name
+	pub name: String,
+	/// First line of the function
+	pub start_line: u32,
+	/// Number of instrumented lines
+	pub lines_found: u32,
+	/// Number of untested lines
+	pub lines_untested: u32,
+}
+
+impl LcovFileCoverage {
+	/// Name of the function a line belongs to, going by the closest function
+	/// starting at or before the line
+	#[must_use]
+	pub fn enclosing_function(&self, line: u32) -> Option<&str> {
+		enclosing_function(&self.functions, line)
+	}
+
+	/// Coverage of the function a line belongs to, see
+	/// [LcovFileCoverage::enclosing_function]
+	#[must_use]
+	pub fn enclosing_function_coverage(&self, line: u32) -> Option<&FunctionCoverage> {
+		let index = self.function_coverage.partition_point(|function| function.start_line <= line);
+		self.function_coverage.get(index.checked_sub(1)?)
+	}
+}
+
+/// A per-file "detailed coverage report" (contains the hit count of every
+/// instrumented line)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LcovFileDetail {
+	/// File name
+	pub filename: String,
+	/// Instrumented lines and how often they were hit
+	pub lines: Vec<(u32, u64)>,
+}
+
+impl LcovFileDetail {
+	/// Lines that were hit exactly once, and are therefore barely tested
+	pub fn barely_tested_lines(&self) -> impl Iterator<Item = u32> + '_ {
+		self.lines.iter().filter(|(_, hits)| *hits == 1).map(|(line, _)| *line)
+	}
+}
+
+/// Totals and per-file data of an Lcov report
+#[derive(Debug, Clone, Default)]
+pub struct LcovStatistics {
+	/// Total number of hit lines
+	pub lines_hit: u64,
+	/// Total number of instrumented lines
+	pub lines_found: u64,
+	/// Per-file coverage
+	pub files: Vec<LcovFileCoverage>,
+}
+
+impl LcovStatistics {
+	/// Calculate the percentage coverage, [None] if no lines are
+	/// instrumented
+	#[must_use]
+	pub fn percentage(&self) -> Option<f64> {
+		metrics::percentage(self.lines_hit, self.lines_found)
+	}
+
+	/// Number of files in the report
+	#[must_use]
+	pub fn file_count(&self) -> usize {
+		self.files.len()
+	}
+}
+
+/// Data of a file gathered by [LcovStatisticsBuilder]
+#[derive(Debug)]
+struct FileAccumulator {
+	/// File name
+	filename: String,
+	/// Hit counts per line
+	lines: BTreeMap<u32, u64>,
+	/// Functions as `(start_line, name)`
+	functions: BTreeSet<(u32, String)>,
+}
+
+/// Folds Lcov records into [LcovStatistics] one record at a time, merging
+/// repeated `SF` sections of the same file by taking the highest hit count of
+/// each line
+#[derive(Debug, Default)]
+struct LcovStatisticsBuilder {
+	/// Every file, in order of first appearance
+	files: Vec<FileAccumulator>,
+	/// Index into `files` by file name
+	indices: HashMap<String, usize>,
+	/// Index of the file of the current section
+	current: Option<usize>,
+}
+
+impl LcovStatisticsBuilder {
+	/// Fold a record into the statistics
+	fn push(&mut self, record: &Record) {
+		match record {
+			Record::SourceFile { path } => {
+				let filename = path.to_string_lossy().to_string();
+				let index = *self.indices.entry(filename.clone()).or_insert_with(|| {
+					self.files.push(FileAccumulator {
+						filename,
+						lines: BTreeMap::new(),
+						functions: BTreeSet::new(),
+					});
+					self.files.len() - 1
+				});
+				self.current = Some(index);
+			}
+			Record::LineData { line, count, .. } => {
+				if let Some(file) = self.current.and_then(|index| self.files.get_mut(index)) {
+					let hits = file.lines.entry(*line).or_default();
+					*hits = (*hits).max(*count);
+				}
+			}
+			Record::FunctionName { name, start_line } => {
+				if let Some(file) = self.current.and_then(|index| self.files.get_mut(index)) {
+					file.functions.insert((*start_line, name.clone()));
+				}
+			}
+			Record::EndOfRecord => self.current = None,
+			_ => {}
+		}
+	}
+
+	/// Finish folding into the hit counts per file
+	fn details(self) -> Vec<LcovFileDetail> {
+		self.files
+			.into_iter()
+			.map(|file| LcovFileDetail {
+				filename: file.filename,
+				lines: file.lines.into_iter().collect(),
+			})
+			.collect()
+	}
+
+	/// Finish folding
+	fn build(self) -> LcovStatistics {
+		let mut statistics = LcovStatistics::default();
+
+		for FileAccumulator { filename, lines, functions } in self.files {
+			let lines_found = lines.len() as u64;
+			let lines_hit = lines.values().filter(|hits| **hits > 0).count() as u64;
+
+			let functions: Vec<_> = functions.into_iter().collect();
+			let mut function_coverage: Vec<_> = functions
+				.iter()
+				.map(|(start_line, name)| FunctionCoverage {
+					name: name.clone(),
+					start_line: *start_line,
+					lines_found: 0,
+					lines_untested: 0,
+				})
+				.collect();
+			for (line, hits) in &lines {
+				let index =
+					function_coverage.partition_point(|function| function.start_line <= *line);
+				if let Some(function) =
+					index.checked_sub(1).and_then(|index| function_coverage.get_mut(index))
+				{
+					function.lines_found += 1;
+					if *hits == 0 {
+						function.lines_untested += 1;
+					}
+				}
+			}
+
+			statistics.lines_hit += lines_hit;
+			statistics.lines_found += lines_found;
+			statistics.files.push(LcovFileCoverage {
+				filename,
+				functions,
+				function_coverage,
+				percentage: metrics::fraction(lines_hit, lines_found).unwrap_or_default(),
+				lines_found,
+				lines: lines
+					.into_iter()
+					.filter_map(|(line, hits)| (hits == 0).then_some(line))
+					.collect(),
+			});
+		}
+
+		statistics
+	}
 }
 
 /// Wrapper for operations on a coverage reports
 #[derive(Debug)]
-pub struct LcovWrapper(Vec<Record>);
+pub struct LcovWrapper {
+	/// Records of the report
+	records: Vec<Record>,
+	/// Statistics of the report, computed on first use
+	statistics: OnceLock<LcovStatistics>,
+}
 
 impl LcovWrapper {
 	/// Build a new [LcovWrapper]
 	pub fn new<P: AsRef<Path>>(file_path: P) -> Result<Self, ParseError> {
-		Report::from_file(file_path)
-			.map(Report::into_records)
-			.map(Iterator::collect::<Vec<_>>)
-			.map(Self)
+		let reader = Reader::open_file(file_path).map_err(ReadError::Io)?;
+		Self::from_records(reader)
+	}
+
+	/// Build a new [LcovWrapper] from a reader over an Lcov report
+	pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, ParseError> {
+		Self::from_records(Reader::new(reader))
 	}
 
-	/// Calculate the percentage coverage
+	/// Build a new [LcovWrapper] from Lcov records
+	fn from_records<B: BufRead>(reader: Reader<B>) -> Result<Self, ParseError> {
+		let records = reader.collect::<Result<Vec<_>, _>>()?;
+		Ok(Self { records, statistics: OnceLock::new() })
+	}
+
+	/// Merge several reports into one, files covered by several reports take
+	/// the highest hit count of each line
 	#[must_use]
-	pub fn percentage(&self) -> f64 {
-		let (lines_hit, lines_found) =
-			self.0.iter().fold((0, 0), |(line_hits, lines_found), record| match record {
-				Record::LinesHit { hit } => (line_hits + u64::from(*hit), lines_found),
-				Record::LinesFound { found } => (line_hits, lines_found + u64::from(*found)),
-				_ => (line_hits, lines_found),
-			});
+	pub fn merge(reports: Vec<Self>) -> Self {
+		Self {
+			records: reports.into_iter().flat_map(|report| report.records).collect(),
+			statistics: OnceLock::new(),
+		}
+	}
 
-		(lines_hit as f64 / lines_found as f64) * 100.0
+	/// Compute the [LcovStatistics] of an Lcov report in a single pass,
+	/// without keeping its records in memory, see
+	/// [LcovWrapper::normalise_paths] for `strip_root`
+	pub fn stream<P: AsRef<Path>>(
+		file_path: P,
+		strip_root: Option<&str>,
+	) -> Result<LcovStatistics, ParseError> {
+		let reader = Reader::open_file(file_path).map_err(ReadError::Io)?;
+		Self::stream_records(reader, strip_root)
 	}
 
-	/// Diff the percentages of a newer coverage file with the current one
+	/// Compute the [LcovStatistics] of an Lcov report from a reader in a single
+	/// pass, without keeping its records in memory
+	pub fn stream_reader<R: BufRead>(
+		reader: R,
+		strip_root: Option<&str>,
+	) -> Result<LcovStatistics, ParseError> {
+		Self::stream_records(Reader::new(reader), strip_root)
+	}
+
+	/// Fold Lcov records into [LcovStatistics], normalising source file paths
+	/// on the way
+	fn stream_records<B: BufRead>(
+		reader: Reader<B>,
+		strip_root: Option<&str>,
+	) -> Result<LcovStatistics, ParseError> {
+		let mut builder = LcovStatisticsBuilder::default();
+		for record in reader {
+			let mut record = record?;
+			if let Record::SourceFile { path } = &mut record {
+				*path = PathBuf::from(normalise_path(&path.to_string_lossy(), strip_root));
+			}
+			builder.push(&record);
+		}
+
+		Ok(builder.build())
+	}
+
+	/// Get the [LcovStatistics] of the report, which are computed in a single
+	/// pass over the records on first use
+	pub fn statistics(&self) -> &LcovStatistics {
+		self.statistics.get_or_init(|| {
+			let mut builder = LcovStatisticsBuilder::default();
+			for record in &self.records {
+				builder.push(record);
+			}
+
+			builder.build()
+		})
+	}
+
+	/// Calculate the percentage coverage, [None] if no lines are
+	/// instrumented
 	#[must_use]
-	pub fn percentage_difference(&self, new_lcov: &Self) -> f64 {
-		new_lcov.percentage() - self.percentage()
+	pub fn percentage(&self) -> Option<f64> {
+		self.statistics().percentage()
+	}
+
+	/// Diff the percentages of a newer coverage file with the current one,
+	/// [None] if either has no instrumented lines
+	#[must_use]
+	pub fn percentage_difference(&self, new_lcov: &Self) -> Option<f64> {
+		metrics::delta(new_lcov.percentage(), self.percentage())
 	}
 
 	/// Group coverage data by file
 	#[must_use]
 	pub fn group_data(&self) -> Vec<LcovFileCoverage> {
-		let mut files = Vec::new();
-		let mut lines_hit = None;
-		let mut lines_found = None;
-
-		for record in &self.0 {
-			match record {
-				Record::SourceFile { path } => {
-					lines_hit = None;
-					lines_found = None;
-
-					files.push(LcovFileCoverage {
-						filename: path.to_string_lossy().to_string(),
-						percentage: 0_f64,
-						lines: Vec::new(),
-					});
-				}
-				Record::LineData { line, count, .. } => {
-					if *count == 0 {
-						if let Some(last) = files.last_mut() {
-							last.lines.push(*line);
-						}
-					}
-				}
-				Record::LinesHit { hit } => {
-					lines_hit = Some(*hit);
+		self.statistics().files.clone()
+	}
 
-					if let Some(found) = lines_found {
-						let percentage = f64::from(*hit) / f64::from(found);
-						if let Some(last) = files.last_mut() {
-							last.percentage = percentage;
-						}
+	/// Normalise the paths of all files with [normalise_path]
+	pub fn normalise_paths(&mut self, strip_root: Option<&str>) {
+		for record in &mut self.records {
+			if let Record::SourceFile { path } = record {
+				*path = PathBuf::from(normalise_path(&path.to_string_lossy(), strip_root));
+			}
+		}
+		self.statistics = OnceLock::new();
+	}
 
-						lines_found = None;
-						lines_hit = None;
-					}
-				}
-				Record::LinesFound { found } => {
-					lines_found = Some(*found);
+	/// Only keep the line data for which `keep(filename, line)` returns true
+	pub fn retain_lines<F: FnMut(&str, u32) -> bool>(&mut self, mut keep: F) {
+		let mut filename = None;
 
-					if let Some(hit) = lines_hit {
-						let percentage = f64::from(hit) / f64::from(*found);
-						if let Some(last) = files.last_mut() {
-							last.percentage = percentage;
-						}
+		self.records.retain(|record| match record {
+			Record::SourceFile { path } => {
+				filename = Some(path.to_string_lossy().to_string());
+				true
+			}
+			Record::LineData { line, .. } => match filename.as_deref() {
+				Some(filename) => keep(filename, *line),
+				None => true,
+			},
+			_ => true,
+		});
+		self.statistics = OnceLock::new();
+	}
 
-						lines_found = None;
-						lines_hit = None;
-					}
-				}
-				_ => {}
+	/// Only keep the files for which `keep(filename)` returns true
+	pub fn retain_files<F: FnMut(&str) -> bool>(&mut self, mut keep: F) {
+		let mut keep_current = true;
+
+		self.records.retain(|record| match record {
+			Record::SourceFile { path } => {
+				keep_current = keep(&path.to_string_lossy());
+				keep_current
 			}
+			Record::EndOfRecord => std::mem::replace(&mut keep_current, true),
+			_ => keep_current,
+		});
+		self.statistics = OnceLock::new();
+	}
+
+	/// Drop lines excluded by [ExclusionMarkers] in the source files from the
+	/// report
+	pub fn apply_exclusions(&mut self, markers: &ExclusionMarkers, path_strategy: &PathStrategy) {
+		let mut excluded: HashMap<String, HashSet<u32>> = HashMap::new();
+
+		self.retain_lines(|filename, line| {
+			!excluded
+				.entry(String::from(filename))
+				.or_insert_with(|| markers.read_excluded_lines(filename, path_strategy))
+				.contains(&line)
+		});
+	}
+
+	/// Group the hit counts of all instrumented lines by file
+	#[must_use]
+	pub fn detailed_data(&self) -> Vec<LcovFileDetail> {
+		let mut builder = LcovStatisticsBuilder::default();
+		for record in &self.records {
+			builder.push(record);
 		}
 
-		files
+		builder.details()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{check_lcov_file, LcovFileDetail, LcovInput, LcovWrapper};
+	use crate::MeowCoverageError;
+
+	#[test]
+	fn test_check_lcov_file() {
+		let directory = std::env::temp_dir().join("meow-coverage-test-check-lcov-file");
+		let _ = std::fs::remove_dir_all(&directory);
+		std::fs::create_dir_all(&directory).unwrap();
+		std::fs::write(directory.join("unit.info"), "").unwrap();
+		std::fs::write(directory.join("report.lcov"), "").unwrap();
+		std::fs::write(directory.join("notes.txt"), "").unwrap();
+
+		let found = directory.join("unit.info");
+		assert!(check_lcov_file(found.to_str().unwrap()).is_ok());
+
+		let missing = directory.join("lcov.info");
+		let error = check_lcov_file(missing.to_str().unwrap()).unwrap_err();
+		assert!(matches!(
+			&error,
+			MeowCoverageError::LcovFileNotFound { candidates, .. }
+				if candidates.iter().any(|candidate| candidate.ends_with("report.lcov"))
+					&& candidates.iter().any(|candidate| candidate.ends_with("unit.info"))
+					&& !candidates.iter().any(|candidate| candidate.ends_with("notes.txt"))
+		));
+		assert!(error.to_string().contains("did you mean one of these?"));
+	}
+
+	#[test]
+	fn test_detailed_data() {
+		let lcov = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,3\nDA:2,1\nDA:3,0\nLF:3\nLH:2\nend_of_record\n".as_bytes(),
+		)
+		.unwrap();
+
+		let files = lcov.detailed_data();
+		assert_eq!(
+			files,
+			&[LcovFileDetail {
+				filename: String::from("/build/src/lib.rs"),
+				lines: vec![(1, 3), (2, 1), (3, 0)],
+			}]
+		);
+		assert_eq!(files[0].barely_tested_lines().collect::<Vec<_>>(), &[2]);
+	}
+
+	#[test]
+	fn test_stream_matches_in_memory() {
+		let report = "SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\nSF:/build/src/main.rs\nDA:1,1\nLF:1\nLH:1\nend_of_record\n";
+
+		let streamed = LcovWrapper::stream_reader(report.as_bytes(), None).unwrap();
+		let lcov = LcovWrapper::from_reader(report.as_bytes()).unwrap();
+
+		assert_eq!(streamed.percentage(), lcov.percentage());
+		assert_eq!(streamed.file_count(), lcov.statistics().file_count());
+		assert_eq!(streamed.files.len(), 2);
+		assert_eq!(streamed.files[0].lines, &[2]);
+		assert_eq!(streamed.files[0].percentage, 0.5);
+		assert_eq!(streamed.files[1].percentage, 1.0);
+	}
+
+	#[test]
+	fn test_stream_normalises_paths() {
+		let report = "SF:C:\\build\\src\\lib.rs\nDA:1,1\nend_of_record\n";
+
+		let streamed = LcovWrapper::stream_reader(report.as_bytes(), Some("C:\\build")).unwrap();
+
+		assert_eq!(streamed.files[0].filename, "src/lib.rs");
+	}
+
+	#[test]
+	fn test_empty_report() {
+		let lcov =
+			LcovWrapper::from_reader("SF:/build/src/lib.rs\nend_of_record\n".as_bytes()).unwrap();
+
+		assert_eq!(lcov.percentage(), None);
+		assert_eq!(lcov.percentage_difference(&lcov), None);
+		assert_eq!(lcov.group_data()[0].percentage, 0_f64);
+	}
+
+	#[test]
+	fn test_retain_lines() {
+		let mut lcov = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nDA:3,0\nLF:3\nLH:1\nend_of_record\n".as_bytes(),
+		)
+		.unwrap();
+		assert_eq!(lcov.group_data()[0].lines, &[2, 3]);
+
+		lcov.retain_lines(|filename, line| filename != "/build/src/lib.rs" || line != 2);
+
+		assert_eq!(lcov.group_data()[0].lines, &[3]);
+		assert_eq!(lcov.percentage(), Some(50_f64));
+	}
+
+	#[test]
+	fn test_retain_files() {
+		let mut lcov = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nend_of_record\nSF:/build/target/generated.rs\nDA:1,0\nend_of_record\n".as_bytes(),
+		)
+		.unwrap();
+
+		lcov.retain_files(|filename| !filename.contains("/target/"));
+
+		let files = lcov.group_data();
+		assert_eq!(files.len(), 1);
+		assert_eq!(files[0].filename, "/build/src/lib.rs");
+		assert_eq!(lcov.percentage(), Some(50_f64));
+	}
+
+	#[test]
+	fn test_merge_duplicate_sections() {
+		let lcov = LcovWrapper::from_reader(
+			"TN:unit\nSF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nDA:3,0\nLF:3\nLH:1\nend_of_record\nTN:integration\nSF:/build/src/lib.rs\nDA:1,0\nDA:2,4\nDA:3,0\nDA:4,0\nLF:4\nLH:1\nend_of_record\n"
+				.as_bytes(),
+		)
+		.unwrap();
+
+		let files = lcov.group_data();
+		assert_eq!(files.len(), 1);
+		assert_eq!(files[0].lines, &[3, 4]);
+		assert_eq!(files[0].percentage, 0.5);
+		assert_eq!(lcov.percentage(), Some(50_f64));
+		assert_eq!(lcov.detailed_data()[0].lines, &[(1, 1), (2, 4), (3, 0), (4, 0)]);
+	}
+
+	#[test]
+	fn test_merge_reports() {
+		let frontend = LcovWrapper::from_reader(
+			"SF:/build/web/app.ts\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\n".as_bytes(),
+		)
+		.unwrap();
+		let backend = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,1\nLF:2\nLH:2\nend_of_record\n".as_bytes(),
+		)
+		.unwrap();
+
+		let lcov = LcovWrapper::merge(vec![frontend, backend]);
+		assert_eq!(lcov.statistics().file_count(), 2);
+		assert_eq!(lcov.percentage(), Some(75_f64));
+	}
+
+	#[test]
+	fn test_parse_lcov_input() {
+		let input: LcovInput = "frontend=web/lcov.info".parse().unwrap();
+		assert_eq!(input.label.as_deref(), Some("frontend"));
+		assert_eq!(input.path, "web/lcov.info");
+		assert_eq!(input.name(), "frontend");
+
+		for path in ["lcov.info", "out/a=b.info", "=lcov.info", "backend="] {
+			assert_eq!(path.parse::<LcovInput>().unwrap(), LcovInput::unlabelled(path));
+		}
+		assert_eq!(LcovInput::unlabelled("lcov.info").name(), "lcov.info");
 	}
 }
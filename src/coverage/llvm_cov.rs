@@ -0,0 +1,197 @@
+//! Helpers for handling code coverage reports in the `llvm-cov export` JSON
+//! format (as produced by `cargo llvm-cov --json`), which carries
+//! column-precise regions instead of whole lines
+use std::{collections::BTreeMap, io::Read, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::MeowCoverageError;
+
+/// Index of the region kind in an exported region
+const REGION_KIND: usize = 7;
+
+/// Region kind of regions that map to code
+const CODE_REGION: u64 = 0;
+
+/// Position of a region as `(line_start, column_start, line_end, column_end)`
+type RegionPosition = (u32, u32, u32, u32);
+
+/// Root of an `llvm-cov export` document
+#[derive(Debug, Deserialize)]
+struct Export {
+	/// Exported coverage data, one entry per object
+	data: Vec<ExportData>,
+}
+
+/// Coverage data of a single object
+#[derive(Debug, Deserialize)]
+struct ExportData {
+	/// Instrumented functions
+	#[serde(default)]
+	functions: Vec<ExportFunction>,
+}
+
+/// An instrumented function
+#[derive(Debug, Deserialize)]
+struct ExportFunction {
+	/// Regions in format `[line_start, column_start, line_end, column_end,
+	/// execution_count, file_id, expanded_file_id, kind, ...]`
+	regions: Vec<Vec<u64>>,
+	/// Files referenced by the regions' `file_id`
+	filenames: Vec<String>,
+}
+
+/// A region of source code and how often it was executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CoverageRegion {
+	/// First line of the region
+	pub line_start: u32,
+	/// First column of the region
+	pub column_start: u32,
+	/// Final line of the region
+	pub line_end: u32,
+	/// Column after the end of the region
+	pub column_end: u32,
+	/// Execution count
+	pub count: u64,
+}
+
+/// Uncovered regions of a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRegionCoverage {
+	/// File name
+	pub filename: String,
+	/// Regions that were never executed, sorted by position
+	pub uncovered_regions: Vec<CoverageRegion>,
+}
+
+/// Wrapper for operations on `llvm-cov export` JSON reports
+#[derive(Debug)]
+pub struct LlvmCovWrapper(Vec<ExportFunction>);
+
+impl LlvmCovWrapper {
+	/// Build a new [LlvmCovWrapper]
+	pub fn new<P: AsRef<Path>>(file_path: P) -> Result<Self, MeowCoverageError> {
+		Self::from_reader(std::io::BufReader::new(std::fs::File::open(file_path)?))
+	}
+
+	/// Build a new [LlvmCovWrapper] from a reader over a JSON report
+	pub fn from_reader<R: Read>(reader: R) -> Result<Self, MeowCoverageError> {
+		let export: Export = serde_json::from_reader(reader)?;
+		Ok(Self(export.data.into_iter().flat_map(|data| data.functions).collect()))
+	}
+
+	/// Collect the never executed code regions per file
+	///
+	/// Generic functions are exported once per instantiation, so a region
+	/// counts as executed if any instantiation executed it
+	#[must_use]
+	pub fn uncovered_regions(&self) -> Vec<FileRegionCoverage> {
+		let mut regions: BTreeMap<&str, BTreeMap<RegionPosition, u64>> = BTreeMap::new();
+
+		for function in &self.0 {
+			for region in &function.regions {
+				let &[line_start, column_start, line_end, column_end, count, file_id, ..] =
+					region.as_slice()
+				else {
+					continue;
+				};
+
+				if region.get(REGION_KIND).copied().unwrap_or(CODE_REGION) != CODE_REGION {
+					continue;
+				}
+
+				let Some(filename) = usize::try_from(file_id)
+					.ok()
+					.and_then(|file_id| function.filenames.get(file_id))
+				else {
+					continue;
+				};
+
+				let key = (
+					u32::try_from(line_start).unwrap_or(u32::MAX),
+					u32::try_from(column_start).unwrap_or(u32::MAX),
+					u32::try_from(line_end).unwrap_or(u32::MAX),
+					u32::try_from(column_end).unwrap_or(u32::MAX),
+				);
+				let entry = regions.entry(filename.as_str()).or_default().entry(key).or_default();
+				*entry = (*entry).max(count);
+			}
+		}
+
+		regions
+			.into_iter()
+			.filter_map(|(filename, regions)| {
+				let uncovered_regions: Vec<_> = regions
+					.into_iter()
+					.filter(|(_, count)| *count == 0)
+					.map(|((line_start, column_start, line_end, column_end), count)| {
+						CoverageRegion { line_start, column_start, line_end, column_end, count }
+					})
+					.collect();
+
+				(!uncovered_regions.is_empty()).then(|| FileRegionCoverage {
+					filename: String::from(filename),
+					uncovered_regions,
+				})
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{CoverageRegion, FileRegionCoverage, LlvmCovWrapper};
+
+	#[test]
+	fn test_uncovered_regions() {
+		let json = r#"{
+			"type": "llvm.coverage.json.export",
+			"version": "2.0.1",
+			"data": [{
+				"files": [],
+				"functions": [
+					{
+						"name": "generic::<u8>",
+						"count": 1,
+						"filenames": ["/build/src/lib.rs"],
+						"regions": [
+							[1, 1, 3, 2, 1, 0, 0, 0],
+							[2, 5, 2, 20, 0, 0, 0, 0],
+							[4, 5, 4, 9, 0, 0, 0, 0],
+							[5, 1, 5, 9, 0, 0, 0, 2]
+						]
+					},
+					{
+						"name": "generic::<u16>",
+						"count": 1,
+						"filenames": ["/build/src/lib.rs"],
+						"regions": [[4, 5, 4, 9, 3, 0, 0, 0]]
+					},
+					{
+						"name": "tested",
+						"count": 4,
+						"filenames": ["/build/src/main.rs"],
+						"regions": [[1, 1, 2, 2, 4, 0, 0, 0]]
+					}
+				]
+			}]
+		}"#;
+
+		let report = LlvmCovWrapper::from_reader(json.as_bytes()).unwrap();
+
+		assert_eq!(
+			report.uncovered_regions(),
+			&[FileRegionCoverage {
+				filename: String::from("/build/src/lib.rs"),
+				uncovered_regions: vec![CoverageRegion {
+					line_start: 2,
+					column_start: 5,
+					line_end: 2,
+					column_end: 20,
+					count: 0
+				}],
+			}]
+		);
+	}
+}
@@ -0,0 +1,369 @@
+//! Options for loading and filtering the Lcov reports of a coverage run
+use std::{collections::HashSet, fs::OpenOptions, io::Write, path::PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+
+use super::{
+	carryforward::Carryforward,
+	exclusion::ExclusionMarkers,
+	helpers::{path_split, PathStrategy},
+	html::LinkTarget,
+	lcov::{check_lcov_file, LabelTotal, LcovInput, LcovStatistics, LcovWrapper},
+	output::RunOutput,
+	owners::CoverageOwners,
+	trend::Trend,
+	uninstrumented::UninstrumentedFiles,
+};
+use crate::{
+	percentage::PercentageFormat,
+	provider::{CommitStatusState, PullRequestInfo, VcsProvider},
+	MeowCoverageError,
+};
+
+/// Options for loading and filtering Lcov reports
+#[derive(Debug, Clone, Default)]
+pub struct LcovOptions {
+	/// Markers excluding lines from coverage
+	pub exclusions: ExclusionMarkers,
+	/// Only report files that exist in the repository at the analysed commit,
+	/// dropping generated or untracked files
+	pub existing_files_only: bool,
+	/// Root to strip from the paths in the reports (for example `C:\build`),
+	/// after normalising Windows path separators
+	pub strip_root: Option<String>,
+	/// Repository files matching these globs that are missing from the
+	/// reports are listed as uninstrumented
+	pub uninstrumented_paths: Option<GlobSet>,
+	/// Count the lines of uninstrumented files as untested in the total
+	pub count_uninstrumented: bool,
+	/// Drop the files matching these globs from the reports
+	pub ignore_paths: Option<GlobSet>,
+}
+
+impl LcovOptions {
+	/// Fetch the files of the repository at the analysed commit, if required
+	/// for filtering or finding uninstrumented files
+	pub async fn repository_files(
+		&self,
+		provider: &dyn VcsProvider,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+	) -> Result<Option<HashSet<String>>, MeowCoverageError> {
+		match self.existing_files_only || self.uninstrumented_paths.is_some() {
+			true => Ok(Some(provider.list_files(owner, repo, commit_sha).await?)),
+			false => Ok(None),
+		}
+	}
+
+	/// Load an Lcov report and apply the options to it
+	pub fn load(
+		&self,
+		lcov_path: &str,
+		path_strategy: &PathStrategy,
+		repository_files: Option<&HashSet<String>>,
+	) -> Result<LcovWrapper, MeowCoverageError> {
+		check_lcov_file(lcov_path)?;
+		let mut lcov = LcovWrapper::new(lcov_path)?;
+		lcov.normalise_paths(self.strip_root.as_deref());
+
+		if let (true, Some(repository_files)) = (self.existing_files_only, repository_files) {
+			lcov.retain_files(|filename| {
+				repository_files.contains(&path_split(filename, path_strategy))
+			});
+		}
+		if let Some(ignore_paths) = &self.ignore_paths {
+			lcov.retain_files(|filename| {
+				!ignore_paths.is_match(path_split(filename, path_strategy))
+			});
+		}
+		lcov.apply_exclusions(&self.exclusions, path_strategy);
+
+		Ok(lcov)
+	}
+
+	/// Load the Lcov reports of a run like [LcovOptions::load] and merge them,
+	/// with the total of every report if there are several
+	pub fn load_inputs(
+		&self,
+		inputs: &[LcovInput],
+		path_strategy: &PathStrategy,
+		repository_files: Option<&HashSet<String>>,
+	) -> Result<(LcovWrapper, Vec<LabelTotal>), MeowCoverageError> {
+		let reports = inputs
+			.iter()
+			.map(|input| self.load(&input.path, path_strategy, repository_files))
+			.collect::<Result<Vec<_>, _>>()?;
+		let labels = match reports.len() > 1 {
+			true => inputs
+				.iter()
+				.zip(&reports)
+				.map(|(input, report)| LabelTotal {
+					label: String::from(input.name()),
+					percentage: report.percentage(),
+				})
+				.collect(),
+			false => Vec::new(),
+		};
+
+		Ok((LcovWrapper::merge(reports), labels))
+	}
+
+	/// Find the uninstrumented files of a run that are not `covered`
+	/// otherwise, none unless [LcovOptions::uninstrumented_paths] is set
+	#[must_use]
+	pub fn uninstrumented_files(
+		&self,
+		statistics: &LcovStatistics,
+		repository_files: Option<&HashSet<String>>,
+		path_strategy: &PathStrategy,
+		covered: &[String],
+	) -> UninstrumentedFiles {
+		match (&self.uninstrumented_paths, repository_files) {
+			(Some(globs), Some(repository_files)) => UninstrumentedFiles::collect(
+				statistics,
+				repository_files,
+				globs,
+				path_strategy,
+				covered,
+				self.count_uninstrumented,
+			),
+			_ => UninstrumentedFiles::default(),
+		}
+	}
+}
+
+/// Build a [GlobSet] matching any of `globs`, [None] if no globs are given
+pub fn build_globs(globs: &[String]) -> Result<Option<GlobSet>, MeowCoverageError> {
+	if globs.is_empty() {
+		return Ok(None);
+	}
+
+	let mut builder = GlobSetBuilder::new();
+	for glob in globs {
+		builder.add(Glob::new(glob)?);
+	}
+
+	Ok(Some(builder.build()?))
+}
+
+/// Options for commenting on pull requests
+#[derive(Debug, Clone)]
+pub struct PullRequestOptions {
+	/// Only create review comments on files matching these globs, the summary
+	/// comment still lists all files
+	pub comment_paths: Option<GlobSet>,
+	/// Maximum number of review comments created concurrently
+	pub max_concurrent_requests: usize,
+	/// Maximum number of review comments, the largest untested ranges get a
+	/// review comment and the rest are only listed in the summary comment
+	pub max_review_comments: Option<usize>,
+	/// Template for the review comment bodies (see
+	/// [render_review_comment](super::render_review_comment)), the plain
+	/// "Untested Lines" body is used if unset
+	pub review_comment_template: Option<String>,
+	/// Skip PRs carrying any of these labels
+	pub skip_if_labels: Vec<String>,
+	/// Skip draft PRs
+	pub skip_draft: bool,
+	/// Fail the run and the commit status if less than this percentage of the
+	/// instrumented changed lines is tested
+	pub require_patch_coverage: Option<f64>,
+	/// Carry the coverage of files missing from the new report forward from
+	/// the tracking records of the base branch
+	pub carryforward: Option<Carryforward>,
+	/// Show the coverage trend of the base branch from its tracking records
+	/// in the summary comment
+	pub trend: Option<Trend>,
+	/// Mention the owners of files with untested changes in the summary
+	/// comment
+	pub owners: Option<CoverageOwners>,
+}
+
+/// What the files and lines in the summary comments link to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+	/// Anchors in the diff of the pull request or commit, which don't resolve
+	/// if the provider collapsed the diff of a large file, or for files the
+	/// commit didn't change
+	#[default]
+	Diff,
+	/// The content of the files at the analysed commit, which always resolves
+	Blob,
+}
+
+impl Default for PullRequestOptions {
+	fn default() -> Self {
+		Self {
+			comment_paths: None,
+			max_concurrent_requests: 4,
+			max_review_comments: None,
+			review_comment_template: None,
+			skip_if_labels: Vec::new(),
+			skip_draft: false,
+			require_patch_coverage: None,
+			carryforward: None,
+			trend: None,
+			owners: None,
+		}
+	}
+}
+
+impl PullRequestOptions {
+	/// Build the [GlobSet] for [PullRequestOptions::comment_paths], [None] if
+	/// no globs are given
+	pub fn build_comment_paths(globs: &[String]) -> Result<Option<GlobSet>, MeowCoverageError> {
+		build_globs(globs)
+	}
+
+	/// Whether review comments should be created on a file
+	#[must_use]
+	pub fn comments_on(&self, path: &str) -> bool {
+		match &self.comment_paths {
+			Some(comment_paths) => comment_paths.is_match(path),
+			None => true,
+		}
+	}
+
+	/// Whether any skip condition is configured, requiring the PR metadata
+	#[must_use]
+	pub fn has_skip_conditions(&self) -> bool {
+		self.skip_draft || !self.skip_if_labels.is_empty()
+	}
+
+	/// Reason for skipping a PR, [None] if it should get a coverage report
+	#[must_use]
+	pub fn skip_reason(&self, pull_request: &PullRequestInfo) -> Option<String> {
+		if self.skip_draft && pull_request.draft {
+			return Some(String::from("the pull request is a draft"));
+		}
+
+		pull_request
+			.labels
+			.iter()
+			.find(|label| self.skip_if_labels.contains(label))
+			.map(|label| format!("the pull request is labelled `{}`", label))
+	}
+}
+
+/// How loudly the results of a coverage run are reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuietMode {
+	/// Post comments and a commit status
+	#[default]
+	Off,
+	/// Only report via the commit status, without posting any comments
+	StatusOnly,
+}
+
+/// Options for reporting the results of a coverage run
+#[derive(Debug, Clone, Default)]
+pub struct ReportOptions {
+	/// How loudly the results are reported
+	pub quiet_mode: QuietMode,
+	/// File to append the report to as Markdown, for example the
+	/// `GITHUB_STEP_SUMMARY` of a GitHub Actions step
+	pub step_summary: Option<PathBuf>,
+	/// Flag the upload is tagged with (for example `unit`), tracked separately
+	/// and merged into the total of the branch
+	pub flag: Option<String>,
+	/// File to write the [RunOutput] of the run to as JSON
+	pub output_json: Option<PathBuf>,
+	/// What the files and lines in the summary comments link to
+	pub links: LinkMode,
+	/// Write the comments and the commit status of pull request runs to this
+	/// file as a [PublishPayload](super::publish::PublishPayload) instead of
+	/// posting them, for pull requests from forks
+	pub publish_payload: Option<PathBuf>,
+	/// Abort before posting if fewer requests of the API rate limit remain
+	pub min_rate_limit_remaining: Option<u64>,
+	/// Only list the untested lines changed by the pushed commits in the
+	/// comments of pushes, like in pull requests
+	pub changed_only: bool,
+	/// Display format of the percentages in the comments and statuses
+	pub percentage_format: PercentageFormat,
+}
+
+impl ReportOptions {
+	/// What the summary comment of pull request `pr_number` at `commit_sha`
+	/// links to
+	#[must_use]
+	pub fn pull_request_links<'a>(&self, pr_number: u64, commit_sha: &'a str) -> LinkTarget<'a> {
+		match self.links {
+			LinkMode::Diff => LinkTarget::PullRequest(pr_number),
+			LinkMode::Blob => LinkTarget::Blob(commit_sha),
+		}
+	}
+
+	/// What the summary comment of a push of `commit_sha` links to
+	#[must_use]
+	pub fn push_links<'a>(&self, commit_sha: &'a str) -> LinkTarget<'a> {
+		match self.links {
+			LinkMode::Diff => LinkTarget::Commit(commit_sha),
+			LinkMode::Blob => LinkTarget::Blob(commit_sha),
+		}
+	}
+
+	/// Whether comments should be posted
+	#[must_use]
+	pub fn posts_comments(&self) -> bool {
+		self.quiet_mode == QuietMode::Off
+	}
+
+	/// Label of the total coverage in comments and statuses, naming the
+	/// [ReportOptions::flag]
+	#[must_use]
+	pub fn total_label(&self) -> String {
+		match &self.flag {
+			Some(flag) => format!("Total ({})", flag),
+			None => String::from("Total"),
+		}
+	}
+
+	/// Set the coverage status on a commit. Unless the status is the only
+	/// report, failing to set it (for example for lack of permissions) is only
+	/// a warning
+	pub async fn create_commit_status(
+		&self,
+		provider: &dyn VcsProvider,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		state: CommitStatusState,
+		description: &str,
+	) -> Result<(), MeowCoverageError> {
+		let status =
+			provider.create_commit_status(owner, repo, commit_sha, state, description).await;
+
+		#[allow(clippy::print_stderr)]
+		match status {
+			Err(why) if self.posts_comments() => {
+				eprintln!("Failed to set the commit status, continuing (why: {})", why);
+				Ok(())
+			}
+			status => status,
+		}
+	}
+
+	/// Write the [RunOutput] to [ReportOptions::output_json], if set
+	pub fn write_output_json(&self, output: &RunOutput) -> Result<(), MeowCoverageError> {
+		match &self.output_json {
+			Some(output_json) => output.write(output_json),
+			None => Ok(()),
+		}
+	}
+
+	/// Append a report to [ReportOptions::step_summary], if set
+	pub fn write_step_summary(&self, body: &str) -> Result<(), MeowCoverageError> {
+		let Some(step_summary) = &self.step_summary else {
+			return Ok(());
+		};
+
+		let mut file = OpenOptions::new().create(true).append(true).open(step_summary)?;
+		writeln!(file, "{}\n", body)?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,134 @@
+//! Machine-readable output of a coverage run, written with `--output-json` so
+//! that later workflow steps can act on what was computed and posted
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::lcov::{LabelTotal, LcovStatistics};
+use crate::{provider::CommitStatusState, MeowCoverageError};
+
+/// Kind of a coverage run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunKind {
+	/// Report for a commit
+	Push,
+	/// Report for a pull request
+	PullRequest,
+}
+
+/// Coverage of a file in a [RunOutput]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileOutput {
+	/// File name as in the Lcov report
+	pub filename: String,
+	/// Percentage coverage
+	pub percentage: f64,
+	/// Untested lines
+	pub untested_lines: Vec<u32>,
+}
+
+/// Commit status set by a run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusOutput {
+	/// `success` or `failure`
+	pub state: String,
+	/// Description of the status
+	pub description: String,
+}
+
+/// What a coverage run computed and posted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunOutput {
+	/// Kind of the run
+	pub kind: RunKind,
+	/// Analysed commit
+	pub commit: String,
+	/// Analysed pull request
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub pull_request: Option<u64>,
+	/// Why the report was skipped, if it was
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub skipped: Option<String>,
+	/// Total percentage coverage, [None] if no lines are instrumented
+	pub percentage: Option<f64>,
+	/// Totals of the labelled reports of the run, empty for a single report
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub labels: Vec<LabelTotal>,
+	/// Total percentage coverage to compare with: of the baseline Lcov file
+	/// for pull requests, of the previous tracking record for pushes
+	pub previous_percentage: Option<f64>,
+	/// Percentage of the instrumented changed lines that are tested (pull
+	/// requests only)
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub patch_percentage: Option<f64>,
+	/// Coverage of every file with untested lines
+	pub files: Vec<FileOutput>,
+	/// Repository files missing from the reports entirely
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub uninstrumented_files: Vec<String>,
+	/// URLs of the posted comments, as far as the provider reports them
+	pub comment_urls: Vec<String>,
+	/// Number of posted review comments
+	pub review_comments: usize,
+	/// Commit status that was set
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub status: Option<StatusOutput>,
+	/// Why the run failed, for example for lack of patch coverage
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub failure: Option<String>,
+}
+
+impl RunOutput {
+	/// Start the output of a run for `commit`
+	#[must_use]
+	pub fn new(kind: RunKind, commit: &str) -> Self {
+		Self {
+			kind,
+			commit: String::from(commit),
+			pull_request: None,
+			skipped: None,
+			percentage: None,
+			labels: Vec::new(),
+			previous_percentage: None,
+			patch_percentage: None,
+			files: Vec::new(),
+			uninstrumented_files: Vec::new(),
+			comment_urls: Vec::new(),
+			review_comments: 0,
+			status: None,
+			failure: None,
+		}
+	}
+
+	/// Record the files with untested lines of `statistics`
+	pub fn set_files(&mut self, statistics: &LcovStatistics) {
+		self.files = statistics
+			.files
+			.iter()
+			.filter(|file| !file.lines.is_empty())
+			.map(|file| FileOutput {
+				filename: file.filename.clone(),
+				percentage: file.percentage * 100_f64,
+				untested_lines: file.lines.clone(),
+			})
+			.collect();
+	}
+
+	/// Record the commit status that was set
+	pub fn set_status(&mut self, state: CommitStatusState, description: &str) {
+		self.status = Some(StatusOutput {
+			state: String::from(match state {
+				CommitStatusState::Success => "success",
+				CommitStatusState::Failure => "failure",
+			}),
+			description: String::from(description),
+		});
+	}
+
+	/// Write the output as JSON to `path`
+	pub fn write(&self, path: &Path) -> Result<(), MeowCoverageError> {
+		std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+		Ok(())
+	}
+}
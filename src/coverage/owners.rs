@@ -0,0 +1,125 @@
+//! Owners of paths of the analysed repository, mentioned in the summary
+//! comment when their files have untested changes, so coverage feedback of
+//! large monorepos reaches the right people
+
+use std::collections::BTreeMap;
+
+use globset::GlobSet;
+use serde::Deserialize;
+
+use super::{helpers::format_paths, options::build_globs};
+use crate::MeowCoverageError;
+
+/// Owners of the files matching path globs, configured as `[[owners]]` in
+/// the configuration file
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OwnerRule {
+	/// Globs of the owned paths, for example `crates/auth/**`
+	pub paths: Vec<String>,
+	/// Teams (`org/team`) or users owning the paths, with or without a
+	/// leading `@`
+	pub owners: Vec<String>,
+}
+
+/// [OwnerRule]s with their globs built
+#[derive(Debug, Clone)]
+pub struct CoverageOwners {
+	/// Globs of the paths and their owners, in the order of the rules
+	rules: Vec<(GlobSet, Vec<String>)>,
+}
+
+impl CoverageOwners {
+	/// Build the globs of `rules`, [None] if there are no rules
+	pub fn build(rules: &[OwnerRule]) -> Result<Option<Self>, MeowCoverageError> {
+		let rules = rules
+			.iter()
+			.filter_map(|rule| {
+				build_globs(&rule.paths)
+					.map(|globs| {
+						let owners = rule
+							.owners
+							.iter()
+							.map(|owner| format!("@{}", owner.trim_start_matches('@')))
+							.collect();
+						globs.map(|globs| (globs, owners))
+					})
+					.transpose()
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok((!rules.is_empty()).then_some(Self { rules }))
+	}
+
+	/// Owners of the `paths` matched by any rule, with the paths they own
+	#[must_use]
+	pub fn owners_of<'a>(
+		&self,
+		paths: impl IntoIterator<Item = &'a str>,
+	) -> BTreeMap<&str, Vec<String>> {
+		let mut owned: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+
+		for path in paths {
+			for (globs, owners) in &self.rules {
+				if !globs.is_match(path) {
+					continue;
+				}
+				for owner in owners {
+					let paths = owned.entry(owner.as_str()).or_default();
+					if !paths.iter().any(|owned_path| owned_path == path) {
+						paths.push(String::from(path));
+					}
+				}
+			}
+		}
+
+		owned
+	}
+
+	/// Mention the owners of the `paths` with untested changes, empty if no
+	/// path is owned
+	#[must_use]
+	pub fn mentions<'a>(&self, paths: impl IntoIterator<Item = &'a str>) -> String {
+		let owned = self.owners_of(paths);
+		if owned.is_empty() {
+			return String::new();
+		}
+
+		format!(
+			"\n\nOwners of files with untested changes:\n\n{}",
+			owned
+				.iter()
+				.map(|(owner, paths)| format!("- {}: {}", owner, format_paths(paths)))
+				.collect::<Vec<_>>()
+				.join("\n")
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{CoverageOwners, OwnerRule};
+
+	#[test]
+	fn test_owner_mentions() {
+		let owners = CoverageOwners::build(&[
+			OwnerRule {
+				paths: vec![String::from("crates/auth/**")],
+				owners: vec![String::from("@famedly/auth"), String::from("alice")],
+			},
+			OwnerRule {
+				paths: vec![String::from("crates/**/*.rs")],
+				owners: vec![String::from("famedly/rust")],
+			},
+		])
+		.unwrap()
+		.unwrap();
+
+		assert_eq!(
+			owners.mentions(["crates/auth/src/lib.rs", "crates/sync/src/lib.rs", "README.md"]),
+			"\n\nOwners of files with untested changes:\n\n- @alice: `crates/auth/src/lib.rs`\n- @famedly/auth: `crates/auth/src/lib.rs`\n- @famedly/rust: `crates/auth/src/lib.rs`, `crates/sync/src/lib.rs`"
+		);
+		assert!(owners.mentions(["README.md"]).is_empty());
+		assert!(CoverageOwners::build(&[]).unwrap().is_none());
+	}
+}
@@ -0,0 +1,376 @@
+//! Two-stage reporting for pull requests from forks, whose tokens can't post
+//! comments: the pull request run writes what it would post to a
+//! [PublishPayload] (for example uploaded as an artifact), and a run with
+//! write permissions (for example on `workflow_run`) posts it with
+//! [publish_from_payload]
+use std::path::Path;
+
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::{
+	helpers::{review_marker, summary_marker},
+	options::{QuietMode, ReportOptions},
+	output::{RunKind, RunOutput},
+};
+use crate::{
+	provider::{check_rate_limit, retry_rate_limited, CommitStatusState, VcsProvider},
+	MeowCoverageError,
+};
+
+/// Review comment on a range of lines in a [PublishPayload]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReviewCommentPayload {
+	/// File path
+	pub path: String,
+	/// First line of the range
+	pub first_line: u32,
+	/// Final line of the range
+	pub final_line: u32,
+	/// Comment body
+	pub body: String,
+}
+
+/// Commit status in a [PublishPayload]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusPayload {
+	/// State of the status
+	pub state: CommitStatusState,
+	/// Description of the status
+	pub description: String,
+}
+
+/// Everything a pull request run posts, to be posted by a later run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublishPayload {
+	/// Analysed pull request
+	pub pull_request: u64,
+	/// Analysed commit
+	pub commit: String,
+	/// Summary comment, [None] if only the commit status is reported
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub comment: Option<String>,
+	/// Review comments on untested ranges
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub review_comments: Vec<ReviewCommentPayload>,
+	/// Commit status, [None] if the report was skipped
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub status: Option<StatusPayload>,
+	/// Maximum number of review comments created concurrently
+	pub max_concurrent_requests: usize,
+}
+
+impl PublishPayload {
+	/// Read a payload written by [PublishPayload::write]
+	pub fn load(path: &Path) -> Result<Self, MeowCoverageError> {
+		Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+	}
+
+	/// Write the payload as JSON to `path`
+	pub fn write(&self, path: &Path) -> Result<(), MeowCoverageError> {
+		std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+		Ok(())
+	}
+
+	/// Post the comments and the commit status of the payload to `owner/repo`,
+	/// recording them in `output`. The summary comment is marked with a hash
+	/// of the payload and every review comment with a hash of itself, so a
+	/// retried run only posts the comments the pull request doesn't have yet,
+	/// and the commit status again in case the previous run failed before it
+	pub async fn publish(
+		&self,
+		provider: &dyn VcsProvider,
+		owner: &str,
+		repo: &str,
+		report_options: &ReportOptions,
+		output: &mut RunOutput,
+	) -> Result<(), MeowCoverageError> {
+		check_rate_limit(
+			provider,
+			u64::from(self.comment.is_some())
+				+ u64::from(self.status.is_some())
+				+ self.review_comments.len() as u64,
+			report_options.min_rate_limit_remaining,
+		)
+		.await?;
+
+		if let Some(comment) = &self.comment {
+			let marker = summary_marker(&serde_json::to_vec(self)?);
+			let comments =
+				provider.list_pull_request_comments(owner, repo, self.pull_request).await?;
+			let unchanged =
+				comments.is_some_and(|comments| comments.iter().any(|body| body.contains(&marker)));
+			#[allow(clippy::print_stderr)]
+			match unchanged {
+				true => {
+					eprintln!("The report is unchanged since the last run, skipping posting it");
+					output.skipped =
+						Some(String::from("the report is unchanged since the last run"));
+				}
+				false => output.comment_urls.extend(
+					provider
+						.create_pull_request_comment(
+							owner,
+							repo,
+							self.pull_request,
+							&format!("{}\n\n{}", comment, marker),
+						)
+						.await?,
+				),
+			}
+		}
+
+		if let Some(status) = &self.status {
+			report_options
+				.create_commit_status(
+					provider,
+					owner,
+					repo,
+					&self.commit,
+					status.state,
+					&status.description,
+				)
+				.await?;
+			output.set_status(status.state, &status.description);
+		}
+
+		let posted = match self.review_comments.is_empty() {
+			true => None,
+			false => provider.list_review_comments(owner, repo, self.pull_request).await?,
+		};
+		let mut review_comments = Vec::new();
+		for comment in &self.review_comments {
+			let marker = review_marker(&serde_json::to_vec(&(&self.commit, comment))?);
+			if !posted.iter().flatten().any(|body| body.contains(&marker)) {
+				review_comments.push((comment, format!("{}\n\n{}", comment.body, marker)));
+			}
+		}
+		output.review_comments = stream::iter(&review_comments)
+			.map(|(comment, body)| async move {
+				retry_rate_limited(|| {
+					provider.create_review_comment(
+						owner,
+						repo,
+						self.pull_request,
+						&self.commit,
+						&comment.path,
+						comment.first_line,
+						comment.final_line,
+						body,
+					)
+				})
+				.await
+			})
+			.buffer_unordered(self.max_concurrent_requests.max(1))
+			.try_collect::<Vec<_>>()
+			.await?
+			.len();
+
+		Ok(())
+	}
+}
+
+/// Post the [PublishPayload] at `payload_path` to the pull request in
+/// `repo_name` it was computed for, writing the [RunOutput] to
+/// [ReportOptions::output_json] if set. The payload is written by code of the
+/// pull request, so it is rejected unless it was computed for the current head
+/// of the pull request and for `expected_commit` (the head of the triggering
+/// `workflow_run`) if given
+pub async fn publish_from_payload(
+	provider: &dyn VcsProvider,
+	repo_name: &str,
+	payload_path: &Path,
+	expected_commit: Option<&str>,
+	report_options: &ReportOptions,
+) -> Result<(), MeowCoverageError> {
+	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+	let payload = PublishPayload::load(payload_path)?;
+
+	if let Some(expected_commit) = expected_commit.filter(|commit| *commit != payload.commit) {
+		return Err(MeowCoverageError::PayloadCommitMismatch(
+			payload.commit,
+			String::from(expected_commit),
+			"head of the workflow run",
+		));
+	}
+	// Bitbucket abbreviates the head commit
+	let head_sha = provider.get_pull_request(owner, repo, payload.pull_request).await?.head_sha;
+	if head_sha.is_empty() || !payload.commit.starts_with(&head_sha) {
+		return Err(MeowCoverageError::PayloadCommitMismatch(
+			payload.commit,
+			head_sha,
+			"head of the pull request",
+		));
+	}
+	let report_options = ReportOptions {
+		quiet_mode: match payload.comment {
+			Some(_) => QuietMode::Off,
+			None => QuietMode::StatusOnly,
+		},
+		..report_options.clone()
+	};
+
+	let mut output = RunOutput::new(RunKind::PullRequest, &payload.commit);
+	output.pull_request = Some(payload.pull_request);
+	payload.publish(provider, owner, repo, &report_options, &mut output).await?;
+
+	report_options.write_output_json(&output)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{publish_from_payload, PublishPayload, ReviewCommentPayload, StatusPayload};
+	use crate::{
+		coverage::options::ReportOptions,
+		provider::{fake::FakeProvider, CommitStatusState, PullRequestInfo},
+		MeowCoverageError,
+	};
+
+	#[tokio::test]
+	async fn test_publish_from_payload() {
+		let payload_path = std::env::temp_dir().join("meow-coverage-test-publish-payload.json");
+		PublishPayload {
+			pull_request: 1,
+			commit: String::from("abcdef"),
+			comment: Some(String::from("<h3>Meow! Coverage</h3>")),
+			review_comments: vec![ReviewCommentPayload {
+				path: String::from("src/lib.rs"),
+				first_line: 1,
+				final_line: 4,
+				body: String::from("🐈‍⬛ Untested Lines 🐈‍⬛"),
+			}],
+			status: Some(StatusPayload {
+				state: CommitStatusState::Success,
+				description: String::from("Total: 50.00%"),
+			}),
+			max_concurrent_requests: 4,
+		}
+		.write(&payload_path)
+		.unwrap();
+
+		let provider = FakeProvider {
+			pull_request: PullRequestInfo {
+				head_sha: String::from("abcdef"),
+				..Default::default()
+			},
+			..Default::default()
+		};
+		publish_from_payload(
+			&provider,
+			"famedly/meow",
+			&payload_path,
+			Some("abcdef"),
+			&ReportOptions::default(),
+		)
+		.await
+		.unwrap();
+
+		let comments = provider.comments.lock().unwrap().clone();
+		assert_eq!(comments.len(), 1);
+		assert!(comments[0].starts_with("<h3>Meow! Coverage</h3>\n\n<!-- meow-coverage-summary:"));
+		assert_eq!(provider.review_comments.lock().unwrap()[0].final_line, 4);
+		assert_eq!(
+			*provider.statuses.lock().unwrap(),
+			[(CommitStatusState::Success, String::from("Total: 50.00%"))]
+		);
+
+		// Publishing the same payload again only posts the commit status
+		publish_from_payload(
+			&provider,
+			"famedly/meow",
+			&payload_path,
+			None,
+			&ReportOptions::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(*provider.comments.lock().unwrap(), comments);
+		assert_eq!(provider.review_comments.lock().unwrap().len(), 1);
+		assert_eq!(provider.statuses.lock().unwrap().len(), 2);
+
+		// A run that failed before posting the review comments completes them
+		provider.review_comments.lock().unwrap().clear();
+		publish_from_payload(
+			&provider,
+			"famedly/meow",
+			&payload_path,
+			None,
+			&ReportOptions::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(*provider.comments.lock().unwrap(), comments);
+		let review_comments = provider.review_comments.lock().unwrap();
+		assert_eq!(review_comments.len(), 1);
+		assert!(review_comments[0]
+			.body
+			.starts_with("🐈\u{200d}⬛ Untested Lines 🐈\u{200d}⬛\n\n<!-- meow-coverage-review:"));
+	}
+
+	#[tokio::test]
+	async fn test_publish_rejects_other_commits() {
+		let payload_path = std::env::temp_dir().join("meow-coverage-test-publish-stale.json");
+		PublishPayload {
+			pull_request: 1,
+			commit: String::from("abcdef"),
+			comment: Some(String::from("<h3>Meow! Coverage</h3>")),
+			review_comments: Vec::new(),
+			status: None,
+			max_concurrent_requests: 4,
+		}
+		.write(&payload_path)
+		.unwrap();
+
+		// The pull request was pushed to since the payload was computed
+		let provider = FakeProvider {
+			pull_request: PullRequestInfo {
+				head_sha: String::from("fedcba"),
+				..Default::default()
+			},
+			..Default::default()
+		};
+		assert!(matches!(
+			publish_from_payload(&provider, "famedly/meow", &payload_path, None, &ReportOptions::default())
+				.await,
+			Err(MeowCoverageError::PayloadCommitMismatch(commit, head, _))
+				if commit == "abcdef" && head == "fedcba"
+		));
+
+		// The payload claims another commit than the run that uploaded it
+		let provider = FakeProvider {
+			pull_request: PullRequestInfo {
+				head_sha: String::from("abcdef"),
+				..Default::default()
+			},
+			..Default::default()
+		};
+		assert!(matches!(
+			publish_from_payload(
+				&provider,
+				"famedly/meow",
+				&payload_path,
+				Some("fedcba"),
+				&ReportOptions::default(),
+			)
+			.await,
+			Err(MeowCoverageError::PayloadCommitMismatch(..))
+		));
+		assert!(provider.comments.lock().unwrap().is_empty());
+
+		// Bitbucket abbreviates the head commit
+		let provider = FakeProvider {
+			pull_request: PullRequestInfo { head_sha: String::from("abc"), ..Default::default() },
+			..Default::default()
+		};
+		publish_from_payload(
+			&provider,
+			"famedly/meow",
+			&payload_path,
+			None,
+			&ReportOptions::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(provider.comments.lock().unwrap().len(), 1);
+	}
+}
@@ -2,17 +2,32 @@
 
 use std::{borrow::Cow, collections::HashMap};
 
-use sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
 
 use super::{
-	helpers::{line_changed_in_hunk, lines_in_same_hunk, path_split},
-	html,
-	lcov::LcovWrapper,
+	carryforward::CarriedFiles,
+	changes::{diff_coverage, ChangedFileCoverage},
+	helpers::{
+		enclosing_function, format_label_totals, format_paths, format_percentage, path_sha256,
+		PathStrategy,
+	},
+	html::{self, LinkTarget},
+	lcov::{FunctionCoverage, LabelTotal, LcovInput, LcovWrapper},
+	options::{LcovOptions, PullRequestOptions, ReportOptions},
+	output::{RunKind, RunOutput},
+	publish::{PublishPayload, ReviewCommentPayload, StatusPayload},
+	trend::BaseTrend,
+	uninstrumented::UninstrumentedFiles,
+};
+use crate::{
+	metrics,
+	percentage::{Percentage, PercentageFormat},
+	provider::{CommitStatusState, PullRequestFile, VcsProvider},
+	MeowCoverageError,
 };
-use crate::{github_api::create_review_comment, MeowCoverageError};
 
 /// File coverage wrapper for PRs
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PullFileCoverageWrapper {
 	/// File Git SHA
 	pub sha: String,
@@ -23,149 +38,1446 @@ pub struct PullFileCoverageWrapper {
 	pub raw_lines: Vec<u32>,
 	/// File path
 	pub realpath: String,
+	/// Percentage coverage of the whole file
+	#[serde(default)]
+	pub percentage: Percentage,
+	/// Functions of the file as `(start_line, name)`, sorted by start line
+	#[serde(default)]
+	pub functions: Vec<(u32, String)>,
+	/// Coverage of the functions containing untested changed lines
+	#[serde(default)]
+	pub untested_functions: Vec<FunctionCoverage>,
 }
 
-/// Generates a report for a Pull Request
-#[allow(clippy::too_many_lines)]
-pub async fn generate_pr_coverage_report(
-	repo_name: &str,
-	source_prefix: &str,
-	commit_id: &str,
-	pr_number: u64,
-	new_lcov_file: &str,
-	old_lcov_file: Option<&str>,
-) -> Result<(), MeowCoverageError> {
-	let new_lcov = LcovWrapper::new(new_lcov_file)?;
+/// Coverage report for a Pull Request, built from the new (and optionally
+/// old) Lcov reports and the patches of the files changed in the PR
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PullCoverageReport {
+	/// Total percentage coverage of the new report, [None] if no lines are
+	/// instrumented
+	pub percentage: Option<f64>,
+	/// Totals of the labelled reports merged into the new report, empty for a
+	/// single report
+	#[serde(default)]
+	pub label_totals: Vec<LabelTotal>,
+	/// Difference in total percentage coverage compared to the old report
+	pub percentage_difference: Option<f64>,
+	/// Changed files with untested changed lines
+	pub untested_changes: Vec<PullFileCoverageWrapper>,
+	/// Changed files in the Lcov report whose patch was unavailable (binary
+	/// or too large) or could not be parsed
+	#[serde(default)]
+	pub unanalysed_files: Vec<String>,
+	/// Number of instrumented lines changed in the PR
+	#[serde(default)]
+	pub patch_lines_found: u64,
+	/// Number of untested lines changed in the PR
+	#[serde(default)]
+	pub patch_lines_untested: u64,
+	/// Files missing from the new report whose coverage was carried forward
+	/// into [PullCoverageReport::percentage], see
+	/// [Carryforward](super::carryforward::Carryforward)
+	#[serde(default)]
+	pub carried_forward_files: Vec<String>,
+	/// Repository files missing from the new report entirely, see
+	/// [UninstrumentedFiles]
+	#[serde(default)]
+	pub uninstrumented_files: Vec<String>,
+	/// Instrumented and tested lines of the files missing from the new report
+	/// that are counted into [PullCoverageReport::percentage]
+	#[serde(skip)]
+	missing_lines: (u64, u64),
+	/// Coverage trend of the base branch, see [Trend](super::trend::Trend)
+	#[serde(default)]
+	pub base_trend: Option<BaseTrend>,
+}
 
-	let percentage_difference = match old_lcov_file {
-		Some(old_lcov_file) => {
-			Some(LcovWrapper::new(old_lcov_file)?.percentage_difference(&new_lcov))
-		}
-		None => None,
-	};
+/// Range of untested lines in a changed file of a PR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UntestedRange<'a> {
+	/// File path
+	pub path: &'a str,
+	/// First line of the range
+	pub first_line: u32,
+	/// Final line of the range
+	pub final_line: u32,
+	/// Function the range belongs to, if known
+	pub function: Option<&'a str>,
+	/// Percentage coverage of the whole file
+	pub file_percentage: Percentage,
+	/// Number of untested ranges of changed lines in the file, including
+	/// this one
+	pub file_ranges: usize,
+}
 
-	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+impl UntestedRange<'_> {
+	/// Number of lines in the range
+	#[must_use]
+	pub fn line_count(&self) -> u32 {
+		self.final_line - self.first_line + 1
+	}
 
-	let untested_changes = {
-		let file_diff_meta = octocrab::instance()
-			.pulls(owner, repo)
-			.list_files(pr_number)
-			.await?
+	/// Number of other untested ranges of changed lines in the file
+	#[must_use]
+	pub fn other_ranges(&self) -> usize {
+		self.file_ranges.saturating_sub(1)
+	}
+}
+
+impl PullCoverageReport {
+	/// Build a [PullCoverageReport]
+	///
+	/// Files of the Lcov report are matched to the `changed_files` of the PR
+	/// by their new and previous (if renamed) path, see [diff_coverage].
+	/// Changed files without a patch are listed as unanalysed
+	#[must_use]
+	pub fn build(
+		new_lcov: &LcovWrapper,
+		old_lcov: Option<&LcovWrapper>,
+		path_strategy: &PathStrategy,
+		changed_files: &[PullRequestFile],
+	) -> Self {
+		let mut unanalysed_files = Vec::new();
+		let mut patch_lines_found = 0;
+		let mut patch_lines_untested = 0;
+
+		let untested_changes = diff_coverage(new_lcov, path_strategy, changed_files)
 			.into_iter()
-			.filter_map(|file_diff| {
-				file_diff.patch.map(|patch| {
-					let patch = format!(
-						"--- a/{}\n+++ b/{}\n{}",
-						file_diff.previous_filename.as_deref().unwrap_or(&file_diff.filename),
-						file_diff.filename,
-						patch
-					);
-
-					(file_diff.filename, patch)
-				})
+			.filter_map(|change| match change {
+				ChangedFileCoverage::Analysed {
+					path,
+					percentage,
+					changed_lines_found,
+					untested_lines,
+					untested_ranges,
+					functions,
+					untested_functions,
+				} => {
+					patch_lines_found += u64::from(changed_lines_found);
+					patch_lines_untested += untested_lines.len() as u64;
+					(!untested_lines.is_empty()).then(|| PullFileCoverageWrapper {
+						hunked_lines: untested_ranges,
+						raw_lines: untested_lines,
+						sha: path_sha256(path.as_str()),
+						realpath: path,
+						percentage,
+						functions,
+						untested_functions,
+					})
+				}
+				ChangedFileCoverage::Unanalysed { path } => {
+					unanalysed_files.push(path);
+					None
+				}
 			})
-			.collect::<HashMap<_, _>>();
+			.collect::<Vec<_>>();
 
-		let grouped_data = new_lcov.group_data();
+		Self {
+			percentage: new_lcov.percentage(),
+			label_totals: Vec::new(),
+			percentage_difference: old_lcov
+				.and_then(|old_lcov| old_lcov.percentage_difference(new_lcov)),
+			untested_changes,
+			unanalysed_files,
+			patch_lines_found,
+			patch_lines_untested,
+			carried_forward_files: Vec::new(),
+			uninstrumented_files: Vec::new(),
+			missing_lines: (0, 0),
+			base_trend: None,
+		}
+	}
 
-		grouped_data
-			.into_iter()
-			.filter_map(|coverage| {
-				let path = path_split(coverage.filename.as_str(), source_prefix);
-
-				let patch_str =
-					file_diff_meta.get(&path).map(|patch| match patch.ends_with('\n') {
-						true => patch.clone(),
-						false => format!("{}\n", patch),
-					})?;
-
-				#[allow(clippy::print_stderr)]
-				let patch = match patch::Patch::from_single(&patch_str) {
-					Ok(patch) => patch,
-					Err(why) => {
-						eprintln!("Error parsing patch, continuing with next (why: {})", why);
-						return None;
-					}
-				};
+	/// Include the `carried` files missing from the new report in the total
+	/// coverage and its difference to the old report
+	pub fn carry_forward(
+		&mut self,
+		carried: CarriedFiles,
+		new_lcov: &LcovWrapper,
+		old_lcov: Option<&LcovWrapper>,
+	) {
+		if carried.paths.is_empty() {
+			return;
+		}
 
-				let raw_lines: Vec<_> = coverage
-					.lines
-					.into_iter()
-					.filter(|line| {
-						patch.hunks.iter().any(|hunk| line_changed_in_hunk(hunk, u64::from(*line)))
-					})
-					.collect();
+		self.count_missing_lines(carried.lines_found, carried.lines_hit, new_lcov, old_lcov);
+		self.carried_forward_files = carried.paths;
+	}
 
-				if raw_lines.is_empty() {
-					return None;
-				}
+	/// List the `uninstrumented` files missing from the new report, counting
+	/// their lines as untested in the total coverage if they were counted
+	pub fn add_uninstrumented(
+		&mut self,
+		uninstrumented: UninstrumentedFiles,
+		new_lcov: &LcovWrapper,
+		old_lcov: Option<&LcovWrapper>,
+	) {
+		if uninstrumented.lines_found > 0 {
+			self.count_missing_lines(uninstrumented.lines_found, 0, new_lcov, old_lcov);
+		}
+		self.uninstrumented_files = uninstrumented.paths;
+	}
+
+	/// Count lines of files missing from the new report into the total
+	/// coverage and its difference to the old report
+	fn count_missing_lines(
+		&mut self,
+		lines_found: u64,
+		lines_hit: u64,
+		new_lcov: &LcovWrapper,
+		old_lcov: Option<&LcovWrapper>,
+	) {
+		self.missing_lines.0 += lines_found;
+		self.missing_lines.1 += lines_hit;
+
+		let statistics = new_lcov.statistics();
+		self.percentage = metrics::percentage(
+			statistics.lines_hit + self.missing_lines.1,
+			statistics.lines_found + self.missing_lines.0,
+		);
+		self.percentage_difference =
+			metrics::delta(self.percentage, old_lcov.and_then(LcovWrapper::percentage));
+	}
+
+	/// Percentage of the instrumented changed lines that are tested, [None]
+	/// if no instrumented lines were changed
+	#[must_use]
+	pub fn patch_percentage(&self) -> Option<f64> {
+		metrics::percentage(
+			self.patch_lines_found.saturating_sub(self.patch_lines_untested),
+			self.patch_lines_found,
+		)
+	}
+
+	/// Whether the PR changes any instrumented lines (or files whose changes
+	/// could not be analysed), for example not if it only touches
+	/// documentation or files missing from the Lcov report
+	#[must_use]
+	pub fn has_measurable_changes(&self) -> bool {
+		self.patch_lines_found > 0 || !self.unanalysed_files.is_empty()
+	}
 
-				let hunked_lines: Vec<(u32, u32)> =
-					raw_lines.iter().copied().fold(Vec::new(), |mut hunked_lines, line| {
-						if let Some(last) = hunked_lines.last_mut() {
-							if lines_in_same_hunk(&patch.hunks, u64::from(last.1), u64::from(line))
-							{
-								last.1 = line;
-								return hunked_lines;
-							}
-						}
-
-						hunked_lines.push((line, line));
-						hunked_lines
-					});
-
-				Some(PullFileCoverageWrapper {
-					hunked_lines,
-					raw_lines,
-					sha: {
-						let mut hasher = Sha256::new();
-						hasher.update(path.as_str());
-						hex::encode(hasher.finalize())
-					},
-					realpath: path,
+	/// Reason the patch coverage fails
+	/// [PullRequestOptions::require_patch_coverage] with the percentages in
+	/// `format`, [None] if it passes or no instrumented lines were changed
+	#[must_use]
+	pub fn patch_coverage_failure(
+		&self,
+		options: &PullRequestOptions,
+		format: PercentageFormat,
+	) -> Option<String> {
+		let required = options.require_patch_coverage?;
+		let percentage = self.patch_percentage()?;
+
+		(percentage < required).then(|| {
+			format!(
+				"Patch coverage {}% is below the required {}%",
+				Percentage::from_f64(percentage).display_with(format),
+				Percentage::from_f64(required).display_with(format)
+			)
+		})
+	}
+
+	/// Commit status description summarising the report, for when no comments
+	/// are posted, labelling the total with [ReportOptions::total_label]
+	#[must_use]
+	pub fn status_description(&self, report_options: &ReportOptions) -> String {
+		let format = report_options.percentage_format;
+		if !self.has_measurable_changes() {
+			return format!(
+				"{}: {}, no measurable changes",
+				report_options.total_label(),
+				format_percentage(self.percentage, format)
+			);
+		}
+
+		let untested_lines: usize =
+			self.untested_changes.iter().map(|change| change.raw_lines.len()).sum();
+
+		format!(
+			"{}: {}{}, {} untested changed line{}",
+			report_options.total_label(),
+			format_percentage(self.percentage, format),
+			match self.percentage_difference {
+				Some(delta) => Cow::Owned(format!(
+					" (Delta: {}%)",
+					Percentage::from_f64(delta).display_with(format)
+				)),
+				None => Cow::Borrowed(""),
+			},
+			untested_lines,
+			match untested_lines == 1 {
+				true => "",
+				false => "s",
+			}
+		)
+	}
+
+	/// Split the untested ranges of the files matching
+	/// [PullRequestOptions::comment_paths] into the ones getting a review
+	/// comment and the ones only listed in the summary comment, keeping the
+	/// [PullRequestOptions::max_review_comments] largest ranges for review
+	/// comments
+	#[must_use]
+	pub fn review_comment_ranges(
+		&self,
+		options: &PullRequestOptions,
+	) -> (Vec<UntestedRange<'_>>, Vec<UntestedRange<'_>>) {
+		let mut ranges = self
+			.untested_changes
+			.iter()
+			.filter(|change| options.comments_on(&change.realpath))
+			.flat_map(|change| {
+				change.hunked_lines.iter().map(|(first_line, final_line)| UntestedRange {
+					path: change.realpath.as_str(),
+					first_line: *first_line,
+					final_line: *final_line,
+					function: enclosing_function(&change.functions, *first_line),
+					file_percentage: change.percentage,
+					file_ranges: change.hunked_lines.len(),
 				})
 			})
-			.collect::<Vec<_>>()
-	};
+			.collect::<Vec<_>>();
 
-	octocrab::instance()
-		.issues(owner, repo)
-		.create_comment(
-			pr_number,
-			format!(
-				"<h3>Meow! Coverage</h3>Total: {:.2}%\n\n{}\n\n{}",
-				new_lcov.percentage(),
-				match percentage_difference {
-					Some(delta) => Cow::Owned(format!("Delta: {:.2}%\n\n", delta)),
-					None => Cow::Borrowed(""),
-				},
-				match untested_changes.is_empty() {
-					true => Cow::Borrowed("🐾 All changes are tested! 🐾"),
-					false => Cow::Owned(html::build_pull_summary(
-						owner,
-						repo,
-						pr_number,
-						&untested_changes
-					)),
+		let Some(max_review_comments) = options.max_review_comments else {
+			return (ranges, Vec::new());
+		};
+		if ranges.len() <= max_review_comments {
+			return (ranges, Vec::new());
+		}
+
+		ranges.sort_by_key(|range| std::cmp::Reverse(range.line_count()));
+		let mut folded = ranges.split_off(max_review_comments);
+		folded.sort_by_key(|range| (range.path, range.first_line));
+
+		(ranges, folded)
+	}
+
+	/// List the functions containing untested changes with how many of their
+	/// lines are untested, empty if no function is known
+	#[must_use]
+	pub fn untested_functions_summary(&self) -> String {
+		let functions = self
+			.untested_changes
+			.iter()
+			.flat_map(|change| {
+				change.untested_functions.iter().map(|function| {
+					format!(
+						"- `{}`: function `{}` has {}/{} lines uncovered",
+						change.realpath,
+						function.name,
+						function.lines_untested,
+						function.lines_found
+					)
+				})
+			})
+			.collect::<Vec<_>>();
+
+		match functions.is_empty() {
+			true => String::new(),
+			false => {
+				format!("\n\nFunctions with untested changes:\n\n{}", functions.join("\n"))
+			}
+		}
+	}
+
+	/// Build the summary comment body for the PR, listing the untested ranges
+	/// that exceed [PullRequestOptions::max_review_comments] separately,
+	/// linking the files to `links`, and labelling the total with
+	/// [ReportOptions::total_label]
+	#[must_use]
+	pub fn comment_body(
+		&self,
+		provider: &dyn VcsProvider,
+		owner: &str,
+		repo: &str,
+		links: LinkTarget<'_>,
+		options: &PullRequestOptions,
+		report_options: &ReportOptions,
+	) -> String {
+		let (_, folded) = self.review_comment_ranges(options);
+		let format = report_options.percentage_format;
+
+		format!(
+			"<h3>Meow! Coverage</h3>{}: {}\n\n{}{}{}{}\n\n{}{}{}{}{}{}{}",
+			report_options.total_label(),
+			format_percentage(self.percentage, format),
+			format_label_totals(&self.label_totals, format),
+			match self.percentage_difference {
+				Some(delta) => Cow::Owned(format!(
+					"Delta: {}%\n\n",
+					Percentage::from_f64(delta).display_with(format)
+				)),
+				None => Cow::Borrowed(""),
+			},
+			match &self.base_trend {
+				Some(trend) => Cow::Owned(format!("{}\n\n", trend.display_with(format))),
+				None => Cow::Borrowed(""),
+			},
+			match (self.patch_percentage(), self.patch_coverage_failure(options, format)) {
+				(Some(patch), Some(failure)) => {
+					Cow::Owned(format!(
+						"Patch: {}%\n\n❌ {}\n\n",
+						Percentage::from_f64(patch).display_with(format),
+						failure
+					))
 				}
-			),
+				(Some(patch), None) => Cow::Owned(format!(
+					"Patch: {}%\n\n",
+					Percentage::from_f64(patch).display_with(format)
+				)),
+				(None, _) => Cow::Borrowed(""),
+			},
+			match (self.untested_changes.is_empty(), self.has_measurable_changes()) {
+				(true, true) => Cow::Borrowed("🐾 All changes are tested! 🐾"),
+				(true, false) => Cow::Borrowed(
+					"ℹ️ No measurable changes, none of the changed lines are instrumented."
+				),
+				(false, _) => Cow::Owned(html::build_pull_summary(
+					provider,
+					owner,
+					repo,
+					links,
+					&self.untested_changes
+				)),
+			},
+			self.untested_functions_summary(),
+			match &options.owners {
+				Some(owners) => Cow::Owned(owners.mentions(
+					self.untested_changes.iter().map(|change| change.realpath.as_str())
+				)),
+				None => Cow::Borrowed(""),
+			},
+			match folded.is_empty() {
+				true => Cow::Borrowed(""),
+				false => Cow::Owned(format!(
+					"\n\n{} more untested ranges did not get a review comment:\n\n{}",
+					folded.len(),
+					html::build_pull_folded_summary(provider, owner, repo, links, &folded)
+				)),
+			},
+			match self.unanalysed_files.is_empty() {
+				true => Cow::Borrowed(""),
+				false => Cow::Owned(format!(
+					"\n\n⚠️ Changes to these files could not be analysed, as their diff is unavailable: {}",
+					format_paths(&self.unanalysed_files)
+				)),
+			},
+			match self.uninstrumented_files.is_empty() {
+				true => Cow::Borrowed(""),
+				false => Cow::Owned(format!(
+					"\n\n⚠️ These files are not covered by any Lcov report: {}",
+					format_paths(&self.uninstrumented_files)
+				)),
+			},
+			match self.carried_forward_files.len() {
+				0 => Cow::Borrowed(""),
+				carried => Cow::Owned(format!(
+					"\n\nℹ️ The coverage of {} file{} missing from the report was carried forward from the base branch.",
+					carried,
+					match carried == 1 {
+						true => "",
+						false => "s",
+					}
+				)),
+			}
 		)
-		.await?;
+	}
+}
+
+/// Review comment template with suggested-test hints, see
+/// [render_review_comment] for the placeholders
+pub const HINT_REVIEW_COMMENT_TEMPLATE: &str =
+	"🐈‍⬛ {title} 🐈‍⬛\n\nConsider adding a test exercising {function}:\n\n{snippet}";
+
+/// Body of the review comment for a range of untested lines, with the
+/// coverage of the file in `format` and its other untested ranges
+fn review_comment_body(range: &UntestedRange<'_>, format: PercentageFormat) -> String {
+	format!(
+		"{}\n\n`{}`: {}% covered{}",
+		match range.first_line == range.final_line {
+			true => "🐈‍⬛ Untested Line 🐈‍⬛",
+			false => "🐈‍⬛ Untested Lines 🐈‍⬛",
+		},
+		range.path,
+		range.file_percentage.display_with(format),
+		match range.other_ranges() {
+			0 => Cow::Borrowed(""),
+			1 => Cow::Borrowed(", 1 more untested range in this file"),
+			other => Cow::Owned(format!(", {} more untested ranges in this file", other)),
+		}
+	)
+}
+
+/// Render a review comment template for a range of untested lines, replacing
+/// the placeholders:
+///
+/// - `{title}`: `Untested Line` or `Untested Lines`
+/// - `{path}`: path of the file
+/// - `{lines}`: the line, or range of lines
+/// - `{function}`: name of the enclosing function from the Lcov `FN` records,
+///   or `these lines` if unknown
+/// - `{snippet}`: the untested code, if the source is available
+/// - `{file_percentage}`: coverage of the whole file in `format`
+/// - `{other_ranges}`: number of other untested ranges of changed lines in the
+///   file
+#[must_use]
+pub fn render_review_comment(
+	template: &str,
+	range: &UntestedRange<'_>,
+	source: Option<&str>,
+	format: PercentageFormat,
+) -> String {
+	let lines = match range.first_line == range.final_line {
+		true => format!("{}", range.first_line),
+		false => format!("{}-{}", range.first_line, range.final_line),
+	};
+	let snippet = source
+		.map(|source| {
+			let code = source
+				.lines()
+				.skip(range.first_line.saturating_sub(1) as usize)
+				.take(range.line_count() as usize)
+				.collect::<Vec<_>>()
+				.join("\n");
+			let language = range.path.rsplit_once('.').map_or("", |(_, extension)| extension);
+
+			format!("```{}\n{}\n```", language, code)
+		})
+		.unwrap_or_default();
+
+	template
+		.replace(
+			"{title}",
+			match range.first_line == range.final_line {
+				true => "Untested Line",
+				false => "Untested Lines",
+			},
+		)
+		.replace("{path}", range.path)
+		.replace("{lines}", &lines)
+		.replace(
+			"{function}",
+			&range
+				.function
+				.map_or_else(|| String::from("these lines"), |name| format!("`{}`", name)),
+		)
+		.replace("{snippet}", &snippet)
+		.replace("{file_percentage}", &format!("{}%", range.file_percentage.display_with(format)))
+		.replace("{other_ranges}", &range.other_ranges().to_string())
+}
 
-	for change in untested_changes {
-		for (first_line, final_line) in change.hunked_lines {
-			create_review_comment(
+/// Check if the report of the pull request `pr_number` is skipped by the
+/// skip conditions of the [PullRequestOptions], writing the output and (empty)
+/// publish payload of the skipped run
+#[allow(clippy::print_stderr)]
+async fn skip_report(
+	provider: &dyn VcsProvider,
+	owner: &str,
+	repo: &str,
+	commit_id: &str,
+	pr_number: u64,
+	pull_request_options: &PullRequestOptions,
+	report_options: &ReportOptions,
+) -> Result<bool, MeowCoverageError> {
+	if !pull_request_options.has_skip_conditions() {
+		return Ok(false);
+	}
+	let pull_request = provider.get_pull_request(owner, repo, pr_number).await?;
+	let Some(reason) = pull_request_options.skip_reason(&pull_request) else {
+		return Ok(false);
+	};
+
+	eprintln!("Skipping the coverage report, as {}", reason);
+	let mut output = RunOutput::new(RunKind::PullRequest, commit_id);
+	output.pull_request = Some(pr_number);
+	output.skipped = Some(reason);
+	if let Some(publish_payload) = &report_options.publish_payload {
+		PublishPayload {
+			pull_request: pr_number,
+			commit: String::from(commit_id),
+			comment: None,
+			review_comments: Vec::new(),
+			status: None,
+			max_concurrent_requests: pull_request_options.max_concurrent_requests,
+		}
+		.write(publish_payload)?;
+	}
+	report_options.write_output_json(&output)?;
+	Ok(true)
+}
+
+/// Build the review comments of the untested ranges of `report`, rendering
+/// the review comment template of the [PullRequestOptions] with the source
+/// of the files at `commit_id` if it shows a snippet
+async fn review_comment_payloads(
+	provider: &dyn VcsProvider,
+	owner: &str,
+	repo: &str,
+	commit_id: &str,
+	report: &PullCoverageReport,
+	pull_request_options: &PullRequestOptions,
+	format: PercentageFormat,
+) -> Result<Vec<ReviewCommentPayload>, MeowCoverageError> {
+	let (ranges, _) = report.review_comment_ranges(pull_request_options);
+
+	let mut sources = HashMap::new();
+	if let Some(template) = pull_request_options.review_comment_template.as_deref() {
+		if template.contains("{snippet}") {
+			for range in &ranges {
+				if !sources.contains_key(range.path) {
+					let source = provider
+						.get_file_at_commit(owner, repo, commit_id, range.path)
+						.await?
+						.map(|content| String::from_utf8_lossy(&content).into_owned());
+					sources.insert(range.path, source);
+				}
+			}
+		}
+	}
+
+	Ok(ranges
+		.into_iter()
+		.map(|range| ReviewCommentPayload {
+			body: match pull_request_options.review_comment_template.as_deref() {
+				Some(template) => render_review_comment(
+					template,
+					&range,
+					sources.get(range.path).and_then(Option::as_deref),
+					format,
+				),
+				None => review_comment_body(&range, format),
+			},
+			path: String::from(range.path),
+			first_line: range.first_line,
+			final_line: range.final_line,
+		})
+		.collect())
+}
+
+/// Publish the comment, review comments, and `status` of the report of the
+/// pull request `pr_number`, or write them to the publish payload of the
+/// [ReportOptions] for a later run to publish
+#[allow(clippy::too_many_arguments)]
+async fn publish_report(
+	provider: &dyn VcsProvider,
+	owner: &str,
+	repo: &str,
+	commit_id: &str,
+	pr_number: u64,
+	report: &PullCoverageReport,
+	comment_body: String,
+	status: StatusPayload,
+	pull_request_options: &PullRequestOptions,
+	report_options: &ReportOptions,
+	output: &mut RunOutput,
+) -> Result<(), MeowCoverageError> {
+	let review_comments = match report_options.posts_comments() {
+		true => {
+			review_comment_payloads(
+				provider,
 				owner,
 				repo,
-				pr_number,
 				commit_id,
-				change.realpath.as_str(),
-				first_line,
-				final_line,
+				report,
+				pull_request_options,
+				report_options.percentage_format,
 			)
+			.await?
+		}
+		false => Vec::new(),
+	};
+
+	let payload = PublishPayload {
+		pull_request: pr_number,
+		commit: String::from(commit_id),
+		comment: report_options.posts_comments().then_some(comment_body),
+		review_comments,
+		status: Some(status),
+		max_concurrent_requests: pull_request_options.max_concurrent_requests,
+	};
+	match &report_options.publish_payload {
+		Some(publish_payload) => payload.write(publish_payload),
+		None => payload.publish(provider, owner, repo, report_options, output).await,
+	}
+}
+
+/// Generates a report for a Pull Request, reading the tracking records the
+/// [PullRequestOptions] carry forward from or show the trend of with
+/// `coverage_provider`
+#[allow(clippy::too_many_arguments)]
+pub async fn generate_pr_coverage_report(
+	provider: &dyn VcsProvider,
+	coverage_provider: &dyn VcsProvider,
+	repo_name: &str,
+	path_strategy: &PathStrategy,
+	commit_id: &str,
+	pr_number: u64,
+	new_lcov_files: &[LcovInput],
+	old_lcov_file: Option<&str>,
+	lcov_options: &LcovOptions,
+	pull_request_options: &PullRequestOptions,
+	report_options: &ReportOptions,
+) -> Result<(), MeowCoverageError> {
+	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+
+	if skip_report(
+		provider,
+		owner,
+		repo,
+		commit_id,
+		pr_number,
+		pull_request_options,
+		report_options,
+	)
+	.await?
+	{
+		return Ok(());
+	}
+
+	let repository_files = lcov_options.repository_files(provider, owner, repo, commit_id).await?;
+	let (new_lcov, label_totals) =
+		lcov_options.load_inputs(new_lcov_files, path_strategy, repository_files.as_ref())?;
+	let old_lcov = old_lcov_file
+		.map(|old_lcov_file| {
+			lcov_options.load(old_lcov_file, path_strategy, repository_files.as_ref())
+		})
+		.transpose()?;
+
+	let changed_files = provider.list_pull_request_files(owner, repo, pr_number).await?;
+
+	let mut report =
+		PullCoverageReport::build(&new_lcov, old_lcov.as_ref(), path_strategy, &changed_files);
+	report.label_totals = label_totals;
+	if let Some(carryforward) = &pull_request_options.carryforward {
+		let carried = carryforward
+			.load(coverage_provider, owner, repo, new_lcov.statistics(), path_strategy)
 			.await?;
+		report.carry_forward(carried, &new_lcov, old_lcov.as_ref());
+	}
+	let uninstrumented = lcov_options.uninstrumented_files(
+		new_lcov.statistics(),
+		repository_files.as_ref(),
+		path_strategy,
+		&report.carried_forward_files,
+	);
+	report.add_uninstrumented(uninstrumented, &new_lcov, old_lcov.as_ref());
+	if let Some(trend) = &pull_request_options.trend {
+		report.base_trend = trend.load(coverage_provider, owner, repo).await?;
+	}
+
+	let mut output = RunOutput::new(RunKind::PullRequest, commit_id);
+	output.pull_request = Some(pr_number);
+	output.percentage = report.percentage;
+	output.previous_percentage =
+		report.percentage.zip(report.percentage_difference).map(|(new, delta)| new - delta);
+	output.patch_percentage = report.patch_percentage();
+	output.labels = report.label_totals.clone();
+	output.uninstrumented_files = report.uninstrumented_files.clone();
+	output.set_files(new_lcov.statistics());
+
+	let format = report_options.percentage_format;
+	let comment_body = report.comment_body(
+		provider,
+		owner,
+		repo,
+		report_options.pull_request_links(pr_number, commit_id),
+		pull_request_options,
+		report_options,
+	);
+	report_options.write_step_summary(&comment_body)?;
+
+	let patch_failure = report.patch_coverage_failure(pull_request_options, format);
+	let state = match patch_failure {
+		Some(_) => CommitStatusState::Failure,
+		None => CommitStatusState::Success,
+	};
+	let status = match (&patch_failure, report_options.posts_comments()) {
+		(Some(failure), _) => failure.clone(),
+		(None, true) if report.has_measurable_changes() => {
+			format!(
+				"{}: {}",
+				report_options.total_label(),
+				format_percentage(report.percentage, format)
+			)
+		}
+		(None, _) => report.status_description(report_options),
+	};
+
+	publish_report(
+		provider,
+		owner,
+		repo,
+		commit_id,
+		pr_number,
+		&report,
+		comment_body,
+		StatusPayload { state, description: status },
+		pull_request_options,
+		report_options,
+		&mut output,
+	)
+	.await?;
+
+	output.failure = patch_failure.clone();
+	report_options.write_output_json(&output)?;
+
+	match patch_failure {
+		Some(failure) => Err(MeowCoverageError::PatchCoverage(failure)),
+		None => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::{HashMap, HashSet};
+
+	use super::{generate_pr_coverage_report, PullCoverageReport, HINT_REVIEW_COMMENT_TEMPLATE};
+	use crate::{
+		coverage::{
+			helpers::PathStrategy,
+			html::LinkTarget,
+			lcov::{LcovInput, LcovWrapper},
+			options::{
+				build_globs, LcovOptions, LinkMode, PullRequestOptions, QuietMode, ReportOptions,
+			},
+			output::{RunKind, RunOutput},
+			publish::PublishPayload,
+		},
+		provider::{
+			fake::FakeProvider, CommitStatusState, PullRequestFile, PullRequestInfo, RepoFile,
+		},
+		MeowCoverageError,
+	};
+
+	#[test]
+	fn test_build_pull_report() {
+		let lcov = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nDA:3,0\nDA:7,0\nLF:4\nLH:1\nend_of_record\n"
+				.as_bytes(),
+		)
+		.unwrap();
+		let changed_files = [PullRequestFile {
+			filename: String::from("src/lib.rs"),
+			previous_filename: None,
+			patch: Some(String::from("@@ -1,1 +1,3 @@\n fn a() {}\n+fn b() {}\n+fn c() {}")),
+		}];
+
+		let report = PullCoverageReport::build(
+			&lcov,
+			None,
+			&PathStrategy::Prefix(String::from("src/")),
+			&changed_files,
+		);
+
+		assert_eq!(report.percentage, Some(25.0));
+		assert_eq!(report.percentage_difference, None);
+		assert_eq!(report.untested_changes.len(), 1);
+		assert_eq!(report.untested_changes[0].realpath, "src/lib.rs");
+		assert_eq!(report.untested_changes[0].raw_lines, &[2, 3]);
+		assert_eq!(report.untested_changes[0].hunked_lines, &[(2, 3)]);
+	}
+
+	#[test]
+	fn test_build_pull_report_renamed_files() {
+		let lcov = LcovWrapper::from_reader(
+			concat!(
+				"SF:/build/src/old.rs\nDA:1,0\nLF:1\nLH:0\nend_of_record\n",
+				"SF:/build/src/moved.rs\nDA:2,0\nLF:1\nLH:0\nend_of_record\n",
+				"SF:/build/src/lib.rs\nDA:1,0\nLF:1\nLH:0\nend_of_record\n",
+			)
+			.as_bytes(),
+		)
+		.unwrap();
+		let changed_files = [
+			// Lcov report still uses the previous path
+			PullRequestFile {
+				filename: String::from("src/new.rs"),
+				previous_filename: Some(String::from("src/old.rs")),
+				patch: Some(String::from("@@ -1,0 +1,1 @@\n+a\n")),
+			},
+			// Moved into a workspace member, only matches after path splitting
+			PullRequestFile {
+				filename: String::from("crates/meow/src/moved.rs"),
+				previous_filename: Some(String::from("src/moved.rs")),
+				patch: Some(String::from("@@ -1,1 +1,2 @@\n a\n+b\n")),
+			},
+			// The new path takes precedence over the previous path of another
+			// file
+			PullRequestFile {
+				filename: String::from("src/main.rs"),
+				previous_filename: Some(String::from("src/lib.rs")),
+				patch: Some(String::from("@@ -1,1 +1,2 @@\n a\n+b\n")),
+			},
+			PullRequestFile {
+				filename: String::from("src/lib.rs"),
+				previous_filename: None,
+				patch: Some(String::from("@@ -1,0 +1,1 @@\n+a\n")),
+			},
+		];
+
+		let report = PullCoverageReport::build(
+			&lcov,
+			None,
+			&PathStrategy::Prefix(String::from("src/")),
+			&changed_files,
+		);
+
+		assert_eq!(
+			report
+				.untested_changes
+				.iter()
+				.map(|change| (change.realpath.as_str(), change.raw_lines.as_slice()))
+				.collect::<Vec<_>>(),
+			&[("src/new.rs", &[1][..]), ("crates/meow/src/moved.rs", &[2]), ("src/lib.rs", &[1])]
+		);
+	}
+
+	#[test]
+	fn test_build_pull_report_unanalysed_files() {
+		let lcov = LcovWrapper::from_reader(
+			concat!(
+				"SF:/build/src/generated.rs\nDA:1,0\nLF:1\nLH:0\nend_of_record\n",
+				"SF:/build/src/lib.rs\nDA:1,0\nLF:1\nLH:0\nend_of_record\n",
+			)
+			.as_bytes(),
+		)
+		.unwrap();
+		let changed_files = [
+			PullRequestFile {
+				filename: String::from("src/generated.rs"),
+				previous_filename: None,
+				patch: None,
+			},
+			// Not in the Lcov report, so not worth a warning
+			PullRequestFile {
+				filename: String::from("logo.png"),
+				previous_filename: None,
+				patch: None,
+			},
+			PullRequestFile {
+				filename: String::from("src/lib.rs"),
+				previous_filename: None,
+				patch: Some(String::from("@@ -1,0 +1,1 @@\n+a\n")),
+			},
+		];
+
+		let report = PullCoverageReport::build(
+			&lcov,
+			None,
+			&PathStrategy::Prefix(String::from("src/")),
+			&changed_files,
+		);
+
+		assert_eq!(report.untested_changes.len(), 1);
+		assert_eq!(report.unanalysed_files, &["src/generated.rs"]);
+		let diff_links = report.comment_body(
+			&FakeProvider::default(),
+			"famedly",
+			"meow",
+			LinkTarget::PullRequest(1),
+			&Default::default(),
+			&ReportOptions::default(),
+		);
+		assert!(diff_links
+			.contains("could not be analysed, as their diff is unavailable: `src/generated.rs`"));
+		assert!(diff_links.contains("pr://famedly/meow/1/src/lib.rs#Some((1, None))"));
+
+		let options = ReportOptions { links: LinkMode::Blob, ..Default::default() };
+		let blob_links = report.comment_body(
+			&FakeProvider::default(),
+			"famedly",
+			"meow",
+			options.pull_request_links(1, "abc"),
+			&Default::default(),
+			&options,
+		);
+		assert!(blob_links.contains("blob://famedly/meow/abc/src/lib.rs#Some((1, None))"));
+		assert!(!blob_links.contains("pr://"));
+	}
+
+	#[tokio::test]
+	async fn test_pr_report_review_comments() {
+		let lcov_path = std::env::temp_dir().join("meow-coverage-test-pr-report.info");
+		std::fs::write(
+			&lcov_path,
+			"SF:/build/src/lib.rs\nDA:1,0\nDA:2,1\nDA:3,0\nDA:4,0\nDA:20,0\nLF:5\nLH:1\nend_of_record\n",
+		)
+		.unwrap();
+
+		let provider = FakeProvider {
+			pull_request_files: vec![PullRequestFile {
+				filename: String::from("src/lib.rs"),
+				previous_filename: None,
+				patch: Some(String::from(
+					"@@ -1,0 +1,4 @@\n+fn a() {}\n+fn b() {}\n+fn c() {}\n+fn d() {}\n@@ -10,1 +20,1 @@\n-fn e() {}\n+fn f() {}\n",
+				)),
+			}],
+			..Default::default()
+		};
+
+		generate_pr_coverage_report(
+			&provider,
+			&provider,
+			"famedly/meow",
+			&PathStrategy::Prefix(String::from("src/")),
+			"abcdef",
+			1,
+			&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+			None,
+			&LcovOptions::default(),
+			&PullRequestOptions { max_concurrent_requests: 2, ..Default::default() },
+			&ReportOptions::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(provider.comments.lock().unwrap().len(), 1);
+
+		let mut review_comments = provider
+			.review_comments
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|comment| (comment.first_line, comment.final_line))
+			.collect::<Vec<_>>();
+		review_comments.sort_unstable();
+		assert_eq!(review_comments, &[(1, 4), (20, 20)]);
+		assert!(provider.review_comments.lock().unwrap().iter().any(|comment| {
+			comment.body.starts_with(
+				"🐈‍⬛ Untested Line 🐈‍⬛\n\n`src/lib.rs`: 20.00% covered, 1 more untested range in this file\n\n<!-- meow-coverage-review:",
+			)
+		}));
+	}
+
+	#[tokio::test]
+	async fn test_pr_report_publish_payload() {
+		let lcov_path = std::env::temp_dir().join("meow-coverage-test-pr-report-payload.info");
+		std::fs::write(
+			&lcov_path,
+			"SF:/build/src/lib.rs\nDA:1,0\nDA:2,1\nLF:2\nLH:1\nend_of_record\n",
+		)
+		.unwrap();
+		let payload_path = std::env::temp_dir().join("meow-coverage-test-pr-report-payload.json");
+
+		let provider = FakeProvider {
+			pull_request_files: vec![PullRequestFile {
+				filename: String::from("src/lib.rs"),
+				previous_filename: None,
+				patch: Some(String::from("@@ -1,0 +1,2 @@\n+fn a() {}\n+fn b() {}\n")),
+			}],
+			..Default::default()
+		};
+		generate_pr_coverage_report(
+			&provider,
+			&provider,
+			"famedly/meow",
+			&PathStrategy::Prefix(String::from("src/")),
+			"abcdef",
+			1,
+			&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+			None,
+			&LcovOptions::default(),
+			&PullRequestOptions::default(),
+			&ReportOptions { publish_payload: Some(payload_path.clone()), ..Default::default() },
+		)
+		.await
+		.unwrap();
+		assert!(provider.comments.lock().unwrap().is_empty());
+		assert!(provider.statuses.lock().unwrap().is_empty());
+
+		let payload = PublishPayload::load(&payload_path).unwrap();
+		assert_eq!(payload.pull_request, 1);
+		assert!(payload.comment.unwrap().contains("Meow! Coverage"));
+		assert_eq!(payload.review_comments.len(), 1);
+		assert_eq!(payload.status.unwrap().state, CommitStatusState::Success);
+	}
+
+	#[tokio::test]
+	async fn test_pr_report_skip_conditions() {
+		let lcov_path = std::env::temp_dir().join("meow-coverage-test-pr-report-skip.info");
+		std::fs::write(&lcov_path, "SF:/build/src/lib.rs\nDA:1,0\nLF:1\nLH:0\nend_of_record\n")
+			.unwrap();
+
+		let provider = FakeProvider {
+			pull_request: PullRequestInfo {
+				draft: true,
+				labels: vec![String::from("no-coverage")],
+				..Default::default()
+			},
+			..Default::default()
+		};
+		for options in [
+			PullRequestOptions { skip_draft: true, ..Default::default() },
+			PullRequestOptions {
+				skip_if_labels: vec![String::from("no-coverage")],
+				..Default::default()
+			},
+		] {
+			generate_pr_coverage_report(
+				&provider,
+				&provider,
+				"famedly/meow",
+				&PathStrategy::Prefix(String::from("src/")),
+				"abcdef",
+				1,
+				&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+				None,
+				&LcovOptions::default(),
+				&options,
+				&ReportOptions::default(),
+			)
+			.await
+			.unwrap();
 		}
+		assert!(provider.comments.lock().unwrap().is_empty());
+
+		generate_pr_coverage_report(
+			&provider,
+			&provider,
+			"famedly/meow",
+			&PathStrategy::Prefix(String::from("src/")),
+			"abcdef",
+			1,
+			&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+			None,
+			&LcovOptions::default(),
+			&PullRequestOptions {
+				skip_if_labels: vec![String::from("skip-coverage")],
+				..Default::default()
+			},
+			&ReportOptions::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(provider.comments.lock().unwrap().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_pr_report_status_only() {
+		let lcov_path = std::env::temp_dir().join("meow-coverage-test-pr-report-status-only.info");
+		std::fs::write(
+			&lcov_path,
+			"SF:/build/src/lib.rs\nDA:1,0\nDA:2,1\nLF:2\nLH:1\nend_of_record\n",
+		)
+		.unwrap();
+		let step_summary = std::env::temp_dir().join("meow-coverage-test-step-summary.md");
+		let _ = std::fs::remove_file(&step_summary);
+
+		let provider = FakeProvider {
+			pull_request_files: vec![PullRequestFile {
+				filename: String::from("src/lib.rs"),
+				previous_filename: None,
+				patch: Some(String::from("@@ -1,0 +1,2 @@\n+fn a() {}\n+fn b() {}\n")),
+			}],
+			..Default::default()
+		};
+
+		generate_pr_coverage_report(
+			&provider,
+			&provider,
+			"famedly/meow",
+			&PathStrategy::Prefix(String::from("src/")),
+			"abcdef",
+			1,
+			&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+			None,
+			&LcovOptions::default(),
+			&PullRequestOptions::default(),
+			&ReportOptions {
+				quiet_mode: QuietMode::StatusOnly,
+				step_summary: Some(step_summary.clone()),
+				flag: None,
+				output_json: None,
+				links: LinkMode::Diff,
+				publish_payload: None,
+				min_rate_limit_remaining: None,
+				changed_only: false,
+				percentage_format: Default::default(),
+			},
+		)
+		.await
+		.unwrap();
+
+		assert!(provider.comments.lock().unwrap().is_empty());
+		assert!(provider.review_comments.lock().unwrap().is_empty());
+		assert_eq!(
+			provider
+				.statuses
+				.lock()
+				.unwrap()
+				.iter()
+				.map(|(_, description)| description.as_str())
+				.collect::<Vec<_>>(),
+			&["Total: 50.00%, 1 untested changed line"]
+		);
+		assert!(std::fs::read_to_string(&step_summary).unwrap().contains("Total: 50.00%"));
+	}
+
+	#[tokio::test]
+	async fn test_pr_report_no_measurable_changes() {
+		let lcov_path =
+			std::env::temp_dir().join("meow-coverage-test-pr-report-no-measurable-changes.info");
+		std::fs::write(
+			&lcov_path,
+			"SF:/build/src/lib.rs\nDA:1,0\nDA:2,1\nLF:2\nLH:1\nend_of_record\n",
+		)
+		.unwrap();
+
+		let provider = FakeProvider {
+			pull_request_files: vec![PullRequestFile {
+				filename: String::from("README.md"),
+				previous_filename: None,
+				patch: Some(String::from("@@ -1,0 +1,1 @@\n+# Meow\n")),
+			}],
+			..Default::default()
+		};
+
+		generate_pr_coverage_report(
+			&provider,
+			&provider,
+			"famedly/meow",
+			&PathStrategy::Prefix(String::from("src/")),
+			"abcdef",
+			1,
+			&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+			None,
+			&LcovOptions::default(),
+			&PullRequestOptions::default(),
+			&ReportOptions::default(),
+		)
+		.await
+		.unwrap();
+
+		let comments = provider.comments.lock().unwrap();
+		assert_eq!(comments.len(), 1);
+		assert!(comments[0].contains("No measurable changes"));
+		assert!(!comments[0].contains("All changes are tested"));
+		assert_eq!(
+			provider.statuses.lock().unwrap()[0],
+			(CommitStatusState::Success, String::from("Total: 50.00%, no measurable changes"))
+		);
+	}
+
+	#[tokio::test]
+	async fn test_pr_report_uninstrumented_files() {
+		let lcov_path =
+			std::env::temp_dir().join("meow-coverage-test-pr-report-uninstrumented-files.info");
+		std::fs::write(&lcov_path, "SF:/build/src/lib.rs\nDA:1,1\nLF:1\nLH:1\nend_of_record\n")
+			.unwrap();
+		let output_json =
+			std::env::temp_dir().join("meow-coverage-test-pr-report-uninstrumented-files.json");
+
+		let provider = FakeProvider {
+			tree: HashSet::from(["src/lib.rs", "src/dead.rs", "README.md"].map(String::from)),
+			..Default::default()
+		};
+		generate_pr_coverage_report(
+			&provider,
+			&provider,
+			"famedly/meow",
+			&PathStrategy::Prefix(String::from("src/")),
+			"abcdef",
+			1,
+			&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+			None,
+			&LcovOptions {
+				uninstrumented_paths: build_globs(&[String::from("src/**/*.rs")]).unwrap(),
+				..Default::default()
+			},
+			&PullRequestOptions::default(),
+			&ReportOptions { output_json: Some(output_json.clone()), ..Default::default() },
+		)
+		.await
+		.unwrap();
+
+		assert!(provider.comments.lock().unwrap()[0]
+			.contains("These files are not covered by any Lcov report: `src/dead.rs`"));
+		let output: RunOutput =
+			serde_json::from_slice(&std::fs::read(&output_json).unwrap()).unwrap();
+		assert_eq!(output.uninstrumented_files, ["src/dead.rs"]);
+		assert_eq!(output.percentage, Some(100_f64));
 	}
 
-	Ok(())
+	#[test]
+	fn test_review_comment_ranges_cap() {
+		let lcov = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,0\nDA:3,0\nDA:4,0\nDA:6,0\nLF:4\nLH:0\nend_of_record\n"
+				.as_bytes(),
+		)
+		.unwrap();
+		let changed_files = [PullRequestFile {
+			filename: String::from("src/lib.rs"),
+			previous_filename: None,
+			patch: Some(String::from(
+				"@@ -1,0 +1,1 @@\n+a\n@@ -2,0 +3,2 @@\n+c\n+d\n@@ -3,0 +6,1 @@\n+f\n",
+			)),
+		}];
+		let report = PullCoverageReport::build(
+			&lcov,
+			None,
+			&PathStrategy::Prefix(String::from("src/")),
+			&changed_files,
+		);
+
+		let (commented, folded) = report.review_comment_ranges(&PullRequestOptions {
+			max_review_comments: Some(1),
+			..Default::default()
+		});
+		assert_eq!(
+			commented.iter().map(|range| (range.first_line, range.final_line)).collect::<Vec<_>>(),
+			&[(3, 4)]
+		);
+		assert_eq!(
+			folded.iter().map(|range| (range.first_line, range.final_line)).collect::<Vec<_>>(),
+			&[(1, 1), (6, 6)]
+		);
+
+		let (commented, folded) = report.review_comment_ranges(&PullRequestOptions::default());
+		assert_eq!(commented.len(), 3);
+		assert!(folded.is_empty());
+
+		let (commented, folded) = report.review_comment_ranges(&PullRequestOptions {
+			comment_paths: PullRequestOptions::build_comment_paths(&[String::from("tests/**")])
+				.unwrap(),
+			..Default::default()
+		});
+		assert!(commented.is_empty());
+		assert!(folded.is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_review_comment_hints() {
+		let lcov_path = std::env::temp_dir().join("meow-coverage-test-review-comment-hints.info");
+		std::fs::write(
+			&lcov_path,
+			"SF:/build/src/lib.rs\nFN:1,a\nFN:3,b\nDA:1,1\nDA:2,1\nDA:3,0\nDA:4,0\nLF:4\nLH:2\nend_of_record\n",
+		)
+		.unwrap();
+
+		let provider = FakeProvider {
+			pull_request_files: vec![PullRequestFile {
+				filename: String::from("src/lib.rs"),
+				previous_filename: None,
+				patch: Some(String::from("@@ -1,2 +1,4 @@\n fn a() {\n }\n+fn b() {\n+}\n")),
+			}],
+			files: HashMap::from([(
+				String::from("famedly/meow/abcdef/src/lib.rs"),
+				RepoFile {
+					content: b"fn a() {\n}\nfn b() {\n}\n".to_vec(),
+					sha: String::from("0"),
+				},
+			)])
+			.into(),
+			..Default::default()
+		};
+
+		generate_pr_coverage_report(
+			&provider,
+			&provider,
+			"famedly/meow",
+			&PathStrategy::Prefix(String::from("src/")),
+			"abcdef",
+			1,
+			&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+			None,
+			&LcovOptions::default(),
+			&PullRequestOptions {
+				review_comment_template: Some(String::from(HINT_REVIEW_COMMENT_TEMPLATE)),
+				..Default::default()
+			},
+			&ReportOptions::default(),
+		)
+		.await
+		.unwrap();
+
+		let review_comments = provider.review_comments.lock().unwrap();
+		assert_eq!(review_comments.len(), 1);
+		assert!(review_comments[0].body.starts_with(
+			"🐈‍⬛ Untested Lines 🐈‍⬛\n\nConsider adding a test exercising `b`:\n\n```rs\nfn b() {\n}\n```\n\n<!-- meow-coverage-review:"
+		));
+		assert!(provider.comments.lock().unwrap()[0].contains(
+			"Functions with untested changes:\n\n- `src/lib.rs`: function `b` has 2/2 lines uncovered"
+		));
+	}
+
+	#[tokio::test]
+	async fn test_pr_report_require_patch_coverage() {
+		let lcov_path =
+			std::env::temp_dir().join("meow-coverage-test-pr-report-require-patch-coverage.info");
+		std::fs::write(
+			&lcov_path,
+			"SF:/build/src/lib.rs\nDA:1,0\nDA:2,1\nDA:3,1\nDA:4,1\nLF:4\nLH:3\nend_of_record\n",
+		)
+		.unwrap();
+		let provider = FakeProvider {
+			pull_request_files: vec![PullRequestFile {
+				filename: String::from("src/lib.rs"),
+				previous_filename: None,
+				patch: Some(String::from("@@ -1,0 +1,2 @@\n+fn a() {}\n+fn b() {}\n")),
+			}],
+			..Default::default()
+		};
+		let path_strategy = PathStrategy::Prefix(String::from("src/"));
+		let output_json =
+			std::env::temp_dir().join("meow-coverage-test-pr-report-require-patch-coverage.json");
+		let lcov_inputs = [LcovInput::unlabelled(lcov_path.to_str().unwrap())];
+		let lcov_options = LcovOptions::default();
+		let report_options =
+			ReportOptions { output_json: Some(output_json.clone()), ..Default::default() };
+		let passing =
+			PullRequestOptions { require_patch_coverage: Some(50.0), ..Default::default() };
+		let failing =
+			PullRequestOptions { require_patch_coverage: Some(80.0), ..Default::default() };
+		let run = |pull_request_options| {
+			generate_pr_coverage_report(
+				&provider,
+				&provider,
+				"famedly/meow",
+				&path_strategy,
+				"abcdef",
+				1,
+				&lcov_inputs,
+				None,
+				&lcov_options,
+				pull_request_options,
+				&report_options,
+			)
+		};
+
+		run(&passing).await.unwrap();
+		assert!(matches!(
+			run(&failing).await,
+			Err(MeowCoverageError::PatchCoverage(failure))
+				if failure == "Patch coverage 50.00% is below the required 80.00%"
+		));
+
+		assert_eq!(
+			*provider.statuses.lock().unwrap(),
+			[
+				(CommitStatusState::Success, String::from("Total: 75.00%")),
+				(
+					CommitStatusState::Failure,
+					String::from("Patch coverage 50.00% is below the required 80.00%")
+				),
+			]
+		);
+		let comments = provider.comments.lock().unwrap();
+		assert!(comments[0].contains("Patch: 50.00%\n\n"));
+		assert!(!comments[0].contains("❌"));
+		assert!(comments[1].contains("❌ Patch coverage 50.00% is below the required 80.00%"));
+
+		let output: RunOutput =
+			serde_json::from_slice(&std::fs::read(&output_json).unwrap()).unwrap();
+		assert_eq!(output.kind, RunKind::PullRequest);
+		assert_eq!((output.pull_request, output.percentage), (Some(1), Some(75_f64)));
+		assert_eq!(output.patch_percentage, Some(50_f64));
+		assert_eq!(output.files[0].untested_lines, [1]);
+		assert_eq!(output.comment_urls, ["https://example.com/comments/2"]);
+		assert_eq!(output.status.unwrap().state, "failure");
+		assert_eq!(
+			output.failure.as_deref(),
+			Some("Patch coverage 50.00% is below the required 80.00%")
+		);
+	}
 }
@@ -1,23 +1,38 @@
 //! Module contains definitions for coverage operations on individual commits
 
-use std::{borrow::Cow, collections::HashMap};
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+};
 
-use hyper::StatusCode;
-use octocrab::params::repos::Reference;
-use sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
 
-use super::{helpers::path_split, html::build_push_summary, lcov::LcovWrapper};
+use super::{
+	changes::{diff_coverage, ChangedFileCoverage},
+	helpers::{
+		format_label_totals, format_paths, format_percentage, path_sha256, path_split,
+		summary_marker, PathStrategy,
+	},
+	html::{build_push_summary, LinkTarget},
+	issues::DropIssues,
+	lcov::{digest_inputs, LabelTotal, LcovInput, LcovWrapper},
+	options::{LcovOptions, ReportOptions},
+	output::{RunKind, RunOutput},
+	uninstrumented::UninstrumentedFiles,
+};
 use crate::{
-	github_api::get_file_sha,
+	metrics,
+	notify::Notifications,
+	provider::{CommitStatusState, VcsProvider},
 	tracking::{
-		author, make_report_path, BranchCoverageRecordCollection, FileCoverageRecord, Team,
-		RECORDS_BRANCH,
+		self, make_report_link, make_report_path, BranchCoverageRecordCollection,
+		FileCoverageRecord, RecordMetadata, RecordOptions, RecordStorage, RetentionSettings, Team,
 	},
 	MeowCoverageError,
 };
 
 /// File coverage wrapper for commits
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PushFileCoverageWrapper {
 	/// File Git SHA
 	pub sha: String,
@@ -25,161 +40,956 @@ pub struct PushFileCoverageWrapper {
 	pub raw_lines: Vec<u32>,
 	/// Percentage coverage
 	pub percentage: f64,
+	/// Number of instrumented lines
+	#[serde(default)]
+	pub lines_found: u64,
 	/// File Path
 	pub realpath: String,
 }
 
-/// Generates a report for a commit
-#[allow(clippy::too_many_lines)]
+/// Coverage report for a commit, built from its Lcov report
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushCoverageReport {
+	/// Total percentage coverage, [None] if no lines are instrumented
+	pub percentage: Option<f64>,
+	/// Totals of the labelled reports merged into the report, empty for a
+	/// single report
+	#[serde(default)]
+	pub label_totals: Vec<LabelTotal>,
+	/// Total number of instrumented lines
+	#[serde(default)]
+	pub lines_found: u64,
+	/// Paths of the files without any untested lines, with their number of
+	/// instrumented lines
+	pub tested_files: Vec<(String, u64)>,
+	/// Files with untested lines
+	pub untested_changes: Vec<PushFileCoverageWrapper>,
+	/// Repository files missing from the report entirely, see
+	/// [UninstrumentedFiles]
+	#[serde(default)]
+	pub uninstrumented_files: Vec<String>,
+	/// Files with untested lines changed by the pushed commits, listed in the
+	/// comment instead of [Self::untested_changes] if the report is
+	/// restricted to the changes, see [Self::restrict_to_changes]
+	#[serde(default)]
+	pub changed_untested: Option<Vec<PushFileCoverageWrapper>>,
+}
+
+impl PushCoverageReport {
+	/// Build a [PushCoverageReport]
+	#[must_use]
+	pub fn build(lcov: &LcovWrapper, path_strategy: &PathStrategy) -> Self {
+		let statistics = lcov.statistics();
+		let tested_files = statistics
+			.files
+			.iter()
+			.filter_map(|coverage| {
+				if !coverage.lines.is_empty() {
+					return None;
+				}
+
+				let path = path_split(coverage.filename.as_str(), path_strategy);
+				Some((path, coverage.lines_found))
+			})
+			.collect::<Vec<_>>();
+
+		let untested_changes = statistics
+			.files
+			.iter()
+			.filter_map(|coverage| {
+				if coverage.lines.is_empty() {
+					return None;
+				}
+
+				let path = path_split(coverage.filename.as_str(), path_strategy);
+				Some(PushFileCoverageWrapper {
+					raw_lines: coverage.lines.clone(),
+					sha: path_sha256(path.as_str()),
+					realpath: path,
+					percentage: coverage.percentage,
+					lines_found: coverage.lines_found,
+				})
+			})
+			.collect::<Vec<_>>();
+
+		Self {
+			percentage: statistics.percentage(),
+			label_totals: Vec::new(),
+			lines_found: statistics.lines_found,
+			tested_files,
+			untested_changes,
+			uninstrumented_files: Vec::new(),
+			changed_untested: None,
+		}
+	}
+
+	/// Restrict the untested files and lines listed in the comment to the
+	/// `changes` of the pushed commits, files whose patch was unavailable keep
+	/// all their untested lines. The records and totals still cover every file
+	pub fn restrict_to_changes(&mut self, changes: &[ChangedFileCoverage]) {
+		let changed_lines: HashMap<&str, Option<&[u32]>> = changes
+			.iter()
+			.map(|change| match change {
+				ChangedFileCoverage::Analysed { path, untested_lines, .. } => {
+					(path.as_str(), Some(untested_lines.as_slice()))
+				}
+				ChangedFileCoverage::Unanalysed { path } => (path.as_str(), None),
+			})
+			.collect();
+
+		self.changed_untested = Some(
+			self.untested_changes
+				.iter()
+				.filter_map(|file_cov| {
+					let raw_lines = match changed_lines.get(file_cov.realpath.as_str())? {
+						Some([]) => return None,
+						Some(lines) => lines.to_vec(),
+						None => file_cov.raw_lines.clone(),
+					};
+					Some(PushFileCoverageWrapper { raw_lines, ..file_cov.clone() })
+				})
+				.collect(),
+		);
+	}
+
+	/// List the `uninstrumented` files missing from the report of `lcov`,
+	/// counting their lines as untested in the total coverage if they were
+	/// counted
+	pub fn add_uninstrumented(&mut self, uninstrumented: UninstrumentedFiles, lcov: &LcovWrapper) {
+		if uninstrumented.lines_found > 0 {
+			self.lines_found += uninstrumented.lines_found;
+			self.percentage = metrics::percentage(lcov.statistics().lines_hit, self.lines_found);
+		}
+		self.uninstrumented_files = uninstrumented.paths;
+	}
+
+	/// Build the comment body for the commit, linking the files to `links`
+	/// and labelling the total with [ReportOptions::total_label]
+	#[must_use]
+	pub fn comment_body(
+		&self,
+		provider: &dyn VcsProvider,
+		owner: &str,
+		repo: &str,
+		links: LinkTarget<'_>,
+		report_options: &ReportOptions,
+	) -> String {
+		let untested_changes = self.changed_untested.as_ref().unwrap_or(&self.untested_changes);
+		format!(
+			"<h3>Meow! Coverage</h3>{}: {}\n\n{}{}{}",
+			report_options.total_label(),
+			format_percentage(self.percentage, report_options.percentage_format),
+			format_label_totals(&self.label_totals, report_options.percentage_format),
+			match untested_changes.is_empty() {
+				true => Cow::Borrowed("🐾 All changes are tested! 🐾"),
+				false =>
+					Cow::Owned(build_push_summary(provider, owner, repo, links, untested_changes)),
+			},
+			match self.uninstrumented_files.is_empty() {
+				true => Cow::Borrowed(""),
+				false => Cow::Owned(format!(
+					"\n\n⚠️ These files are not covered by any Lcov report: {}",
+					format_paths(&self.uninstrumented_files)
+				)),
+			}
+		)
+	}
+
+	/// Number of files in the report, as counted by [Self::file_records]
+	#[must_use]
+	pub fn file_count(&self) -> usize {
+		self.untested_changes.len() + self.tested_files.len()
+	}
+
+	/// Build the per-file records stored in the centralised coverage tracking
+	/// repository
+	#[must_use]
+	pub fn file_records(&self) -> HashMap<String, FileCoverageRecord> {
+		let mut files = HashMap::new();
+
+		for file_cov in &self.untested_changes {
+			files.insert(
+				file_cov.realpath.clone(),
+				FileCoverageRecord::new(
+					file_cov.percentage * 100_f64,
+					file_cov.lines_found,
+					file_cov.raw_lines.clone(),
+				),
+			);
+		}
+
+		for (file, lines_found) in &self.tested_files {
+			files.insert(file.clone(), FileCoverageRecord::new(100_f64, *lines_found, Vec::new()));
+		}
+
+		files
+	}
+
+	/// Build the percentages of the labelled reports stored in the
+	/// centralised coverage tracking repository, skipping reports without
+	/// instrumented lines
+	#[must_use]
+	pub fn label_records(&self) -> BTreeMap<String, f64> {
+		self.label_totals
+			.iter()
+			.filter_map(|label| Some((label.label.clone(), label.percentage?)))
+			.collect()
+	}
+}
+
+/// Number of attempts of updating a tracking record conflicting with
+/// concurrent updates
+const RECORD_UPDATE_ATTEMPTS: usize = 5;
+
+/// Generates a report for a commit, recording it in the tracking repository
+/// if `coverage_colllecton_info` (branch, tracking repository, team, and
+/// record options) is passed, and updating the drop issue of tracked
+/// branches. With [ReportOptions::changed_only], the comment only lists the
+/// untested lines changed since `before_sha`, the commit the branch pointed
+/// to before the push. The tracking repository is accessed with
+/// `coverage_provider`
+#[allow(clippy::too_many_arguments)]
 pub async fn generate_push_coverage_report(
-	lcov_path: &str,
+	provider: &dyn VcsProvider,
+	coverage_provider: &dyn VcsProvider,
+	lcov_inputs: &[LcovInput],
 	repo_name: &str,
-	source_prefix: &str,
+	path_strategy: &PathStrategy,
 	commit_sha: &str,
-	coverage_colllecton_info: Option<(&str, &str, Team)>,
+	before_sha: Option<&str>,
+	coverage_colllecton_info: Option<(&str, &str, Team, RecordOptions)>,
+	notifications: &Notifications,
+	drop_issues: &DropIssues,
+	lcov_options: &LcovOptions,
+	report_options: &ReportOptions,
 ) -> Result<(), MeowCoverageError> {
-	let lcov = LcovWrapper::new(lcov_path)?;
-
 	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
 
-	let lcov_data = lcov.group_data();
-	let tested_files = lcov_data
-		.iter()
-		.filter_map(|coverage| {
-			if !coverage.lines.is_empty() {
-				return None;
+	let repository_files = lcov_options.repository_files(provider, owner, repo, commit_sha).await?;
+	let (lcov, label_totals) =
+		lcov_options.load_inputs(lcov_inputs, path_strategy, repository_files.as_ref())?;
+
+	let mut report = PushCoverageReport::build(&lcov, path_strategy);
+	report.label_totals = label_totals;
+	report.add_uninstrumented(
+		lcov_options.uninstrumented_files(
+			lcov.statistics(),
+			repository_files.as_ref(),
+			path_strategy,
+			&[],
+		),
+		&lcov,
+	);
+	#[allow(clippy::print_stderr)]
+	if report_options.changed_only {
+		let changed_files = match before_sha {
+			Some(before_sha) => {
+				provider.compare_commits(owner, repo, before_sha, commit_sha).await?
+			}
+			None => None,
+		};
+		match changed_files {
+			Some(changed_files) => {
+				report.restrict_to_changes(&diff_coverage(&lcov, path_strategy, &changed_files));
 			}
+			None => eprintln!(
+				"Changes of the push are unavailable (new branch or no compare API), listing all untested lines"
+			),
+		}
+	}
+	let mut output = RunOutput::new(RunKind::Push, commit_sha);
+	output.percentage = report.percentage;
+	output.labels = report.label_totals.clone();
+	output.uninstrumented_files = report.uninstrumented_files.clone();
+	output.set_files(lcov.statistics());
 
-			let path = path_split(coverage.filename.as_str(), source_prefix);
-			Some(path)
-		})
-		.collect::<Vec<_>>();
+	let comment_body = report.comment_body(
+		provider,
+		owner,
+		repo,
+		report_options.push_links(commit_sha),
+		report_options,
+	);
+	report_options.write_step_summary(&comment_body)?;
 
-	let untested_changes = lcov_data
-		.into_iter()
-		.filter_map(|coverage| {
-			if coverage.lines.is_empty() {
-				return None;
-			}
+	let status = format!(
+		"{}: {}",
+		report_options.total_label(),
+		format_percentage(report.percentage, report_options.percentage_format)
+	);
 
-			let path = path_split(coverage.filename.as_str(), source_prefix);
-			Some(PushFileCoverageWrapper {
-				raw_lines: coverage.lines,
-				sha: {
-					let mut hasher = Sha256::new();
-					hasher.update(path.as_str());
-					hex::encode(hasher.finalize())
-				},
-				realpath: path,
-				percentage: coverage.percentage,
-			})
-		})
-		.collect::<Vec<_>>();
+	// A retried run skips the comment if the previous run posted it, but
+	// reposts the commit status in case the previous run failed before
+	// posting it
+	let unchanged = match report_options.posts_comments() {
+		true => {
+			post_comment(provider, owner, repo, commit_sha, &comment_body, &status, &mut output)
+				.await?
+		}
+		false => false,
+	};
 
-	octocrab::instance()
-		.commits(owner, repo)
-		.create_comment(
+	report_options
+		.create_commit_status(
+			provider,
+			owner,
+			repo,
 			commit_sha,
-			format!(
-				"<h3>Meow! Coverage</h3>Total: {:.2}%\n\n{}",
-				lcov.percentage(),
-				match untested_changes.is_empty() {
-					true => Cow::Borrowed("🐾 All changes are tested! 🐾"),
-					false =>
-						Cow::Owned(build_push_summary(owner, repo, commit_sha, &untested_changes)),
-				}
-			),
+			CommitStatusState::Success,
+			&status,
 		)
-		.send()
 		.await?;
+	output.set_status(CommitStatusState::Success, &status);
 
-	if let Some((branch, coverage_repo, team)) = coverage_colllecton_info {
-		let report_path = make_report_path(owner, repo, branch);
-		let (coverage_owner, coverage_repo) =
-			coverage_repo.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+	#[allow(clippy::print_stderr)]
+	if coverage_colllecton_info.is_some() && report.percentage.is_none() {
+		eprintln!("Report has no instrumented lines, skipping coverage tracking");
+	}
 
-		let (mut record_collection, sha): (BranchCoverageRecordCollection, Option<String>) = {
-			let (parts, body) = octocrab::instance()
-				.repos(coverage_owner, coverage_repo)
-				.raw_file(Reference::Branch(String::from(RECORDS_BRANCH)), report_path.as_str())
-				.await?
-				.into_parts();
-			if parts.status == StatusCode::NOT_FOUND {
-				None
-			} else if parts.status == StatusCode::UNAUTHORIZED {
-				return Err(MeowCoverageError::MissingAccessToCoverageRepo);
-			} else {
-				let bytes = hyper::body::to_bytes(body).await?;
-				let sha = get_file_sha(
+	output.previous_percentage = track_and_notify(
+		provider,
+		coverage_provider,
+		lcov_inputs,
+		repo_name,
+		commit_sha,
+		coverage_colllecton_info,
+		&report,
+		notifications,
+		drop_issues,
+		report_options,
+		unchanged,
+	)
+	.await?;
+	report_options.write_output_json(&output)
+}
+
+/// Post `comment_body` as a comment on the commit `commit_sha` with a marker of
+/// its hash and the `status`, unless the commit already has a comment with
+/// the same marker. Returns if the comment was already posted
+async fn post_comment(
+	provider: &dyn VcsProvider,
+	owner: &str,
+	repo: &str,
+	commit_sha: &str,
+	comment_body: &str,
+	status: &str,
+	output: &mut RunOutput,
+) -> Result<bool, MeowCoverageError> {
+	let marker = summary_marker(format!("{}\n{}", comment_body, status).as_bytes());
+	let comments = provider.list_commit_comments(owner, repo, commit_sha).await?;
+	let unchanged =
+		comments.is_some_and(|comments| comments.iter().any(|body| body.contains(&marker)));
+	#[allow(clippy::print_stderr)]
+	match unchanged {
+		true => {
+			eprintln!("The report is unchanged since the last run, skipping posting it");
+			output.skipped = Some(String::from("the report is unchanged since the last run"));
+		}
+		false => output.comment_urls.extend(
+			provider
+				.create_commit_comment(
+					owner,
+					repo,
+					commit_sha,
+					&format!("{}\n\n{}", comment_body, marker),
+				)
+				.await?,
+		),
+	}
+	Ok(unchanged)
+}
+
+/// Record `report` of the commit `commit_sha` in the tracking repository if
+/// `coverage_colllecton_info` is passed, dispatch the rebuild of the tracking
+/// repository, update the drop issue of the branch, and notify about the
+/// percentage. A retried run doesn't record a commit that is already
+/// recorded, and only notifies if it recorded the commit, or for untracked
+/// runs, if the comment was not `unchanged` since the last run. Returns the
+/// percentage of the branch before the push
+#[allow(clippy::too_many_arguments)]
+async fn track_and_notify(
+	provider: &dyn VcsProvider,
+	coverage_provider: &dyn VcsProvider,
+	lcov_inputs: &[LcovInput],
+	repo_name: &str,
+	commit_sha: &str,
+	coverage_colllecton_info: Option<(&str, &str, Team, RecordOptions)>,
+	report: &PushCoverageReport,
+	notifications: &Notifications,
+	drop_issues: &DropIssues,
+	report_options: &ReportOptions,
+	unchanged: bool,
+) -> Result<Option<f64>, MeowCoverageError> {
+	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+
+	let tracked = match (coverage_colllecton_info, report.percentage) {
+		(Some((branch, coverage_repo, team, record_options)), Some(percentage)) => {
+			let metadata = RecordMetadata {
+				lcov_digest: Some(digest_inputs(lcov_inputs)?),
+				..RecordMetadata::new(commit_sha)
+			};
+			let config = tracking::fetch_tracking_config(coverage_provider, coverage_repo).await?;
+			let storage = config.storage.open(coverage_provider, coverage_repo)?;
+			let (coverage_owner, coverage_repo) =
+				coverage_repo.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+
+			let update = update_record(
+				storage.as_ref(),
+				&make_report_path(owner, repo, branch),
+				&format!("Add report for {}/{} ({})", coverage_owner, coverage_repo, branch),
+				team,
+				percentage,
+				report,
+				&metadata,
+				&record_options,
+				&config.retention,
+				report_options,
+			)
+			.await?;
+
+			coverage_provider
+				.dispatch_workflow(
 					coverage_owner,
 					coverage_repo,
-					Reference::Branch(String::from(RECORDS_BRANCH)),
-					&report_path,
+					"main.yml",
+					"main",
+					serde_json::json!({"repo-name": repo_name, "branch": branch}),
 				)
 				.await?;
-				Some((serde_json::from_slice(&bytes)?, Some(sha)))
+
+			let report_link = make_report_link(coverage_owner, coverage_repo, owner, repo, branch);
+
+			#[allow(clippy::print_stderr)]
+			if let Err(why) = drop_issues
+				.update(
+					provider,
+					owner,
+					repo,
+					branch,
+					update.previous_percentage,
+					update.total_percentage,
+					&report_link,
+					report_options.percentage_format,
+				)
+				.await
+			{
+				eprintln!("Failed to update the coverage drop issue (why: {})", why);
 			}
+
+			Some((branch, update, report_link))
 		}
-		.unwrap_or_else(|| (BranchCoverageRecordCollection { team, records: Vec::new() }, None));
+		_ => None,
+	};
 
-		let mut files = HashMap::new();
+	// Tracked flagged uploads are compared by the merged total of all flags
+	let (branch, previous_percentage, percentage, report_link, notify) = match tracked {
+		Some((branch, update, report_link)) => (
+			Some(branch),
+			update.previous_percentage,
+			Some(update.total_percentage),
+			Some(report_link),
+			update.recorded,
+		),
+		None => (None, None, report.percentage, None, !unchanged),
+	};
+	if let Some(percentage) = percentage.filter(|_| notify) {
+		notifications
+			.notify(
+				repo_name,
+				branch,
+				previous_percentage,
+				percentage,
+				report_link,
+				report_options.percentage_format,
+			)
+			.await;
+	}
 
-		for file_cov in untested_changes {
-			files.insert(
-				file_cov.realpath.clone(),
-				FileCoverageRecord::new(file_cov.percentage, file_cov.raw_lines),
-			);
-		}
+	Ok(previous_percentage)
+}
+
+/// Outcome of [update_record]
+struct RecordUpdate {
+	/// Total percentage of the branch before the recorded commit
+	previous_percentage: Option<f64>,
+	/// Total percentage of the branch after the recorded commit
+	total_percentage: f64,
+	/// Whether the commit was recorded, or was already recorded by a previous
+	/// run
+	recorded: bool,
+}
 
-		for file in tested_files {
-			files.insert(file, FileCoverageRecord::new(10000_f64, Vec::new()));
+/// Add the record of `report` to the records at `report_path` in `storage`,
+/// reapplying it on top of the latest records while concurrent runs update
+/// them in the meantime. The commit of the `metadata` is not recorded again
+/// if it already has a record (of the flag of the upload)
+#[allow(clippy::too_many_arguments)]
+async fn update_record(
+	storage: &dyn RecordStorage,
+	report_path: &str,
+	message: &str,
+	team: Team,
+	percentage: f64,
+	report: &PushCoverageReport,
+	metadata: &RecordMetadata,
+	record_options: &RecordOptions,
+	retention: &RetentionSettings,
+	report_options: &ReportOptions,
+) -> Result<RecordUpdate, MeowCoverageError> {
+	let mut attempt = 1;
+	loop {
+		let (mut record_collection, sha): (BranchCoverageRecordCollection, Option<String>) =
+			match storage.get(report_path).await? {
+				Some(file) => {
+					(BranchCoverageRecordCollection::from_slice(&file.content)?, Some(file.sha))
+				}
+				None => (BranchCoverageRecordCollection::new(team), None),
+			};
+
+		let records = match report_options.flag.as_deref() {
+			Some(flag) => record_collection.flags.get(flag),
+			None => Some(&record_collection),
+		};
+		#[allow(clippy::print_stderr)]
+		if records.is_some_and(|records| is_recorded(records, &metadata.commit)) {
+			eprintln!("The commit is already recorded, skipping recording it again");
+			return Ok(RecordUpdate {
+				previous_percentage: record_collection
+					.records
+					.iter()
+					.filter(|record| record.commit.as_deref() != Some(metadata.commit.as_str()))
+					.max_by_key(|record| record.timestamp)
+					.map(|record| record.percentage.as_f64()),
+				total_percentage: record_collection
+					.latest()
+					.map_or(percentage, |record| record.percentage.as_f64()),
+				recorded: false,
+			});
 		}
 
-		record_collection.add_new_record(lcov.percentage(), files);
+		record_collection
+			.check_upload(
+				report_options.flag.as_deref(),
+				percentage,
+				report.file_count(),
+				record_options,
+				report_options.percentage_format,
+			)
+			.map_err(MeowCoverageError::RejectedUpload)?;
+		let previous_percentage =
+			record_collection.latest().map(|record| record.percentage.as_f64());
+		match report_options.flag.as_deref() {
+			Some(flag) => record_collection.add_new_flag_record(
+				flag,
+				percentage,
+				report.lines_found,
+				report.file_records(),
+				&report.label_records(),
+				metadata,
+				record_options,
+				retention,
+			),
+			None => record_collection.add_new_record(
+				percentage,
+				report.lines_found,
+				report.file_records(),
+				&report.label_records(),
+				metadata,
+				record_options,
+				retention,
+			),
+		}
+		let total_percentage =
+			record_collection.latest().map_or(percentage, |record| record.percentage.as_f64());
 
+		if let Some(compression) = record_options.compression {
+			record_collection.compress(compression)?;
+		}
 		let content = serde_json::to_vec(&record_collection)?;
 
-		match sha {
-			Some(sha) => {
-				octocrab::instance()
-					.repos(coverage_owner, coverage_repo)
-					.update_file(
-						report_path,
-						format!("Add report for {}/{} ({})", coverage_owner, coverage_repo, branch),
-						content,
-						sha,
-					)
-					.branch(RECORDS_BRANCH)
-					.author(author())
-					.commiter(author())
-					.send()
-					.await?
+		match storage.put(report_path, message, content, sha).await {
+			Ok(()) => {
+				return Ok(RecordUpdate { previous_percentage, total_percentage, recorded: true })
 			}
-			None => {
-				octocrab::instance()
-					.repos(coverage_owner, coverage_repo)
-					.create_file(
-						report_path,
-						format!("Add report for {}/{} ({})", coverage_owner, coverage_repo, branch),
-						content,
-					)
-					.branch(RECORDS_BRANCH)
-					.author(author())
-					.commiter(author())
-					.send()
-					.await?
+			Err(MeowCoverageError::FileConflict(_)) if attempt < RECORD_UPDATE_ATTEMPTS => {
+				attempt += 1;
 			}
+			Err(MeowCoverageError::FileConflict(path)) => {
+				return Err(MeowCoverageError::RecordUpdateConflict(path, attempt));
+			}
+			Err(error) => return Err(error),
+		}
+	}
+}
+/// Check if `records` hold a record of `commit`, also if it is only named by
+/// the metadata of the latest record
+fn is_recorded(records: &BranchCoverageRecordCollection, commit: &str) -> bool {
+	records.record_of_commit(commit).is_some()
+		|| records
+			.latest()
+			.and_then(|record| record.metadata.as_ref())
+			.is_some_and(|metadata| metadata.commit == commit)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::BTreeMap, sync::Mutex};
+
+	use super::{generate_push_coverage_report, PushCoverageReport};
+	use crate::{
+		coverage::{
+			helpers::PathStrategy,
+			issues::DropIssues,
+			lcov::{LcovInput, LcovWrapper},
+			options::{LcovOptions, LinkMode, ReportOptions},
+			uninstrumented::UninstrumentedFiles,
+		},
+		notify::Notifications,
+		percentage::Percentage,
+		provider::{fake::FakeProvider, PullRequestFile},
+		tracking::{BranchCoverageRecordCollection, RecordOptions, Team},
+		MeowCoverageError,
+	};
+
+	#[tokio::test]
+	async fn test_push_report_with_tracking() {
+		let lcov_path = std::env::temp_dir().join("meow-coverage-test-push-report.info");
+		std::fs::write(
+			&lcov_path,
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\nSF:/build/src/main.rs\nDA:1,1\nLF:1\nLH:1\nend_of_record\n",
+		)
+		.unwrap();
+
+		let provider = FakeProvider::default();
+		generate_push_coverage_report(
+			&provider,
+			&provider,
+			&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+			"famedly/meow",
+			&PathStrategy::Prefix(String::from("src/")),
+			"abcdef",
+			None,
+			Some(("main", "famedly/coverage", Team::Workflow, RecordOptions::default())),
+			&Notifications::default(),
+			&DropIssues::default(),
+			&LcovOptions::default(),
+			&ReportOptions::default(),
+		)
+		.await
+		.unwrap();
+
+		let comments = provider.comments.lock().unwrap();
+		assert_eq!(comments.len(), 1);
+		assert!(comments[0].contains("Total: 66.67%"));
+		assert!(comments[0].contains("src/lib.rs"));
+
+		let files = provider.files.lock().unwrap();
+		let record = files.get("famedly/coverage/records/famedly/meow/main.meowcov.json").unwrap();
+		let collection: BranchCoverageRecordCollection =
+			serde_json::from_slice(&record.content).unwrap();
+		assert_eq!(collection.team, Team::Workflow);
+		assert_eq!(collection.records.len(), 1);
+		assert_eq!(collection.records[0].percentage, Percentage::from_basis_points(6667));
+		assert_eq!(collection.records[0].commit.as_deref(), Some("abcdef"));
+		assert_eq!(collection.records[0].lines, Some(3));
+		let metadata = collection.records[0].metadata.as_ref().unwrap();
+		assert_eq!(metadata.commit, "abcdef");
+		assert_eq!(metadata.tool_version, env!("CARGO_PKG_VERSION"));
+		assert_eq!(
+			metadata.lcov_digest.as_deref().map(str::len),
+			Some(64),
+			"digest is a hex SHA-256"
+		);
+
+		assert_eq!(provider.dispatched_workflows.lock().unwrap().len(), 1);
+	}
+
+	#[test]
+	fn test_count_uninstrumented() {
+		let lcov = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\n".as_bytes(),
+		)
+		.unwrap();
+		let mut report =
+			PushCoverageReport::build(&lcov, &PathStrategy::Prefix(String::from("src/")));
+
+		report.add_uninstrumented(
+			UninstrumentedFiles { paths: vec![String::from("src/dead.rs")], lines_found: 0 },
+			&lcov,
+		);
+		assert_eq!((report.percentage, report.lines_found), (Some(50_f64), 2));
+
+		report.add_uninstrumented(
+			UninstrumentedFiles { paths: vec![String::from("src/dead.rs")], lines_found: 2 },
+			&lcov,
+		);
+		assert_eq!((report.percentage, report.lines_found), (Some(25_f64), 4));
+		assert_eq!(report.uninstrumented_files, ["src/dead.rs"]);
+	}
+
+	#[test]
+	fn test_push_comment_links() {
+		let lcov = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nDA:3,0\nLF:3\nLH:1\nend_of_record\n".as_bytes(),
+		)
+		.unwrap();
+		let report = PushCoverageReport::build(&lcov, &PathStrategy::Prefix(String::from("src/")));
+		let comment = |links| {
+			let options = ReportOptions { links, ..Default::default() };
+			report.comment_body(
+				&FakeProvider::default(),
+				"famedly",
+				"meow",
+				options.push_links("abcdef"),
+				&options,
+			)
+		};
+
+		let diff = comment(LinkMode::Diff);
+		assert!(diff.contains("commit://famedly/meow/abcdef/src/lib.rs#Some((2, Some(3)))"));
+		let blob = comment(LinkMode::Blob);
+		assert!(blob.contains("blob://famedly/meow/abcdef/src/lib.rs#Some((2, Some(3)))"));
+		assert!(!blob.contains("commit://"));
+	}
+
+	#[tokio::test]
+	async fn test_push_report_changed_only() {
+		let lcov_path = std::env::temp_dir().join("meow-coverage-test-push-changed-only.info");
+		std::fs::write(
+			&lcov_path,
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nDA:3,0\nDA:10,0\nLF:4\nLH:1\nend_of_record\nSF:/build/src/large.rs\nDA:1,0\nLF:1\nLH:0\nend_of_record\nSF:/build/src/unchanged.rs\nDA:1,0\nLF:1\nLH:0\nend_of_record\n",
+		)
+		.unwrap();
+
+		let provider = FakeProvider {
+			compared_files: Some(vec![
+				PullRequestFile {
+					filename: String::from("src/lib.rs"),
+					previous_filename: None,
+					patch: Some(String::from("@@ -1,1 +1,3 @@\n a\n+b\n+c\n")),
+				},
+				PullRequestFile {
+					filename: String::from("src/large.rs"),
+					previous_filename: None,
+					patch: None,
+				},
+			]),
+			..Default::default()
 		};
+		generate_push_coverage_report(
+			&provider,
+			&provider,
+			&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+			"famedly/meow",
+			&PathStrategy::Prefix(String::from("src/")),
+			"abcdef",
+			Some("fedcba"),
+			Some(("main", "famedly/coverage", Team::Workflow, RecordOptions::default())),
+			&Notifications::default(),
+			&DropIssues::default(),
+			&LcovOptions::default(),
+			&ReportOptions { changed_only: true, ..Default::default() },
+		)
+		.await
+		.unwrap();
 
-		octocrab::instance()
-			.actions()
-			.create_workflow_dispatch(coverage_owner, coverage_repo, "main.yml", "main")
-			.inputs(
-				serde_json::json!({"repo-name": format!("{}/{}", owner, repo), "branch": branch}),
+		let comments = provider.comments.lock().unwrap();
+		assert!(comments[0].contains("Total: 16.67%"));
+		assert!(comments[0].contains("src/lib.rs#Some((2, Some(3)))"));
+		assert!(!comments[0].contains("src/lib.rs#Some((10"));
+		assert!(comments[0].contains("src/large.rs"));
+		assert!(!comments[0].contains("src/unchanged.rs"));
+
+		let files = provider.files.lock().unwrap();
+		let record = files.get("famedly/coverage/records/famedly/meow/main.meowcov.json").unwrap();
+		let collection: BranchCoverageRecordCollection =
+			serde_json::from_slice(&record.content).unwrap();
+		assert_eq!(collection.records[0].files.as_ref().map(|files| files.len()), Some(3));
+	}
+
+	#[tokio::test]
+	async fn test_push_report_coverage_provider() {
+		let lcov_path = std::env::temp_dir().join("meow-coverage-test-push-coverage-provider.info");
+		std::fs::write(
+			&lcov_path,
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\n",
+		)
+		.unwrap();
+
+		let provider = FakeProvider::default();
+		let coverage_provider = FakeProvider::default();
+
+		// Retried runs post the commit status and dispatch the rebuild again,
+		// but neither the comment nor the record
+		for _ in 0..2 {
+			generate_push_coverage_report(
+				&provider,
+				&coverage_provider,
+				&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+				"famedly/meow",
+				&PathStrategy::Prefix(String::from("src/")),
+				"abcdef",
+				None,
+				Some(("main", "famedly/coverage", Team::Workflow, RecordOptions::default())),
+				&Notifications::default(),
+				&DropIssues::default(),
+				&LcovOptions::default(),
+				&ReportOptions::default(),
 			)
-			.send()
-			.await?;
+			.await
+			.unwrap();
+		}
+
+		// The report goes to the analysed repository, the record and the
+		// rebuild to the tracking repository
+		assert_eq!(provider.comments.lock().unwrap().len(), 1);
+		assert_eq!(provider.statuses.lock().unwrap().len(), 2);
+		assert!(provider.files.lock().unwrap().is_empty());
+		assert!(provider.dispatched_workflows.lock().unwrap().is_empty());
+		assert!(coverage_provider.comments.lock().unwrap().is_empty());
+		let files = coverage_provider.files.lock().unwrap();
+		let record = files.get("famedly/coverage/records/famedly/meow/main.meowcov.json").unwrap();
+		let collection: BranchCoverageRecordCollection =
+			serde_json::from_slice(&record.content).unwrap();
+		assert_eq!(collection.records.len(), 1);
+		assert_eq!(coverage_provider.dispatched_workflows.lock().unwrap().len(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_push_report_labelled_reports() {
+		let directory = std::env::temp_dir();
+		let frontend = directory.join("meow-coverage-test-push-frontend.info");
+		let backend = directory.join("meow-coverage-test-push-backend.info");
+		std::fs::write(
+			&frontend,
+			"SF:/build/web/app.ts\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\n",
+		)
+		.unwrap();
+		std::fs::write(
+			&backend,
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,1\nLF:2\nLH:2\nend_of_record\n",
+		)
+		.unwrap();
+
+		let provider = FakeProvider::default();
+		generate_push_coverage_report(
+			&provider,
+			&provider,
+			&[
+				format!("frontend={}", frontend.display()).parse().unwrap(),
+				format!("backend={}", backend.display()).parse().unwrap(),
+			],
+			"famedly/meow",
+			&PathStrategy::Prefix(String::from("src/")),
+			"abcdef",
+			None,
+			Some(("main", "famedly/coverage", Team::Workflow, RecordOptions::default())),
+			&Notifications::default(),
+			&DropIssues::default(),
+			&LcovOptions::default(),
+			&ReportOptions::default(),
+		)
+		.await
+		.unwrap();
+
+		let comments = provider.comments.lock().unwrap();
+		assert!(
+			comments[0].contains("Total: 75.00%\n\n- `frontend`: 50.00%\n- `backend`: 100.00%\n")
+		);
+
+		let files = provider.files.lock().unwrap();
+		let record = files.get("famedly/coverage/records/famedly/meow/main.meowcov.json").unwrap();
+		let collection: BranchCoverageRecordCollection =
+			serde_json::from_slice(&record.content).unwrap();
+		assert_eq!(collection.records[0].percentage, Percentage::from_basis_points(7500));
+		assert_eq!(
+			collection.records[0].labels,
+			BTreeMap::from([
+				(String::from("backend"), Percentage::from_basis_points(10000)),
+				(String::from("frontend"), Percentage::from_basis_points(5000))
+			])
+		);
+	}
+
+	#[tokio::test]
+	async fn test_push_report_record_conflict_retry() {
+		let lcov_path = std::env::temp_dir().join("meow-coverage-test-push-conflict.info");
+		std::fs::write(&lcov_path, "SF:/build/src/lib.rs\nDA:1,1\nLF:1\nLH:1\nend_of_record\n")
+			.unwrap();
+
+		let lcov_inputs = [LcovInput::unlabelled(lcov_path.to_str().unwrap())];
+		let lcov_inputs = &lcov_inputs;
+		let report = |provider| async move {
+			generate_push_coverage_report(
+				provider,
+				provider,
+				lcov_inputs,
+				"famedly/meow",
+				&PathStrategy::Prefix(String::from("src/")),
+				"abcdef",
+				None,
+				Some(("main", "famedly/coverage", Team::Workflow, RecordOptions::default())),
+				&Notifications::default(),
+				&DropIssues::default(),
+				&LcovOptions::default(),
+				&ReportOptions::default(),
+			)
+			.await
+		};
+
+		let provider = FakeProvider { put_file_conflicts: Mutex::new(2), ..Default::default() };
+		report(&provider).await.unwrap();
+		assert_eq!(provider.files.lock().unwrap().len(), 1);
+		assert_eq!(*provider.put_file_conflicts.lock().unwrap(), 0);
+
+		let provider = FakeProvider { put_file_conflicts: Mutex::new(10), ..Default::default() };
+		assert!(matches!(
+			report(&provider).await,
+			Err(MeowCoverageError::RecordUpdateConflict(_, 5))
+		));
+		assert!(provider.files.lock().unwrap().is_empty());
+		assert!(provider.dispatched_workflows.lock().unwrap().is_empty());
 	}
 
-	Ok(())
+	#[tokio::test]
+	async fn test_push_report_retry_after_failed_record() {
+		let lcov_path = std::env::temp_dir().join("meow-coverage-test-push-retry.info");
+		std::fs::write(&lcov_path, "SF:/build/src/lib.rs\nDA:1,1\nLF:1\nLH:1\nend_of_record\n")
+			.unwrap();
+
+		let provider = FakeProvider { put_file_conflicts: Mutex::new(10), ..Default::default() };
+		let report = || async {
+			generate_push_coverage_report(
+				&provider,
+				&provider,
+				&[LcovInput::unlabelled(lcov_path.to_str().unwrap())],
+				"famedly/meow",
+				&PathStrategy::Prefix(String::from("src/")),
+				"abcdef",
+				None,
+				Some(("main", "famedly/coverage", Team::Workflow, RecordOptions::default())),
+				&Notifications::default(),
+				&DropIssues::default(),
+				&LcovOptions::default(),
+				&ReportOptions::default(),
+			)
+			.await
+		};
+
+		// The comment was posted before recording the push failed
+		assert!(matches!(report().await, Err(MeowCoverageError::RecordUpdateConflict(_, 5))));
+		assert_eq!(provider.comments.lock().unwrap().len(), 1);
+		assert!(provider.files.lock().unwrap().is_empty());
+
+		// The retry records the push without posting the comment again
+		*provider.put_file_conflicts.lock().unwrap() = 0;
+		report().await.unwrap();
+		assert_eq!(provider.comments.lock().unwrap().len(), 1);
+		let files = provider.files.lock().unwrap();
+		let record = files.get("famedly/coverage/records/famedly/meow/main.meowcov.json").unwrap();
+		let collection: BranchCoverageRecordCollection =
+			serde_json::from_slice(&record.content).unwrap();
+		assert_eq!(collection.records.len(), 1);
+		assert_eq!(provider.dispatched_workflows.lock().unwrap().len(), 1);
+	}
 }
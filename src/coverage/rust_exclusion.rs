@@ -0,0 +1,134 @@
+//! Rust-aware exclusion of lines from coverage: items in `#[cfg(test)]`
+//! modules and functions, and items annotated with `#[coverage(off)]` (or
+//! the older `#[no_coverage]`), found by parsing the source with [syn]
+use std::collections::HashSet;
+
+use syn::{spanned::Spanned, visit::Visit, Attribute};
+
+/// Whether an attribute excludes its item from coverage
+fn is_excluding(attribute: &Attribute) -> bool {
+	let path = attribute.path();
+	let tokens = || {
+		attribute
+			.meta
+			.require_list()
+			.map(|list| list.tokens.to_string().replace(' ', ""))
+			.unwrap_or_default()
+	};
+
+	if path.is_ident("no_coverage") {
+		return true;
+	}
+	if path.is_ident("coverage") {
+		return tokens() == "off";
+	}
+	if path.is_ident("cfg") {
+		return tokens() == "test";
+	}
+	if path.is_ident("cfg_attr") {
+		return tokens().ends_with(",coverage(off)");
+	}
+
+	false
+}
+
+/// Collects the lines of excluded items
+#[derive(Default)]
+struct ExcludedItems {
+	/// Excluded lines
+	lines: HashSet<u32>,
+}
+
+impl ExcludedItems {
+	/// Exclude the lines of `item` if any of its `attributes` excludes it,
+	/// returns whether it was excluded
+	fn exclude(&mut self, attributes: &[Attribute], item: &impl Spanned) -> bool {
+		if !attributes.iter().any(is_excluding) {
+			return false;
+		}
+
+		let span = item.span();
+		self.lines.extend(span.start().line as u32..=span.end().line as u32);
+		true
+	}
+}
+
+impl<'ast> Visit<'ast> for ExcludedItems {
+	fn visit_item_mod(&mut self, item: &'ast syn::ItemMod) {
+		if !self.exclude(&item.attrs, item) {
+			syn::visit::visit_item_mod(self, item);
+		}
+	}
+
+	fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+		if !self.exclude(&item.attrs, item) {
+			syn::visit::visit_item_fn(self, item);
+		}
+	}
+
+	fn visit_item_impl(&mut self, item: &'ast syn::ItemImpl) {
+		if !self.exclude(&item.attrs, item) {
+			syn::visit::visit_item_impl(self, item);
+		}
+	}
+
+	fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+		if !self.exclude(&item.attrs, item) {
+			syn::visit::visit_impl_item_fn(self, item);
+		}
+	}
+
+	fn visit_trait_item_fn(&mut self, item: &'ast syn::TraitItemFn) {
+		if !self.exclude(&item.attrs, item) {
+			syn::visit::visit_trait_item_fn(self, item);
+		}
+	}
+}
+
+/// Collect the lines of the items of a Rust source file excluded from
+/// coverage by attributes, none if the file can't be parsed
+#[must_use]
+pub fn rust_excluded_lines(source: &str) -> HashSet<u32> {
+	let Ok(file) = syn::parse_file(source) else {
+		return HashSet::new();
+	};
+
+	let mut excluded = ExcludedItems::default();
+	excluded.visit_file(&file);
+	excluded.lines
+}
+
+#[cfg(test)]
+mod tests {
+	use super::rust_excluded_lines;
+
+	#[test]
+	fn test_rust_excluded_lines() {
+		let source = "\
+fn tested() {}
+
+#[coverage(off)]
+fn debug() {
+	println!();
+}
+
+impl Foo {
+	#[cfg_attr(coverage_nightly, coverage(off))]
+	fn fmt(&self) {}
+
+	fn run(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test() {}
+}
+";
+
+		let mut excluded = rust_excluded_lines(source).into_iter().collect::<Vec<_>>();
+		excluded.sort_unstable();
+		assert_eq!(excluded, &[3, 4, 5, 6, 9, 10, 15, 16, 17, 18, 19]);
+		assert!(rust_excluded_lines("fn broken(").is_empty());
+	}
+}
@@ -0,0 +1,72 @@
+//! Stable, versioned summary of a coverage run, so that downstream pipelines
+//! can consume meow-coverage data without reparsing the Lcov report
+use serde::{Deserialize, Serialize};
+
+use super::lcov::{LcovFileCoverage, LcovWrapper};
+use crate::MeowCoverageError;
+
+/// Current version of the [CoverageSummary] schema
+pub const COVERAGE_SUMMARY_VERSION: u32 = 1;
+
+/// Versioned summary of a coverage run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverageSummary {
+	/// Schema version, see [COVERAGE_SUMMARY_VERSION]
+	pub version: u32,
+	/// Total percentage coverage, [None] if no lines are instrumented
+	pub percentage: Option<f64>,
+	/// Per-file coverage
+	pub files: Vec<LcovFileCoverage>,
+}
+
+impl CoverageSummary {
+	/// Build a [CoverageSummary] from an Lcov report
+	#[must_use]
+	pub fn new(lcov: &LcovWrapper) -> Self {
+		let statistics = lcov.statistics();
+		Self {
+			version: COVERAGE_SUMMARY_VERSION,
+			percentage: statistics.percentage(),
+			files: statistics.files.clone(),
+		}
+	}
+
+	/// Parse a serialised [CoverageSummary], rejecting summaries written with a
+	/// newer schema version
+	pub fn from_slice(data: &[u8]) -> Result<Self, MeowCoverageError> {
+		let summary: Self = serde_json::from_slice(data)?;
+
+		match summary.version > COVERAGE_SUMMARY_VERSION {
+			true => Err(MeowCoverageError::UnsupportedSummaryVersion(summary.version)),
+			false => Ok(summary),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{CoverageSummary, COVERAGE_SUMMARY_VERSION};
+	use crate::{coverage::lcov::LcovWrapper, MeowCoverageError};
+
+	#[test]
+	fn test_summary_round_trip() {
+		let lcov = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\n".as_bytes(),
+		)
+		.unwrap();
+
+		let summary = CoverageSummary::new(&lcov);
+		let parsed = CoverageSummary::from_slice(&serde_json::to_vec(&summary).unwrap()).unwrap();
+
+		assert_eq!(parsed.version, COVERAGE_SUMMARY_VERSION);
+		assert_eq!(parsed.percentage, Some(50_f64));
+		assert_eq!(parsed.files[0].filename, "/build/src/lib.rs");
+		assert_eq!(parsed.files[0].lines, &[2]);
+
+		let newer = serde_json::json!({ "version": COVERAGE_SUMMARY_VERSION + 1, "percentage": 0, "files": [] });
+		assert!(matches!(
+			CoverageSummary::from_slice(&serde_json::to_vec(&newer).unwrap()),
+			Err(MeowCoverageError::UnsupportedSummaryVersion(_))
+		));
+	}
+}
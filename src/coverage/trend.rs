@@ -0,0 +1,148 @@
+//! Coverage trend of the base branch of a pull request, read from its
+//! tracking records, to put the change of a PR into perspective
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	percentage::{Percentage, PercentageFormat},
+	provider::VcsProvider,
+	tracking::{self, BranchCoverageRecordCollection},
+	MeowCoverageError,
+};
+
+/// Where the trend of the base branch is read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trend {
+	/// Tracking repository in format `OWNER/REPO`
+	pub coverage_repo: String,
+	/// Branch of the analysed repository whose records are read, usually the
+	/// base branch of the PR
+	pub base_branch: String,
+}
+
+/// Total percentage coverage of a branch now and in the past
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BaseTrend {
+	/// Name of the branch
+	pub branch: String,
+	/// Percentage of the latest record
+	pub latest: Percentage,
+	/// Percentage of the newest record at least 7 days old
+	pub week_ago: Option<Percentage>,
+	/// Percentage of the newest record at least 30 days old
+	pub month_ago: Option<Percentage>,
+}
+
+impl BaseTrend {
+	/// Build the trend of `branch` from its `collection` as seen at `now`,
+	/// [None] if the collection has no records
+	#[must_use]
+	pub fn from_records(
+		branch: &str,
+		collection: &BranchCoverageRecordCollection,
+		now: time::OffsetDateTime,
+	) -> Option<Self> {
+		let percentage_ago = |days: i64| {
+			collection.percentage_at((now - time::Duration::days(days)).unix_timestamp())
+		};
+
+		Some(Self {
+			branch: String::from(branch),
+			latest: collection.latest()?.percentage,
+			week_ago: percentage_ago(7),
+			month_ago: percentage_ago(30),
+		})
+	}
+
+	/// Describe the trend for comments with the percentages in `format`
+	#[must_use]
+	pub fn display_with(&self, format: PercentageFormat) -> String {
+		let mut trend =
+			format!("Base branch `{}`: {}%", self.branch, self.latest.display_with(format));
+		if let Some(week_ago) = self.week_ago {
+			trend.push_str(&format!(" → 7d ago {}%", week_ago.display_with(format)));
+		}
+		if let Some(month_ago) = self.month_ago {
+			trend.push_str(&format!(" → 30d ago {}%", month_ago.display_with(format)));
+		}
+		trend
+	}
+}
+
+impl Trend {
+	/// Fetch the records of [Trend::base_branch] and build its trend, [None]
+	/// if the branch is not tracked
+	pub async fn load(
+		&self,
+		provider: &dyn VcsProvider,
+		owner: &str,
+		repo: &str,
+	) -> Result<Option<BaseTrend>, MeowCoverageError> {
+		let storage = tracking::open_storage(provider, &self.coverage_repo).await?;
+		let collection =
+			tracking::fetch_stored_records(storage.as_ref(), owner, repo, &self.base_branch)
+				.await?;
+
+		Ok(collection.and_then(|collection| {
+			BaseTrend::from_records(&self.base_branch, &collection, time::OffsetDateTime::now_utc())
+		}))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+
+	use super::{BaseTrend, Trend};
+	use crate::{
+		percentage::{Percentage, PercentageFormat, Rounding},
+		provider::{fake::FakeProvider, RepoFile},
+		tracking::{BranchCoverageRecord, BranchCoverageRecordCollection, Team},
+	};
+
+	#[tokio::test]
+	async fn test_base_trend() {
+		let now = time::OffsetDateTime::now_utc();
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		for (days, percentage) in [(40, 7530), (10, 7900), (1, 8120)] {
+			collection.records.push(BranchCoverageRecord {
+				timestamp: (now - time::Duration::days(days)).unix_timestamp(),
+				percentage: Percentage::from_basis_points(percentage),
+				commit: None,
+				lines: None,
+				files: None,
+				files_gz: None,
+				labels: BTreeMap::new(),
+				metadata: None,
+			});
+		}
+
+		let trend = BaseTrend::from_records("main", &collection, now).unwrap();
+		assert_eq!(
+			trend.display_with(PercentageFormat::default()),
+			"Base branch `main`: 81.20% → 7d ago 79.00% → 30d ago 75.30%"
+		);
+
+		collection.records.remove(0);
+		let trend = BaseTrend::from_records("main", &collection, now).unwrap();
+		assert_eq!(trend.month_ago, None);
+		assert_eq!(
+			trend.display_with(PercentageFormat { precision: 1, rounding: Rounding::Nearest }),
+			"Base branch `main`: 81.2% → 7d ago 79.0%"
+		);
+
+		let provider = FakeProvider::default();
+		let trend = Trend {
+			coverage_repo: String::from("famedly/coverage"),
+			base_branch: String::from("main"),
+		};
+		assert_eq!(trend.load(&provider, "famedly", "meow").await.unwrap(), None);
+
+		provider.files.lock().unwrap().insert(
+			String::from("famedly/coverage/records/famedly/meow/main.meowcov.json"),
+			RepoFile { content: serde_json::to_vec(&collection).unwrap(), sha: String::from("1") },
+		);
+		let loaded = trend.load(&provider, "famedly", "meow").await.unwrap().unwrap();
+		assert_eq!(loaded.latest, Percentage::from_basis_points(8120));
+	}
+}
@@ -0,0 +1,98 @@
+//! Files of the repository missing from the Lcov report entirely, for example
+//! because no test compiles them, so they don't silently escape the total
+
+use std::collections::HashSet;
+
+use globset::GlobSet;
+
+use super::{
+	helpers::{path_split, PathStrategy},
+	lcov::LcovStatistics,
+};
+
+/// Repository files matching the expected globs that no report covers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UninstrumentedFiles {
+	/// Paths of the files, sorted
+	pub paths: Vec<String>,
+	/// Number of lines of the files counted as untested, zero unless counting
+	/// against the total
+	pub lines_found: u64,
+}
+
+impl UninstrumentedFiles {
+	/// Pick the `repository_files` matching `globs` that are missing from
+	/// `statistics` and not in `covered` (for example carried forward files).
+	/// With `count_lines`, the non-blank lines of the files in the working
+	/// directory are counted as untested, unreadable files count no lines
+	#[must_use]
+	pub fn collect(
+		statistics: &LcovStatistics,
+		repository_files: &HashSet<String>,
+		globs: &GlobSet,
+		path_strategy: &PathStrategy,
+		covered: &[String],
+		count_lines: bool,
+	) -> Self {
+		let present = statistics
+			.files
+			.iter()
+			.map(|file| path_split(&file.filename, path_strategy))
+			.collect::<HashSet<_>>();
+
+		let mut paths = repository_files
+			.iter()
+			.filter(|path| globs.is_match(path.as_str()))
+			.filter(|path| !present.contains(*path) && !covered.contains(*path))
+			.cloned()
+			.collect::<Vec<_>>();
+		paths.sort();
+
+		let lines_found = match count_lines {
+			true => paths.iter().map(|path| count_source_lines(path)).sum(),
+			false => 0,
+		};
+
+		Self { paths, lines_found }
+	}
+}
+
+/// Number of non-blank lines of the file at `path`, zero if it can't be read
+fn count_source_lines(path: &str) -> u64 {
+	std::fs::read_to_string(path)
+		.map(|source| source.lines().filter(|line| !line.trim().is_empty()).count() as u64)
+		.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+
+	use super::UninstrumentedFiles;
+	use crate::coverage::{helpers::PathStrategy, lcov::LcovWrapper, options::build_globs};
+
+	#[test]
+	fn test_uninstrumented_files() {
+		let lcov = LcovWrapper::from_reader(
+			"SF:/build/src/lib.rs\nDA:1,1\nLF:1\nLH:1\nend_of_record\n".as_bytes(),
+		)
+		.unwrap();
+		let repository_files = HashSet::from(
+			["src/lib.rs", "src/dead.rs", "src/carried.rs", "README.md", "Cargo.toml"]
+				.map(String::from),
+		);
+		let globs = build_globs(&[String::from("src/**/*.rs")]).unwrap().unwrap();
+
+		let uninstrumented = UninstrumentedFiles::collect(
+			lcov.statistics(),
+			&repository_files,
+			&globs,
+			&PathStrategy::Prefix(String::from("src/")),
+			&[String::from("src/carried.rs")],
+			true,
+		);
+		assert_eq!(uninstrumented.paths, ["src/dead.rs"]);
+		// Not checked out in the working directory of the tests
+		assert_eq!(uninstrumented.lines_found, 0);
+	}
+}
@@ -1,11 +1,13 @@
 //! Helpers for operations on the GitHub API that are unsuported by [octocrab]
 
 use hyper::{header::ACCEPT, http::HeaderValue, HeaderMap};
-use octocrab::params::repos::Reference;
+use octocrab::{models::repos::CommitAuthor, params::repos::Reference, Octocrab, Page};
 use serde::Deserialize;
 
 /// Create a review comment on a PR
+#[allow(clippy::too_many_arguments)]
 pub async fn create_review_comment(
+	client: &Octocrab,
 	owner: &str,
 	repo: &str,
 	pull_id: u64,
@@ -13,12 +15,13 @@ pub async fn create_review_comment(
 	path: &str,
 	first_line: u32,
 	final_line: u32,
+	body: &str,
 ) -> Result<(), octocrab::Error> {
 	let route = format!("/repos/{}/{}/pulls/{}/comments", owner, repo, pull_id);
 
 	let body = match first_line == final_line {
 		true => serde_json::json!({
-			"body": "🐈‍⬛ Untested Line 🐈‍⬛",
+			"body": body,
 			"commit_id": commit_id,
 			"path": path,
 			"start_side": "RIGHT",
@@ -26,7 +29,7 @@ pub async fn create_review_comment(
 			"side": "RIGHT"
 		}),
 		false => serde_json::json!({
-			"body": "🐈‍⬛ Untested Lines 🐈‍⬛",
+			"body": body,
 			"commit_id": commit_id,
 			"path": path,
 			"start_line": first_line,
@@ -36,7 +39,7 @@ pub async fn create_review_comment(
 		}),
 	};
 
-	let _: serde_json::Value = octocrab::instance().post(route, Some(&body)).await?;
+	let _: serde_json::Value = client.post(route, Some(&body)).await?;
 
 	Ok(())
 }
@@ -50,6 +53,7 @@ struct ShaWrapper {
 
 /// Create a review comment on a PR
 pub async fn get_file_sha(
+	client: &Octocrab,
 	owner: &str,
 	repo: &str,
 	reference: Reference,
@@ -61,9 +65,362 @@ pub async fn get_file_sha(
 	let mut headers = HeaderMap::new();
 	headers.insert(ACCEPT, HeaderValue::from_static("application/vnd.github.v3"));
 
-	let value: ShaWrapper = octocrab::instance()
+	let value: ShaWrapper = client
 		.get_with_headers(route, Some(&[("ref", reference.ref_url())]), Some(headers))
 		.await?;
 
 	Ok(value.sha)
 }
+
+/// Fetch the raw content of the blob `sha`
+pub async fn get_blob(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	sha: &str,
+) -> Result<hyper::Response<hyper::Body>, octocrab::Error> {
+	let route = format!("/repos/{}/{}/git/blobs/{}", owner, repo, sha);
+
+	let mut headers = HeaderMap::new();
+	headers.insert(ACCEPT, HeaderValue::from_static("application/vnd.github.raw"));
+
+	octocrab::map_github_error(client._get_with_headers(route, Some(headers)).await?).await
+}
+
+/// Entry of a Git tree
+#[derive(Debug, Deserialize)]
+pub struct TreeEntry {
+	/// Path relative to the repository root
+	pub path: String,
+	/// Object type (`blob`, `tree`, or `commit`)
+	#[serde(rename = "type")]
+	pub kind: String,
+}
+
+/// Response of the Git trees API
+#[derive(Debug, Deserialize)]
+pub struct TreeResponse {
+	/// Entries of the tree
+	pub tree: Vec<TreeEntry>,
+	/// Whether the tree was too large to be returned in full
+	#[serde(default)]
+	pub truncated: bool,
+}
+
+/// Fetch the tree of a commit, including all subtrees
+pub async fn get_recursive_tree(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	commit_sha: &str,
+) -> Result<TreeResponse, octocrab::Error> {
+	let route = format!("/repos/{}/{}/git/trees/{}", owner, repo, commit_sha);
+
+	client.get(route, Some(&[("recursive", "1")])).await
+}
+
+/// Fetch the full unified diff of a PR, returns [None] if GitHub refuses to
+/// render it (for example when the diff is too large)
+pub async fn get_pull_request_diff(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	pull_id: u64,
+) -> Result<Option<String>, octocrab::Error> {
+	let route = format!("/repos/{}/{}/pulls/{}", owner, repo, pull_id);
+
+	let mut headers = HeaderMap::new();
+	headers.insert(ACCEPT, HeaderValue::from_static("application/vnd.github.diff"));
+
+	let response = client._get_with_headers(route, Some(headers)).await?;
+	match response.status().is_success() {
+		true => Ok(Some(client.body_to_string(response).await?)),
+		false => Ok(None),
+	}
+}
+
+/// Fetch the full unified diff between two commits, returns [None] if GitHub
+/// refuses to render it (for example when the diff is too large)
+pub async fn get_comparison_diff(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	base_sha: &str,
+	head_sha: &str,
+) -> Result<Option<String>, octocrab::Error> {
+	let route = format!("/repos/{}/{}/compare/{}...{}", owner, repo, base_sha, head_sha);
+
+	let mut headers = HeaderMap::new();
+	headers.insert(ACCEPT, HeaderValue::from_static("application/vnd.github.diff"));
+
+	let response = client._get_with_headers(route, Some(headers)).await?;
+	match response.status().is_success() {
+		true => Ok(Some(client.body_to_string(response).await?)),
+		false => Ok(None),
+	}
+}
+
+/// Team with access to a repository
+#[derive(Debug, Deserialize)]
+struct RepositoryTeam {
+	/// Team slug
+	slug: String,
+}
+
+/// List the slugs of the teams with access to a repository, following all
+/// pages
+pub async fn list_repository_teams(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+) -> Result<Vec<String>, octocrab::Error> {
+	let route = format!("/repos/{}/{}/teams", owner, repo);
+
+	let page: Page<RepositoryTeam> = client.get(route, Some(&[("per_page", "100")])).await?;
+
+	Ok(client.all_pages(page).await?.into_iter().map(|team| team.slug).collect())
+}
+
+/// Response item of the commit comments API
+#[derive(Debug, Deserialize)]
+struct CommitComment {
+	/// Comment body
+	body: String,
+}
+
+/// List the bodies of the comments on a commit, following all pages
+pub async fn list_commit_comments(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	commit_sha: &str,
+) -> Result<Vec<String>, octocrab::Error> {
+	let route = format!("/repos/{}/{}/commits/{}/comments", owner, repo, commit_sha);
+
+	let page: Page<CommitComment> = client.get(route, Some(&[("per_page", "100")])).await?;
+
+	Ok(client.all_pages(page).await?.into_iter().map(|comment| comment.body).collect())
+}
+
+/// Object a Git reference points to
+#[derive(Debug, Deserialize)]
+struct GitObject {
+	/// Object SHA
+	sha: String,
+}
+
+/// Response of the Git refs API
+#[derive(Debug, Deserialize)]
+struct GitReference {
+	/// Object the reference points to
+	object: GitObject,
+}
+
+/// Response of the Git commits API
+#[derive(Debug, Deserialize)]
+struct GitCommit {
+	/// Commit SHA
+	sha: String,
+	/// Tree of the commit
+	tree: GitObject,
+}
+
+/// Whether an error is GitHub reporting a missing reference, file, or an
+/// empty repository
+pub(crate) fn is_not_found(error: &octocrab::Error) -> bool {
+	matches!(error, octocrab::Error::GitHub { source, .. }
+		if source.message == "Not Found" || source.message.contains("is empty"))
+}
+
+/// Fetch the SHA of the head of `branch`, [None] if the branch (or any
+/// commit) does not exist
+pub async fn get_branch_head(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+) -> Result<Option<String>, octocrab::Error> {
+	match client
+		.get::<GitReference, _, _>(
+			format!("/repos/{}/{}/git/ref/heads/{}", owner, repo, branch),
+			None::<&()>,
+		)
+		.await
+	{
+		Ok(head) => Ok(Some(head.object.sha)),
+		Err(error) if is_not_found(&error) => Ok(None),
+		Err(error) => Err(error),
+	}
+}
+
+/// Whether `path` exists on `branch`
+pub async fn file_exists(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+	path: &str,
+) -> Result<bool, octocrab::Error> {
+	match get_file_sha(client, owner, repo, Reference::Branch(String::from(branch)), path).await {
+		Ok(_) => Ok(true),
+		Err(error) if is_not_found(&error) => Ok(false),
+		Err(error) => Err(error),
+	}
+}
+
+/// Create `branch` pointing to a new commit without parents containing only
+/// `files` (path and content), the repository must not be empty
+pub async fn create_orphan_branch(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+	message: &str,
+	files: &[(String, String)],
+	author: &CommitAuthor,
+) -> Result<(), octocrab::Error> {
+	let entries = files
+		.iter()
+		.map(|(path, content)| {
+			serde_json::json!({
+				"path": path,
+				"mode": "100644",
+				"type": "blob",
+				"content": content,
+			})
+		})
+		.collect::<Vec<_>>();
+	let tree: GitObject = client
+		.post(
+			format!("/repos/{}/{}/git/trees", owner, repo),
+			Some(&serde_json::json!({ "tree": entries })),
+		)
+		.await?;
+
+	let author = serde_json::json!({ "name": author.name, "email": author.email });
+	let commit: GitObject = client
+		.post(
+			format!("/repos/{}/{}/git/commits", owner, repo),
+			Some(&serde_json::json!({
+				"message": message,
+				"tree": tree.sha,
+				"parents": [],
+				"author": author,
+				"committer": author,
+			})),
+		)
+		.await?;
+
+	let _: serde_json::Value = client
+		.post(
+			format!("/repos/{}/{}/git/refs", owner, repo),
+			Some(&serde_json::json!({
+				"ref": format!("refs/heads/{}", branch),
+				"sha": commit.sha,
+			})),
+		)
+		.await?;
+
+	Ok(())
+}
+
+/// Number of attempts of [commit_files] when the branch moved concurrently
+const COMMIT_ATTEMPTS: usize = 5;
+
+/// Commit `files` (path, and content or [None] to delete the file) to
+/// `branch` in a single commit on top of its current head. The blobs are
+/// created first, then the tree, commit, and reference update are retried on
+/// top of the new head if the branch moved in the meantime
+pub async fn commit_files(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+	message: &str,
+	files: &[(String, Option<String>)],
+	author: &CommitAuthor,
+) -> Result<(), octocrab::Error> {
+	let mut entries = Vec::with_capacity(files.len());
+	for (path, content) in files {
+		let sha = match content {
+			Some(content) => {
+				let blob: GitObject = client
+					.post(
+						format!("/repos/{}/{}/git/blobs", owner, repo),
+						Some(&serde_json::json!({ "content": content, "encoding": "utf-8" })),
+					)
+					.await?;
+				Some(blob.sha)
+			}
+			None => None,
+		};
+		entries.push(serde_json::json!({
+			"path": path,
+			"mode": "100644",
+			"type": "blob",
+			"sha": sha,
+		}));
+	}
+
+	let author = serde_json::json!({ "name": author.name, "email": author.email });
+	let mut attempt = 1;
+	loop {
+		match try_commit_tree(client, owner, repo, branch, message, &entries, &author).await {
+			Err(octocrab::Error::GitHub { source, .. })
+				if source.message.to_lowercase().contains("fast forward")
+					&& attempt < COMMIT_ATTEMPTS =>
+			{
+				attempt += 1;
+			}
+			result => return result,
+		}
+	}
+}
+
+/// Create a tree of `entries` on top of the head of `branch`, commit it, and
+/// move `branch` to the commit if its head is unchanged
+async fn try_commit_tree(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+	message: &str,
+	entries: &[serde_json::Value],
+	author: &serde_json::Value,
+) -> Result<(), octocrab::Error> {
+	let head: GitReference = client
+		.get(format!("/repos/{}/{}/git/ref/heads/{}", owner, repo, branch), None::<&()>)
+		.await?;
+	let parent: GitCommit = client
+		.get(format!("/repos/{}/{}/git/commits/{}", owner, repo, head.object.sha), None::<&()>)
+		.await?;
+
+	let tree: GitObject = client
+		.post(
+			format!("/repos/{}/{}/git/trees", owner, repo),
+			Some(&serde_json::json!({ "base_tree": parent.tree.sha, "tree": entries })),
+		)
+		.await?;
+
+	let commit: GitObject = client
+		.post(
+			format!("/repos/{}/{}/git/commits", owner, repo),
+			Some(&serde_json::json!({
+				"message": message,
+				"tree": tree.sha,
+				"parents": [parent.sha],
+				"author": author,
+				"committer": author,
+			})),
+		)
+		.await?;
+
+	let _: serde_json::Value = client
+		.patch(
+			format!("/repos/{}/{}/git/refs/heads/{}", owner, repo, branch),
+			Some(&serde_json::json!({ "sha": commit.sha, "force": false })),
+		)
+		.await?;
+
+	Ok(())
+}
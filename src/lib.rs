@@ -0,0 +1,200 @@
+//! A code coverage visualiser integrated into GitHub
+//!
+//! This crate exposes the building blocks used by the `meow-coverage` binary
+//! so that other tools can embed them:
+//!
+//! - [coverage] parses LCOV reports, intersects them with pull request diffs,
+//!   and builds the push/pull request reports
+//! - [tracking] contains the centralised coverage tracking records and their
+//!   visualisation
+//! - [github_api] contains the GitHub API operations unsupported by [octocrab]
+//! - [provider] abstracts the hosting provider reports are posted to
+//! - [notify] sends notifications when coverage regresses
+//! - [context] reads the run context from the GitHub Actions environment
+//! - [artifacts] exchanges Lcov reports between runs via GitHub Actions
+//!   artifacts
+//! - [config] reads the configuration file of the analysed repository
+//! - [percentage] stores and displays coverage percentages consistently
+//! - [metrics] computes coverage percentages and deltas
+
+use ::lcov::report::ParseError;
+use thiserror::Error;
+
+pub mod artifacts;
+pub mod config;
+pub mod context;
+pub mod coverage;
+pub mod github_api;
+pub mod metrics;
+pub mod notify;
+pub mod percentage;
+pub mod provider;
+pub mod tracking;
+
+/// Error collection
+#[derive(Debug, Error)]
+pub enum MeowCoverageError {
+	/// LCOV parsing error ([ParseError])
+	#[error("Lcov Parsing Failed: {0}")]
+	LcovParse(#[from] ParseError),
+	/// GitHub API Error ([octocrab::Error])
+	#[error("GitHub API Error: {0}")]
+	GitHub(#[from] octocrab::Error),
+	/// Repository name not in format `OWNER/REPO`
+	#[error("Repo Name must be in format OWNER/REPO")]
+	RepoNameMissingSlash,
+	/// Patch parsing error [patch::ParseError]
+	#[error("Patch Parse Error: {0}")]
+	Patch(String),
+	/// HTTP client error ([reqwest::Error])
+	#[error("HTTP Error: {0}")]
+	Http(#[from] reqwest::Error),
+	/// Provider returned an unexpected response
+	#[error("Provider Error: {0}")]
+	Provider(String),
+	/// A base URL is required for the selected provider
+	#[error("The selected provider requires --base-url")]
+	MissingBaseUrl,
+	/// Sending a notification failed
+	#[error("Notification Error: {0}")]
+	Notification(String),
+	/// Hyper error
+	#[error("Hyper Error: {0}")]
+	Hyper(#[from] hyper::Error),
+	/// serde_json error
+	#[error("Serde Error: {0}")]
+	SerdeJson(#[from] serde_json::Error),
+	/// The PR does not meet
+	/// [coverage::options::PullRequestOptions::require_patch_coverage]
+	#[error("{0}")]
+	PatchCoverage(String),
+	/// A required argument was neither passed nor found in the run context
+	#[error("Missing --{0}, pass it explicitly or use --auto-context in GitHub Actions")]
+	MissingArgument(&'static str),
+	/// Zip archive error
+	#[error("Zip Error: {0}")]
+	Zip(#[from] zip::result::ZipError),
+	/// Uploading artifacts outside of an action
+	#[error("Uploading artifacts requires ACTIONS_RUNTIME_TOKEN and ACTIONS_RESULTS_URL, which are only available to actions")]
+	MissingActionsRuntime,
+	/// A `workflow_dispatch` event lacks a required input
+	#[error("The workflow_dispatch event is missing the `{0}` input")]
+	MissingDispatchInput(&'static str),
+	/// The `action` subcommand does not support the triggering event
+	#[error("Unsupported GitHub Actions event: {0}")]
+	UnsupportedEvent(String),
+	/// GitHub token does not have permission to access the contents of the
+	/// coverage repo
+	#[error("Token does not have permission to access coverage repo")]
+	MissingAccessToCoverageRepo,
+	/// [std::io::Error] vairant
+	#[error(transparent)]
+	Io(#[from] std::io::Error),
+	/// Attempted to build a report on a branch that is missing valid reports
+	#[error("Attempted to build a report on a branch that is missing valid reports")]
+	ReportMissingInfo,
+	/// Invalid glob pattern ([globset::Error])
+	#[error("Invalid Glob: {0}")]
+	Glob(#[from] globset::Error),
+	/// A file was changed concurrently while updating it
+	#[error("{0} was changed concurrently")]
+	FileConflict(String),
+	/// Updating a tracking record kept conflicting with concurrent updates
+	#[error("Gave up updating the tracking record {0} after {1} attempts conflicting with concurrent updates, rerun the job to retry")]
+	RecordUpdateConflict(String, usize),
+	/// Coverage summary was written with a newer, unsupported schema version
+	#[error("Unsupported coverage summary version: {0}")]
+	UnsupportedSummaryVersion(u32),
+	/// Invalid configuration file ([toml::de::Error])
+	#[error("Config Error: {0}")]
+	Config(#[from] toml::de::Error),
+	/// Lcov file does not exist, with the Lcov reports found nearby
+	#[error("Lcov file {path} not found{}", format_candidates(.candidates))]
+	LcovFileNotFound {
+		/// Path of the missing Lcov file
+		path: String,
+		/// Lcov reports found at well known paths and next to the missing file
+		candidates: Vec<String>,
+	},
+	/// Invalid regex rewriting Lcov paths ([regex::Error])
+	#[error("Invalid Path Rewrite: {0}")]
+	PathRewrite(#[from] regex::Error),
+	/// An upload failed the sanity checks against the previous tracking
+	/// record
+	#[error("Rejected the tracking record: {0}, pass --force to record it anyway")]
+	RejectedUpload(String),
+	/// Fewer API requests remain than `--min-rate-limit-remaining` requires
+	#[error("Only {remaining} API requests remain, fewer than the required {required}, the rate limit resets in {reset_in} seconds")]
+	RateLimitBudget {
+		/// Number of remaining requests
+		remaining: u64,
+		/// Minimum number of remaining requests
+		required: u64,
+		/// Seconds until the rate limit resets
+		reset_in: i64,
+	},
+	/// The branch (`OWNER/REPO (BRANCH)`) has no tracking records
+	#[error("{0} is not tracked")]
+	BranchNotTracked(String),
+	/// The tracking records of the branch keep no record of the commit
+	/// (`OWNER/REPO (BRANCH@COMMIT)`)
+	#[error("{0} has no tracking record")]
+	CommitNotRecorded(String),
+	/// The ref (for example a tag) could not be resolved to a commit
+	#[error("{0} could not be resolved to a commit")]
+	RefNotFound(String),
+	/// The tokens lack access to repositories the run needs, found before
+	/// doing any work
+	#[error("The token lacks access the run needs, grant it and rerun:\n  {}", .0.join("\n  "))]
+	MissingAccess(Vec<String>),
+	/// The S3 record storage needs credentials from the environment
+	#[error("The S3 record storage requires AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY")]
+	MissingStorageCredentials,
+	/// An image was requested with a zero width or height
+	#[error("Invalid image size {0}x{1}, the width and height must be at least 1")]
+	InvalidImageSize(u32, u32),
+	/// A publish payload was computed for another commit than the one it
+	/// would be posted for
+	#[error("Refusing to publish the payload of commit {0}, which is not the {2} {1}")]
+	PayloadCommitMismatch(String, String, &'static str),
+	/// A command commits or downloads through the GitHub API, but another
+	/// provider was selected
+	#[error("{0} is only supported with --provider github")]
+	GitHubOnly(&'static str),
+	/// XML deserialisation error ([quick_xml::DeError])
+	#[error("XML Error: {0}")]
+	Xml(#[from] quick_xml::DeError),
+	/// Invalid key for signing a request ([hmac::digest::InvalidLength])
+	#[error("Signing Error: {0}")]
+	Signing(#[from] hmac::digest::InvalidLength),
+}
+
+/// List the candidates of [MeowCoverageError::LcovFileNotFound]
+fn format_candidates(candidates: &[String]) -> String {
+	match candidates.is_empty() {
+		true => String::from(
+			", and no Lcov reports were found nearby. Check the output path of your coverage tool",
+		),
+		false => format!(", did you mean one of these?\n  {}", candidates.join("\n  ")),
+	}
+}
+
+impl MeowCoverageError {
+	/// Whether the error was caused by hitting a rate limit of the provider
+	#[must_use]
+	pub fn is_rate_limited(&self) -> bool {
+		match self {
+			Self::Http(error) => error.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS),
+			Self::GitHub(octocrab::Error::GitHub { source, .. }) => {
+				source.message.to_lowercase().contains("rate limit")
+			}
+			_ => false,
+		}
+	}
+}
+
+impl From<patch::ParseError<'_>> for MeowCoverageError {
+	fn from(value: patch::ParseError<'_>) -> Self {
+		Self::Patch(format!("{}", value))
+	}
+}
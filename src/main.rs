@@ -1,20 +1,93 @@
 //! A code coverage visualiser integrated into GitHub
 
-use std::path::PathBuf;
+use std::{
+	path::{Path, PathBuf},
+	sync::Arc,
+};
 
-use ::lcov::report::ParseError;
 use clap::Parser;
-use thiserror::Error;
-use tracking::Team;
-
-mod coverage;
-pub mod github_api;
-mod tracking;
+use meow_coverage::{
+	artifacts,
+	config::RepositoryConfig,
+	context::{GitHubContext, GitHubEvent},
+	coverage,
+	coverage::{
+		carryforward::Carryforward,
+		diff::{CoverageDiff, DiffFormat},
+		exclusion::ExclusionMarkers,
+		heatmap::Heatmap,
+		helpers::PathStrategy,
+		issues::DropIssues,
+		lcov::{check_lcov_file, LcovInput, LcovWrapper},
+		options::{self, LcovOptions, LinkMode, PullRequestOptions, QuietMode, ReportOptions},
+		owners::CoverageOwners,
+		trend::Trend,
+	},
+	notify::{MatrixNotifier, NotificationRules, Notifications, Notifier, WebhookNotifier},
+	percentage::{Percentage, PercentageFormat, Rounding},
+	provider,
+	provider::{
+		AccessRequirement, AzureDevOpsProvider, BitbucketProvider, CachedProvider, ForgejoProvider,
+		GitHubProvider, VcsProvider, BITBUCKET_API_URL,
+	},
+	tracking,
+	tracking::{
+		DigestPeriod, DigestTarget, RecordCompression, RecordOptions, RetentionSettings, Team,
+		TeamSource,
+	},
+	MeowCoverageError,
+};
+use octocrab::Octocrab;
 
 /// Meow-Coverage CLI Main Command
 #[derive(Debug, clap::Parser)]
 #[clap(author, version, about, long_about = None)]
+#[allow(clippy::large_enum_variant)]
 enum CliMainCommand {
+	/// Commands talking to the provider
+	#[clap(flatten)]
+	Provider(CliProviderCommand),
+	/// Commands running locally
+	#[clap(flatten)]
+	Local(CliLocalCommand),
+}
+
+/// Commands running locally, which need neither a token nor a repository
+#[derive(Debug, clap::Subcommand)]
+enum CliLocalCommand {
+	/// Compare two Lcov files locally and print the per-file deltas and the
+	/// newly uncovered and covered lines, without talking to any provider
+	Diff {
+		/// Old Lcov file path
+		#[clap(long)]
+		old_lcov_file: String,
+
+		/// New Lcov file path
+		#[clap(long)]
+		new_lcov_file: String,
+
+		/// Output format
+		#[clap(long, value_enum, default_value_t = CliDiffFormat::Terminal)]
+		format: CliDiffFormat,
+
+		/// Root to strip from the paths in the Lcov reports (for example
+		/// `C:\build`), useful for reports produced on Windows
+		#[clap(long)]
+		strip_path_root: Option<String>,
+	},
+	/// Render coverage visualisations locally, without talking to any
+	/// provider
+	Visualize {
+		/// Visualisation subcommand
+		#[clap(subcommand)]
+		command: CliVisualizeCommand,
+	},
+}
+
+/// Commands talking to the provider of the repository
+#[derive(Debug, clap::Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum CliProviderCommand {
 	/// Centralised coverage tracking repo operations
 	Tracking {
 		/// Coverage repository in format `OWNER/REPO`
@@ -26,35 +99,141 @@ enum CliMainCommand {
 	},
 	/// Analyse coverage for a single run
 	CoverageRun {
-		/// Prefix for locating source files in Lcov paths (for example 'src/')
-		#[clap(long)]
-		source_prefix: String,
+		/// Mapping of Lcov paths to repository paths
+		#[clap(flatten)]
+		paths: CliPathArgs,
 
 		/// Commit ID
 		#[clap(long)]
-		commit_id: String,
+		commit_id: Option<String>,
 
-		/// New Lcov file path
-		#[clap(long)]
-		new_lcov_file: String,
+		/// New Lcov file path, repeat as `LABEL=PATH` to merge several
+		/// labelled reports (for example `frontend` and `backend`) into the
+		/// total and show the total of every report
+		#[clap(long, required = true)]
+		new_lcov_file: Vec<LcovInput>,
+
+		/// Lcov loading and reporting options
+		#[clap(flatten)]
+		run: CliRunArgs,
 
 		/// Choose if Push or PullRequest based
 		#[clap(subcommand)]
 		command: CliCoverageCommand,
 	},
+	/// Entrypoint for GitHub Actions: picks the mode from the triggering event
+	/// (`push`, `pull_request`, or a `workflow_dispatch` rebuilding the
+	/// tracking repo), reading the run context from the environment
+	Action {
+		/// Lcov files, tracking, and reporting options of the action
+		#[clap(flatten)]
+		action: CliActionArgs,
+	},
+	/// Compare the coverage of two refs of the repository, read from their
+	/// tracking records or from two Lcov files, and print the comparison or
+	/// post it as a commit comment, for example for release candidate reviews
+	Compare {
+		/// Refs or Lcov files to compare and where to report the comparison
+		#[clap(flatten)]
+		compare: CliCompareArgs,
+	},
+	/// Post the comments and the commit status written by a pull request run
+	/// passing `--publish-payload`, for example from a `workflow_run`
+	/// triggered by the run of a pull request from a fork
+	PublishFromArtifact {
+		/// Path to the payload written by the pull request run
+		#[clap(long)]
+		payload: PathBuf,
+
+		/// Commit the payload must have been computed for, defaults to the head
+		/// of the triggering `workflow_run` with `--auto-context`
+		#[clap(long)]
+		expected_commit: Option<String>,
+
+		/// Write what was posted to this file as JSON
+		#[clap(long)]
+		output_json: Option<PathBuf>,
+	},
+}
+
+impl CliProviderCommand {
+	/// Name of the command if it commits to the tracking repository or
+	/// downloads artifacts through the GitHub API, which is only available
+	/// with `--provider github`. The action only rebuilds the tracking
+	/// repository on `workflow_dispatch` `event`s
+	fn github_only(&self, event: Option<&GitHubEvent>) -> Option<&'static str> {
+		match self {
+			Self::Tracking { command, .. } => match command {
+				CliTrackingCommand::Rebuild { .. } => Some("tracking rebuild"),
+				CliTrackingCommand::RemoveBranch { .. } => Some("tracking remove-branch"),
+				CliTrackingCommand::RenameBranch { .. } => Some("tracking rename-branch"),
+				CliTrackingCommand::RemoveRepo { .. } => Some("tracking remove-repo"),
+				CliTrackingCommand::Digest { .. } => Some("tracking digest"),
+				CliTrackingCommand::Backfill { .. } => Some("tracking backfill"),
+				CliTrackingCommand::Init => Some("tracking init"),
+				CliTrackingCommand::List { .. }
+				| CliTrackingCommand::Show { .. }
+				| CliTrackingCommand::Changelog { .. }
+				| CliTrackingCommand::BuildSite { .. } => None,
+			},
+			Self::Action { .. } => matches!(event, Some(GitHubEvent::WorkflowDispatch(_)))
+				.then_some("rebuilding the tracking repository on workflow_dispatch"),
+			Self::CoverageRun { .. } | Self::Compare { .. } | Self::PublishFromArtifact { .. } => {
+				None
+			}
+		}
+	}
 }
 
 /// Meow-Coverage CLI Arguments
 #[derive(Debug, clap::Parser)]
 #[clap(author, version, about, long_about = None)]
 struct CliArgs {
-	/// GitHub API Token
+	/// GitHub API Token (or the API token of the selected provider)
+	#[clap(long)]
+	github_token: Option<String>,
+
+	/// API token for the centralised coverage tracking repository, for setups
+	/// where the token of the analysed repository can't write to it, defaults
+	/// to the GitHub API token
 	#[clap(long)]
-	github_token: String,
+	coverage_repo_token: Option<String>,
 
 	/// Repository name in format `OWNER/REPO`
 	#[clap(long)]
-	repo_name: String,
+	repo_name: Option<String>,
+
+	/// Fill in the token, repository name, commit ID, pull request number,
+	/// and branch from the GitHub Actions environment, unless passed
+	/// explicitly
+	#[clap(long)]
+	auto_context: bool,
+
+	/// Hosting provider of the repository
+	#[clap(long, value_enum, default_value_t = CliProvider::Github)]
+	provider: CliProvider,
+
+	/// Base URL of the provider instance, required for Forgejo (for example
+	/// `https://codeberg.org`) and Azure DevOps (for example
+	/// `https://dev.azure.com/famedly`), defaults to `https://api.bitbucket.org`
+	/// for Bitbucket
+	#[clap(long)]
+	base_url: Option<String>,
+
+	/// Number of decimals of the percentages in comments, statuses, records,
+	/// and the visualisations (0 to 2)
+	#[clap(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=2))]
+	percentage_precision: u8,
+
+	/// How percentages are rounded to the precision
+	#[clap(long, value_enum, default_value_t = CliRounding::Nearest)]
+	percentage_rounding: CliRounding,
+
+	/// Abort with an error at startup and before posting comments if fewer
+	/// requests of the API rate limit remain, instead of failing mid-way
+	/// (GitHub only)
+	#[clap(long)]
+	min_rate_limit_remaining: Option<u64>,
 
 	/// Choose if analysing coverage for a single run, or managing the
 	/// centralised coverage tracking repo
@@ -62,6 +241,122 @@ struct CliArgs {
 	command: CliMainCommand,
 }
 
+/// Supported hosting providers
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum CliProvider {
+	/// GitHub
+	Github,
+	/// Gitea or Forgejo
+	#[clap(alias = "gitea")]
+	Forgejo,
+	/// Bitbucket Cloud, the repository owner is the workspace
+	Bitbucket,
+	/// Azure DevOps, the repository owner is the project and the base URL is
+	/// the organisation URL
+	AzureDevops,
+}
+
+impl CliProvider {
+	/// Build the [VcsProvider] authenticated with `token`, GitHub uses the
+	/// `client` built for the token
+	fn build(
+		self,
+		base_url: Option<&str>,
+		token: String,
+		client: Arc<Octocrab>,
+	) -> Result<Box<dyn VcsProvider>, MeowCoverageError> {
+		Ok(match self {
+			Self::Github => Box::new(GitHubProvider::new(client)),
+			Self::Forgejo => Box::new(ForgejoProvider::new(
+				base_url.ok_or(MeowCoverageError::MissingBaseUrl)?,
+				token,
+			)),
+			Self::Bitbucket => {
+				Box::new(BitbucketProvider::new(base_url.unwrap_or(BITBUCKET_API_URL), token))
+			}
+			Self::AzureDevops => Box::new(AzureDevOpsProvider::new(
+				base_url.ok_or(MeowCoverageError::MissingBaseUrl)?,
+				token,
+			)),
+		})
+	}
+}
+
+/// How percentages are rounded to their display precision
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum CliRounding {
+	/// Round to the nearest value
+	Nearest,
+	/// Round down, so coverage is never overstated
+	Down,
+}
+
+/// What the files and lines in summary comments link to
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum CliLinkMode {
+	/// Anchors in the diff of the pull request or commit
+	Diff,
+	/// The files at the analysed commit
+	Blob,
+}
+
+/// How the files of tracking records are compressed
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum CliRecordCompression {
+	/// Store untested lines as ranges of consecutive lines
+	Ranges,
+	/// Store untested lines as ranges and gzip the files
+	Gzip,
+}
+
+/// How loudly the results of a coverage run are reported
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum CliQuietMode {
+	/// Post comments and a commit status
+	Off,
+	/// Only report via the commit status
+	StatusOnly,
+}
+
+/// Subcommand wrapper for rendering coverage visualisations
+#[derive(Debug, clap::Subcommand)]
+enum CliVisualizeCommand {
+	/// Render a treemap of the per-file coverage, the area of a file is its
+	/// number of instrumented lines and its colour its coverage
+	Heatmap {
+		/// Lcov file path
+		#[clap(long, required_unless_present = "record", conflicts_with = "record")]
+		lcov_file: Option<String>,
+
+		/// Tracking record file (`records/OWNER/REPO/BRANCH.json`) to render
+		/// the latest record of
+		#[clap(long)]
+		record: Option<PathBuf>,
+
+		/// Output file path
+		#[clap(long)]
+		output: PathBuf,
+
+		/// Output format, inferred from the extension of the output file if
+		/// not set
+		#[clap(long, value_enum)]
+		format: Option<CliHeatmapFormat>,
+
+		/// Width in pixels
+		#[clap(long, default_value_t = 800, value_parser = clap::value_parser!(u32).range(1..))]
+		width: u32,
+
+		/// Height in pixels
+		#[clap(long, default_value_t = 500, value_parser = clap::value_parser!(u32).range(1..))]
+		height: u32,
+
+		/// Root to strip from the paths in the Lcov report (for example
+		/// `C:\build`), useful for reports produced on Windows
+		#[clap(long)]
+		strip_path_root: Option<String>,
+	},
+}
+
 /// Subcommand wrapper for managing the centralised coverage tracking repo
 #[derive(Debug, clap::Subcommand)]
 enum CliTrackingCommand {
@@ -74,14 +369,123 @@ enum CliTrackingCommand {
 		tracking_repo_records: PathBuf,
 
 		/// Repository branch to generate individualised report on
+		#[clap(long, required_unless_present = "all")]
+		branch: Option<String>,
+		/// Regenerate the reports of all branches in the records in a single
+		/// commit, instead of the report of `--branch`
+		#[clap(long, conflicts_with = "branch")]
+		all: bool,
+	},
+	/// Remove branches of a repository from the tracking records
+	RemoveBranch {
+		/// Repository branch to remove, can be a glob (for example
+		/// `feature/*`) and repeated
+		#[clap(long = "branch", required = true)]
+		branches: Vec<String>,
+	},
+	/// Move the records of a renamed branch of a repository to its new name,
+	/// keeping its history
+	RenameBranch {
+		/// Previous name of the branch
 		#[clap(long)]
+		from: String,
+		/// New name of the branch
+		#[clap(long)]
+		to: String,
+	},
+	/// Remove all branches of repositories from the tracking records, with
+	/// their reports
+	RemoveRepo {
+		/// Repository to remove (`OWNER/REPO`), can be a glob (for example
+		/// `famedly/legacy-*`) and repeated, `--repo-name` if not passed
+		#[clap(long = "repo")]
+		repos: Vec<String>,
+	},
+	/// Publish a digest of the coverage changes of all tracked branches
+	Digest {
+		/// Path to where the `records` branch of the tracking repository is
+		/// cloned
+		#[clap(long = "records")]
+		tracking_repo_records: PathBuf,
+
+		/// Period covered by the digest
+		#[clap(long, value_enum, default_value = "weekly")]
+		period: CliDigestPeriod,
+
+		/// Where to publish the digest
+		#[clap(long, value_enum, default_value = "commit")]
+		publish: CliDigestTarget,
+	},
+	/// List all tracked branches with their latest coverage
+	List {
+		/// Path to where the `records` branch of the tracking repository is
+		/// cloned, read via the API if not passed
+		#[clap(long = "records")]
+		tracking_repo_records: Option<PathBuf>,
+	},
+	/// Show the history, deltas, and worst covered files of a tracked branch
+	Show {
+		/// Repository of the branch (`OWNER/REPO`)
+		repo: String,
+		/// Branch to show
 		branch: String,
+
+		/// Path to where the `records` branch of the tracking repository is
+		/// cloned, read via the API if not passed
+		#[clap(long = "records")]
+		tracking_repo_records: Option<PathBuf>,
 	},
-	/// Remove a branch of a repository from the tracking records
-	RemoveBranch {
-		/// Repository branch to remove
+	/// Reconstruct the coverage history of a branch from the Lcov artifacts
+	/// uploaded by its past push runs with `--upload-artifact`, so newly
+	/// tracked branches get a populated trend
+	Backfill {
+		/// Repository of the branch (`OWNER/REPO`), `--repo-name` if not passed
+		#[clap(long)]
+		repo: Option<String>,
+		/// Branch to backfill
 		#[clap(long)]
 		branch: String,
+		/// Date (`YYYY-MM-DD`) to reconstruct the history since
+		#[clap(long, value_parser = parse_since)]
+		since: i64,
+		/// Team to track the branch for if it has no records yet
+		#[clap(long, default_value_t = Team::Other)]
+		coverage_team: Team,
+	},
+	/// Summarise the coverage changes of a branch between two releases as
+	/// markdown, for release notes
+	Changelog {
+		/// Repository of the releases (`OWNER/REPO`), `--repo-name` if not
+		/// passed
+		#[clap(long)]
+		repo: Option<String>,
+		/// Tracked branch the releases were tagged on
+		#[clap(long, default_value = "main")]
+		branch: String,
+		/// Tag of the earlier release
+		#[clap(long)]
+		from_tag: String,
+		/// Tag of the later release
+		#[clap(long)]
+		to_tag: String,
+		/// Write the changelog to this file instead of the standard output
+		#[clap(long)]
+		output: Option<PathBuf>,
+	},
+	/// Create the `main` and `records` branches of a new tracking repository,
+	/// keeping existing files
+	Init,
+	/// Build a static HTML site of the records, for publishing via GitHub
+	/// Pages
+	BuildSite {
+		/// Path to where the `records` branch of the tracking repository is
+		/// cloned
+		#[clap(long = "records")]
+		tracking_repo_records: PathBuf,
+
+		/// Directory to write the site to
+		#[clap(long, default_value = "site")]
+		output: PathBuf,
 	},
 }
 
@@ -89,130 +493,1685 @@ enum CliTrackingCommand {
 #[derive(Debug, clap::Subcommand)]
 enum CliCoverageCommand {
 	/// Run for a commit
-	Push,
+	Push {
+		/// Notification options
+		#[clap(flatten)]
+		notify: CliNotifyArgs,
+		/// Branch for the commit, the artifact of which `--upload-artifact`
+		/// uploads
+		#[clap(long)]
+		branch: Option<String>,
+	},
 	/// Run for a commit and collect the report afterwards
 	PushWithReport {
+		/// Notification options
+		#[clap(flatten)]
+		notify: CliNotifyArgs,
 		/// Branch for the commit
 		#[clap(long)]
-		branch: String,
+		branch: Option<String>,
 		/// Repository for submitting the coverage report record to
 		#[clap(long)]
 		coverage_repo: String,
-		/// Repository for submitting the coverage report record to
-		#[clap(long)]
-		coverage_team: Team,
+		/// Team responsible for the repository, overriding `--team-source`
+		#[clap(long, required_unless_present = "team_source")]
+		coverage_team: Option<Team>,
+		/// Tracking record options
+		#[clap(flatten)]
+		records: CliRecordArgs,
 	},
 	/// Run for a PR
 	PullRequest {
 		/// Pull request identifier
 		#[clap(long)]
-		pr_number: u64,
+		pr_number: Option<u64>,
 
 		/// Old Lcov file path
-		#[clap(long)]
+		#[clap(long, conflicts_with = "baseline_artifact")]
 		old_lcov_file: Option<String>,
+
+		/// Pull request options
+		#[clap(flatten)]
+		pull_request: CliPullRequestArgs,
 	},
 }
 
-/// Error collection
-#[derive(Debug, Error)]
-pub enum MeowCoverageError {
-	/// LCOV parsing error ([ParseError])
-	#[error("Lcov Parsing Failed: {0}")]
-	LcovParse(#[from] ParseError),
-	/// GitHub API Error ([octocrab::Error])
-	#[error("GitHub API Error: {0}")]
-	GitHub(#[from] octocrab::Error),
-	/// Repository name not in format `OWNER/REPO`
-	#[error("Repo Name must be in format OWNER/REPO")]
-	RepoNameMissingSlash,
-	/// Patch parsing error [patch::ParseError]
-	#[error("Patch Parse Error: {0}")]
-	Patch(String),
-	/// Hyper error
-	#[error("Hyper Error: {0}")]
-	Hyper(#[from] hyper::Error),
-	/// serde_json error
-	#[error("Serde Error: {0}")]
-	SerdeJson(#[from] serde_json::Error),
-	/// GitHub token does not have permission to access the contents of the
-	/// coverage repo
-	#[error("Token does not have permission to access coverage repo")]
-	MissingAccessToCoverageRepo,
-	/// [std::io::Error] vairant
-	#[error(transparent)]
-	Io(#[from] std::io::Error),
-	/// Attempted to build a report on a branch that is missing valid reports
-	#[error("Attempted to build a report on a branch that is missing valid reports")]
-	ReportMissingInfo,
-}
-
-impl From<patch::ParseError<'_>> for MeowCoverageError {
-	fn from(value: patch::ParseError<'_>) -> Self {
-		Self::Patch(format!("{}", value))
+/// Arguments of the `compare` command, either both refs or both Lcov files
+#[derive(Debug, clap::Args)]
+struct CliCompareArgs {
+	/// Base ref, a tracked branch for its latest record or `BRANCH@COMMIT`
+	/// for the record of an earlier commit of the branch
+	#[clap(long, required_unless_present = "base_lcov_file", requires_all = ["head", "coverage_repo"])]
+	base: Option<String>,
+
+	/// Head ref, like `--base`
+	#[clap(long, required_unless_present = "head_lcov_file", requires = "base")]
+	head: Option<String>,
+
+	/// Repository the tracking records of the refs are read from, using the
+	/// record storage of the configuration file
+	#[clap(long)]
+	coverage_repo: Option<String>,
+
+	/// Lcov file of the base instead of its tracking record
+	#[clap(long, conflicts_with = "base", requires = "head_lcov_file")]
+	base_lcov_file: Option<String>,
+
+	/// Lcov file of the head instead of its tracking record
+	#[clap(long, conflicts_with = "head", requires = "base_lcov_file")]
+	head_lcov_file: Option<String>,
+
+	/// Root to strip from the paths in the Lcov files (for example
+	/// `C:\build`), useful for reports produced on Windows
+	#[clap(long)]
+	strip_path_root: Option<String>,
+
+	/// Output format
+	#[clap(long, value_enum, default_value_t = CliDiffFormat::Markdown)]
+	format: CliDiffFormat,
+
+	/// Write the comparison to this file instead of the standard output
+	#[clap(long)]
+	output: Option<PathBuf>,
+
+	/// Post the comparison as a markdown comment on this commit
+	#[clap(long)]
+	comment_on: Option<String>,
+}
+
+impl CliCompareArgs {
+	/// Compare the refs or Lcov files of `repo_name`, writing the comparison
+	/// and posting it if configured with the percentages in
+	/// `percentage_format`, the records of refs are read with
+	/// `coverage_provider`
+	async fn compare(
+		self,
+		provider: &dyn VcsProvider,
+		coverage_provider: &dyn VcsProvider,
+		repo_name: &str,
+		percentage_format: PercentageFormat,
+	) -> Result<(), MeowCoverageError> {
+		let (diff, title) = match (self.base, self.head, self.base_lcov_file, self.head_lcov_file) {
+			(Some(base), Some(head), _, _) => {
+				let coverage_repo = self
+					.coverage_repo
+					.ok_or(MeowCoverageError::MissingArgument("coverage-repo"))?;
+				let storage =
+					tracking::open_storage(coverage_provider, coverage_repo.as_str()).await?;
+				(
+					tracking::compare_tracked(storage.as_ref(), repo_name, &base, &head).await?,
+					format!("Coverage Comparison: `{}` → `{}`", base, head),
+				)
+			}
+			(_, _, Some(base_lcov_file), Some(head_lcov_file)) => (
+				CoverageDiff::new(
+					&load_lcov(&base_lcov_file, self.strip_path_root.as_deref())?,
+					&load_lcov(&head_lcov_file, self.strip_path_root.as_deref())?,
+				),
+				format!("Coverage Comparison: `{}` → `{}`", base_lcov_file, head_lcov_file),
+			),
+			_ => return Err(MeowCoverageError::MissingArgument("base")),
+		};
+
+		let markdown = diff.markdown_titled(&title, percentage_format);
+		let content = match self.format {
+			CliDiffFormat::Markdown => markdown.clone(),
+			format => diff.render(format.diff_format(), percentage_format)?,
+		};
+		match &self.output {
+			Some(output) => std::fs::write(output, content)?,
+			None => std::io::Write::write_all(&mut std::io::stdout(), content.as_bytes())?,
+		}
+
+		if let Some(commit) = self.comment_on {
+			let (owner, repo) =
+				repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+			provider.create_commit_comment(owner, repo, &commit, &markdown).await?;
+		}
+		Ok(())
 	}
 }
 
-#[tokio::main]
-async fn main() -> Result<(), MeowCoverageError> {
-	let args = CliArgs::parse();
+/// Arguments of the `action` command
+#[derive(Debug, clap::Args)]
+struct CliActionArgs {
+	/// Mapping of Lcov paths to repository paths, required unless
+	/// rebuilding the tracking repo
+	#[clap(flatten)]
+	paths: CliPathArgs,
+
+	/// New Lcov file path, or several as `LABEL=PATH`, required unless
+	/// rebuilding the tracking repo
+	#[clap(long)]
+	new_lcov_file: Vec<LcovInput>,
+
+	/// Baseline Lcov file path for pull requests, skipped if the file does
+	/// not exist (for example when no baseline artifact was found)
+	#[clap(long, conflicts_with = "baseline_artifact")]
+	old_lcov_file: Option<String>,
+
+	/// Repository for submitting the coverage report records of pushes to
+	#[clap(long)]
+	coverage_repo: Option<String>,
+
+	/// Team for submitting the coverage report records of pushes to
+	#[clap(long, requires = "coverage_repo")]
+	coverage_team: Option<Team>,
+
+	/// Tracking record options for pushes
+	#[clap(flatten)]
+	records: CliRecordArgs,
+
+	/// Path to where the `records` branch of the tracking repository is
+	/// cloned, for `workflow_dispatch` runs in the tracking repository
+	#[clap(long = "records")]
+	tracking_repo_records: Option<PathBuf>,
+
+	/// Lcov loading and reporting options
+	#[clap(flatten)]
+	run: CliRunArgs,
+
+	/// Pull request options
+	#[clap(flatten)]
+	pull_request: CliPullRequestArgs,
+
+	/// Notification options for pushes
+	#[clap(flatten)]
+	notify: CliNotifyArgs,
+}
+
+/// Mapping of Lcov paths to repository paths, one of the strategies is
+/// required unless configured in the configuration file
+#[derive(Debug, clap::Args)]
+struct CliPathArgs {
+	/// Prefix for locating source files in Lcov paths (for example 'src/')
+	#[clap(long, conflicts_with_all = ["strip_components", "path_rewrite"])]
+	source_prefix: Option<String>,
+
+	/// Strip this many leading components of Lcov paths instead, for example
+	/// 3 for Go module paths like `github.com/org/repo/pkg/file.go`
+	#[clap(long, conflicts_with = "path_rewrite")]
+	strip_components: Option<usize>,
+
+	/// Rewrite Lcov paths by replacing the first match of a regex instead,
+	/// the replacement may refer to capture groups as `$1`
+	#[clap(long, num_args = 2, value_names = ["PATTERN", "REPLACEMENT"])]
+	path_rewrite: Option<Vec<String>>,
+
+	/// Configuration file of the repository, defaults to `.meow-coverage.toml`
+	/// if it exists
+	#[clap(long)]
+	config: Option<PathBuf>,
+}
+
+impl CliPathArgs {
+	/// Read the configuration file of the repository, fetched from the
+	/// analysed commit if there is no local file
+	async fn config(
+		&self,
+		provider: &dyn VcsProvider,
+		repo_name: &str,
+		commit_id: &str,
+	) -> Result<RepositoryConfig, MeowCoverageError> {
+		RepositoryConfig::load_or_fetch(self.config.as_deref(), provider, repo_name, commit_id)
+			.await
+	}
+
+	/// Build the [PathStrategy] from the arguments, falling back to the
+	/// `config`uration file
+	fn path_strategy(self, config: &RepositoryConfig) -> Result<PathStrategy, MeowCoverageError> {
+		match (self.source_prefix, self.strip_components, self.path_rewrite.as_deref()) {
+			(Some(prefix), _, _) => Ok(PathStrategy::Prefix(prefix)),
+			(_, Some(count), _) => Ok(PathStrategy::StripComponents(count)),
+			(_, _, Some([pattern, replacement])) => {
+				Ok(PathStrategy::rewrite(pattern, replacement.clone())?)
+			}
+			_ => config.paths.clone().ok_or(MeowCoverageError::MissingArgument("source-prefix")),
+		}
+	}
+}
+
+/// Options for loading Lcov reports and reporting the results of a run
+#[derive(Debug, clap::Args)]
+struct CliRunArgs {
+	/// Additional marker excluding the line it is on from coverage (for
+	/// example `meow: ignore`), on top of `LCOV_EXCL_LINE` and
+	/// `LCOV_EXCL_START`/`LCOV_EXCL_STOP` blocks
+	#[clap(long)]
+	exclusion_marker: Option<String>,
+
+	/// Also exclude Rust items in `#[cfg(test)]` modules and functions, and
+	/// items annotated with `#[coverage(off)]` or `#[no_coverage]`, parsing
+	/// the source files from the working directory
+	#[clap(long)]
+	rust_exclusions: bool,
+
+	/// Only report files that exist in the repository at the commit,
+	/// dropping generated or untracked files from the reports
+	#[clap(long)]
+	existing_files_only: bool,
+
+	/// Root to strip from the paths in the Lcov reports (for example
+	/// `C:\build`), useful for reports produced on Windows
+	#[clap(long)]
+	strip_path_root: Option<String>,
+
+	/// List the repository files matching this glob (for example
+	/// `src/**/*.rs`) that no Lcov report covers at all as uninstrumented
+	#[clap(long)]
+	uninstrumented_paths: Vec<String>,
+
+	/// Count the non-blank lines of uninstrumented files (read from the
+	/// working directory) as untested in the total
+	#[clap(long, requires = "uninstrumented_paths")]
+	count_uninstrumented: bool,
+
+	/// `status-only` reports solely via the commit status, without posting
+	/// any comments
+	#[clap(long, value_enum, default_value_t = CliQuietMode::Off)]
+	quiet_mode: CliQuietMode,
+
+	/// Append the report to the GitHub Actions step summary (the file at
+	/// `GITHUB_STEP_SUMMARY`)
+	#[clap(long)]
+	step_summary: bool,
+
+	/// Upload the new Lcov file of pushes as the Actions artifact of the
+	/// branch, for pull request runs passing `--baseline-artifact` (GitHub
+	/// Actions only)
+	#[clap(long)]
+	upload_artifact: bool,
+
+	/// Tag the upload with a flag (for example `unit` or `integration`):
+	/// tracked pushes are recorded separately per flag and merged into the
+	/// total of the branch, and comments name the flag
+	#[clap(long)]
+	flag: Option<String>,
+
+	/// Write what the run computed and posted (percentages, untested lines
+	/// per file, comment URLs, and the commit status) to this file as JSON
+	#[clap(long)]
+	output_json: Option<PathBuf>,
+
+	/// What the files and lines in the summary comments link to, `blob`
+	/// links to the files at the analysed commit, which resolve even if the
+	/// diff is collapsed or the file unchanged. Defaults to the `links` of
+	/// the configuration file, or `diff`
+	#[clap(long, value_enum)]
+	links: Option<CliLinkMode>,
+
+	/// Write the comments and the commit status of pull request runs to this
+	/// file instead of posting them, for pull requests from forks whose
+	/// tokens are read-only. Post them with `publish-from-artifact` from a
+	/// `workflow_run` with write permissions
+	#[clap(long)]
+	publish_payload: Option<PathBuf>,
+
+	/// Only list the untested lines changed by the pushed commits in the
+	/// comments of pushes, comparing the commit before the push with the
+	/// analysed commit (GitHub only). Totals and records still cover all files
+	#[clap(long)]
+	changed_only: bool,
+}
+
+impl CliRunArgs {
+	/// Build the configured [LcovOptions] and [ReportOptions] with the
+	/// percentages in `percentage_format`, falling back to the
+	/// `config`uration file
+	fn options(
+		self,
+		config: &RepositoryConfig,
+		min_rate_limit_remaining: Option<u64>,
+		percentage_format: PercentageFormat,
+	) -> Result<(LcovOptions, ReportOptions), MeowCoverageError> {
+		Ok((
+			LcovOptions {
+				exclusions: ExclusionMarkers {
+					custom: self.exclusion_marker,
+					rust_attributes: self.rust_exclusions,
+					..Default::default()
+				},
+				existing_files_only: self.existing_files_only,
+				strip_root: self.strip_path_root,
+				uninstrumented_paths: options::build_globs(&self.uninstrumented_paths)?,
+				count_uninstrumented: self.count_uninstrumented,
+				ignore_paths: options::build_globs(&config.ignore)?,
+			},
+			ReportOptions {
+				quiet_mode: match (self.quiet_mode, config.status_only) {
+					(CliQuietMode::StatusOnly, _) | (_, true) => QuietMode::StatusOnly,
+					(CliQuietMode::Off, false) => QuietMode::Off,
+				},
+				step_summary: match self.step_summary {
+					true => std::env::var_os("GITHUB_STEP_SUMMARY").map(PathBuf::from),
+					false => None,
+				},
+				flag: self.flag,
+				output_json: self.output_json,
+				links: match self.links {
+					Some(CliLinkMode::Diff) => LinkMode::Diff,
+					Some(CliLinkMode::Blob) => LinkMode::Blob,
+					None => config.links.unwrap_or_default(),
+				},
+				publish_payload: self.publish_payload,
+				min_rate_limit_remaining,
+				changed_only: self.changed_only,
+				percentage_format,
+			},
+		))
+	}
+}
+
+/// Options for pull request runs
+#[derive(Debug, clap::Args)]
+struct CliPullRequestArgs {
+	/// Maximum number of review comments created concurrently
+	#[clap(long, default_value_t = 4)]
+	max_concurrent_requests: usize,
+
+	/// Maximum number of review comments, the largest untested ranges get
+	/// a review comment and the rest are listed in the summary comment
+	#[clap(long)]
+	max_review_comments: Option<usize>,
+
+	/// Only create review comments on files matching this glob (for
+	/// example `src/**`), can be passed multiple times. The summary
+	/// comment still lists all files
+	#[clap(long)]
+	comment_paths: Vec<String>,
+
+	/// Template for review comment bodies, with the placeholders
+	/// `{title}`, `{path}`, `{lines}`, `{function}`, `{snippet}`,
+	/// `{file_percentage}`, and `{other_ranges}`
+	#[clap(long, conflicts_with = "review_comment_hints")]
+	review_comment_template: Option<String>,
+
+	/// Include suggested-test hints in review comments: the enclosing
+	/// function and the untested code
+	#[clap(long)]
+	review_comment_hints: bool,
+
+	/// Exit successfully without commenting if the PR has this label, can
+	/// be passed multiple times
+	#[clap(long = "skip-if-label")]
+	skip_if_labels: Vec<String>,
+
+	/// Exit successfully without commenting if the PR is a draft
+	#[clap(long)]
+	skip_draft: bool,
+
+	/// Fail the run and the commit status if less than this percentage of
+	/// the instrumented changed lines is tested, independent of the total
+	/// coverage
+	#[clap(long)]
+	require_patch_coverage: Option<f64>,
+
+	/// Download the baseline Lcov file from the newest Actions artifact of
+	/// the base branch, as uploaded by push runs passing `--upload-artifact`
+	/// (GitHub only)
+	#[clap(long)]
+	baseline_artifact: bool,
+
+	/// Carry the coverage of files missing from the new Lcov file (for
+	/// example of skipped test jobs) forward from the tracking records of the
+	/// base branch in this coverage repository (`OWNER/REPO`)
+	#[clap(long)]
+	carryforward_from: Option<String>,
+
+	/// Show the coverage trend of the base branch from its tracking records in
+	/// this coverage repository (`OWNER/REPO`) in the summary comment, the
+	/// action defaults to `--coverage-repo`
+	#[clap(long)]
+	trend_from: Option<String>,
+
+	/// Base branch to download the baseline artifact of, to carry coverage
+	/// forward from and to show the trend of, read from the GitHub Actions
+	/// environment if not passed
+	#[clap(long)]
+	base_branch: Option<String>,
+
+	/// Cache the changed files of the pull request (per head commit) and the
+	/// files of the repository in this directory across runs, for example
+	/// with `actions/cache`
+	#[clap(long)]
+	cache_dir: Option<PathBuf>,
+
+	/// Mention the owners of files with untested changes (the `[[owners]]`
+	/// of the configuration file) in the summary comment, also enabled by
+	/// `mention_owners = true` in the configuration file
+	#[clap(long)]
+	mention_owners: bool,
+}
+
+impl CliPullRequestArgs {
+	/// Wrap the provider in a [CachedProvider] for the head commit, if
+	/// configured
+	fn provider(&self, provider: Box<dyn VcsProvider>, head_sha: &str) -> Box<dyn VcsProvider> {
+		match &self.cache_dir {
+			Some(cache_dir) => Box::new(CachedProvider::new(provider, cache_dir.clone(), head_sha)),
+			None => provider,
+		}
+	}
+
+	/// Resolve the baseline Lcov file, downloading the baseline artifact of
+	/// the base branch with `client` into a temporary file if configured
+	async fn old_lcov_file(
+		&self,
+		client: &Octocrab,
+		old_lcov_file: Option<String>,
+		repo_name: &str,
+		context_base_branch: Option<String>,
+	) -> Result<Option<String>, MeowCoverageError> {
+		if !self.baseline_artifact {
+			return Ok(old_lcov_file);
+		}
+
+		let base_branch = self
+			.base_branch
+			.clone()
+			.or(context_base_branch)
+			.ok_or(MeowCoverageError::MissingArgument("base-branch"))?;
+		let (owner, repo) =
+			repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+
+		match artifacts::download_lcov_artifact(client, owner, repo, &base_branch).await? {
+			Some(lcov) => {
+				let path = std::env::temp_dir().join(artifacts::artifact_name(&base_branch));
+				std::fs::write(&path, lcov)?;
+				Ok(Some(path.to_string_lossy().into_owned()))
+			}
+			None => {
+				#[allow(clippy::print_stderr)]
+				{
+					eprintln!(
+						"No baseline artifact found for branch {}, reporting without it",
+						base_branch
+					);
+				}
+				Ok(None)
+			}
+		}
+	}
+
+	/// Build the configured [PullRequestOptions], falling back to the
+	/// `config`uration file
+	fn pull_request_options(
+		self,
+		context_base_branch: Option<String>,
+		config: &RepositoryConfig,
+	) -> Result<PullRequestOptions, MeowCoverageError> {
+		let base_branch = self.base_branch.or(context_base_branch);
+		let carryforward = match self.carryforward_from {
+			Some(coverage_repo) => Some(Carryforward {
+				coverage_repo,
+				base_branch: base_branch
+					.clone()
+					.ok_or(MeowCoverageError::MissingArgument("base-branch"))?,
+			}),
+			None => None,
+		};
+		let trend = match self.trend_from {
+			Some(coverage_repo) => Some(Trend {
+				coverage_repo,
+				base_branch: base_branch
+					.ok_or(MeowCoverageError::MissingArgument("base-branch"))?,
+			}),
+			None => None,
+		};
+
+		Ok(PullRequestOptions {
+			comment_paths: PullRequestOptions::build_comment_paths(&self.comment_paths)?,
+			max_concurrent_requests: self.max_concurrent_requests,
+			max_review_comments: self.max_review_comments.or(config.max_review_comments),
+			review_comment_template: match self.review_comment_hints
+				|| (config.review_comment_hints && self.review_comment_template.is_none())
+			{
+				true => Some(String::from(coverage::HINT_REVIEW_COMMENT_TEMPLATE)),
+				false => self.review_comment_template,
+			},
+			skip_if_labels: self.skip_if_labels,
+			skip_draft: self.skip_draft,
+			require_patch_coverage: self.require_patch_coverage.or(config.require_patch_coverage),
+			carryforward,
+			trend,
+			owners: match self.mention_owners || config.mention_owners {
+				true => CoverageOwners::build(&config.owners)?,
+				false => None,
+			},
+		})
+	}
+}
+
+/// Options for the records of pushes in the tracking repository
+#[derive(Debug, clap::Args)]
+struct CliRecordArgs {
+	/// Keep a tracking record for every commit instead of one per day
+	#[clap(long)]
+	per_commit_records: bool,
+
+	/// Keep the coverage percentages of the files of older tracking records,
+	/// for the most regressed files of the branch report
+	#[clap(long)]
+	file_history: bool,
+
+	/// Open an issue in the repository when the coverage dropped by more than
+	/// this many points compared to the previous tracking record, closed
+	/// once the coverage recovered
+	#[clap(long)]
+	open_issue_on_drop: Option<f64>,
+
+	/// Label of the issues opened on coverage drops, can be repeated
+	#[clap(long = "drop-issue-label")]
+	drop_issue_labels: Vec<String>,
+
+	/// Remove records older than this many days (0 keeps records of any
+	/// age), stored as the retention of the branch together with the other
+	/// retention options
+	#[clap(long)]
+	retention_days: Option<u32>,
+
+	/// Keep at most this many records, removing the oldest first
+	#[clap(long)]
+	retention_max_records: Option<usize>,
+
+	/// Keep one record per week of the records older than the retention
+	/// period instead of removing them
+	#[clap(long)]
+	retention_downsample: bool,
+
+	/// Compress the files of the tracking records of big repositories,
+	/// records are decompressed transparently when read
+	#[clap(long, value_enum)]
+	compress_records: Option<CliRecordCompression>,
+
+	/// Reject the upload if its number of files dropped by more than this
+	/// percentage compared to the previous tracking record, for example
+	/// because a test job of a broken CI run did not produce its report
+	#[clap(long)]
+	max_file_drop: Option<f64>,
+
+	/// Reject the upload if its coverage changed by more than this many
+	/// points compared to the previous tracking record
+	#[clap(long)]
+	max_coverage_change: Option<f64>,
+
+	/// Record the upload even if it fails `--max-file-drop` or
+	/// `--max-coverage-change`
+	#[clap(long)]
+	force: bool,
+
+	/// Derive the team responsible for the repository from the owners of
+	/// the whole repository in the CODEOWNERS file, or the teams with access
+	/// to the repository, unless `--coverage-team` is passed
+	#[clap(long, value_enum)]
+	team_source: Option<CliTeamSource>,
+}
+
+/// Period covered by a digest
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum CliDigestPeriod {
+	/// The last 7 days
+	Weekly,
+	/// The last 30 days
+	Monthly,
+}
+
+/// Where to publish a digest
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum CliDigestTarget {
+	/// Commit it to `digests/` on the `main` branch of the tracking repository
+	Commit,
+	/// Open an issue in the tracking repository
+	Issue,
+}
+
+/// Output format of the `diff` and `compare` commands
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum CliDiffFormat {
+	/// Markdown tables and lists
+	Markdown,
+	/// JSON for further processing
+	Json,
+	/// Plain text
+	Terminal,
+}
+
+impl CliDiffFormat {
+	/// The [DiffFormat] of the format
+	fn diff_format(self) -> DiffFormat {
+		match self {
+			Self::Markdown => DiffFormat::Markdown,
+			Self::Json => DiffFormat::Json,
+			Self::Terminal => DiffFormat::Terminal,
+		}
+	}
+}
+
+/// Image format of the `visualize heatmap` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum CliHeatmapFormat {
+	/// Scalable vector graphics, with the coverage of the files as tooltips
+	Svg,
+	/// Portable network graphics
+	Png,
+}
+
+/// Source to derive the responsible team from
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum CliTeamSource {
+	/// Owners of the `*` rule of the CODEOWNERS file
+	Codeowners,
+	/// Teams with access to the repository (GitHub and Forgejo)
+	RepositoryTeams,
+}
+
+impl CliRecordArgs {
+	/// Resolve the team responsible for the repository, `team` if passed,
+	/// otherwise derived from the configured source, falling back to
+	/// [Team::Other] if no owner is named like a team
+	async fn team(
+		&self,
+		team: Option<Team>,
+		provider: &dyn VcsProvider,
+		repo_name: &str,
+		commit_id: &str,
+	) -> Result<Team, MeowCoverageError> {
+		if let Some(team) = team {
+			return Ok(team);
+		}
+		let source = match self.team_source {
+			Some(CliTeamSource::Codeowners) => TeamSource::Codeowners,
+			Some(CliTeamSource::RepositoryTeams) => TeamSource::RepositoryTeams,
+			None => return Err(MeowCoverageError::MissingArgument("coverage-team")),
+		};
+		let (owner, repo) =
+			repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+
+		match tracking::derive_team(provider, owner, repo, commit_id, source).await? {
+			Some(team) => Ok(team),
+			None => {
+				#[allow(clippy::print_stderr)]
+				{
+					eprintln!("No team could be derived for {}, tracking it as Other", repo_name);
+				}
+				Ok(Team::Other)
+			}
+		}
+	}
+
+	/// Build the configured [DropIssues]
+	fn drop_issues(&self) -> DropIssues {
+		DropIssues { drop: self.open_issue_on_drop, labels: self.drop_issue_labels.clone() }
+	}
+
+	/// Build the configured [RecordOptions]
+	fn record_options(&self) -> RecordOptions {
+		let retention = match self.retention_days.is_some()
+			|| self.retention_max_records.is_some()
+			|| self.retention_downsample
+		{
+			true => {
+				let default = RetentionSettings::default();
+				Some(RetentionSettings {
+					days: match self.retention_days {
+						Some(0) => None,
+						Some(days) => Some(days),
+						None => default.days,
+					},
+					max_records: self.retention_max_records,
+					downsample: self.retention_downsample,
+				})
+			}
+			false => None,
+		};
+
+		RecordOptions {
+			per_commit: self.per_commit_records,
+			retention,
+			file_history: self.file_history,
+			compression: self.compress_records.map(|compression| match compression {
+				CliRecordCompression::Ranges => RecordCompression::Ranges,
+				CliRecordCompression::Gzip => RecordCompression::Gzip,
+			}),
+			max_file_drop: self.max_file_drop.filter(|_| !self.force).map(Percentage::from_f64),
+			max_coverage_change: self
+				.max_coverage_change
+				.filter(|_| !self.force)
+				.map(Percentage::from_f64),
+		}
+	}
+}
+
+/// Options for notifying about coverage regressions on pushes
+#[derive(Debug, clap::Args)]
+struct CliNotifyArgs {
+	/// Notify when the coverage percentage is below this value
+	#[clap(long)]
+	notify_below: Option<f64>,
+	/// Notify when the coverage percentage dropped by more than this many
+	/// points compared to the previous tracking record
+	#[clap(long)]
+	notify_drop: Option<f64>,
+	/// Matrix homeserver URL to send notifications with
+	#[clap(long, requires_all = ["matrix_access_token", "matrix_room_id"])]
+	matrix_homeserver: Option<String>,
+	/// Matrix access token to send notifications with
+	#[clap(long)]
+	matrix_access_token: Option<String>,
+	/// Matrix room ID to send notifications to
+	#[clap(long)]
+	matrix_room_id: Option<String>,
+	/// Webhook URL to post JSON notifications to (compatible with Slack
+	/// incoming webhooks)
+	#[clap(long)]
+	notify_webhook: Option<String>,
+}
+
+impl CliNotifyArgs {
+	/// Build the configured [Notifications]
+	fn notifications(self) -> Result<Notifications, MeowCoverageError> {
+		let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+
+		if let (Some(homeserver), Some(access_token), Some(room_id)) =
+			(self.matrix_homeserver, self.matrix_access_token, self.matrix_room_id)
+		{
+			notifiers.push(Box::new(MatrixNotifier::new(&homeserver, access_token, room_id)?));
+		}
+
+		if let Some(url) = self.notify_webhook {
+			notifiers.push(Box::new(WebhookNotifier::new(url)));
+		}
+
+		Ok(Notifications {
+			rules: NotificationRules { below: self.notify_below, drop: self.notify_drop },
+			notifiers,
+		})
+	}
+}
+
+/// Parse the `--since` date of backfills into a unix timestamp
+fn parse_since(value: &str) -> Result<i64, String> {
+	artifacts::parse_timestamp(value).ok_or_else(|| format!("{} is not a YYYY-MM-DD date", value))
+}
+
+/// Compare two Lcov files and write the difference with the percentages in
+/// `percentage_format` to stdout
+fn diff(
+	old_lcov_file: &str,
+	new_lcov_file: &str,
+	format: CliDiffFormat,
+	strip_path_root: Option<&str>,
+	percentage_format: PercentageFormat,
+) -> Result<(), MeowCoverageError> {
+	let output = CoverageDiff::new(
+		&load_lcov(old_lcov_file, strip_path_root)?,
+		&load_lcov(new_lcov_file, strip_path_root)?,
+	)
+	.render(format.diff_format(), percentage_format)?;
+	std::io::Write::write_all(&mut std::io::stdout(), output.as_bytes())?;
+	Ok(())
+}
+
+/// Load the Lcov file at `path` for comparisons, stripping `strip_path_root`
+/// from its paths
+fn load_lcov(path: &str, strip_path_root: Option<&str>) -> Result<LcovWrapper, MeowCoverageError> {
+	check_lcov_file(path)?;
+	let mut lcov = LcovWrapper::new(path)?;
+	lcov.normalise_paths(strip_path_root);
+	Ok(lcov)
+}
+
+/// Render the heatmap of an Lcov file or of the latest record of a tracking
+/// record file, with the percentages of SVG tooltips in `percentage_format`
+fn heatmap(
+	command: CliVisualizeCommand,
+	percentage_format: PercentageFormat,
+) -> Result<(), MeowCoverageError> {
+	let CliVisualizeCommand::Heatmap {
+		lcov_file,
+		record,
+		output,
+		format,
+		width,
+		height,
+		strip_path_root,
+	} = command;
+
+	let heatmap = match (lcov_file, record) {
+		(Some(lcov_file), _) => {
+			check_lcov_file(&lcov_file)?;
+			Heatmap::from_statistics(&LcovWrapper::stream(&lcov_file, strip_path_root.as_deref())?)
+		}
+		(None, Some(record)) => {
+			let collection =
+				tracking::BranchCoverageRecordCollection::from_slice(&std::fs::read(record)?)?;
+			collection.latest().map(Heatmap::from_record).unwrap_or_default()
+		}
+		(None, None) => return Err(MeowCoverageError::MissingArgument("lcov-file")),
+	};
+
+	let format = format.unwrap_or(
+		match output.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png")) {
+			true => CliHeatmapFormat::Png,
+			false => CliHeatmapFormat::Svg,
+		},
+	);
+	let content = match format {
+		CliHeatmapFormat::Svg => heatmap.render_svg(width, height, percentage_format)?.into_bytes(),
+		CliHeatmapFormat::Png => heatmap.render_png(width, height)?,
+	};
+	std::fs::write(output, content)?;
+	Ok(())
+}
+
+/// Report the branches removed from the tracking records
+#[allow(clippy::print_stderr)]
+fn print_removed(removed: &[(String, String, String)]) {
+	if removed.is_empty() {
+		eprintln!("No tracked branches matched");
+	}
+	for (owner, repo, branch) in removed {
+		eprintln!("Removed {}/{} ({})", owner, repo, branch);
+	}
+}
+
+/// Upload the new Lcov files, concatenated, as the artifact of `branch`
+async fn upload_artifact(
+	branch: &str,
+	new_lcov_files: &[LcovInput],
+) -> Result<(), MeowCoverageError> {
+	let mut lcov = Vec::new();
+	for input in new_lcov_files {
+		lcov.extend(std::fs::read(&input.path)?);
+		if !lcov.ends_with(b"\n") {
+			lcov.push(b'\n');
+		}
+	}
+	artifacts::upload_lcov_artifact(branch, &lcov).await
+}
+
+/// Permissions of fine-grained tokens for reporting on pull requests
+const PULL_REQUEST_PERMISSIONS: &str = "`pull-requests: write`, `statuses: write`";
+
+/// Permissions of fine-grained tokens for reporting on pushes
+const PUSH_PERMISSIONS: &str = "`contents: write`, `statuses: write`";
+
+/// Permissions of fine-grained tokens for recording in the tracking repository
+const COVERAGE_REPO_PERMISSIONS: &str =
+	"`contents: write`, `actions: write`, pass a token with --coverage-repo-token";
+
+/// Check the access of the tokens before a coverage run: reporting on
+/// `repo_name` with the `report_permissions` (reading it if the report is
+/// only written to a publish payload), and recording in `coverage_repo`
+async fn check_run_access(
+	provider: &dyn VcsProvider,
+	coverage_provider: &dyn VcsProvider,
+	repo_name: &str,
+	report_options: &ReportOptions,
+	report_permissions: &str,
+	coverage_repo: Option<&str>,
+) -> Result<(), MeowCoverageError> {
+	let reports = report_options.publish_payload.is_none();
+	let mut requirements = vec![AccessRequirement {
+		provider,
+		repo_name,
+		write: reports,
+		permissions: match reports {
+			true => report_permissions,
+			false => "`contents: read`",
+		},
+	}];
+	if let Some(coverage_repo) = coverage_repo {
+		requirements.push(AccessRequirement {
+			provider: coverage_provider,
+			repo_name: coverage_repo,
+			write: true,
+			permissions: COVERAGE_REPO_PERMISSIONS,
+		});
+	}
+
+	provider::check_access(&requirements).await
+}
+
+/// Clients and settings shared by the commands talking to the provider
+struct ProviderRun {
+	/// Run context read from the GitHub Actions environment
+	context: GitHubContext,
+	/// Analysed repository in format `OWNER/REPO`
+	repo_name: String,
+	/// Whether the provider is GitHub
+	github: bool,
+	/// GitHub client of the analysed repository
+	client: Arc<Octocrab>,
+	/// GitHub client of the tracking repository
+	coverage_client: Arc<Octocrab>,
+	/// Provider of the analysed repository
+	provider: Box<dyn VcsProvider>,
+	/// Provider of the tracking repository
+	coverage_provider: Box<dyn VcsProvider>,
+	/// Minimum remaining requests of the API rate limit
+	min_rate_limit_remaining: Option<u64>,
+	/// Format of the percentages
+	percentage_format: PercentageFormat,
+}
+
+/// Analysed commit of a coverage run
+struct RunCommit {
+	/// Commit ID
+	id: String,
+	/// Configuration file of the repository at the commit
+	config: RepositoryConfig,
+	/// Mapping of Lcov paths to repository paths
+	path_strategy: PathStrategy,
+}
 
-	octocrab::initialise(octocrab::Octocrab::builder().personal_token(args.github_token).build()?);
+impl RunCommit {
+	/// Read the configuration file of the repository at the commit `id` and
+	/// build the mapping of Lcov paths from `paths`
+	async fn load(
+		run: &ProviderRun,
+		paths: CliPathArgs,
+		id: String,
+	) -> Result<Self, MeowCoverageError> {
+		let config = paths.config(run.provider.as_ref(), run.repo_name.as_str(), &id).await?;
+		let path_strategy = paths.path_strategy(&config)?;
+		Ok(Self { id, config, path_strategy })
+	}
+}
 
-	match args.command {
-		CliMainCommand::Tracking { coverage_repo_name, command } => match command {
-			CliTrackingCommand::Rebuild { tracking_repo_records, branch } => {
+/// Run a tracking repository `command` on `coverage_repo_name`
+async fn tracking(
+	run: ProviderRun,
+	coverage_repo_name: &str,
+	command: CliTrackingCommand,
+) -> Result<(), MeowCoverageError> {
+	let coverage_provider = run.coverage_provider.as_ref();
+	match command {
+		CliTrackingCommand::Rebuild { tracking_repo_records, branch, .. } => match branch {
+			Some(branch) => {
 				tracking::rebuild(
+					&run.coverage_client,
 					&tracking_repo_records,
-					coverage_repo_name.as_str(),
-					args.repo_name.as_str(),
+					coverage_repo_name,
+					run.repo_name.as_str(),
 					branch.as_str(),
+					run.percentage_format,
 				)
 				.await
 			}
-			CliTrackingCommand::RemoveBranch { branch } => {
-				tracking::remove_branch_from_tracking(
-					coverage_repo_name.as_str(),
-					args.repo_name.as_str(),
-					branch.as_str(),
+			None => {
+				tracking::rebuild_all(
+					&run.coverage_client,
+					&tracking_repo_records,
+					coverage_repo_name,
+					run.percentage_format,
 				)
 				.await
 			}
 		},
-		CliMainCommand::CoverageRun { source_prefix, commit_id, new_lcov_file, command } => {
-			match command {
-				CliCoverageCommand::PullRequest { pr_number, old_lcov_file } => {
-					coverage::generate_pr_coverage_report(
-						args.repo_name.as_str(),
-						source_prefix.as_str(),
-						commit_id.as_str(),
-						pr_number,
-						new_lcov_file.as_str(),
-						old_lcov_file.as_deref(),
-					)
-					.await
-				}
-				CliCoverageCommand::Push => {
-					coverage::generate_push_coverage_report(
-						new_lcov_file.as_str(),
-						args.repo_name.as_str(),
-						source_prefix.as_str(),
-						commit_id.as_str(),
-						None,
-					)
-					.await
-				}
-				CliCoverageCommand::PushWithReport { branch, coverage_repo, coverage_team } => {
-					coverage::generate_push_coverage_report(
-						new_lcov_file.as_str(),
-						args.repo_name.as_str(),
-						source_prefix.as_str(),
-						commit_id.as_str(),
-						Some((branch.as_str(), coverage_repo.as_str(), coverage_team)),
-					)
-					.await
-				}
+		CliTrackingCommand::RemoveBranch { branches } => {
+			let removed = tracking::remove_from_tracking(
+				&run.coverage_client,
+				coverage_provider,
+				coverage_repo_name,
+				&[run.repo_name],
+				&branches,
+			)
+			.await?;
+			print_removed(&removed);
+			Ok(())
+		}
+		CliTrackingCommand::RenameBranch { from, to } => {
+			rename_branch(&run, coverage_repo_name, &from, &to).await
+		}
+		CliTrackingCommand::Backfill { repo, branch, since, coverage_team } => {
+			let repo = repo.unwrap_or_else(|| run.repo_name.clone());
+			backfill(&run, coverage_repo_name, &repo, &branch, since, coverage_team).await
+		}
+		CliTrackingCommand::Changelog { repo, branch, from_tag, to_tag, output } => {
+			let repo = repo.unwrap_or_else(|| run.repo_name.clone());
+			changelog(&run, coverage_repo_name, &repo, &branch, (&from_tag, &to_tag), output).await
+		}
+		CliTrackingCommand::RemoveRepo { mut repos } => {
+			if repos.is_empty() {
+				repos.push(run.repo_name);
 			}
+			let removed = tracking::remove_from_tracking(
+				&run.coverage_client,
+				coverage_provider,
+				coverage_repo_name,
+				&repos,
+				&[],
+			)
+			.await?;
+			print_removed(&removed);
+			Ok(())
+		}
+		CliTrackingCommand::Digest { tracking_repo_records, period, publish } => {
+			digest(&run, coverage_repo_name, &tracking_repo_records, period, publish).await
+		}
+		CliTrackingCommand::List { tracking_repo_records } => {
+			let list = tracking::list_tracked(
+				coverage_provider,
+				coverage_repo_name,
+				tracking_repo_records.as_deref(),
+				run.percentage_format,
+			)
+			.await?;
+			std::io::Write::write_all(&mut std::io::stdout(), list.as_bytes())?;
+			Ok(())
+		}
+		CliTrackingCommand::Show { repo, branch, tracking_repo_records } => {
+			let details = tracking::show_tracked(
+				coverage_provider,
+				coverage_repo_name,
+				tracking_repo_records.as_deref(),
+				repo.as_str(),
+				branch.as_str(),
+				run.percentage_format,
+			)
+			.await?;
+			std::io::Write::write_all(&mut std::io::stdout(), details.as_bytes())?;
+			Ok(())
+		}
+		CliTrackingCommand::Init => init(&run, coverage_repo_name).await,
+		CliTrackingCommand::BuildSite { tracking_repo_records, output } => {
+			build_site(&run, coverage_repo_name, &tracking_repo_records, &output).await
+		}
+	}
+}
+
+/// Move the records of the branch `from` of the analysed repository to `to`
+async fn rename_branch(
+	run: &ProviderRun,
+	coverage_repo_name: &str,
+	from: &str,
+	to: &str,
+) -> Result<(), MeowCoverageError> {
+	let renamed = tracking::rename_tracked_branch(
+		&run.coverage_client,
+		run.coverage_provider.as_ref(),
+		coverage_repo_name,
+		run.repo_name.as_str(),
+		from,
+		to,
+	)
+	.await?;
+	match renamed {
+		true => Ok(()),
+		false => Err(MeowCoverageError::BranchNotTracked(format!("{} ({})", run.repo_name, from))),
+	}
+}
+
+/// Backfill the records of `branch` of `repo` since the timestamp `since`,
+/// tracking it for `coverage_team` if it has no records yet
+async fn backfill(
+	run: &ProviderRun,
+	coverage_repo_name: &str,
+	repo: &str,
+	branch: &str,
+	since: i64,
+	coverage_team: Team,
+) -> Result<(), MeowCoverageError> {
+	let added = tracking::backfill_branch(
+		&run.client,
+		run.coverage_provider.as_ref(),
+		coverage_repo_name,
+		repo,
+		branch.trim_start_matches("refs/heads/"),
+		since,
+		coverage_team,
+	)
+	.await?;
+	#[allow(clippy::print_stderr)]
+	{
+		eprintln!("Backfilled {} records of {} ({})", added, repo, branch);
+	}
+	Ok(())
+}
+
+/// Write the changelog of `branch` of `repo` between the `tags` (from, to) to
+/// `output`, or to stdout
+async fn changelog(
+	run: &ProviderRun,
+	coverage_repo_name: &str,
+	repo: &str,
+	branch: &str,
+	(from_tag, to_tag): (&str, &str),
+	output: Option<PathBuf>,
+) -> Result<(), MeowCoverageError> {
+	let storage =
+		tracking::open_storage(run.coverage_provider.as_ref(), coverage_repo_name).await?;
+	let changelog = tracking::changelog(
+		run.provider.as_ref(),
+		storage.as_ref(),
+		repo,
+		branch.trim_start_matches("refs/heads/"),
+		from_tag.trim_start_matches("refs/tags/"),
+		to_tag.trim_start_matches("refs/tags/"),
+		run.percentage_format,
+	)
+	.await?;
+	match output {
+		Some(output) => std::fs::write(output, changelog)?,
+		None => std::io::Write::write_all(&mut std::io::stdout(), changelog.as_bytes())?,
+	}
+	Ok(())
+}
+
+/// Create the branches of the tracking repository `coverage_repo_name`
+async fn init(run: &ProviderRun, coverage_repo_name: &str) -> Result<(), MeowCoverageError> {
+	let created =
+		tracking::init_tracking(&run.coverage_client, coverage_repo_name, run.percentage_format)
+			.await?;
+	#[allow(clippy::print_stderr)]
+	match created.is_empty() {
+		true => eprintln!("{} is already initialised", coverage_repo_name),
+		false => eprintln!("Created {}", created.join(", ")),
+	}
+	Ok(())
+}
+
+/// Publish the digest of the `period` of the records cloned at
+/// `tracking_repo_records` to the `publish` target
+async fn digest(
+	run: &ProviderRun,
+	coverage_repo_name: &str,
+	tracking_repo_records: &Path,
+	period: CliDigestPeriod,
+	publish: CliDigestTarget,
+) -> Result<(), MeowCoverageError> {
+	tracking::digest(
+		&run.coverage_client,
+		tracking_repo_records,
+		coverage_repo_name,
+		match period {
+			CliDigestPeriod::Weekly => DigestPeriod::Weekly,
+			CliDigestPeriod::Monthly => DigestPeriod::Monthly,
+		},
+		match publish {
+			CliDigestTarget::Commit => DigestTarget::Commit,
+			CliDigestTarget::Issue => DigestTarget::Issue,
+		},
+		run.percentage_format,
+	)
+	.await
+}
+
+/// Build the static site of the records cloned at `tracking_repo_records`
+/// into `output`
+async fn build_site(
+	run: &ProviderRun,
+	coverage_repo_name: &str,
+	tracking_repo_records: &Path,
+	output: &Path,
+) -> Result<(), MeowCoverageError> {
+	let pages = tracking::build_site(
+		tracking_repo_records,
+		coverage_repo_name,
+		output,
+		run.percentage_format,
+	)
+	.await?;
+	#[allow(clippy::print_stderr)]
+	{
+		eprintln!("Built site of {} branches in {}", pages, output.display());
+	}
+	Ok(())
+}
+
+/// Post the comments and the commit status of the publish `payload`, which
+/// must have been computed for `expected_commit` if passed, checking the
+/// access of the token before reading the payload
+async fn publish_from_artifact(
+	run: ProviderRun,
+	payload: &Path,
+	expected_commit: Option<String>,
+	output_json: Option<PathBuf>,
+) -> Result<(), MeowCoverageError> {
+	provider::check_access(&[AccessRequirement {
+		provider: run.provider.as_ref(),
+		repo_name: run.repo_name.as_str(),
+		write: true,
+		permissions: PULL_REQUEST_PERMISSIONS,
+	}])
+	.await?;
+
+	coverage::publish::publish_from_payload(
+		run.provider.as_ref(),
+		run.repo_name.as_str(),
+		payload,
+		expected_commit.or(run.context.workflow_run_head_sha).as_deref(),
+		&ReportOptions {
+			output_json,
+			min_rate_limit_remaining: run.min_rate_limit_remaining,
+			..Default::default()
+		},
+	)
+	.await
+}
+
+/// Run a coverage `command` for the commit `commit_id`
+async fn coverage_run(
+	run: ProviderRun,
+	paths: CliPathArgs,
+	commit_id: Option<String>,
+	new_lcov_file: &[LcovInput],
+	run_args: CliRunArgs,
+	command: CliCoverageCommand,
+) -> Result<(), MeowCoverageError> {
+	let commit_id = commit_id
+		.or_else(|| run.context.commit_id.clone())
+		.ok_or(MeowCoverageError::MissingArgument("commit-id"))?;
+	let commit = RunCommit::load(&run, paths, commit_id).await?;
+	match command {
+		CliCoverageCommand::PullRequest { pr_number, old_lcov_file, pull_request } => {
+			pull_request_run(
+				run,
+				commit,
+				new_lcov_file,
+				run_args,
+				pr_number,
+				old_lcov_file,
+				pull_request,
+			)
+			.await
+		}
+		CliCoverageCommand::Push { notify, branch } => {
+			push_run(run, commit, new_lcov_file, run_args, branch, notify).await
+		}
+		CliCoverageCommand::PushWithReport {
+			notify,
+			branch,
+			coverage_repo,
+			coverage_team,
+			records,
+		} => {
+			let branch = branch
+				.or_else(|| run.context.branch.clone())
+				.ok_or(MeowCoverageError::MissingArgument("branch"))?;
+			tracked_push_run(
+				run,
+				commit,
+				new_lcov_file,
+				run_args,
+				branch.as_str(),
+				Some((coverage_repo.as_str(), coverage_team, &records)),
+				notify,
+			)
+			.await
+		}
+	}
+}
+
+/// Report the coverage of the pull request `pr_number` at `commit`
+async fn pull_request_run(
+	run: ProviderRun,
+	commit: RunCommit,
+	new_lcov_file: &[LcovInput],
+	run_args: CliRunArgs,
+	pr_number: Option<u64>,
+	old_lcov_file: Option<String>,
+	pull_request: CliPullRequestArgs,
+) -> Result<(), MeowCoverageError> {
+	let pr_number = pr_number
+		.or(run.context.pr_number)
+		.ok_or(MeowCoverageError::MissingArgument("pr-number"))?;
+	let (lcov_options, report_options) =
+		run_args.options(&commit.config, run.min_rate_limit_remaining, run.percentage_format)?;
+	check_run_access(
+		run.provider.as_ref(),
+		run.coverage_provider.as_ref(),
+		run.repo_name.as_str(),
+		&report_options,
+		PULL_REQUEST_PERMISSIONS,
+		None,
+	)
+	.await?;
+	let old_lcov_file = pull_request
+		.old_lcov_file(
+			&run.client,
+			old_lcov_file,
+			run.repo_name.as_str(),
+			run.context.base_branch.clone(),
+		)
+		.await?;
+	let provider = pull_request.provider(run.provider, commit.id.as_str());
+
+	coverage::generate_pr_coverage_report(
+		provider.as_ref(),
+		run.coverage_provider.as_ref(),
+		run.repo_name.as_str(),
+		&commit.path_strategy,
+		commit.id.as_str(),
+		pr_number,
+		new_lcov_file,
+		old_lcov_file.as_deref(),
+		&lcov_options,
+		&pull_request.pull_request_options(run.context.base_branch, &commit.config)?,
+		&report_options,
+	)
+	.await
+}
+
+/// Report the coverage of the pushed `commit` of `branch` without recording
+/// it
+async fn push_run(
+	run: ProviderRun,
+	commit: RunCommit,
+	new_lcov_file: &[LcovInput],
+	run_args: CliRunArgs,
+	branch: Option<String>,
+	notify: CliNotifyArgs,
+) -> Result<(), MeowCoverageError> {
+	let artifact_branch = match run_args.upload_artifact {
+		true => Some(
+			branch
+				.or_else(|| run.context.branch.clone())
+				.ok_or(MeowCoverageError::MissingArgument("branch"))?,
+		),
+		false => None,
+	};
+	let (lcov_options, report_options) =
+		run_args.options(&commit.config, run.min_rate_limit_remaining, run.percentage_format)?;
+	check_run_access(
+		run.provider.as_ref(),
+		run.coverage_provider.as_ref(),
+		run.repo_name.as_str(),
+		&report_options,
+		PUSH_PERMISSIONS,
+		None,
+	)
+	.await?;
+	if let Some(branch) = artifact_branch {
+		upload_artifact(branch.as_str(), new_lcov_file).await?;
+	}
+
+	coverage::generate_push_coverage_report(
+		run.provider.as_ref(),
+		run.coverage_provider.as_ref(),
+		new_lcov_file,
+		run.repo_name.as_str(),
+		&commit.path_strategy,
+		commit.id.as_str(),
+		run.context.push_before.as_deref(),
+		None,
+		&notify.notifications()?,
+		&DropIssues::default(),
+		&lcov_options,
+		&report_options,
+	)
+	.await
+}
+
+/// Report the coverage of the pushed `commit` of `branch`, recording it in the
+/// tracking repository if `tracking` (repository, team overriding the team
+/// source, and record options) is passed
+async fn tracked_push_run(
+	run: ProviderRun,
+	commit: RunCommit,
+	new_lcov_file: &[LcovInput],
+	run_args: CliRunArgs,
+	branch: &str,
+	tracking: Option<(&str, Option<Team>, &CliRecordArgs)>,
+	notify: CliNotifyArgs,
+) -> Result<(), MeowCoverageError> {
+	let upload = run_args.upload_artifact;
+	let (lcov_options, report_options) =
+		run_args.options(&commit.config, run.min_rate_limit_remaining, run.percentage_format)?;
+	let coverage_provider = run.coverage_provider.as_ref();
+	// The access is checked before the team lookup and the upload make any
+	// requests
+	check_run_access(
+		run.provider.as_ref(),
+		coverage_provider,
+		run.repo_name.as_str(),
+		&report_options,
+		PUSH_PERMISSIONS,
+		tracking.map(|(coverage_repo, ..)| coverage_repo),
+	)
+	.await?;
+	let tracking = match tracking {
+		Some((coverage_repo, coverage_team, records)) => {
+			let coverage_team = records
+				.team(
+					coverage_team.or(commit.config.team),
+					run.provider.as_ref(),
+					run.repo_name.as_str(),
+					commit.id.as_str(),
+				)
+				.await?;
+			Some((coverage_repo, coverage_team, records))
+		}
+		None => None,
+	};
+	if upload {
+		upload_artifact(branch, new_lcov_file).await?;
+	}
+	// Renamed branches are only followed through the GitHub API
+	if let (Some((coverage_repo, ..)), Some(before)) =
+		(tracking, run.context.default_branch_before.as_deref().filter(|_| run.github))
+	{
+		tracking::follow_renamed_branch(
+			&run.coverage_client,
+			coverage_provider,
+			coverage_repo,
+			run.repo_name.as_str(),
+			branch,
+			before,
+		)
+		.await?;
+	}
+
+	coverage::generate_push_coverage_report(
+		run.provider.as_ref(),
+		coverage_provider,
+		new_lcov_file,
+		run.repo_name.as_str(),
+		&commit.path_strategy,
+		commit.id.as_str(),
+		run.context.push_before.as_deref(),
+		tracking.map(|(coverage_repo, coverage_team, records)| {
+			(branch, coverage_repo, coverage_team, records.record_options())
+		}),
+		&notify.notifications()?,
+		&tracking.map(|(_, _, records)| records.drop_issues()).unwrap_or_default(),
+		&lcov_options,
+		&report_options,
+	)
+	.await
+}
+
+/// Run the GitHub Action for the triggering event
+async fn action(run: ProviderRun, action: CliActionArgs) -> Result<(), MeowCoverageError> {
+	let event = run.context.event.clone().unwrap_or(GitHubEvent::Other(String::from("unknown")));
+	match &event {
+		GitHubEvent::WorkflowDispatch(inputs) => {
+			let tracking_repo_records = action
+				.tracking_repo_records
+				.ok_or(MeowCoverageError::MissingArgument("records"))?;
+			let target_repo = inputs
+				.get("repo-name")
+				.ok_or(MeowCoverageError::MissingDispatchInput("repo-name"))?;
+			let branch =
+				inputs.get("branch").ok_or(MeowCoverageError::MissingDispatchInput("branch"))?;
+
+			return tracking::rebuild(
+				&run.coverage_client,
+				&tracking_repo_records,
+				run.repo_name.as_str(),
+				target_repo.as_str(),
+				branch.as_str(),
+				run.percentage_format,
+			)
+			.await;
+		}
+		GitHubEvent::Other(name) => {
+			return Err(MeowCoverageError::UnsupportedEvent(name.clone()));
+		}
+		GitHubEvent::Push | GitHubEvent::PullRequest => {}
+	}
+
+	if action.new_lcov_file.is_empty() {
+		return Err(MeowCoverageError::MissingArgument("new-lcov-file"));
+	}
+	let commit_id =
+		run.context.commit_id.clone().ok_or(MeowCoverageError::MissingArgument("commit-id"))?;
+	let commit = RunCommit::load(&run, action.paths, commit_id).await?;
+
+	match event == GitHubEvent::PullRequest {
+		true => {
+			let pr_number =
+				run.context.pr_number.ok_or(MeowCoverageError::MissingArgument("pr-number"))?;
+			#[allow(clippy::print_stderr)]
+			let old_lcov_file = action.old_lcov_file.filter(|path| match Path::new(path).exists() {
+				true => true,
+				false => {
+					eprintln!("Baseline Lcov file {} not found, reporting without it", path);
+					false
+				}
+			});
+			let mut pull_request = action.pull_request;
+			pull_request.trend_from = pull_request.trend_from.or(action.coverage_repo);
+
+			pull_request_run(
+				run,
+				commit,
+				&action.new_lcov_file,
+				action.run,
+				Some(pr_number),
+				old_lcov_file,
+				pull_request,
+			)
+			.await
+		}
+		false => {
+			let branch =
+				run.context.branch.clone().ok_or(MeowCoverageError::MissingArgument("branch"))?;
+			tracked_push_run(
+				run,
+				commit,
+				&action.new_lcov_file,
+				action.run,
+				branch.as_str(),
+				action
+					.coverage_repo
+					.as_deref()
+					.map(|coverage_repo| (coverage_repo, action.coverage_team, &action.records)),
+				action.notify,
+			)
+			.await
+		}
+	}
+}
+
+#[tokio::main]
+async fn main() -> Result<(), MeowCoverageError> {
+	let args = CliArgs::parse();
+	let percentage_format = PercentageFormat {
+		precision: args.percentage_precision,
+		rounding: match args.percentage_rounding {
+			CliRounding::Nearest => Rounding::Nearest,
+			CliRounding::Down => Rounding::Down,
+		},
+	};
+
+	let command = match args.command {
+		CliMainCommand::Local(CliLocalCommand::Diff {
+			old_lcov_file,
+			new_lcov_file,
+			format,
+			strip_path_root,
+		}) => {
+			return diff(
+				&old_lcov_file,
+				&new_lcov_file,
+				format,
+				strip_path_root.as_deref(),
+				percentage_format,
+			);
+		}
+		CliMainCommand::Local(CliLocalCommand::Visualize { command }) => {
+			return heatmap(command, percentage_format)
+		}
+		CliMainCommand::Provider(command) => command,
+	};
+
+	// Fail before talking to the provider if an Lcov file was misplaced
+	if let CliProviderCommand::CoverageRun { new_lcov_file, .. }
+	| CliProviderCommand::Action { action: CliActionArgs { new_lcov_file, .. } } = &command
+	{
+		for input in new_lcov_file {
+			check_lcov_file(&input.path)?;
+		}
+	}
+
+	let context = match args.auto_context || matches!(command, CliProviderCommand::Action { .. }) {
+		true => GitHubContext::from_env()?,
+		false => GitHubContext::default(),
+	};
+	// Fail before another provider's token is sent to the GitHub API
+	let github = matches!(args.provider, CliProvider::Github);
+	if let Some(command) = command.github_only(context.event.as_ref()).filter(|_| !github) {
+		return Err(MeowCoverageError::GitHubOnly(command));
+	}
+	let github_token = args
+		.github_token
+		.or(context.token.clone())
+		.ok_or(MeowCoverageError::MissingArgument("github-token"))?;
+	let repo_name = args
+		.repo_name
+		.or(context.repo_name.clone())
+		.ok_or(MeowCoverageError::MissingArgument("repo-name"))?;
+
+	// The tracking repository gets its own client, which only differs from the
+	// client of the analysed repository with a `--coverage-repo-token`
+	let coverage_repo_token = args.coverage_repo_token.unwrap_or_else(|| github_token.clone());
+	let client = Arc::new(Octocrab::builder().personal_token(github_token.clone()).build()?);
+	let coverage_client =
+		Arc::new(Octocrab::builder().personal_token(coverage_repo_token.clone()).build()?);
+	let run = ProviderRun {
+		provider: args.provider.build(args.base_url.as_deref(), github_token, client.clone())?,
+		coverage_provider: args.provider.build(
+			args.base_url.as_deref(),
+			coverage_repo_token,
+			coverage_client.clone(),
+		)?,
+		context,
+		repo_name,
+		github,
+		client,
+		coverage_client,
+		min_rate_limit_remaining: args.min_rate_limit_remaining,
+		percentage_format,
+	};
+
+	provider::check_rate_limit(run.provider.as_ref(), 0, run.min_rate_limit_remaining).await?;
+
+	match command {
+		CliProviderCommand::Tracking { coverage_repo_name, command } => {
+			tracking(run, coverage_repo_name.as_str(), command).await
+		}
+		CliProviderCommand::CoverageRun {
+			paths,
+			commit_id,
+			new_lcov_file,
+			run: run_args,
+			command,
+		} => coverage_run(run, paths, commit_id, &new_lcov_file, run_args, command).await,
+		CliProviderCommand::Compare { compare } => {
+			compare
+				.compare(
+					run.provider.as_ref(),
+					run.coverage_provider.as_ref(),
+					&run.repo_name,
+					percentage_format,
+				)
+				.await
+		}
+		CliProviderCommand::PublishFromArtifact { payload, expected_commit, output_json } => {
+			publish_from_artifact(run, &payload, expected_commit, output_json).await
 		}
+		CliProviderCommand::Action { action: action_args } => action(run, action_args).await,
 	}
 }
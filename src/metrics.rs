@@ -0,0 +1,107 @@
+//! Coverage arithmetic shared by the reports, diffs, and tracking records, so
+//! totals, per-file percentages, and deltas are computed the same everywhere.
+//! Rounding and clamping of the results is left to
+//! [Percentage](crate::percentage::Percentage)
+
+/// Fraction (0 to 1) of the `lines_found` instrumented lines that were hit,
+/// [None] if no lines are instrumented. Hits beyond the instrumented lines
+/// count as full coverage
+#[must_use]
+pub fn fraction(lines_hit: u64, lines_found: u64) -> Option<f64> {
+	(lines_found > 0).then(|| lines_hit.min(lines_found) as f64 / lines_found as f64)
+}
+
+/// Percentage (0 to 100) of the `lines_found` instrumented lines that were
+/// hit, [None] if no lines are instrumented. Hits beyond the instrumented
+/// lines count as full coverage
+#[must_use]
+pub fn percentage(lines_hit: u64, lines_found: u64) -> Option<f64> {
+	(lines_found > 0).then(|| 100_f64 * lines_hit.min(lines_found) as f64 / lines_found as f64)
+}
+
+/// Change from the `old` to the `new` percentage, [None] if either is missing
+#[must_use]
+pub fn delta(new: Option<f64>, old: Option<f64>) -> Option<f64> {
+	Some(new? - old?)
+}
+
+#[cfg(test)]
+mod tests {
+	use proptest::prelude::*;
+
+	use super::{delta, fraction, percentage};
+	use crate::percentage::{Percentage, PercentageFormat, Rounding};
+
+	#[test]
+	fn test_metrics() {
+		assert_eq!(percentage(0, 0), None);
+		assert_eq!(fraction(0, 0), None);
+		assert_eq!(percentage(0, 3), Some(0_f64));
+		assert_eq!(percentage(3, 3), Some(100_f64));
+		assert_eq!(percentage(4, 3), Some(100_f64));
+		assert_eq!(fraction(1, 4), Some(0.25));
+		assert_eq!(Percentage::from_f64(percentage(2, 3).unwrap()).basis_points(), 6667);
+		assert_eq!(delta(Some(75_f64), Some(50_f64)), Some(25_f64));
+		assert_eq!(delta(None, Some(50_f64)), None);
+		assert_eq!(delta(Some(50_f64), None), None);
+	}
+
+	proptest! {
+		#[test]
+		fn prop_percentage_bounds(lines_hit in 0_u64..100_000, lines_found in 1_u64..100_000) {
+			let total = percentage(lines_hit, lines_found).unwrap();
+			prop_assert!((0_f64..=100_f64).contains(&total));
+			prop_assert_eq!(total == 100_f64, lines_hit >= lines_found);
+			prop_assert_eq!(total == 0_f64, lines_hit == 0);
+
+			let share = fraction(lines_hit, lines_found).unwrap();
+			prop_assert!((0_f64..=1_f64).contains(&share));
+			prop_assert!((share * 100_f64 - total).abs() < 1e-9);
+		}
+
+		#[test]
+		fn prop_percentage_monotonic(lines_hit in 0_u64..100_000, lines_found in 1_u64..100_000) {
+			let lower = percentage(lines_hit, lines_found).unwrap();
+			let higher = percentage(lines_hit + 1, lines_found).unwrap();
+			prop_assert!(lower <= higher);
+			prop_assert!(Percentage::from_f64(lower) <= Percentage::from_f64(higher));
+		}
+
+		#[test]
+		fn prop_delta(new in 0_f64..=100_f64, old in 0_f64..=100_f64) {
+			let change = delta(Some(new), Some(old)).unwrap();
+			prop_assert_eq!(change, -delta(Some(old), Some(new)).unwrap());
+			prop_assert!((-100_f64..=100_f64).contains(&change));
+			prop_assert_eq!(delta(Some(new), Some(new)), Some(0_f64));
+		}
+
+		#[test]
+		fn prop_percentage_rounding(value in -150_f64..150_f64) {
+			let rounded = Percentage::from_f64(value);
+			prop_assert!(rounded <= Percentage::FULL);
+			prop_assert!(rounded >= Percentage::ZERO - Percentage::FULL);
+			prop_assert!((rounded.as_f64() - value.clamp(-100_f64, 100_f64)).abs() <= 0.005);
+			prop_assert_eq!(Percentage::from_f64(rounded.as_f64()), rounded);
+		}
+
+		#[test]
+		fn prop_percentage_display(basis_points in -10000_i16..=10000) {
+			let value = Percentage::from_basis_points(basis_points);
+			let shown = |precision, rounding| {
+				value.display_with(PercentageFormat { precision, rounding }).to_string()
+			};
+
+			prop_assert_eq!(shown(2, Rounding::Nearest), shown(2, Rounding::Down));
+			prop_assert_eq!(
+				(shown(2, Rounding::Nearest).parse::<f64>().unwrap() * 100_f64).round() as i16,
+				basis_points
+			);
+			for precision in 0..=2 {
+				let nearest = shown(precision, Rounding::Nearest).parse::<f64>().unwrap();
+				let down = shown(precision, Rounding::Down).parse::<f64>().unwrap();
+				prop_assert!(down.abs() <= value.as_f64().abs() + 1e-9);
+				prop_assert!((nearest - value.as_f64()).abs() <= 0.5 / 10_f64.powi(i32::from(precision)) + 1e-9);
+			}
+		}
+	}
+}
@@ -0,0 +1,181 @@
+//! Notifications sent when the coverage of a push regresses
+
+mod matrix;
+mod webhook;
+
+use async_trait::async_trait;
+pub use matrix::MatrixNotifier;
+pub use webhook::WebhookNotifier;
+
+use crate::{
+	percentage::{Percentage, PercentageFormat},
+	MeowCoverageError,
+};
+
+/// Coverage of a push that triggered a notification
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageNotification {
+	/// Repository in format `OWNER/REPO`
+	pub repo: String,
+	/// Branch of the push, if known
+	pub branch: Option<String>,
+	/// Coverage percentage of the previous record, if known
+	pub old_percentage: Option<f64>,
+	/// Coverage percentage of the push
+	pub new_percentage: f64,
+	/// Link to the tracking report, if the push is tracked
+	pub report_link: Option<String>,
+	/// Human readable reason for the notification
+	pub reason: String,
+}
+
+impl CoverageNotification {
+	/// Build a plain text message for the notification
+	#[must_use]
+	pub fn message(&self) -> String {
+		let mut message = format!(
+			"Meow! Coverage of {}{}: {}",
+			self.repo,
+			self.branch.as_deref().map(|branch| format!(" ({})", branch)).unwrap_or_default(),
+			self.reason
+		);
+
+		if let Some(report_link) = &self.report_link {
+			message.push_str(&format!("\nReport: {}", report_link));
+		}
+
+		message
+	}
+}
+
+/// Rules deciding when a notification is sent
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NotificationRules {
+	/// Notify when the coverage percentage is below this value
+	pub below: Option<f64>,
+	/// Notify when the coverage percentage dropped by more than this many
+	/// points compared to the previous record
+	pub drop: Option<f64>,
+}
+
+impl NotificationRules {
+	/// Returns the reason for notifying with the percentages in `format`, if
+	/// any of the rules match
+	#[must_use]
+	pub fn evaluate(
+		&self,
+		old_percentage: Option<f64>,
+		new_percentage: f64,
+		format: PercentageFormat,
+	) -> Option<String> {
+		let dropped = old_percentage.zip(self.drop).and_then(|(old_percentage, drop)| {
+			(old_percentage - new_percentage > drop).then(|| {
+				format!(
+					"coverage dropped from {}% to {}%",
+					Percentage::from_f64(old_percentage).display_with(format),
+					Percentage::from_f64(new_percentage).display_with(format)
+				)
+			})
+		});
+
+		dropped.or_else(|| {
+			self.below.and_then(|below| {
+				(new_percentage < below).then(|| {
+					format!(
+						"coverage {}% is below the threshold of {}%",
+						Percentage::from_f64(new_percentage).display_with(format),
+						Percentage::from_f64(below).display_with(format)
+					)
+				})
+			})
+		})
+	}
+}
+
+/// A sink notifications are sent to
+#[async_trait]
+pub trait Notifier: std::fmt::Debug + Send + Sync {
+	/// Send a notification
+	async fn notify(&self, notification: &CoverageNotification) -> Result<(), MeowCoverageError>;
+}
+
+/// Notification rules and the sinks they send to
+#[derive(Debug, Default)]
+pub struct Notifications {
+	/// When to notify
+	pub rules: NotificationRules,
+	/// Where to notify
+	pub notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl Notifications {
+	/// Evaluate the rules and notify all sinks if any match with the
+	/// percentages in `format`, failures are reported but do not fail the run
+	#[allow(clippy::print_stderr)]
+	pub async fn notify(
+		&self,
+		repo: &str,
+		branch: Option<&str>,
+		old_percentage: Option<f64>,
+		new_percentage: f64,
+		report_link: Option<String>,
+		format: PercentageFormat,
+	) {
+		if self.notifiers.is_empty() {
+			return;
+		}
+
+		let Some(reason) = self.rules.evaluate(old_percentage, new_percentage, format) else {
+			return;
+		};
+
+		let notification = CoverageNotification {
+			repo: String::from(repo),
+			branch: branch.map(String::from),
+			old_percentage,
+			new_percentage,
+			report_link,
+			reason,
+		};
+
+		for notifier in &self.notifiers {
+			if let Err(why) = notifier.notify(&notification).await {
+				eprintln!("Failed to send notification (why: {})", why);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::NotificationRules;
+	use crate::percentage::{PercentageFormat, Rounding};
+
+	#[test]
+	fn test_notification_rules() {
+		let rules = NotificationRules { below: Some(80.0), drop: Some(2.0) };
+		let format = PercentageFormat::default();
+
+		assert_eq!(rules.evaluate(Some(85.0), 84.0, format), None);
+		assert_eq!(rules.evaluate(None, 90.0, format), None);
+		assert_eq!(
+			rules.evaluate(Some(85.0), 82.5, format).as_deref(),
+			Some("coverage dropped from 85.00% to 82.50%")
+		);
+		assert_eq!(
+			rules.evaluate(Some(80.0), 79.0, format).as_deref(),
+			Some("coverage 79.00% is below the threshold of 80.00%")
+		);
+		assert_eq!(
+			rules
+				.evaluate(
+					Some(80.0),
+					79.0,
+					PercentageFormat { precision: 0, rounding: Rounding::Down }
+				)
+				.as_deref(),
+			Some("coverage 79% is below the threshold of 80%")
+		);
+		assert_eq!(NotificationRules::default().evaluate(Some(100.0), 0.0, format), None);
+	}
+}
@@ -0,0 +1,71 @@
+//! [Notifier] sending messages to a Matrix room
+
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+
+use super::{CoverageNotification, Notifier};
+use crate::MeowCoverageError;
+
+/// Sends notifications as messages to a Matrix room
+#[derive(Debug, Clone)]
+pub struct MatrixNotifier {
+	/// HTTP client used for all requests
+	client: Client,
+	/// Homeserver URL, for example `https://matrix.famedly.de`
+	homeserver: Url,
+	/// Access token of the sending user
+	access_token: String,
+	/// Room ID, for example `!abcdef:famedly.de`
+	room_id: String,
+}
+
+impl MatrixNotifier {
+	/// Create a new [MatrixNotifier]
+	pub fn new(
+		homeserver: &str,
+		access_token: String,
+		room_id: String,
+	) -> Result<Self, MeowCoverageError> {
+		let homeserver = Url::parse(homeserver).map_err(|why| {
+			MeowCoverageError::Notification(format!("Invalid homeserver URL: {}", why))
+		})?;
+
+		Ok(Self { client: Client::new(), homeserver, access_token, room_id })
+	}
+}
+
+#[async_trait]
+impl Notifier for MatrixNotifier {
+	async fn notify(&self, notification: &CoverageNotification) -> Result<(), MeowCoverageError> {
+		let transaction_id =
+			format!("meow-coverage-{}", time::OffsetDateTime::now_utc().unix_timestamp_nanos());
+
+		let mut url = self.homeserver.clone();
+		url.path_segments_mut()
+			.map_err(|_| MeowCoverageError::Notification(String::from("Invalid homeserver URL")))?
+			.pop_if_empty()
+			.extend([
+				"_matrix",
+				"client",
+				"v3",
+				"rooms",
+				self.room_id.as_str(),
+				"send",
+				"m.room.message",
+				transaction_id.as_str(),
+			]);
+
+		self.client
+			.put(url)
+			.bearer_auth(&self.access_token)
+			.json(&serde_json::json!({
+				"msgtype": "m.text",
+				"body": notification.message(),
+			}))
+			.send()
+			.await?
+			.error_for_status()?;
+
+		Ok(())
+	}
+}
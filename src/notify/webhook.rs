@@ -0,0 +1,83 @@
+//! [Notifier] posting JSON payloads to a generic webhook
+
+use async_trait::async_trait;
+use reqwest::Client;
+
+use super::{CoverageNotification, Notifier};
+use crate::MeowCoverageError;
+
+/// Posts notifications as JSON to a webhook URL, the payload includes a
+/// `text` field so it can be used with Slack incoming webhooks directly
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+	/// HTTP client used for all requests
+	client: Client,
+	/// Webhook URL
+	url: String,
+}
+
+impl WebhookNotifier {
+	/// Create a new [WebhookNotifier]
+	#[must_use]
+	pub fn new(url: String) -> Self {
+		Self { client: Client::new(), url }
+	}
+}
+
+/// Build the JSON payload for a notification
+fn payload(notification: &CoverageNotification) -> serde_json::Value {
+	serde_json::json!({
+		"text": notification.message(),
+		"repo": notification.repo,
+		"branch": notification.branch,
+		"old_percentage": notification.old_percentage,
+		"new_percentage": notification.new_percentage,
+		"report_link": notification.report_link,
+		"reason": notification.reason,
+	})
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+	async fn notify(&self, notification: &CoverageNotification) -> Result<(), MeowCoverageError> {
+		self.client
+			.post(&self.url)
+			.json(&payload(notification))
+			.send()
+			.await?
+			.error_for_status()?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::payload;
+	use crate::notify::CoverageNotification;
+
+	#[test]
+	fn test_webhook_payload() {
+		let notification = CoverageNotification {
+			repo: String::from("famedly/meow"),
+			branch: Some(String::from("main")),
+			old_percentage: Some(85.0),
+			new_percentage: 80.0,
+			report_link: None,
+			reason: String::from("coverage dropped from 85.00% to 80.00%"),
+		};
+
+		assert_eq!(
+			payload(&notification),
+			serde_json::json!({
+				"text": "Meow! Coverage of famedly/meow (main): coverage dropped from 85.00% to 80.00%",
+				"repo": "famedly/meow",
+				"branch": "main",
+				"old_percentage": 85.0,
+				"new_percentage": 80.0,
+				"report_link": null,
+				"reason": "coverage dropped from 85.00% to 80.00%",
+			})
+		);
+	}
+}
@@ -0,0 +1,150 @@
+//! Coverage percentages stored as basis points (hundredths of a percent), as
+//! kept in the tracking records, displayed in the [PercentageFormat] of the
+//! comments, records, and visualisations
+
+use std::{fmt::Display, ops::Sub};
+
+use serde::{Deserialize, Serialize};
+
+/// How percentages are rounded to the display precision
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rounding {
+	/// Round to the nearest value, halves away from zero
+	#[default]
+	Nearest,
+	/// Truncate towards zero, so coverage is never overstated
+	Down,
+}
+
+/// Display format of [Percentage]s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PercentageFormat {
+	/// Number of decimals, at most 2 as percentages are stored as basis
+	/// points
+	pub precision: u8,
+	/// Rounding to the precision
+	pub rounding: Rounding,
+}
+
+impl Default for PercentageFormat {
+	fn default() -> Self {
+		Self { precision: 2, rounding: Rounding::Nearest }
+	}
+}
+
+/// A percentage (or a difference of percentages) in basis points, so 1% is
+/// 100. Serialised as the number of basis points
+#[derive(
+	Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Percentage(i16);
+
+impl Percentage {
+	/// 0%
+	pub const ZERO: Self = Self(0);
+	/// 100%
+	pub const FULL: Self = Self(10000);
+
+	/// Percentage of `basis_points` hundredths of a percent
+	#[must_use]
+	pub const fn from_basis_points(basis_points: i16) -> Self {
+		Self(basis_points)
+	}
+
+	/// Round a percentage (or difference) from 0 to 100 to basis points,
+	/// clamping it to ±100%
+	#[must_use]
+	pub fn from_f64(percentage: f64) -> Self {
+		Self((percentage.clamp(-100_f64, 100_f64) * 100_f64).round() as i16)
+	}
+
+	/// Number of basis points
+	#[must_use]
+	pub const fn basis_points(self) -> i16 {
+		self.0
+	}
+
+	/// The percentage from 0 to 100
+	#[must_use]
+	pub fn as_f64(self) -> f64 {
+		f64::from(self.0) / 100_f64
+	}
+
+	/// Display in `format`, honouring the `+` flag for differences
+	#[must_use]
+	pub fn display_with(self, format: PercentageFormat) -> impl Display {
+		FormattedPercentage { percentage: self, format }
+	}
+}
+
+impl Sub for Percentage {
+	type Output = Self;
+
+	fn sub(self, rhs: Self) -> Self {
+		Self(self.0.saturating_sub(rhs.0))
+	}
+}
+
+/// A [Percentage] with its display format
+struct FormattedPercentage {
+	/// The percentage
+	percentage: Percentage,
+	/// The format
+	format: PercentageFormat,
+}
+
+impl Display for FormattedPercentage {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let precision = u32::from(self.format.precision.min(2));
+		let scale = 10_i32.pow(2 - precision);
+		let basis_points = i32::from(self.percentage.0);
+		let rounded = match self.format.rounding {
+			Rounding::Nearest => (basis_points + basis_points.signum() * scale / 2) / scale,
+			Rounding::Down => basis_points / scale,
+		};
+
+		let sign = match (rounded < 0, f.sign_plus()) {
+			(true, _) => "-",
+			(false, true) => "+",
+			(false, false) => "",
+		};
+		let decimals = 10_i32.pow(precision);
+		let (whole, fraction) = (rounded.abs() / decimals, rounded.abs() % decimals);
+		match precision {
+			0 => write!(f, "{}{}", sign, whole),
+			_ => write!(f, "{}{}.{:0width$}", sign, whole, fraction, width = precision as usize),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Percentage, PercentageFormat, Rounding};
+
+	#[test]
+	fn test_percentage_display() {
+		let format = |precision, rounding| PercentageFormat { precision, rounding };
+		let percentage = Percentage::from_f64(66.666);
+		assert_eq!(percentage.basis_points(), 6667);
+		assert_eq!(percentage.display_with(PercentageFormat::default()).to_string(), "66.67");
+		assert_eq!(percentage.display_with(format(1, Rounding::Nearest)).to_string(), "66.7");
+		assert_eq!(percentage.display_with(format(1, Rounding::Down)).to_string(), "66.6");
+		assert_eq!(percentage.display_with(format(0, Rounding::Nearest)).to_string(), "67");
+		assert_eq!(percentage.display_with(format(0, Rounding::Down)).to_string(), "66");
+
+		let delta = Percentage::from_f64(50.0) - percentage;
+		assert_eq!(delta.basis_points(), -1667);
+		assert_eq!(format!("{:+}", delta.display_with(PercentageFormat::default())), "-16.67");
+		assert_eq!(
+			format!("{:+}", Percentage::from_f64(0.05).display_with(PercentageFormat::default())),
+			"+0.05"
+		);
+		assert_eq!(
+			Percentage::from_f64(-0.04).display_with(format(1, Rounding::Down)).to_string(),
+			"0.0"
+		);
+		assert_eq!(Percentage::from_f64(250.0), Percentage::FULL);
+		assert_eq!(Percentage::FULL.as_f64(), 100_f64);
+	}
+}
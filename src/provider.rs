@@ -0,0 +1,741 @@
+//! Abstraction over the version control system hosting providers coverage
+//! reports are posted to
+
+mod azure;
+mod bitbucket;
+mod cached;
+#[cfg(test)]
+pub(crate) mod fake;
+mod forgejo;
+mod github;
+
+use std::{collections::HashSet, future::Future, time::Duration};
+
+use async_trait::async_trait;
+pub use azure::AzureDevOpsProvider;
+pub use bitbucket::{BitbucketProvider, BITBUCKET_API_URL};
+pub use cached::CachedProvider;
+pub use forgejo::ForgejoProvider;
+pub use github::GitHubProvider;
+use serde::{Deserialize, Serialize};
+
+use crate::MeowCoverageError;
+
+/// A file changed in a pull request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PullRequestFile {
+	/// Path of the file after the change
+	pub filename: String,
+	/// Path of the file before the change, if it was renamed
+	pub previous_filename: Option<String>,
+	/// Unified diff hunks of the change (without the `---`/`+++` header), if
+	/// the provider supplied them
+	pub patch: Option<String>,
+}
+
+/// Metadata of a pull request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PullRequestInfo {
+	/// Whether the pull request is a draft
+	pub draft: bool,
+	/// Names of the labels on the pull request
+	pub labels: Vec<String>,
+	/// Current head commit SHA of the pull request, abbreviated by Bitbucket
+	pub head_sha: String,
+}
+
+/// A file fetched from a repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoFile {
+	/// Raw file contents
+	pub content: Vec<u8>,
+	/// Blob SHA of the file, required for updating it
+	pub sha: String,
+}
+
+/// An issue of a repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+	/// Issue number
+	pub number: u64,
+	/// Issue body
+	pub body: String,
+}
+
+/// A commit a ref (for example a tag) points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo {
+	/// Commit SHA
+	pub sha: String,
+	/// Unix timestamp the commit was committed at
+	pub timestamp: i64,
+}
+
+/// Line, or range of lines, to link to
+pub type LineRange = Option<(u32, Option<u32>)>;
+
+/// State of a commit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitStatusState {
+	/// The check passed
+	Success,
+	/// The check failed
+	Failure,
+}
+
+/// Split a unified diff covering many files (as produced by `git diff`) into
+/// per-file [PullRequestFile]s, for providers without a per-file patch API
+#[must_use]
+pub fn split_unified_diff(diff: &str) -> Vec<PullRequestFile> {
+	let mut files = Vec::new();
+	let mut current: Option<(Option<String>, Option<String>, Option<String>)> = None;
+
+	let mut finish = |current: Option<(Option<String>, Option<String>, Option<String>)>| {
+		if let Some((Some(old), Some(new), patch)) = current {
+			files.push(PullRequestFile {
+				previous_filename: (old != new).then_some(old),
+				filename: new,
+				patch,
+			});
+		}
+	};
+
+	for line in diff.lines() {
+		if let Some(header) = line.strip_prefix("diff --git a/") {
+			finish(current.take());
+			current = Some(match header.rsplit_once(" b/") {
+				Some((old, new)) => (Some(String::from(old)), Some(String::from(new)), None),
+				None => (None, None, None),
+			});
+			continue;
+		}
+
+		let Some((old, new, patch)) = current.as_mut() else {
+			continue;
+		};
+
+		match patch {
+			Some(patch) => {
+				patch.push_str(line);
+				patch.push('\n');
+			}
+			None if line.starts_with("@@") => *patch = Some(format!("{}\n", line)),
+			None => {
+				if let Some(path) =
+					line.strip_prefix("rename from ").or_else(|| line.strip_prefix("--- a/"))
+				{
+					*old = Some(String::from(path));
+				} else if let Some(path) =
+					line.strip_prefix("rename to ").or_else(|| line.strip_prefix("+++ b/"))
+				{
+					*new = Some(String::from(path));
+				} else if line == "+++ /dev/null" {
+					*new = None;
+				}
+			}
+		}
+	}
+
+	finish(current);
+
+	files
+}
+
+/// Number of times an operation is retried after hitting a rate limit
+const RATE_LIMIT_RETRIES: u32 = 5;
+
+/// Longest time in seconds to wait for the rate limit to reset before bulk
+/// operations exceeding the remaining budget, they are attempted anyway after
+/// a warning otherwise
+const MAX_RATE_LIMIT_DEFER: i64 = 60;
+
+/// Remaining budget of the API rate limit of a provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+	/// Number of remaining requests
+	pub remaining: u64,
+	/// Unix timestamp of when the budget resets
+	pub reset: i64,
+}
+
+/// Check the rate limit budget of `provider` before making about
+/// `estimated_calls` requests. Fails with
+/// [MeowCoverageError::RateLimitBudget] if fewer than `min_remaining`
+/// requests remain, and waits for the budget to reset (if it resets within a
+/// minute) or warns if fewer than `estimated_calls` remain. Providers without
+/// a rate limit API always pass
+pub async fn check_rate_limit(
+	provider: &dyn VcsProvider,
+	estimated_calls: u64,
+	min_remaining: Option<u64>,
+) -> Result<(), MeowCoverageError> {
+	let Some(rate_limit) = provider.rate_limit().await? else {
+		return Ok(());
+	};
+	let reset_in = (rate_limit.reset - time::OffsetDateTime::now_utc().unix_timestamp()).max(0);
+
+	if let Some(required) = min_remaining.filter(|required| rate_limit.remaining < *required) {
+		return Err(MeowCoverageError::RateLimitBudget {
+			remaining: rate_limit.remaining,
+			required,
+			reset_in,
+		});
+	}
+	if rate_limit.remaining >= estimated_calls {
+		return Ok(());
+	}
+
+	#[allow(clippy::print_stderr)]
+	match reset_in <= MAX_RATE_LIMIT_DEFER {
+		true => {
+			eprintln!(
+				"Only {} API requests remain for about {} requests, waiting {} seconds for the rate limit to reset",
+				rate_limit.remaining, estimated_calls, reset_in
+			);
+			tokio::time::sleep(Duration::from_secs(reset_in as u64 + 1)).await;
+		}
+		false => eprintln!(
+			"Only {} API requests remain for about {} requests, the rate limit resets in {} seconds, continuing",
+			rate_limit.remaining, estimated_calls, reset_in
+		),
+	}
+
+	Ok(())
+}
+
+/// Access of the token of a provider to a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepositoryAccess {
+	/// Whether the repository can be read
+	pub read: bool,
+	/// Whether the repository can be written to, [None] if the provider does
+	/// not report it for the token (for example for GitHub Actions tokens)
+	pub write: Option<bool>,
+}
+
+/// Access a run needs to a repository, see [check_access]
+#[derive(Debug, Clone, Copy)]
+pub struct AccessRequirement<'a> {
+	/// Provider authenticated with the token accessing the repository
+	pub provider: &'a dyn VcsProvider,
+	/// Repository in format `OWNER/REPO`
+	pub repo_name: &'a str,
+	/// Whether the run writes to the repository
+	pub write: bool,
+	/// Permissions the token needs for the access, named if it lacks them
+	pub permissions: &'a str,
+}
+
+/// Check the access of the tokens to the repositories before doing any work.
+/// Fails with a single [MeowCoverageError::MissingAccess] listing every
+/// missing access instead of failing halfway through with reports already
+/// posted. Access the providers don't report is assumed to be granted
+pub async fn check_access(requirements: &[AccessRequirement<'_>]) -> Result<(), MeowCoverageError> {
+	let mut missing = Vec::new();
+	for requirement in requirements {
+		let (owner, repo) =
+			requirement.repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+		let Some(access) = requirement.provider.repository_access(owner, repo).await? else {
+			continue;
+		};
+
+		if !access.read || (requirement.write && access.write == Some(false)) {
+			missing.push(format!(
+				"{} access to {} ({})",
+				match requirement.write {
+					true => "write",
+					false => "read",
+				},
+				requirement.repo_name,
+				requirement.permissions
+			));
+		}
+	}
+
+	match missing.is_empty() {
+		true => Ok(()),
+		false => Err(MeowCoverageError::MissingAccess(missing)),
+	}
+}
+
+/// Run a provider operation, retrying it with exponential backoff while it
+/// fails because of a rate limit
+pub async fn retry_rate_limited<T, F, Fut>(mut operation: F) -> Result<T, MeowCoverageError>
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = Result<T, MeowCoverageError>>,
+{
+	let mut attempt = 0;
+
+	loop {
+		match operation().await {
+			Err(error) if error.is_rate_limited() && attempt < RATE_LIMIT_RETRIES => {
+				tokio::time::sleep(Duration::from_secs(2_u64.pow(attempt))).await;
+				attempt += 1;
+			}
+			result => return result,
+		}
+	}
+}
+
+/// Check the response of a file update, mapping `409 Conflict` to
+/// [MeowCoverageError::FileConflict]
+pub fn check_put_file_response(
+	response: reqwest::Response,
+	path: &str,
+) -> Result<(), MeowCoverageError> {
+	match response.status() == reqwest::StatusCode::CONFLICT {
+		true => Err(MeowCoverageError::FileConflict(String::from(path))),
+		false => {
+			response.error_for_status()?;
+			Ok(())
+		}
+	}
+}
+
+/// Operations a version control system hosting provider must support
+#[async_trait]
+pub trait VcsProvider: std::fmt::Debug + Send + Sync {
+	/// Fetch the metadata of a pull request
+	async fn get_pull_request(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<PullRequestInfo, MeowCoverageError>;
+
+	/// List the files changed in a pull request
+	async fn list_pull_request_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<Vec<PullRequestFile>, MeowCoverageError>;
+
+	/// Create a summary comment on a pull request, returns the URL of the
+	/// comment if the provider reports one
+	async fn create_pull_request_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError>;
+
+	/// Create a review comment on a range of lines of a pull request
+	#[allow(clippy::too_many_arguments)]
+	async fn create_review_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		commit_id: &str,
+		path: &str,
+		first_line: u32,
+		final_line: u32,
+		body: &str,
+	) -> Result<(), MeowCoverageError>;
+
+	/// Create a comment on a commit, returns the URL of the comment if the
+	/// provider reports one
+	async fn create_commit_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError>;
+
+	/// Fetch a file from a branch of a repository, returns [None] if the file
+	/// does not exist
+	async fn get_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+	) -> Result<Option<RepoFile>, MeowCoverageError>;
+
+	/// Fetch the contents of a file at a commit, returns [None] if the file
+	/// does not exist
+	async fn get_file_at_commit(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+	) -> Result<Option<Vec<u8>>, MeowCoverageError>;
+
+	/// Create or update (if `sha` is provided) a file on a branch of a
+	/// repository, fails with [MeowCoverageError::FileConflict] if the file
+	/// was changed concurrently (no longer matching `sha`)
+	#[allow(clippy::too_many_arguments)]
+	async fn put_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+		message: &str,
+		content: Vec<u8>,
+		sha: Option<String>,
+	) -> Result<(), MeowCoverageError>;
+
+	/// Trigger a workflow in a repository
+	async fn dispatch_workflow(
+		&self,
+		owner: &str,
+		repo: &str,
+		workflow: &str,
+		branch: &str,
+		inputs: serde_json::Value,
+	) -> Result<(), MeowCoverageError>;
+
+	/// List the paths of all files in a repository at a commit
+	async fn list_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+	) -> Result<HashSet<String>, MeowCoverageError>;
+
+	/// Set a status on a commit, used for gating merges. Providers without
+	/// commit status support ignore this
+	async fn create_commit_status(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_commit_sha: &str,
+		_state: CommitStatusState,
+		_description: &str,
+	) -> Result<(), MeowCoverageError> {
+		Ok(())
+	}
+
+	/// List the names of the teams with access to a repository. Providers
+	/// without team support return no teams
+	async fn list_repository_teams(
+		&self,
+		_owner: &str,
+		_repo: &str,
+	) -> Result<Vec<String>, MeowCoverageError> {
+		Ok(Vec::new())
+	}
+
+	/// Find an open issue whose body contains `marker`. Providers without
+	/// issue support find none
+	async fn find_open_issue(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_marker: &str,
+	) -> Result<Option<Issue>, MeowCoverageError> {
+		Ok(None)
+	}
+
+	/// Open an issue with `labels`. Providers without issue support ignore
+	/// this
+	async fn create_issue(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_title: &str,
+		_body: &str,
+		_labels: &[String],
+	) -> Result<(), MeowCoverageError> {
+		Ok(())
+	}
+
+	/// Comment on an issue and close it. Providers without issue support
+	/// ignore this
+	async fn close_issue(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_number: u64,
+		_comment: &str,
+	) -> Result<(), MeowCoverageError> {
+		Ok(())
+	}
+
+	/// List the bodies of the comments on a pull request. Providers without a
+	/// comment listing return [None]
+	async fn list_pull_request_comments(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_pr_number: u64,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		Ok(None)
+	}
+
+	/// List the bodies of the review comments on a pull request. Providers
+	/// without a review comment listing return [None]
+	async fn list_review_comments(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_pr_number: u64,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		Ok(None)
+	}
+
+	/// List the bodies of the comments on a commit. Providers without a
+	/// comment listing return [None]
+	async fn list_commit_comments(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_commit_sha: &str,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		Ok(None)
+	}
+
+	/// List the files changed between two commits, with their patches.
+	/// Providers without a compare API return [None]
+	async fn compare_commits(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_base_sha: &str,
+		_head_sha: &str,
+	) -> Result<Option<Vec<PullRequestFile>>, MeowCoverageError> {
+		Ok(None)
+	}
+
+	/// Resolve a ref (branch, tag, or abbreviated SHA) of a repository to the
+	/// commit it points to, [None] if the ref does not exist. Providers
+	/// without ref resolution resolve none
+	async fn resolve_commit(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_git_ref: &str,
+	) -> Result<Option<CommitInfo>, MeowCoverageError> {
+		Ok(None)
+	}
+
+	/// Fetch the access of the token to a repository. Providers without a
+	/// permission API return [None]
+	async fn repository_access(
+		&self,
+		_owner: &str,
+		_repo: &str,
+	) -> Result<Option<RepositoryAccess>, MeowCoverageError> {
+		Ok(None)
+	}
+
+	/// Fetch the remaining budget of the API rate limit. Providers without a
+	/// rate limit API return [None]
+	async fn rate_limit(&self) -> Result<Option<RateLimit>, MeowCoverageError> {
+		Ok(None)
+	}
+
+	/// Makes a file, and optionally line, specific link to the diff of a pull
+	/// request
+	fn pull_request_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		path: &str,
+		line: LineRange,
+	) -> String;
+
+	/// Makes a file, and optionally line, specific link for a commit
+	fn commit_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String;
+
+	/// Makes a file, and optionally line, specific link to the content of the
+	/// file at a commit, the same as [VcsProvider::commit_file_link] unless
+	/// that links to the diff
+	fn blob_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		self.commit_file_link(owner, repo, commit_sha, path, line)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use super::{
+		check_access, check_rate_limit, fake::FakeProvider, retry_rate_limited, split_unified_diff,
+		AccessRequirement, PullRequestFile, RateLimit, RepositoryAccess, RATE_LIMIT_RETRIES,
+	};
+	use crate::MeowCoverageError;
+
+	/// A `429 Too Many Requests` error
+	fn rate_limited() -> MeowCoverageError {
+		let response = hyper::Response::builder().status(429).body(String::new()).unwrap();
+		reqwest::Response::from(response).error_for_status().unwrap_err().into()
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_retry_rate_limited() {
+		assert!(rate_limited().is_rate_limited());
+		assert!(!MeowCoverageError::RepoNameMissingSlash.is_rate_limited());
+
+		let start = tokio::time::Instant::now();
+		let mut attempts = 0;
+		let result = retry_rate_limited(|| {
+			attempts += 1;
+			let attempt = attempts;
+			async move {
+				match attempt {
+					1 | 2 => Err(rate_limited()),
+					_ => Ok(attempt),
+				}
+			}
+		})
+		.await;
+		assert_eq!(result.unwrap(), 3);
+		// Backed off for 1 and 2 seconds
+		assert_eq!(start.elapsed().as_secs(), 3);
+
+		// Other errors are not retried
+		let mut attempts = 0;
+		let result = retry_rate_limited(|| {
+			attempts += 1;
+			async { Err::<(), _>(MeowCoverageError::RepoNameMissingSlash) }
+		})
+		.await;
+		assert!(result.is_err());
+		assert_eq!(attempts, 1);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_retry_rate_limited_gives_up() {
+		let start = tokio::time::Instant::now();
+		let mut attempts = 0;
+		let result = retry_rate_limited(|| {
+			attempts += 1;
+			async { Err::<(), _>(rate_limited()) }
+		})
+		.await;
+		assert!(result.unwrap_err().is_rate_limited());
+		assert_eq!(attempts, RATE_LIMIT_RETRIES + 1);
+		assert_eq!(start.elapsed().as_secs(), 2_u64.pow(RATE_LIMIT_RETRIES) - 1);
+	}
+
+	#[tokio::test]
+	async fn test_check_rate_limit() {
+		check_rate_limit(&FakeProvider::default(), 100, Some(10)).await.unwrap();
+
+		let reset = time::OffsetDateTime::now_utc().unix_timestamp() + 3600;
+		let provider = FakeProvider {
+			rate_limit: Some(RateLimit { remaining: 5, reset }),
+			..Default::default()
+		};
+		check_rate_limit(&provider, 100, Some(5)).await.unwrap();
+		assert!(matches!(
+			check_rate_limit(&provider, 0, Some(10)).await,
+			Err(MeowCoverageError::RateLimitBudget { remaining: 5, required: 10, .. })
+		));
+	}
+
+	#[tokio::test]
+	async fn test_check_access() {
+		let provider = FakeProvider {
+			access: HashMap::from([
+				(String::from("famedly/meow"), RepositoryAccess { read: true, write: None }),
+				(
+					String::from("famedly/private"),
+					RepositoryAccess { read: false, write: Some(false) },
+				),
+				(
+					String::from("famedly/coverage"),
+					RepositoryAccess { read: true, write: Some(false) },
+				),
+			]),
+			..Default::default()
+		};
+		let requirement = |repo_name, write| AccessRequirement {
+			provider: &provider,
+			repo_name,
+			write,
+			permissions: "`contents: write`",
+		};
+
+		// Unreported access is assumed to be granted
+		check_access(&[requirement("famedly/meow", true), requirement("famedly/other", true)])
+			.await
+			.unwrap();
+		check_access(&[requirement("famedly/coverage", false)]).await.unwrap();
+
+		let error = check_access(&[
+			requirement("famedly/meow", true),
+			requirement("famedly/private", false),
+			requirement("famedly/coverage", true),
+		])
+		.await
+		.unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"The token lacks access the run needs, grant it and rerun:\n  read access to famedly/private (`contents: write`)\n  write access to famedly/coverage (`contents: write`)"
+		);
+	}
+
+	#[test]
+	fn test_split_unified_diff() {
+		let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,1 +1,2 @@
+ fn a() {}
++fn b() {}
+diff --git a/src/old.rs b/src/new.rs
+similarity index 90%
+rename from src/old.rs
+rename to src/new.rs
+--- a/src/old.rs
++++ b/src/new.rs
+@@ -3,0 +4,1 @@
++fn c() {}
+diff --git a/src/gone.rs b/src/gone.rs
+deleted file mode 100644
+--- a/src/gone.rs
++++ /dev/null
+@@ -1,1 +0,0 @@
+-fn d() {}
+diff --git a/logo.png b/logo.png
+Binary files a/logo.png and b/logo.png differ
+";
+
+		assert_eq!(
+			split_unified_diff(diff),
+			&[
+				PullRequestFile {
+					filename: String::from("src/lib.rs"),
+					previous_filename: None,
+					patch: Some(String::from("@@ -1,1 +1,2 @@\n fn a() {}\n+fn b() {}\n")),
+				},
+				PullRequestFile {
+					filename: String::from("src/new.rs"),
+					previous_filename: Some(String::from("src/old.rs")),
+					patch: Some(String::from("@@ -3,0 +4,1 @@\n+fn c() {}\n")),
+				},
+				PullRequestFile {
+					filename: String::from("logo.png"),
+					previous_filename: None,
+					patch: None,
+				},
+			]
+		);
+	}
+}
@@ -0,0 +1,726 @@
+//! [VcsProvider] implementation for Azure DevOps Services/Server, the
+//! repository owner is the project and the base URL includes the organisation
+
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+
+use super::{
+	check_put_file_response, CommitStatusState, LineRange, PullRequestFile, PullRequestInfo,
+	RepoFile, VcsProvider,
+};
+use crate::{tracking::author, MeowCoverageError};
+
+/// REST API version used for all requests
+const API_VERSION: &str = "7.0";
+
+/// Object ID used for a ref that does not exist yet
+const EMPTY_OBJECT_ID: &str = "0000000000000000000000000000000000000000";
+
+/// Wrapper for list responses
+#[derive(Debug, Deserialize)]
+struct ValueList<T> {
+	/// Listed values
+	value: Vec<T>,
+}
+
+/// Label (tag) of a pull request
+#[derive(Debug, Deserialize)]
+struct Label {
+	/// Label name
+	name: String,
+}
+
+/// Response of the pull request API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullRequestResponse {
+	/// Whether the pull request is a draft
+	#[serde(default)]
+	is_draft: bool,
+	/// Labels of the pull request
+	#[serde(default)]
+	labels: Vec<Label>,
+	/// Head commit of the source branch
+	last_merge_source_commit: CommitRef,
+}
+
+/// Commit reference in a pull request iteration
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommitRef {
+	/// Commit ID
+	commit_id: String,
+}
+
+/// A pull request iteration
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Iteration {
+	/// Iteration ID
+	id: u64,
+	/// Head commit of the source branch
+	source_ref_commit: CommitRef,
+	/// Merge base of the source and target branches
+	common_ref_commit: CommitRef,
+}
+
+/// Item of a change entry or tree listing
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeItem {
+	/// Path of the item, with a leading `/`
+	path: String,
+	/// Whether the item is a folder
+	#[serde(default)]
+	is_folder: bool,
+}
+
+/// A changed item of a pull request iteration
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeEntry {
+	/// Kind of change, for example `edit` or `rename, edit`
+	change_type: String,
+	/// Changed item
+	item: ChangeItem,
+	/// Path of the item before a rename
+	original_path: Option<String>,
+}
+
+/// Response of the iteration changes API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IterationChanges {
+	/// Changed items
+	change_entries: Vec<ChangeEntry>,
+	/// Number of entries to skip for the next page
+	next_skip: Option<u64>,
+}
+
+/// A git ref
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitRef {
+	/// Full ref name
+	name: String,
+	/// Commit the ref points to
+	object_id: String,
+}
+
+/// A pipeline definition
+#[derive(Debug, Deserialize)]
+struct Pipeline {
+	/// Pipeline ID
+	id: u64,
+	/// Pipeline name
+	name: String,
+}
+
+/// Azure DevOps provider talking to the Git REST API
+#[derive(Debug, Clone)]
+pub struct AzureDevOpsProvider {
+	/// HTTP client used for all requests
+	client: Client,
+	/// Organisation URL, for example `https://dev.azure.com/famedly`
+	base_url: String,
+	/// Personal access token
+	token: String,
+}
+
+impl AzureDevOpsProvider {
+	/// Create a new [AzureDevOpsProvider]
+	#[must_use]
+	pub fn new(base_url: &str, token: String) -> Self {
+		Self {
+			client: Client::new(),
+			base_url: String::from(base_url.trim_end_matches('/')),
+			token,
+		}
+	}
+
+	/// Build an authenticated request to a project API route
+	fn request(&self, method: reqwest::Method, project: &str, route: &str) -> RequestBuilder {
+		self.client
+			.request(method, format!("{}/{}/_apis{}", self.base_url, project, route))
+			.query(&[("api-version", API_VERSION)])
+			.basic_auth("", Some(&self.token))
+	}
+
+	/// Fetch the contents of a file at a version, returns [None] if the file
+	/// does not exist
+	async fn item_content(
+		&self,
+		project: &str,
+		repo: &str,
+		path: &str,
+		version: &str,
+		version_type: &str,
+	) -> Result<Option<Vec<u8>>, MeowCoverageError> {
+		let response = self
+			.request(reqwest::Method::GET, project, &format!("/git/repositories/{}/items", repo))
+			.query(&[
+				("path", path),
+				("versionDescriptor.version", version),
+				("versionDescriptor.versionType", version_type),
+				("$format", "octetStream"),
+			])
+			.send()
+			.await?;
+
+		match response.status() {
+			StatusCode::NOT_FOUND => Ok(None),
+			StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+				Err(MeowCoverageError::MissingAccessToCoverageRepo)
+			}
+			_ => Ok(Some(response.error_for_status()?.bytes().await?.to_vec())),
+		}
+	}
+
+	/// Fetch the commit a branch points to, returns [None] if the branch does
+	/// not exist
+	async fn branch_head(
+		&self,
+		project: &str,
+		repo: &str,
+		branch: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		let refs: ValueList<GitRef> = self
+			.request(reqwest::Method::GET, project, &format!("/git/repositories/{}/refs", repo))
+			.query(&[("filter", format!("heads/{}", branch))])
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		let name = format!("refs/heads/{}", branch);
+		Ok(refs.value.into_iter().find(|git_ref| git_ref.name == name).map(|head| head.object_id))
+	}
+
+	/// Create a pull request thread
+	async fn create_thread(
+		&self,
+		project: &str,
+		repo: &str,
+		pr_number: u64,
+		body: &str,
+		thread_context: Option<serde_json::Value>,
+	) -> Result<(), MeowCoverageError> {
+		self.request(
+			reqwest::Method::POST,
+			project,
+			&format!("/git/repositories/{}/pullRequests/{}/threads", repo, pr_number),
+		)
+		.json(&thread_payload(body, thread_context))
+		.send()
+		.await?
+		.error_for_status()?;
+
+		Ok(())
+	}
+}
+
+#[async_trait]
+impl VcsProvider for AzureDevOpsProvider {
+	/// The labels of Azure DevOps pull requests are their tags
+	async fn get_pull_request(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<PullRequestInfo, MeowCoverageError> {
+		let pull_request: PullRequestResponse = self
+			.request(
+				reqwest::Method::GET,
+				owner,
+				&format!("/git/repositories/{}/pullRequests/{}", repo, pr_number),
+			)
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		Ok(PullRequestInfo {
+			draft: pull_request.is_draft,
+			labels: pull_request.labels.into_iter().map(|label| label.name).collect(),
+			head_sha: pull_request.last_merge_source_commit.commit_id,
+		})
+	}
+
+	/// Azure DevOps has no patch API, so the patches are built by diffing the
+	/// files at the merge base and the head of the latest iteration
+	async fn list_pull_request_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<Vec<PullRequestFile>, MeowCoverageError> {
+		let iterations: ValueList<Iteration> = self
+			.request(
+				reqwest::Method::GET,
+				owner,
+				&format!("/git/repositories/{}/pullRequests/{}/iterations", repo, pr_number),
+			)
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+		let iteration =
+			iterations.value.into_iter().max_by_key(|iteration| iteration.id).ok_or_else(|| {
+				MeowCoverageError::Provider(String::from("Pull request has no iterations"))
+			})?;
+
+		let mut changes = Vec::new();
+		let mut skip = 0;
+		loop {
+			let page: IterationChanges = self
+				.request(
+					reqwest::Method::GET,
+					owner,
+					&format!(
+						"/git/repositories/{}/pullRequests/{}/iterations/{}/changes",
+						repo, pr_number, iteration.id
+					),
+				)
+				.query(&[("$compareTo", 0), ("$skip", skip)])
+				.send()
+				.await?
+				.error_for_status()?
+				.json()
+				.await?;
+
+			changes.extend(page.change_entries);
+			match page.next_skip {
+				Some(next_skip) if next_skip > skip => skip = next_skip,
+				_ => break,
+			}
+		}
+
+		let mut files = Vec::new();
+		for change in changes {
+			if change.item.is_folder || change.change_type.contains("delete") {
+				continue;
+			}
+
+			let old_path = change.original_path.as_deref().unwrap_or(&change.item.path);
+			let old = self
+				.item_content(
+					owner,
+					repo,
+					old_path,
+					&iteration.common_ref_commit.commit_id,
+					"commit",
+				)
+				.await?
+				.unwrap_or_default();
+			let new = self
+				.item_content(
+					owner,
+					repo,
+					&change.item.path,
+					&iteration.source_ref_commit.commit_id,
+					"commit",
+				)
+				.await?
+				.unwrap_or_default();
+
+			files.push(diff_change(change, &old, &new));
+		}
+
+		Ok(files)
+	}
+
+	async fn create_pull_request_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		self.create_thread(owner, repo, pr_number, body, None).await?;
+		Ok(None)
+	}
+
+	async fn create_review_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		_commit_id: &str,
+		path: &str,
+		first_line: u32,
+		final_line: u32,
+		body: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.create_thread(
+			owner,
+			repo,
+			pr_number,
+			body,
+			Some(review_thread_context(path, first_line, final_line)),
+		)
+		.await
+	}
+
+	async fn create_commit_comment(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		commit_sha: &str,
+		_body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		#[allow(clippy::print_stderr)]
+		{
+			eprintln!(
+				"Azure DevOps does not support commit comments, skipping comment on {}",
+				commit_sha
+			);
+		}
+
+		Ok(None)
+	}
+
+	/// Azure DevOps updates files by pushing commits, the returned `sha` is the
+	/// commit the branch points to, which is used as the base of the push
+	async fn get_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+	) -> Result<Option<RepoFile>, MeowCoverageError> {
+		let Some(head) = self.branch_head(owner, repo, branch).await? else {
+			return Ok(None);
+		};
+
+		Ok(self
+			.item_content(owner, repo, &format!("/{}", path), &head, "commit")
+			.await?
+			.map(|content| RepoFile { content, sha: head }))
+	}
+
+	async fn get_file_at_commit(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+	) -> Result<Option<Vec<u8>>, MeowCoverageError> {
+		self.item_content(owner, repo, &format!("/{}", path), commit_sha, "commit").await
+	}
+
+	async fn put_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+		message: &str,
+		content: Vec<u8>,
+		sha: Option<String>,
+	) -> Result<(), MeowCoverageError> {
+		let change_type = match sha {
+			Some(_) => "edit",
+			None => "add",
+		};
+		let old_object_id = match sha {
+			Some(sha) => sha,
+			None => self
+				.branch_head(owner, repo, branch)
+				.await?
+				.unwrap_or_else(|| String::from(EMPTY_OBJECT_ID)),
+		};
+		let author = author();
+
+		let response = self
+			.request(reqwest::Method::POST, owner, &format!("/git/repositories/{}/pushes", repo))
+			.json(&serde_json::json!({
+				"refUpdates": [{
+					"name": format!("refs/heads/{}", branch),
+					"oldObjectId": old_object_id,
+				}],
+				"commits": [{
+					"comment": message,
+					"author": { "name": author.name, "email": author.email },
+					"changes": [{
+						"changeType": change_type,
+						"item": { "path": format!("/{}", path) },
+						"newContent": {
+							"content": BASE64.encode(content),
+							"contentType": "base64encoded",
+						},
+					}],
+				}],
+			}))
+			.send()
+			.await?;
+
+		check_put_file_response(response, path)
+	}
+
+	async fn list_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+	) -> Result<HashSet<String>, MeowCoverageError> {
+		let items: ValueList<ChangeItem> = self
+			.request(reqwest::Method::GET, owner, &format!("/git/repositories/{}/items", repo))
+			.query(&[
+				("recursionLevel", "full"),
+				("versionDescriptor.version", commit_sha),
+				("versionDescriptor.versionType", "commit"),
+			])
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		Ok(items
+			.value
+			.into_iter()
+			.filter(|item| !item.is_folder)
+			.map(|item| String::from(item.path.trim_start_matches('/')))
+			.collect())
+	}
+
+	/// Runs the pipeline named `workflow` (with or without its `.yml`
+	/// extension), passing `inputs` as template parameters
+	async fn dispatch_workflow(
+		&self,
+		owner: &str,
+		_repo: &str,
+		workflow: &str,
+		branch: &str,
+		inputs: serde_json::Value,
+	) -> Result<(), MeowCoverageError> {
+		let pipelines: ValueList<Pipeline> = self
+			.request(reqwest::Method::GET, owner, "/pipelines")
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+		let pipeline = pipelines
+			.value
+			.into_iter()
+			.find(|pipeline| {
+				pipeline.name == workflow || pipeline.name == workflow.trim_end_matches(".yml")
+			})
+			.ok_or_else(|| {
+				MeowCoverageError::Provider(format!("No pipeline named {} found", workflow))
+			})?;
+
+		self.request(reqwest::Method::POST, owner, &format!("/pipelines/{}/runs", pipeline.id))
+			.json(&serde_json::json!({
+				"resources": {
+					"repositories": { "self": { "refName": format!("refs/heads/{}", branch) } },
+				},
+				"templateParameters": inputs,
+			}))
+			.send()
+			.await?
+			.error_for_status()?;
+
+		Ok(())
+	}
+
+	async fn create_commit_status(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		state: CommitStatusState,
+		description: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.request(
+			reqwest::Method::POST,
+			owner,
+			&format!("/git/repositories/{}/commits/{}/statuses", repo, commit_sha),
+		)
+		.json(&serde_json::json!({
+			"state": match state {
+				CommitStatusState::Success => "succeeded",
+				CommitStatusState::Failure => "failed",
+			},
+			"description": description,
+			"context": { "name": "coverage", "genre": "meow-coverage" },
+		}))
+		.send()
+		.await?
+		.error_for_status()?;
+
+		Ok(())
+	}
+
+	fn pull_request_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		format!(
+			"{}/{}/_git/{}/pullrequest/{}?_a=files&path=/{}{}",
+			self.base_url,
+			owner,
+			repo,
+			pr_number,
+			path,
+			line_query(line)
+		)
+	}
+
+	fn commit_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		format!(
+			"{}/{}/_git/{}?path=/{}&version=GC{}{}",
+			self.base_url,
+			owner,
+			repo,
+			path,
+			commit_sha,
+			line_query(line)
+		)
+	}
+}
+
+/// Build the changed file of a pull request from the contents of the file at
+/// the merge base (`old`) and at the head (`new`)
+fn diff_change(change: ChangeEntry, old: &[u8], new: &[u8]) -> PullRequestFile {
+	let old = String::from_utf8_lossy(old);
+	let new = String::from_utf8_lossy(new);
+	let patch = similar::TextDiff::from_lines(old.as_ref(), new.as_ref())
+		.unified_diff()
+		.context_radius(3)
+		.to_string();
+
+	let filename = String::from(change.item.path.trim_start_matches('/'));
+	let previous_filename = change
+		.original_path
+		.map(|path| String::from(path.trim_start_matches('/')))
+		.filter(|path| *path != filename);
+
+	PullRequestFile { filename, previous_filename, patch: (!patch.is_empty()).then_some(patch) }
+}
+
+/// Payload of a new active pull request thread with a single text comment,
+/// anchored to lines of a file by `thread_context`
+fn thread_payload(body: &str, thread_context: Option<serde_json::Value>) -> serde_json::Value {
+	serde_json::json!({
+		"comments": [{ "parentCommentId": 0, "content": body, "commentType": 1 }],
+		"status": 1,
+		"threadContext": thread_context,
+	})
+}
+
+/// Thread context anchoring a thread to lines of the new version of a file
+fn review_thread_context(path: &str, first_line: u32, final_line: u32) -> serde_json::Value {
+	serde_json::json!({
+		"filePath": format!("/{}", path),
+		"rightFileStart": { "line": first_line, "offset": 1 },
+		"rightFileEnd": { "line": final_line, "offset": 1 },
+	})
+}
+
+/// Query parameters selecting a line range in the Azure DevOps file viewer
+fn line_query(line: LineRange) -> String {
+	match line {
+		Some((start_line, end_line)) => format!(
+			"&line={}&lineEnd={}&lineStartColumn=1&lineEndColumn=1&lineStyle=plain",
+			start_line,
+			end_line.unwrap_or(start_line) + 1
+		),
+		None => String::new(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		diff_change, line_query, review_thread_context, thread_payload, AzureDevOpsProvider,
+		ChangeEntry, ChangeItem,
+	};
+	use crate::provider::VcsProvider;
+
+	#[test]
+	fn test_diff_change() {
+		let change = |path: &str, original_path: Option<&str>| ChangeEntry {
+			change_type: String::from("rename, edit"),
+			item: ChangeItem { path: String::from(path), is_folder: false },
+			original_path: original_path.map(String::from),
+		};
+
+		let file = diff_change(
+			change("/src/new.rs", Some("/src/old.rs")),
+			b"fn a() {}\nfn b() {}\n",
+			b"fn a() {}\nfn c() {}\n",
+		);
+		assert_eq!(file.filename, "src/new.rs");
+		assert_eq!(file.previous_filename.as_deref(), Some("src/old.rs"));
+		assert_eq!(
+			file.patch.as_deref(),
+			Some("@@ -1,2 +1,2 @@\n fn a() {}\n-fn b() {}\n+fn c() {}\n")
+		);
+
+		// An edit in place is no rename, and identical contents have no patch
+		let file = diff_change(change("/src/lib.rs", Some("/src/lib.rs")), b"a\n", b"a\n");
+		assert_eq!(file.previous_filename, None);
+		assert_eq!(file.patch, None);
+	}
+
+	#[test]
+	fn test_thread_payload() {
+		assert_eq!(
+			thread_payload("Summary", None),
+			serde_json::json!({
+				"comments": [{ "parentCommentId": 0, "content": "Summary", "commentType": 1 }],
+				"status": 1,
+				"threadContext": null,
+			})
+		);
+		assert_eq!(
+			thread_payload("Untested", Some(review_thread_context("src/lib.rs", 3, 5)))
+				["threadContext"],
+			serde_json::json!({
+				"filePath": "/src/lib.rs",
+				"rightFileStart": { "line": 3, "offset": 1 },
+				"rightFileEnd": { "line": 5, "offset": 1 },
+			})
+		);
+	}
+
+	#[test]
+	fn test_links() {
+		assert_eq!(line_query(None), "");
+		// The end of the selection is exclusive
+		assert_eq!(
+			line_query(Some((3, None))),
+			"&line=3&lineEnd=4&lineStartColumn=1&lineEndColumn=1&lineStyle=plain"
+		);
+
+		let provider = AzureDevOpsProvider::new("https://dev.azure.com/famedly/", String::new());
+		assert_eq!(
+			provider.pull_request_file_link("meow", "coverage", 7, "src/lib.rs", Some((3, Some(5)))),
+			"https://dev.azure.com/famedly/meow/_git/coverage/pullrequest/7?_a=files&path=/src/lib.rs&line=3&lineEnd=6&lineStartColumn=1&lineEndColumn=1&lineStyle=plain"
+		);
+		assert_eq!(
+			provider.commit_file_link("meow", "coverage", "abcd", "src/lib.rs", None),
+			"https://dev.azure.com/famedly/meow/_git/coverage?path=/src/lib.rs&version=GCabcd"
+		);
+	}
+}
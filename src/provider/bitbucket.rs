@@ -0,0 +1,619 @@
+//! [VcsProvider] implementation for Bitbucket Cloud
+
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use reqwest::{header::AUTHORIZATION, Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+
+use super::{
+	check_put_file_response, split_unified_diff, CommitStatusState, LineRange, PullRequestFile,
+	PullRequestInfo, RepoFile, VcsProvider,
+};
+use crate::{tracking::author, MeowCoverageError};
+
+/// Default base URL of the Bitbucket Cloud API
+pub const BITBUCKET_API_URL: &str = "https://api.bitbucket.org";
+
+/// Commit a branch points to
+#[derive(Debug, Deserialize)]
+struct BranchTarget {
+	/// Commit hash
+	hash: String,
+}
+
+/// Response of the branch API
+#[derive(Debug, Deserialize)]
+struct BranchResponse {
+	/// Commit the branch points to
+	target: BranchTarget,
+}
+
+/// Entry of a source listing
+#[derive(Debug, Deserialize)]
+struct SourceEntry {
+	/// Path relative to the repository root
+	path: String,
+	/// Entry type (`commit_file` or `commit_directory`)
+	#[serde(rename = "type")]
+	kind: String,
+}
+
+/// Page of a paginated response
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+	/// Values on this page
+	values: Vec<T>,
+	/// URL of the next page
+	next: Option<String>,
+}
+
+/// Source branch of a pull request
+#[derive(Debug, Deserialize)]
+struct PullRequestSource {
+	/// Head commit of the source branch, with an abbreviated hash
+	commit: BranchTarget,
+}
+
+/// Response of the pull request API
+#[derive(Debug, Deserialize)]
+struct PullRequestResponse {
+	/// Whether the pull request is a draft
+	#[serde(default)]
+	draft: bool,
+	/// Source branch of the pull request
+	source: PullRequestSource,
+}
+
+/// Link of a comment
+#[derive(Debug, Deserialize)]
+struct CommentLink {
+	/// Link URL
+	href: String,
+}
+
+/// Links of a comment
+#[derive(Debug, Default, Deserialize)]
+struct CommentLinks {
+	/// Web page of the comment
+	html: Option<CommentLink>,
+}
+
+/// Response of creating a comment
+#[derive(Debug, Deserialize)]
+struct CommentResponse {
+	/// Links of the comment
+	#[serde(default)]
+	links: CommentLinks,
+}
+
+/// Bitbucket Cloud provider talking to the 2.0 REST API, the repository owner
+/// is the workspace
+#[derive(Debug, Clone)]
+pub struct BitbucketProvider {
+	/// HTTP client used for all requests
+	client: Client,
+	/// Base URL of the API
+	base_url: String,
+	/// Base URL of the web interface, used for links
+	web_url: String,
+	/// Access token
+	token: String,
+}
+
+impl BitbucketProvider {
+	/// Create a new [BitbucketProvider]
+	#[must_use]
+	pub fn new(base_url: &str, token: String) -> Self {
+		let base_url = String::from(base_url.trim_end_matches('/'));
+		Self {
+			client: Client::new(),
+			web_url: base_url.replacen("://api.", "://", 1),
+			base_url,
+			token,
+		}
+	}
+
+	/// Build an authenticated request to an API route
+	fn request(&self, method: reqwest::Method, route: &str) -> RequestBuilder {
+		self.client
+			.request(method, format!("{}/2.0{}", self.base_url, route))
+			.header(AUTHORIZATION, format!("Bearer {}", self.token))
+	}
+
+	/// Post a comment, returning its web URL
+	async fn post_comment(
+		&self,
+		route: &str,
+		body: serde_json::Value,
+	) -> Result<Option<String>, MeowCoverageError> {
+		let comment: CommentResponse = self
+			.request(reqwest::Method::POST, route)
+			.json(&body)
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		Ok(comment.links.html.map(|html| html.href))
+	}
+
+	/// Post a comment on a pull request, optionally inline
+	async fn post_pull_request_comment(
+		&self,
+		workspace: &str,
+		repo: &str,
+		pr_number: u64,
+		body: serde_json::Value,
+	) -> Result<Option<String>, MeowCoverageError> {
+		self.post_comment(
+			&format!("/repositories/{}/{}/pullrequests/{}/comments", workspace, repo, pr_number),
+			body,
+		)
+		.await
+	}
+
+	/// Build the request committing `content` to `path` via the form encoded
+	/// source API, with the commit `parent` as the expected branch head
+	#[allow(clippy::too_many_arguments)]
+	fn put_file_request(
+		&self,
+		workspace: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+		message: &str,
+		content: &[u8],
+		parent: Option<&str>,
+	) -> RequestBuilder {
+		let author = author();
+		let author = format!("{} <{}>", author.name, author.email);
+		let content = String::from_utf8_lossy(content);
+
+		let mut form = vec![
+			(path, content.as_ref()),
+			("message", message),
+			("branch", branch),
+			("author", author.as_str()),
+		];
+		if let Some(parent) = parent {
+			form.push(("parents", parent));
+		}
+
+		self.request(reqwest::Method::POST, &format!("/repositories/{}/{}/src", workspace, repo))
+			.form(&form)
+	}
+}
+
+/// Body of an inline comment, Bitbucket anchors it to the final line of the
+/// range on the new version of the file
+fn inline_comment(path: &str, final_line: u32, body: &str) -> serde_json::Value {
+	serde_json::json!({
+		"content": { "raw": body },
+		"inline": { "path": path, "to": final_line },
+	})
+}
+
+#[async_trait]
+impl VcsProvider for BitbucketProvider {
+	async fn get_pull_request(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<PullRequestInfo, MeowCoverageError> {
+		let pull_request: PullRequestResponse = self
+			.request(
+				reqwest::Method::GET,
+				&format!("/repositories/{}/{}/pullrequests/{}", owner, repo, pr_number),
+			)
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		// Bitbucket has no pull request labels
+		Ok(PullRequestInfo {
+			draft: pull_request.draft,
+			labels: Vec::new(),
+			head_sha: pull_request.source.commit.hash,
+		})
+	}
+
+	async fn list_pull_request_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<Vec<PullRequestFile>, MeowCoverageError> {
+		let diff = self
+			.request(
+				reqwest::Method::GET,
+				&format!("/repositories/{}/{}/pullrequests/{}/diff", owner, repo, pr_number),
+			)
+			.send()
+			.await?
+			.error_for_status()?
+			.text()
+			.await?;
+
+		Ok(split_unified_diff(&diff))
+	}
+
+	async fn create_pull_request_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		self.post_pull_request_comment(
+			owner,
+			repo,
+			pr_number,
+			serde_json::json!({ "content": { "raw": body } }),
+		)
+		.await
+	}
+
+	async fn create_review_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		_commit_id: &str,
+		path: &str,
+		_first_line: u32,
+		final_line: u32,
+		body: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.post_pull_request_comment(
+			owner,
+			repo,
+			pr_number,
+			inline_comment(path, final_line, body),
+		)
+		.await?;
+		Ok(())
+	}
+
+	async fn create_commit_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		self.post_comment(
+			&format!("/repositories/{}/{}/commit/{}/comments", owner, repo, commit_sha),
+			serde_json::json!({ "content": { "raw": body } }),
+		)
+		.await
+	}
+
+	/// Bitbucket has no blob SHAs, the returned `sha` is the commit the branch
+	/// points to, which is used as the parent when updating the file
+	async fn get_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+	) -> Result<Option<RepoFile>, MeowCoverageError> {
+		let branch_response = self
+			.request(
+				reqwest::Method::GET,
+				&format!("/repositories/{}/{}/refs/branches/{}", owner, repo, branch),
+			)
+			.send()
+			.await?;
+
+		match branch_response.status() {
+			StatusCode::NOT_FOUND => return Ok(None),
+			StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+				return Err(MeowCoverageError::MissingAccessToCoverageRepo)
+			}
+			_ => {}
+		}
+
+		let head: BranchResponse = branch_response.error_for_status()?.json().await?;
+
+		let response = self
+			.request(
+				reqwest::Method::GET,
+				&format!("/repositories/{}/{}/src/{}/{}", owner, repo, head.target.hash, path),
+			)
+			.send()
+			.await?;
+
+		if response.status() == StatusCode::NOT_FOUND {
+			return Ok(None);
+		}
+
+		let content = response.error_for_status()?.bytes().await?.to_vec();
+
+		Ok(Some(RepoFile { content, sha: head.target.hash }))
+	}
+
+	async fn get_file_at_commit(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+	) -> Result<Option<Vec<u8>>, MeowCoverageError> {
+		let response = self
+			.request(
+				reqwest::Method::GET,
+				&format!("/repositories/{}/{}/src/{}/{}", owner, repo, commit_sha, path),
+			)
+			.send()
+			.await?;
+
+		match response.status() {
+			StatusCode::NOT_FOUND => Ok(None),
+			_ => Ok(Some(response.error_for_status()?.bytes().await?.to_vec())),
+		}
+	}
+
+	async fn put_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+		message: &str,
+		content: Vec<u8>,
+		sha: Option<String>,
+	) -> Result<(), MeowCoverageError> {
+		let response = self
+			.put_file_request(owner, repo, branch, path, message, &content, sha.as_deref())
+			.send()
+			.await?;
+
+		check_put_file_response(response, path)
+	}
+
+	/// Runs the custom pipeline named `workflow`, passing `inputs` as pipeline
+	/// variables
+	async fn dispatch_workflow(
+		&self,
+		owner: &str,
+		repo: &str,
+		workflow: &str,
+		branch: &str,
+		inputs: serde_json::Value,
+	) -> Result<(), MeowCoverageError> {
+		let variables = inputs
+			.as_object()
+			.into_iter()
+			.flatten()
+			.map(|(key, value)| {
+				serde_json::json!({
+					"key": key,
+					"value": value.as_str().map_or_else(|| value.to_string(), String::from),
+				})
+			})
+			.collect::<Vec<_>>();
+
+		self.request(
+			reqwest::Method::POST,
+			&format!("/repositories/{}/{}/pipelines/", owner, repo),
+		)
+		.json(&serde_json::json!({
+			"target": {
+				"type": "pipeline_ref_target",
+				"ref_type": "branch",
+				"ref_name": branch,
+				"selector": { "type": "custom", "pattern": workflow },
+			},
+			"variables": variables,
+		}))
+		.send()
+		.await?
+		.error_for_status()?;
+
+		Ok(())
+	}
+
+	async fn list_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+	) -> Result<HashSet<String>, MeowCoverageError> {
+		let mut files = HashSet::new();
+		let mut request = self
+			.request(
+				reqwest::Method::GET,
+				&format!("/repositories/{}/{}/src/{}/", owner, repo, commit_sha),
+			)
+			.query(&[("max_depth", "1000"), ("pagelen", "100")]);
+
+		loop {
+			let page: Page<SourceEntry> = request.send().await?.error_for_status()?.json().await?;
+
+			files.extend(
+				page.values
+					.into_iter()
+					.filter(|entry| entry.kind == "commit_file")
+					.map(|entry| entry.path),
+			);
+
+			let Some(next) = page.next else {
+				break;
+			};
+			request = self.client.get(next).header(AUTHORIZATION, format!("Bearer {}", self.token));
+		}
+
+		Ok(files)
+	}
+
+	async fn create_commit_status(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		state: CommitStatusState,
+		description: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.request(
+			reqwest::Method::POST,
+			&format!("/repositories/{}/{}/commit/{}/statuses/build", owner, repo, commit_sha),
+		)
+		.json(&serde_json::json!({
+			"state": match state {
+				CommitStatusState::Success => "SUCCESSFUL",
+				CommitStatusState::Failure => "FAILED",
+			},
+			"key": "meow-coverage-coverage",
+			"name": "meow-coverage/coverage",
+			"description": description,
+			"url": format!("{}/{}/{}/commits/{}", self.web_url, owner, repo, commit_sha),
+		}))
+		.send()
+		.await?
+		.error_for_status()?;
+
+		Ok(())
+	}
+
+	fn pull_request_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		format!(
+			"{}/{}/{}/pull-requests/{}/diff#{}",
+			self.web_url,
+			owner,
+			repo,
+			pr_number,
+			match line {
+				Some((start_line, _)) => format!("L{}T{}", path, start_line),
+				None => format!("chg-{}", path),
+			}
+		)
+	}
+
+	fn commit_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		format!(
+			"{}/{}/{}/src/{}/{}{}",
+			self.web_url,
+			owner,
+			repo,
+			commit_sha,
+			path,
+			match line {
+				Some((start_line, Some(end_line))) => format!("#lines-{}:{}", start_line, end_line),
+				Some((line, None)) => format!("#lines-{}", line),
+				None => String::new(),
+			}
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{inline_comment, BitbucketProvider, BITBUCKET_API_URL};
+	use crate::{provider::VcsProvider, tracking::author};
+
+	#[test]
+	fn test_inline_comment() {
+		assert_eq!(
+			inline_comment("src/lib.rs", 12, "Untested"),
+			serde_json::json!({
+				"content": { "raw": "Untested" },
+				"inline": { "path": "src/lib.rs", "to": 12 },
+			})
+		);
+	}
+
+	#[test]
+	fn test_put_file_request() {
+		let provider = BitbucketProvider::new(BITBUCKET_API_URL, String::from("secret"));
+		let author = author();
+		let author = format!("{} <{}>", author.name, author.email);
+
+		let request = provider
+			.put_file_request(
+				"famedly",
+				"coverage",
+				"main",
+				"records/a b.json",
+				"Update",
+				b"{}",
+				Some("abcd"),
+			)
+			.build()
+			.unwrap();
+		assert_eq!(request.method(), reqwest::Method::POST);
+		assert_eq!(
+			request.url().as_str(),
+			"https://api.bitbucket.org/2.0/repositories/famedly/coverage/src"
+		);
+		assert_eq!(request.headers()["authorization"], "Bearer secret");
+		assert_eq!(request.headers()["content-type"], "application/x-www-form-urlencoded");
+		let body = std::str::from_utf8(request.body().unwrap().as_bytes().unwrap()).unwrap();
+		let form = reqwest::Url::parse(&format!("http://localhost/?{}", body))
+			.unwrap()
+			.query_pairs()
+			.into_owned()
+			.collect::<Vec<_>>();
+		assert_eq!(
+			form,
+			[
+				("records/a b.json", "{}"),
+				("message", "Update"),
+				("branch", "main"),
+				("author", author.as_str()),
+				("parents", "abcd"),
+			]
+			.map(|(key, value)| (String::from(key), String::from(value)))
+		);
+
+		let request = provider
+			.put_file_request("famedly", "coverage", "main", "a.json", "Add", b"{}", None)
+			.build()
+			.unwrap();
+		let body = std::str::from_utf8(request.body().unwrap().as_bytes().unwrap()).unwrap();
+		assert!(!body.contains("parents"));
+	}
+
+	#[test]
+	fn test_links() {
+		let provider = BitbucketProvider::new(BITBUCKET_API_URL, String::new());
+		assert_eq!(
+			provider.pull_request_file_link("famedly", "meow", 7, "src/lib.rs", Some((3, Some(5)))),
+			"https://bitbucket.org/famedly/meow/pull-requests/7/diff#Lsrc/lib.rsT3"
+		);
+		assert_eq!(
+			provider.pull_request_file_link("famedly", "meow", 7, "src/lib.rs", None),
+			"https://bitbucket.org/famedly/meow/pull-requests/7/diff#chg-src/lib.rs"
+		);
+		assert_eq!(
+			provider.commit_file_link("famedly", "meow", "abcd", "src/lib.rs", Some((3, Some(5)))),
+			"https://bitbucket.org/famedly/meow/src/abcd/src/lib.rs#lines-3:5"
+		);
+		assert_eq!(
+			provider.commit_file_link("famedly", "meow", "abcd", "src/lib.rs", Some((3, None))),
+			"https://bitbucket.org/famedly/meow/src/abcd/src/lib.rs#lines-3"
+		);
+		assert_eq!(
+			provider.commit_file_link("famedly", "meow", "abcd", "src/lib.rs", None),
+			"https://bitbucket.org/famedly/meow/src/abcd/src/lib.rs"
+		);
+	}
+}
@@ -0,0 +1,392 @@
+//! [VcsProvider] wrapper caching the results of immutable lookups on disk,
+//! so re-runs for the same pull request head don't fetch them again
+
+use std::{
+	collections::HashSet,
+	path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+	CommitInfo, CommitStatusState, Issue, LineRange, PullRequestFile, PullRequestInfo, RateLimit,
+	RepoFile, RepositoryAccess, VcsProvider,
+};
+use crate::MeowCoverageError;
+
+/// Caches the changed files of a pull request (keyed by its head commit) and
+/// the files of a repository at a commit in a directory, forwarding all
+/// other operations. Branch contents and SHAs change with every push and are
+/// never cached
+#[derive(Debug)]
+pub struct CachedProvider {
+	/// Provider the operations are forwarded to
+	inner: Box<dyn VcsProvider>,
+	/// Directory of the cache
+	directory: PathBuf,
+	/// Head commit of the analysed pull request
+	head_sha: String,
+}
+
+impl CachedProvider {
+	/// Cache the lookups of `inner` in `directory`, `head_sha` is the head
+	/// commit of the analysed pull request
+	#[must_use]
+	pub fn new(inner: Box<dyn VcsProvider>, directory: PathBuf, head_sha: &str) -> Self {
+		Self { inner, directory, head_sha: String::from(head_sha) }
+	}
+
+	/// Read a cache entry, [None] if it is missing or unreadable
+	fn read<T: DeserializeOwned>(&self, path: &Path) -> Option<T> {
+		let content = std::fs::read(self.directory.join(path)).ok()?;
+		#[allow(clippy::print_stderr)]
+		match serde_json::from_slice(&content) {
+			Ok(value) => Some(value),
+			Err(why) => {
+				eprintln!("Ignoring corrupt cache entry {} (why: {})", path.display(), why);
+				None
+			}
+		}
+	}
+
+	/// Write a cache entry, failing to do so only costs the next run an API
+	/// request
+	fn write<T: Serialize>(&self, path: &Path, value: &T) {
+		let path = self.directory.join(path);
+		let result = serde_json::to_vec(value).map_err(std::io::Error::from).and_then(|content| {
+			if let Some(parent) = path.parent() {
+				std::fs::create_dir_all(parent)?;
+			}
+			std::fs::write(&path, content)
+		});
+
+		#[allow(clippy::print_stderr)]
+		if let Err(why) = result {
+			eprintln!("Failed to write cache entry {} (why: {})", path.display(), why);
+		}
+	}
+
+	/// Read a cache entry, or fetch and store it
+	async fn cached<T, F>(&self, path: PathBuf, fetch: F) -> Result<T, MeowCoverageError>
+	where
+		T: Serialize + DeserializeOwned,
+		F: std::future::Future<Output = Result<T, MeowCoverageError>>,
+	{
+		if let Some(value) = self.read(&path) {
+			return Ok(value);
+		}
+
+		let value = fetch.await?;
+		self.write(&path, &value);
+		Ok(value)
+	}
+}
+
+#[async_trait]
+impl VcsProvider for CachedProvider {
+	async fn get_pull_request(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<PullRequestInfo, MeowCoverageError> {
+		self.inner.get_pull_request(owner, repo, pr_number).await
+	}
+
+	async fn list_pull_request_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<Vec<PullRequestFile>, MeowCoverageError> {
+		self.cached(
+			Path::new(owner)
+				.join(repo)
+				.join("pulls")
+				.join(format!("{}-{}.json", pr_number, self.head_sha)),
+			self.inner.list_pull_request_files(owner, repo, pr_number),
+		)
+		.await
+	}
+
+	async fn create_pull_request_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		self.inner.create_pull_request_comment(owner, repo, pr_number, body).await
+	}
+
+	async fn create_review_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		commit_id: &str,
+		path: &str,
+		first_line: u32,
+		final_line: u32,
+		body: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.inner
+			.create_review_comment(
+				owner, repo, pr_number, commit_id, path, first_line, final_line, body,
+			)
+			.await
+	}
+
+	async fn create_commit_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		self.inner.create_commit_comment(owner, repo, commit_sha, body).await
+	}
+
+	async fn get_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+	) -> Result<Option<RepoFile>, MeowCoverageError> {
+		self.inner.get_file(owner, repo, branch, path).await
+	}
+
+	async fn get_file_at_commit(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+	) -> Result<Option<Vec<u8>>, MeowCoverageError> {
+		self.inner.get_file_at_commit(owner, repo, commit_sha, path).await
+	}
+
+	async fn put_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+		message: &str,
+		content: Vec<u8>,
+		sha: Option<String>,
+	) -> Result<(), MeowCoverageError> {
+		self.inner.put_file(owner, repo, branch, path, message, content, sha).await
+	}
+
+	async fn dispatch_workflow(
+		&self,
+		owner: &str,
+		repo: &str,
+		workflow: &str,
+		branch: &str,
+		inputs: serde_json::Value,
+	) -> Result<(), MeowCoverageError> {
+		self.inner.dispatch_workflow(owner, repo, workflow, branch, inputs).await
+	}
+
+	async fn list_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+	) -> Result<HashSet<String>, MeowCoverageError> {
+		self.cached(
+			Path::new(owner).join(repo).join("trees").join(format!("{}.json", commit_sha)),
+			self.inner.list_files(owner, repo, commit_sha),
+		)
+		.await
+	}
+
+	async fn create_commit_status(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		state: CommitStatusState,
+		description: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.inner.create_commit_status(owner, repo, commit_sha, state, description).await
+	}
+
+	async fn list_repository_teams(
+		&self,
+		owner: &str,
+		repo: &str,
+	) -> Result<Vec<String>, MeowCoverageError> {
+		self.inner.list_repository_teams(owner, repo).await
+	}
+
+	async fn find_open_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		marker: &str,
+	) -> Result<Option<Issue>, MeowCoverageError> {
+		self.inner.find_open_issue(owner, repo, marker).await
+	}
+
+	async fn create_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		title: &str,
+		body: &str,
+		labels: &[String],
+	) -> Result<(), MeowCoverageError> {
+		self.inner.create_issue(owner, repo, title, body, labels).await
+	}
+
+	async fn close_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		number: u64,
+		comment: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.inner.close_issue(owner, repo, number, comment).await
+	}
+
+	async fn list_pull_request_comments(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		self.inner.list_pull_request_comments(owner, repo, pr_number).await
+	}
+
+	async fn list_review_comments(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		self.inner.list_review_comments(owner, repo, pr_number).await
+	}
+
+	async fn list_commit_comments(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		self.inner.list_commit_comments(owner, repo, commit_sha).await
+	}
+
+	async fn compare_commits(
+		&self,
+		owner: &str,
+		repo: &str,
+		base_sha: &str,
+		head_sha: &str,
+	) -> Result<Option<Vec<PullRequestFile>>, MeowCoverageError> {
+		self.inner.compare_commits(owner, repo, base_sha, head_sha).await
+	}
+
+	async fn resolve_commit(
+		&self,
+		owner: &str,
+		repo: &str,
+		git_ref: &str,
+	) -> Result<Option<CommitInfo>, MeowCoverageError> {
+		self.inner.resolve_commit(owner, repo, git_ref).await
+	}
+
+	async fn repository_access(
+		&self,
+		owner: &str,
+		repo: &str,
+	) -> Result<Option<RepositoryAccess>, MeowCoverageError> {
+		self.inner.repository_access(owner, repo).await
+	}
+
+	async fn rate_limit(&self) -> Result<Option<RateLimit>, MeowCoverageError> {
+		self.inner.rate_limit().await
+	}
+
+	fn pull_request_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		self.inner.pull_request_file_link(owner, repo, pr_number, path, line)
+	}
+
+	fn commit_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		self.inner.commit_file_link(owner, repo, commit_sha, path, line)
+	}
+
+	fn blob_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		self.inner.blob_file_link(owner, repo, commit_sha, path, line)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::CachedProvider;
+	use crate::provider::{fake::FakeProvider, PullRequestFile, VcsProvider};
+
+	#[tokio::test]
+	async fn test_cached_provider() {
+		let directory = std::env::temp_dir().join("meow-coverage-test-cached-provider");
+		let _ = std::fs::remove_dir_all(&directory);
+		let changed_files = vec![PullRequestFile {
+			filename: String::from("src/lib.rs"),
+			previous_filename: None,
+			patch: Some(String::from("@@ -1,0 +1,1 @@\n+fn a() {}\n")),
+		}];
+
+		let provider = CachedProvider::new(
+			Box::new(FakeProvider {
+				pull_request_files: changed_files.clone(),
+				..Default::default()
+			}),
+			directory.clone(),
+			"abcdef",
+		);
+		assert_eq!(
+			provider.list_pull_request_files("famedly", "meow", 1).await.unwrap(),
+			changed_files
+		);
+		assert!(directory.join("famedly/meow/pulls/1-abcdef.json").exists());
+
+		// A later run for the same head reads the cache instead of the provider
+		let cached =
+			CachedProvider::new(Box::<FakeProvider>::default(), directory.clone(), "abcdef");
+		assert_eq!(
+			cached.list_pull_request_files("famedly", "meow", 1).await.unwrap(),
+			changed_files
+		);
+		let pushed =
+			CachedProvider::new(Box::<FakeProvider>::default(), directory.clone(), "123456");
+		assert!(pushed.list_pull_request_files("famedly", "meow", 1).await.unwrap().is_empty());
+
+		std::fs::write(directory.join("famedly/meow/pulls/1-abcdef.json"), "{").unwrap();
+		assert!(cached.list_pull_request_files("famedly", "meow", 1).await.unwrap().is_empty());
+	}
+}
@@ -0,0 +1,371 @@
+//! In-memory [VcsProvider] used by unit tests
+
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Mutex,
+};
+
+use async_trait::async_trait;
+
+use super::{
+	CommitInfo, CommitStatusState, Issue, LineRange, PullRequestFile, PullRequestInfo, RateLimit,
+	RepoFile, RepositoryAccess, VcsProvider,
+};
+use crate::MeowCoverageError;
+
+/// A review comment recorded by [FakeProvider]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakeReviewComment {
+	/// File path
+	pub path: String,
+	/// First line of the commented range
+	pub first_line: u32,
+	/// Final line of the commented range
+	pub final_line: u32,
+	/// Comment body
+	pub body: String,
+}
+
+/// An issue recorded by [FakeProvider]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakeIssue {
+	/// Issue title
+	pub title: String,
+	/// Issue body
+	pub body: String,
+	/// Issue labels
+	pub labels: Vec<String>,
+	/// Comment the issue was closed with, [None] while open
+	pub closing_comment: Option<String>,
+}
+
+/// In-memory provider recording every write
+#[derive(Debug, Default)]
+pub struct FakeProvider {
+	/// Metadata returned for every pull request
+	pub pull_request: PullRequestInfo,
+	/// Files returned for every pull request
+	pub pull_request_files: Vec<PullRequestFile>,
+	/// Files returned for every comparison of two commits, [None] to behave
+	/// like a provider without a compare API
+	pub compared_files: Option<Vec<PullRequestFile>>,
+	/// Paths of the files in the analysed repository, at every commit
+	pub tree: HashSet<String>,
+	/// Repository files keyed by `owner/repo/branch/path` (or
+	/// `owner/repo/commit/path`)
+	pub files: Mutex<HashMap<String, RepoFile>>,
+	/// Pull request and commit comments
+	pub comments: Mutex<Vec<String>>,
+	/// Review comments
+	pub review_comments: Mutex<Vec<FakeReviewComment>>,
+	/// Commit statuses and their descriptions
+	pub statuses: Mutex<Vec<(CommitStatusState, String)>>,
+	/// Dispatched workflows and their inputs
+	pub dispatched_workflows: Mutex<Vec<(String, serde_json::Value)>>,
+	/// Teams with access to every repository
+	pub teams: Vec<String>,
+	/// Number of upcoming file updates failing with a conflict
+	pub put_file_conflicts: Mutex<usize>,
+	/// Issues, numbered by their index
+	pub issues: Mutex<Vec<FakeIssue>>,
+	/// Remaining rate limit budget, [None] for no rate limit
+	pub rate_limit: Option<RateLimit>,
+	/// Commits refs resolve to, in every repository
+	pub refs: HashMap<String, CommitInfo>,
+	/// Access of the token to repositories (`OWNER/REPO`), unreported for
+	/// others
+	pub access: HashMap<String, RepositoryAccess>,
+}
+
+impl FakeProvider {
+	/// Key for [FakeProvider::files]
+	fn file_key(owner: &str, repo: &str, branch: &str, path: &str) -> String {
+		format!("{}/{}/{}/{}", owner, repo, branch, path)
+	}
+}
+
+#[allow(clippy::unwrap_used)]
+#[async_trait]
+impl VcsProvider for FakeProvider {
+	async fn get_pull_request(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_pr_number: u64,
+	) -> Result<PullRequestInfo, MeowCoverageError> {
+		Ok(self.pull_request.clone())
+	}
+
+	async fn list_pull_request_files(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_pr_number: u64,
+	) -> Result<Vec<PullRequestFile>, MeowCoverageError> {
+		Ok(self.pull_request_files.clone())
+	}
+
+	async fn create_pull_request_comment(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_pr_number: u64,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		let mut comments = self.comments.lock().unwrap();
+		comments.push(String::from(body));
+		Ok(Some(format!("https://example.com/comments/{}", comments.len())))
+	}
+
+	async fn create_review_comment(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_pr_number: u64,
+		_commit_id: &str,
+		path: &str,
+		first_line: u32,
+		final_line: u32,
+		body: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.review_comments.lock().unwrap().push(FakeReviewComment {
+			path: String::from(path),
+			first_line,
+			final_line,
+			body: String::from(body),
+		});
+		Ok(())
+	}
+
+	async fn create_commit_comment(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_commit_sha: &str,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		let mut comments = self.comments.lock().unwrap();
+		comments.push(String::from(body));
+		Ok(Some(format!("https://example.com/comments/{}", comments.len())))
+	}
+
+	async fn get_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+	) -> Result<Option<RepoFile>, MeowCoverageError> {
+		Ok(self.files.lock().unwrap().get(&Self::file_key(owner, repo, branch, path)).cloned())
+	}
+
+	async fn get_file_at_commit(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+	) -> Result<Option<Vec<u8>>, MeowCoverageError> {
+		Ok(self
+			.files
+			.lock()
+			.unwrap()
+			.get(&Self::file_key(owner, repo, commit_sha, path))
+			.map(|file| file.content.clone()))
+	}
+
+	async fn put_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+		_message: &str,
+		content: Vec<u8>,
+		_sha: Option<String>,
+	) -> Result<(), MeowCoverageError> {
+		let mut conflicts = self.put_file_conflicts.lock().unwrap();
+		if *conflicts > 0 {
+			*conflicts -= 1;
+			return Err(MeowCoverageError::FileConflict(String::from(path)));
+		}
+
+		let mut files = self.files.lock().unwrap();
+		let sha = format!("{}", files.len());
+		files.insert(Self::file_key(owner, repo, branch, path), RepoFile { content, sha });
+		Ok(())
+	}
+
+	async fn dispatch_workflow(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		workflow: &str,
+		_branch: &str,
+		inputs: serde_json::Value,
+	) -> Result<(), MeowCoverageError> {
+		self.dispatched_workflows.lock().unwrap().push((String::from(workflow), inputs));
+		Ok(())
+	}
+
+	async fn list_files(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_commit_sha: &str,
+	) -> Result<HashSet<String>, MeowCoverageError> {
+		Ok(self.tree.clone())
+	}
+
+	async fn create_commit_status(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_commit_sha: &str,
+		state: CommitStatusState,
+		description: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.statuses.lock().unwrap().push((state, String::from(description)));
+		Ok(())
+	}
+
+	async fn list_repository_teams(
+		&self,
+		_owner: &str,
+		_repo: &str,
+	) -> Result<Vec<String>, MeowCoverageError> {
+		Ok(self.teams.clone())
+	}
+
+	async fn find_open_issue(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		marker: &str,
+	) -> Result<Option<Issue>, MeowCoverageError> {
+		Ok(self.issues.lock().unwrap().iter().enumerate().find_map(|(number, issue)| {
+			(issue.closing_comment.is_none() && issue.body.contains(marker))
+				.then(|| Issue { number: number as u64, body: issue.body.clone() })
+		}))
+	}
+
+	async fn create_issue(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		title: &str,
+		body: &str,
+		labels: &[String],
+	) -> Result<(), MeowCoverageError> {
+		self.issues.lock().unwrap().push(FakeIssue {
+			title: String::from(title),
+			body: String::from(body),
+			labels: labels.to_vec(),
+			closing_comment: None,
+		});
+		Ok(())
+	}
+
+	async fn close_issue(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		number: u64,
+		comment: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.issues.lock().unwrap()[number as usize].closing_comment = Some(String::from(comment));
+		Ok(())
+	}
+
+	async fn list_pull_request_comments(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_pr_number: u64,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		Ok(Some(self.comments.lock().unwrap().clone()))
+	}
+
+	async fn list_review_comments(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_pr_number: u64,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		let review_comments = self.review_comments.lock().unwrap();
+		Ok(Some(review_comments.iter().map(|comment| comment.body.clone()).collect()))
+	}
+
+	async fn list_commit_comments(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_commit_sha: &str,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		Ok(Some(self.comments.lock().unwrap().clone()))
+	}
+
+	async fn compare_commits(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		_base_sha: &str,
+		_head_sha: &str,
+	) -> Result<Option<Vec<PullRequestFile>>, MeowCoverageError> {
+		Ok(self.compared_files.clone())
+	}
+
+	async fn resolve_commit(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		git_ref: &str,
+	) -> Result<Option<CommitInfo>, MeowCoverageError> {
+		Ok(self.refs.get(git_ref).cloned())
+	}
+
+	async fn repository_access(
+		&self,
+		owner: &str,
+		repo: &str,
+	) -> Result<Option<RepositoryAccess>, MeowCoverageError> {
+		Ok(self.access.get(&format!("{}/{}", owner, repo)).copied())
+	}
+
+	async fn rate_limit(&self) -> Result<Option<RateLimit>, MeowCoverageError> {
+		Ok(self.rate_limit)
+	}
+
+	fn pull_request_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		format!("pr://{}/{}/{}/{}#{:?}", owner, repo, pr_number, path, line)
+	}
+
+	fn commit_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		format!("commit://{}/{}/{}/{}#{:?}", owner, repo, commit_sha, path, line)
+	}
+
+	fn blob_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		format!("blob://{}/{}/{}/{}#{:?}", owner, repo, commit_sha, path, line)
+	}
+}
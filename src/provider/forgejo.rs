@@ -0,0 +1,603 @@
+//! [VcsProvider] implementation for Gitea and Forgejo
+
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use reqwest::{header::AUTHORIZATION, Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+
+use super::{
+	check_put_file_response, split_unified_diff, CommitStatusState, Issue, LineRange,
+	PullRequestFile, PullRequestInfo, RepoFile, VcsProvider,
+};
+use crate::{tracking::author, MeowCoverageError};
+
+/// Response of the contents API
+#[derive(Debug, Deserialize)]
+struct ContentsResponse {
+	/// File blob SHA
+	sha: String,
+	/// Base64 encoded file contents
+	content: Option<String>,
+}
+
+/// Entry of a Git tree
+#[derive(Debug, Deserialize)]
+struct TreeEntry {
+	/// Path relative to the repository root
+	path: String,
+	/// Object type (`blob`, `tree`, or `commit`)
+	#[serde(rename = "type")]
+	kind: String,
+}
+
+/// Response of the Git trees API
+#[derive(Debug, Deserialize)]
+struct TreeResponse {
+	/// Entries of the tree on the requested page
+	#[serde(default)]
+	tree: Vec<TreeEntry>,
+	/// Whether there are more pages
+	#[serde(default)]
+	truncated: bool,
+}
+
+/// Response of creating a comment
+#[derive(Debug, Deserialize)]
+struct CommentResponse {
+	/// Web URL of the comment
+	html_url: Option<String>,
+}
+
+/// Label of a pull request
+#[derive(Debug, Deserialize)]
+struct Label {
+	/// Label name
+	name: String,
+}
+
+/// Team with access to a repository
+#[derive(Debug, Deserialize)]
+struct Team {
+	/// Team name
+	name: String,
+}
+
+/// Issue returned by the issues API
+#[derive(Debug, Deserialize)]
+struct IssueResponse {
+	/// Issue number
+	number: u64,
+	/// Issue body
+	#[serde(default)]
+	body: String,
+}
+
+/// Head of a pull request
+#[derive(Debug, Deserialize)]
+struct PullRequestHead {
+	/// Head commit SHA
+	sha: String,
+}
+
+/// Response of the pull request API
+#[derive(Debug, Deserialize)]
+struct PullRequestResponse {
+	/// Whether the pull request is a draft (`WIP:` title prefix)
+	#[serde(default)]
+	draft: bool,
+	/// Labels of the pull request
+	#[serde(default)]
+	labels: Vec<Label>,
+	/// Head of the pull request
+	head: PullRequestHead,
+}
+
+/// Gitea/Forgejo provider talking to the `/api/v1` REST API
+#[derive(Debug, Clone)]
+pub struct ForgejoProvider {
+	/// HTTP client used for all requests
+	client: Client,
+	/// Base URL of the instance, for example `https://codeberg.org`
+	base_url: String,
+	/// Access token
+	token: String,
+}
+
+impl ForgejoProvider {
+	/// Create a new [ForgejoProvider]
+	#[must_use]
+	pub fn new(base_url: &str, token: String) -> Self {
+		Self {
+			client: Client::new(),
+			base_url: String::from(base_url.trim_end_matches('/')),
+			token,
+		}
+	}
+
+	/// Build an authenticated request to an API route
+	fn request(&self, method: reqwest::Method, route: &str) -> RequestBuilder {
+		self.client
+			.request(method, format!("{}/api/v1{}", self.base_url, route))
+			.header(AUTHORIZATION, format!("token {}", self.token))
+	}
+}
+
+#[async_trait]
+impl VcsProvider for ForgejoProvider {
+	async fn get_pull_request(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<PullRequestInfo, MeowCoverageError> {
+		let pull_request: PullRequestResponse = self
+			.request(
+				reqwest::Method::GET,
+				&format!("/repos/{}/{}/pulls/{}", owner, repo, pr_number),
+			)
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		Ok(PullRequestInfo {
+			draft: pull_request.draft,
+			labels: pull_request.labels.into_iter().map(|label| label.name).collect(),
+			head_sha: pull_request.head.sha,
+		})
+	}
+
+	async fn list_pull_request_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<Vec<PullRequestFile>, MeowCoverageError> {
+		let diff = self
+			.request(
+				reqwest::Method::GET,
+				&format!("/repos/{}/{}/pulls/{}.diff", owner, repo, pr_number),
+			)
+			.send()
+			.await?
+			.error_for_status()?
+			.text()
+			.await?;
+
+		Ok(split_unified_diff(&diff))
+	}
+
+	async fn create_pull_request_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		let comment: CommentResponse = self
+			.request(
+				reqwest::Method::POST,
+				&format!("/repos/{}/{}/issues/{}/comments", owner, repo, pr_number),
+			)
+			.json(&serde_json::json!({ "body": body }))
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		Ok(comment.html_url)
+	}
+
+	async fn create_review_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		commit_id: &str,
+		path: &str,
+		_first_line: u32,
+		final_line: u32,
+		body: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.request(
+			reqwest::Method::POST,
+			&format!("/repos/{}/{}/pulls/{}/reviews", owner, repo, pr_number),
+		)
+		.json(&review_payload(commit_id, path, final_line, body))
+		.send()
+		.await?
+		.error_for_status()?;
+
+		Ok(())
+	}
+
+	async fn create_commit_comment(
+		&self,
+		_owner: &str,
+		_repo: &str,
+		commit_sha: &str,
+		_body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		#[allow(clippy::print_stderr)]
+		{
+			eprintln!(
+				"Forgejo does not support commit comments, skipping comment on {}",
+				commit_sha
+			);
+		}
+
+		Ok(None)
+	}
+
+	async fn get_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+	) -> Result<Option<RepoFile>, MeowCoverageError> {
+		let response = self
+			.request(reqwest::Method::GET, &format!("/repos/{}/{}/contents/{}", owner, repo, path))
+			.query(&[("ref", branch)])
+			.send()
+			.await?;
+
+		match response.status() {
+			StatusCode::NOT_FOUND => return Ok(None),
+			StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+				return Err(MeowCoverageError::MissingAccessToCoverageRepo)
+			}
+			_ => {}
+		}
+
+		let contents: ContentsResponse = response.error_for_status()?.json().await?;
+		let content = BASE64
+			.decode(contents.content.unwrap_or_default().replace('\n', ""))
+			.map_err(|why| MeowCoverageError::Provider(format!("Invalid file content: {}", why)))?;
+
+		Ok(Some(RepoFile { content, sha: contents.sha }))
+	}
+
+	async fn get_file_at_commit(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+	) -> Result<Option<Vec<u8>>, MeowCoverageError> {
+		Ok(self.get_file(owner, repo, commit_sha, path).await?.map(|file| file.content))
+	}
+
+	async fn put_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+		message: &str,
+		content: Vec<u8>,
+		sha: Option<String>,
+	) -> Result<(), MeowCoverageError> {
+		let method = match sha {
+			Some(_) => reqwest::Method::PUT,
+			None => reqwest::Method::POST,
+		};
+		let author = author();
+
+		let response = self
+			.request(method, &format!("/repos/{}/{}/contents/{}", owner, repo, path))
+			.json(&serde_json::json!({
+				"branch": branch,
+				"message": message,
+				"content": BASE64.encode(content),
+				"sha": sha,
+				"author": { "name": author.name, "email": author.email },
+				"committer": { "name": author.name, "email": author.email },
+			}))
+			.send()
+			.await?;
+
+		check_put_file_response(response, path)
+	}
+
+	async fn dispatch_workflow(
+		&self,
+		owner: &str,
+		repo: &str,
+		workflow: &str,
+		branch: &str,
+		inputs: serde_json::Value,
+	) -> Result<(), MeowCoverageError> {
+		self.request(
+			reqwest::Method::POST,
+			&format!("/repos/{}/{}/actions/workflows/{}/dispatches", owner, repo, workflow),
+		)
+		.json(&serde_json::json!({ "ref": branch, "inputs": inputs }))
+		.send()
+		.await?
+		.error_for_status()?;
+
+		Ok(())
+	}
+
+	async fn list_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+	) -> Result<HashSet<String>, MeowCoverageError> {
+		let mut files = HashSet::new();
+
+		for page in 1.. {
+			let response: TreeResponse = self
+				.request(
+					reqwest::Method::GET,
+					&format!("/repos/{}/{}/git/trees/{}", owner, repo, commit_sha),
+				)
+				.query(&[("recursive", "true"), ("per_page", "1000"), ("page", &page.to_string())])
+				.send()
+				.await?
+				.error_for_status()?
+				.json()
+				.await?;
+
+			files.extend(
+				response
+					.tree
+					.into_iter()
+					.filter(|entry| entry.kind == "blob")
+					.map(|entry| entry.path),
+			);
+
+			if !response.truncated {
+				break;
+			}
+		}
+
+		Ok(files)
+	}
+
+	async fn create_commit_status(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		state: CommitStatusState,
+		description: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.request(
+			reqwest::Method::POST,
+			&format!("/repos/{}/{}/statuses/{}", owner, repo, commit_sha),
+		)
+		.json(&serde_json::json!({
+			"state": status_state(state),
+			"context": "meow-coverage/coverage",
+			"description": description,
+		}))
+		.send()
+		.await?
+		.error_for_status()?;
+
+		Ok(())
+	}
+
+	async fn list_repository_teams(
+		&self,
+		owner: &str,
+		repo: &str,
+	) -> Result<Vec<String>, MeowCoverageError> {
+		let teams: Vec<Team> = self
+			.request(reqwest::Method::GET, &format!("/repos/{}/{}/teams", owner, repo))
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		Ok(teams.into_iter().map(|team| team.name).collect())
+	}
+
+	async fn find_open_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		marker: &str,
+	) -> Result<Option<Issue>, MeowCoverageError> {
+		for page in 1.. {
+			let issues: Vec<IssueResponse> = self
+				.request(reqwest::Method::GET, &format!("/repos/{}/{}/issues", owner, repo))
+				.query(&[
+					("state", "open"),
+					("type", "issues"),
+					("limit", "50"),
+					("page", &page.to_string()),
+				])
+				.send()
+				.await?
+				.error_for_status()?
+				.json()
+				.await?;
+
+			if issues.is_empty() {
+				break;
+			}
+			if let Some(issue) = issues.into_iter().find(|issue| issue.body.contains(marker)) {
+				return Ok(Some(Issue { number: issue.number, body: issue.body }));
+			}
+		}
+
+		Ok(None)
+	}
+
+	async fn create_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		title: &str,
+		body: &str,
+		labels: &[String],
+	) -> Result<(), MeowCoverageError> {
+		if !labels.is_empty() {
+			#[allow(clippy::print_stderr)]
+			{
+				eprintln!(
+					"Forgejo issues are labelled by label ID, not adding labels to the issue"
+				);
+			}
+		}
+
+		self.request(reqwest::Method::POST, &format!("/repos/{}/{}/issues", owner, repo))
+			.json(&serde_json::json!({ "title": title, "body": body }))
+			.send()
+			.await?
+			.error_for_status()?;
+
+		Ok(())
+	}
+
+	async fn close_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		number: u64,
+		comment: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.request(
+			reqwest::Method::POST,
+			&format!("/repos/{}/{}/issues/{}/comments", owner, repo, number),
+		)
+		.json(&serde_json::json!({ "body": comment }))
+		.send()
+		.await?
+		.error_for_status()?;
+
+		self.request(
+			reqwest::Method::PATCH,
+			&format!("/repos/{}/{}/issues/{}", owner, repo, number),
+		)
+		.json(&serde_json::json!({ "state": "closed" }))
+		.send()
+		.await?
+		.error_for_status()?;
+
+		Ok(())
+	}
+
+	fn pull_request_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		let path_hash = hex::encode(Sha1::digest(path));
+		format!(
+			"{}/{}/{}/pulls/{}/files#diff-{}{}",
+			self.base_url,
+			owner,
+			repo,
+			pr_number,
+			path_hash,
+			match line {
+				Some((start_line, _)) => format!("R{}", start_line),
+				None => String::new(),
+			}
+		)
+	}
+
+	fn commit_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		format!(
+			"{}/{}/{}/src/commit/{}/{}{}",
+			self.base_url,
+			owner,
+			repo,
+			commit_sha,
+			path,
+			match line {
+				Some((start_line, Some(end_line))) => format!("#L{}-L{}", start_line, end_line),
+				Some((line, None)) => format!("#L{}", line),
+				None => String::new(),
+			}
+		)
+	}
+}
+
+/// Payload of a review with a single comment on the new version of the file,
+/// Forgejo anchors it to the final line of the range
+fn review_payload(commit_id: &str, path: &str, final_line: u32, body: &str) -> serde_json::Value {
+	serde_json::json!({
+		"commit_id": commit_id,
+		"event": "COMMENT",
+		"comments": [{
+			"path": path,
+			"body": body,
+			"new_position": final_line,
+		}],
+	})
+}
+
+/// Forgejo name of a commit status state
+fn status_state(state: CommitStatusState) -> &'static str {
+	match state {
+		CommitStatusState::Success => "success",
+		CommitStatusState::Failure => "failure",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{review_payload, status_state, ForgejoProvider};
+	use crate::provider::{CommitStatusState, VcsProvider};
+
+	#[test]
+	fn test_review_payload() {
+		assert_eq!(
+			review_payload("abcd", "src/lib.rs", 12, "Untested"),
+			serde_json::json!({
+				"commit_id": "abcd",
+				"event": "COMMENT",
+				"comments": [{ "path": "src/lib.rs", "body": "Untested", "new_position": 12 }],
+			})
+		);
+		assert_eq!(status_state(CommitStatusState::Success), "success");
+		assert_eq!(status_state(CommitStatusState::Failure), "failure");
+	}
+
+	#[test]
+	fn test_links() {
+		let provider = ForgejoProvider::new("https://codeberg.org/", String::new());
+		assert_eq!(
+			provider.pull_request_file_link("famedly", "meow", 7, "src/lib.rs", Some((3, Some(5)))),
+			"https://codeberg.org/famedly/meow/pulls/7/files#diff-b24749917179fb5e3e613ed2a703fcdcc6cdf9daR3"
+		);
+		assert_eq!(
+			provider.pull_request_file_link("famedly", "meow", 7, "src/lib.rs", None),
+			"https://codeberg.org/famedly/meow/pulls/7/files#diff-b24749917179fb5e3e613ed2a703fcdcc6cdf9da"
+		);
+		assert_eq!(
+			provider.commit_file_link("famedly", "meow", "abcd", "src/lib.rs", Some((3, Some(5)))),
+			"https://codeberg.org/famedly/meow/src/commit/abcd/src/lib.rs#L3-L5"
+		);
+		assert_eq!(
+			provider.commit_file_link("famedly", "meow", "abcd", "src/lib.rs", Some((3, None))),
+			"https://codeberg.org/famedly/meow/src/commit/abcd/src/lib.rs#L3"
+		);
+		assert_eq!(
+			provider.commit_file_link("famedly", "meow", "abcd", "src/lib.rs", None),
+			"https://codeberg.org/famedly/meow/src/commit/abcd/src/lib.rs"
+		);
+	}
+}
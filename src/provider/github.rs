@@ -0,0 +1,551 @@
+//! [VcsProvider] implementation for GitHub
+
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Arc,
+};
+
+use async_trait::async_trait;
+use hyper::StatusCode;
+use octocrab::{
+	models::{IssueState, StatusState},
+	params::{self, repos::Reference},
+	Octocrab,
+};
+
+use super::{
+	split_unified_diff, CommitInfo, CommitStatusState, Issue, LineRange, PullRequestFile,
+	PullRequestInfo, RateLimit, RepoFile, RepositoryAccess, VcsProvider,
+};
+use crate::{
+	coverage::{
+		helpers::path_sha256,
+		html::{make_blob_link, make_commit_link, make_pull_link},
+	},
+	github_api::{
+		create_review_comment, get_blob, get_comparison_diff, get_file_sha, get_pull_request_diff,
+		get_recursive_tree, is_not_found, list_commit_comments, list_repository_teams,
+	},
+	tracking::author,
+	MeowCoverageError,
+};
+
+/// Number of files after which GitHub truncates the file list of a comparison
+const COMPARISON_FILES_LIMIT: usize = 300;
+
+/// GitHub provider backed by an [Octocrab] client
+#[derive(Debug, Clone)]
+pub struct GitHubProvider {
+	/// Client used for all requests
+	client: Arc<Octocrab>,
+}
+
+impl GitHubProvider {
+	/// Create a new [GitHubProvider]
+	#[must_use]
+	pub fn new(client: Arc<Octocrab>) -> Self {
+		Self { client }
+	}
+
+	/// Access the underlying [Octocrab] client
+	#[must_use]
+	pub fn client(&self) -> &Octocrab {
+		&self.client
+	}
+}
+
+#[async_trait]
+impl VcsProvider for GitHubProvider {
+	async fn get_pull_request(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<PullRequestInfo, MeowCoverageError> {
+		let pull_request = self.client.pulls(owner, repo).get(pr_number).await?;
+
+		Ok(PullRequestInfo {
+			draft: pull_request.draft.unwrap_or(false),
+			labels: pull_request
+				.labels
+				.unwrap_or_default()
+				.into_iter()
+				.map(|label| label.name)
+				.collect(),
+			head_sha: pull_request.head.sha,
+		})
+	}
+
+	async fn list_pull_request_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<Vec<PullRequestFile>, MeowCoverageError> {
+		let page = self.client.pulls(owner, repo).list_files(pr_number).await?;
+
+		let mut files: Vec<_> = self
+			.client
+			.all_pages(page)
+			.await?
+			.into_iter()
+			.map(|file_diff| PullRequestFile {
+				filename: file_diff.filename,
+				previous_filename: file_diff.previous_filename,
+				patch: file_diff.patch,
+			})
+			.collect();
+
+		// The files API omits the patch of large files, the full diff of the PR
+		// still includes them (unless it is too large as a whole)
+		if files.iter().any(|file| file.patch.is_none()) {
+			let full_patches =
+				match get_pull_request_diff(&self.client, owner, repo, pr_number).await? {
+					Some(diff) => split_unified_diff(&diff)
+						.into_iter()
+						.filter_map(|file| file.patch.map(|patch| (file.filename, patch)))
+						.collect(),
+					None => HashMap::new(),
+				};
+
+			for file in files.iter_mut().filter(|file| file.patch.is_none()) {
+				file.patch = full_patches.get(&file.filename).cloned();
+			}
+		}
+
+		Ok(files)
+	}
+
+	async fn create_pull_request_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		let comment = self.client.issues(owner, repo).create_comment(pr_number, body).await?;
+		Ok(Some(String::from(comment.html_url)))
+	}
+
+	async fn create_review_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		commit_id: &str,
+		path: &str,
+		first_line: u32,
+		final_line: u32,
+		body: &str,
+	) -> Result<(), MeowCoverageError> {
+		create_review_comment(
+			&self.client,
+			owner,
+			repo,
+			pr_number,
+			commit_id,
+			path,
+			first_line,
+			final_line,
+			body,
+		)
+		.await?;
+		Ok(())
+	}
+
+	async fn create_commit_comment(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		body: &str,
+	) -> Result<Option<String>, MeowCoverageError> {
+		let comment =
+			self.client.commits(owner, repo).create_comment(commit_sha, body).send().await?;
+		Ok(Some(String::from(comment.html_url)))
+	}
+
+	async fn get_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+	) -> Result<Option<RepoFile>, MeowCoverageError> {
+		let reference = Reference::Branch(String::from(branch));
+		let sha = match get_file_sha(&self.client, owner, repo, reference, path).await {
+			Ok(sha) => sha,
+			Err(error) if is_not_found(&error) => return Ok(None),
+			Err(octocrab::Error::GitHub { source, .. }) if source.message == "Bad credentials" => {
+				return Err(MeowCoverageError::MissingAccessToCoverageRepo)
+			}
+			Err(error) => return Err(error.into()),
+		};
+
+		// The content is fetched by the SHA it is updated with, so an update of
+		// the branch in between fails the update as a conflict
+		let body = get_blob(&self.client, owner, repo, &sha).await?.into_body();
+		let content = hyper::body::to_bytes(body).await?.to_vec();
+
+		Ok(Some(RepoFile { content, sha }))
+	}
+
+	async fn get_file_at_commit(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+	) -> Result<Option<Vec<u8>>, MeowCoverageError> {
+		let response = self
+			.client
+			.repos(owner, repo)
+			.raw_file(Reference::Commit(String::from(commit_sha)), path)
+			.await?;
+		if response.status() == StatusCode::NOT_FOUND {
+			return Ok(None);
+		}
+
+		let body = octocrab::map_github_error(response).await?.into_body();
+		Ok(Some(hyper::body::to_bytes(body).await?.to_vec()))
+	}
+
+	async fn put_file(
+		&self,
+		owner: &str,
+		repo: &str,
+		branch: &str,
+		path: &str,
+		message: &str,
+		content: Vec<u8>,
+		sha: Option<String>,
+	) -> Result<(), MeowCoverageError> {
+		let result = match sha {
+			Some(sha) => {
+				self.client
+					.repos(owner, repo)
+					.update_file(path, message, content, sha)
+					.branch(branch)
+					.author(author())
+					.commiter(author())
+					.send()
+					.await
+			}
+			None => {
+				self.client
+					.repos(owner, repo)
+					.create_file(path, message, content)
+					.branch(branch)
+					.author(author())
+					.commiter(author())
+					.send()
+					.await
+			}
+		};
+
+		// GitHub reports a changed file as `409` ("does not match") and a file
+		// created in the meantime as `422` ("sha wasn't supplied")
+		match result {
+			Ok(_) => Ok(()),
+			Err(octocrab::Error::GitHub { source, .. })
+				if source.message.contains("does not match")
+					|| source.message.contains("wasn't supplied") =>
+			{
+				Err(MeowCoverageError::FileConflict(String::from(path)))
+			}
+			Err(error) => Err(error.into()),
+		}
+	}
+
+	async fn dispatch_workflow(
+		&self,
+		owner: &str,
+		repo: &str,
+		workflow: &str,
+		branch: &str,
+		inputs: serde_json::Value,
+	) -> Result<(), MeowCoverageError> {
+		self.client
+			.actions()
+			.create_workflow_dispatch(owner, repo, workflow, branch)
+			.inputs(inputs)
+			.send()
+			.await?;
+		Ok(())
+	}
+
+	#[allow(clippy::print_stderr)]
+	async fn list_files(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+	) -> Result<HashSet<String>, MeowCoverageError> {
+		let tree = get_recursive_tree(&self.client, owner, repo, commit_sha).await?;
+		if tree.truncated {
+			eprintln!(
+				"Tree of {}/{}@{} is truncated, some files are missing",
+				owner, repo, commit_sha
+			);
+		}
+
+		Ok(tree
+			.tree
+			.into_iter()
+			.filter(|entry| entry.kind == "blob")
+			.map(|entry| entry.path)
+			.collect())
+	}
+
+	async fn create_commit_status(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		state: CommitStatusState,
+		description: &str,
+	) -> Result<(), MeowCoverageError> {
+		self.client
+			.repos(owner, repo)
+			.create_status(
+				String::from(commit_sha),
+				match state {
+					CommitStatusState::Success => StatusState::Success,
+					CommitStatusState::Failure => StatusState::Failure,
+				},
+			)
+			.context(String::from("meow-coverage/coverage"))
+			.description(String::from(description))
+			.send()
+			.await?;
+
+		Ok(())
+	}
+
+	async fn list_repository_teams(
+		&self,
+		owner: &str,
+		repo: &str,
+	) -> Result<Vec<String>, MeowCoverageError> {
+		Ok(list_repository_teams(&self.client, owner, repo).await?)
+	}
+
+	async fn find_open_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		marker: &str,
+	) -> Result<Option<Issue>, MeowCoverageError> {
+		let page = self
+			.client
+			.issues(owner, repo)
+			.list()
+			.state(params::State::Open)
+			.per_page(100)
+			.send()
+			.await?;
+
+		Ok(self.client.all_pages(page).await?.into_iter().find_map(|issue| {
+			let body = issue.body.filter(|body| body.contains(marker))?;
+			issue.pull_request.is_none().then_some(Issue { number: issue.number, body })
+		}))
+	}
+
+	async fn create_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		title: &str,
+		body: &str,
+		labels: &[String],
+	) -> Result<(), MeowCoverageError> {
+		self.client
+			.issues(owner, repo)
+			.create(title)
+			.body(body)
+			.labels(labels.to_vec())
+			.send()
+			.await?;
+		Ok(())
+	}
+
+	async fn close_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		number: u64,
+		comment: &str,
+	) -> Result<(), MeowCoverageError> {
+		let issues = self.client.issues(owner, repo);
+		issues.create_comment(number, comment).await?;
+		issues.update(number).state(IssueState::Closed).send().await?;
+		Ok(())
+	}
+
+	async fn list_pull_request_comments(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		let page =
+			self.client.issues(owner, repo).list_comments(pr_number).per_page(100).send().await?;
+		Ok(Some(
+			self.client
+				.all_pages(page)
+				.await?
+				.into_iter()
+				.filter_map(|comment| comment.body)
+				.collect(),
+		))
+	}
+
+	async fn list_review_comments(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		let page = self
+			.client
+			.pulls(owner, repo)
+			.list_comments(Some(pr_number))
+			.per_page(100)
+			.send()
+			.await?;
+		Ok(Some(
+			self.client.all_pages(page).await?.into_iter().map(|comment| comment.body).collect(),
+		))
+	}
+
+	async fn list_commit_comments(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+	) -> Result<Option<Vec<String>>, MeowCoverageError> {
+		Ok(Some(list_commit_comments(&self.client, owner, repo, commit_sha).await?))
+	}
+
+	async fn compare_commits(
+		&self,
+		owner: &str,
+		repo: &str,
+		base_sha: &str,
+		head_sha: &str,
+	) -> Result<Option<Vec<PullRequestFile>>, MeowCoverageError> {
+		let comparison =
+			self.client.commits(owner, repo).compare(base_sha, head_sha).send().await?;
+
+		let files: Vec<_> = comparison
+			.files
+			.unwrap_or_default()
+			.into_iter()
+			.map(|file| PullRequestFile {
+				filename: file.filename,
+				previous_filename: file.previous_filename,
+				patch: file.patch,
+			})
+			.collect();
+
+		// The comparison API lists at most 300 files and does not paginate
+		// them, the full diff still includes all of them (unless it is too
+		// large as a whole)
+		if files.len() >= COMPARISON_FILES_LIMIT {
+			if let Some(diff) =
+				get_comparison_diff(&self.client, owner, repo, base_sha, head_sha).await?
+			{
+				return Ok(Some(split_unified_diff(&diff)));
+			}
+		}
+
+		Ok(Some(files))
+	}
+
+	async fn resolve_commit(
+		&self,
+		owner: &str,
+		repo: &str,
+		git_ref: &str,
+	) -> Result<Option<CommitInfo>, MeowCoverageError> {
+		// GitHub reports unknown refs as `422` ("No commit found")
+		let commit = match self.client.commits(owner, repo).get(git_ref).await {
+			Ok(commit) => commit,
+			Err(octocrab::Error::GitHub { source, .. })
+				if source.message.starts_with("No commit found") =>
+			{
+				return Ok(None);
+			}
+			Err(error) => return Err(error.into()),
+		};
+
+		Ok(Some(CommitInfo {
+			timestamp: commit
+				.commit
+				.committer
+				.and_then(|committer| committer.date)
+				.map_or(0, |date| date.timestamp()),
+			sha: commit.sha,
+		}))
+	}
+
+	async fn repository_access(
+		&self,
+		owner: &str,
+		repo: &str,
+	) -> Result<Option<RepositoryAccess>, MeowCoverageError> {
+		// Repositories the token can't read are reported as missing, and
+		// installation tokens (like GitHub Actions tokens) get no permissions
+		match self.client.repos(owner, repo).get().await {
+			Ok(repository) => Ok(Some(RepositoryAccess {
+				read: true,
+				write: repository.permissions.map(|permissions| permissions.push),
+			})),
+			Err(octocrab::Error::GitHub { source, .. }) if source.message == "Not Found" => {
+				Ok(Some(RepositoryAccess { read: false, write: Some(false) }))
+			}
+			Err(error) => Err(error.into()),
+		}
+	}
+
+	async fn rate_limit(&self) -> Result<Option<RateLimit>, MeowCoverageError> {
+		let core = self.client.ratelimit().get().await?.resources.core;
+		Ok(Some(RateLimit {
+			remaining: core.remaining as u64,
+			reset: i64::try_from(core.reset).unwrap_or(i64::MAX),
+		}))
+	}
+
+	fn pull_request_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		pr_number: u64,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		make_pull_link(owner, repo, pr_number, &path_sha256(path), line)
+	}
+
+	fn commit_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		make_commit_link(owner, repo, commit_sha, &path_sha256(path), line)
+	}
+
+	fn blob_file_link(
+		&self,
+		owner: &str,
+		repo: &str,
+		commit_sha: &str,
+		path: &str,
+		line: LineRange,
+	) -> String {
+		make_blob_link(owner, repo, commit_sha, path, line)
+	}
+}
@@ -1,23 +1,44 @@
 //! This module contains shared definitions and helpers for tracking coverage
 //! and constructing centralised visualisations
 
+mod changelog;
+mod digest;
 mod management;
 mod models;
+mod query;
+mod site;
+mod storage;
+mod teams;
 mod visualisation;
 
-use std::{fmt::Display, path::Path};
+use std::path::Path;
 
+pub use changelog::*;
+pub use digest::*;
 pub use management::*;
 pub use models::*;
-use octocrab::models::repos::CommitAuthor;
+use octocrab::{models::repos::CommitAuthor, Octocrab};
+pub use query::*;
+pub use site::*;
+pub use storage::*;
+pub use teams::*;
 
-use crate::{github_api::get_file_sha, MeowCoverageError};
+use crate::{
+	github_api::commit_files,
+	percentage::{Percentage, PercentageFormat},
+	provider::VcsProvider,
+	MeowCoverageError,
+};
 
 /// Constant for the `records` branch
 pub const RECORDS_BRANCH: &str = "records";
 
+/// Path of the [TrackingConfig] on the `records` branch
+pub const TRACKING_CONFIG_PATH: &str = "meowcov.config.json";
+
 /// Builds the [CommitAuthor] used for operations on the centralised coverage
 /// tracking repository
+#[must_use]
 pub fn author() -> CommitAuthor {
 	CommitAuthor {
 		name: String::from("Meow! Coverage"),
@@ -26,23 +47,110 @@ pub fn author() -> CommitAuthor {
 }
 
 /// Make a report path by `owner`, `repo`, and `branch`
+#[must_use]
 pub fn make_report_path(owner: &str, repo: &str, branch: &str) -> String {
 	format!("{}/{}/{}.meowcov.json", owner, repo, branch)
 }
 
-/// Turn an f64 percentage into a u16 percentage
-fn make_percent(percentage: f64) -> i16 {
-	(percentage.clamp(-100_f64, 100_f64) * 100_f64).round().clamp(-10000_f64, 10000_f64) as i16
+/// Fetch the records of `owner`, `repo`, and `branch` from the [RecordStorage]
+/// configured in the tracking repository `coverage_repo_name` (`OWNER/REPO`),
+/// [None] if the branch is not tracked
+pub async fn fetch_records(
+	provider: &dyn VcsProvider,
+	coverage_repo_name: &str,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+) -> Result<Option<BranchCoverageRecordCollection>, MeowCoverageError> {
+	let storage = open_storage(provider, coverage_repo_name).await?;
+	fetch_stored_records(storage.as_ref(), owner, repo, branch).await
+}
+
+/// Fetch the [TrackingConfig] from the `records` branch of the tracking
+/// repository `coverage_repo_name` (`OWNER/REPO`), the default configuration
+/// if there is none
+pub async fn fetch_tracking_config(
+	provider: &dyn VcsProvider,
+	coverage_repo_name: &str,
+) -> Result<TrackingConfig, MeowCoverageError> {
+	match GitStorage::new(provider, coverage_repo_name)?.get(TRACKING_CONFIG_PATH).await? {
+		Some(file) => Ok(serde_json::from_slice(&file.content)?),
+		None => Ok(TrackingConfig::default()),
+	}
+}
+
+/// Open the [RecordStorage] configured in the [TrackingConfig] of the
+/// tracking repository `coverage_repo_name` (`OWNER/REPO`)
+pub async fn open_storage<'a>(
+	provider: &'a dyn VcsProvider,
+	coverage_repo_name: &'a str,
+) -> Result<Box<dyn RecordStorage + 'a>, MeowCoverageError> {
+	let config = fetch_tracking_config(provider, coverage_repo_name).await?;
+	config.storage.open(provider, coverage_repo_name)
 }
 
-/// Rebuild the visualisation for a single project (and the README)
+/// Fetch the records of `owner`, `repo`, and `branch` from a [RecordStorage],
+/// [None] if the branch is not tracked
+pub async fn fetch_stored_records(
+	storage: &dyn RecordStorage,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+) -> Result<Option<BranchCoverageRecordCollection>, MeowCoverageError> {
+	match storage.get(&make_report_path(owner, repo, branch)).await? {
+		Some(file) => Ok(Some(BranchCoverageRecordCollection::from_slice(&file.content)?)),
+		None => Ok(None),
+	}
+}
+
+/// Make a link to the rendered report of `owner`, `repo`, and `branch` in the
+/// centralised coverage tracking repository
+#[must_use]
+pub fn make_report_link(
+	coverage_owner: &str,
+	coverage_repo: &str,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+) -> String {
+	format!(
+		"https://github.com/{}/{}/blob/main/reports/{}/{}/{}.md",
+		coverage_owner, coverage_repo, owner, repo, branch
+	)
+}
+
+/// Make the path of the shields.io endpoint badge of `owner`, `repo`, and
+/// `branch` on the `main` branch of the centralised coverage tracking
+/// repository
+#[must_use]
+pub fn make_badge_path(owner: &str, repo: &str, branch: &str) -> String {
+	format!("badge/{}/{}/{}.json", owner, repo, branch)
+}
+
+/// Read the [TrackingConfig] from the `records` branch checked out at
+/// `records`, the default configuration if there is none
+fn read_config(records: &Path) -> Result<TrackingConfig, MeowCoverageError> {
+	let config_path = records.join(TRACKING_CONFIG_PATH);
+	Ok(match config_path.exists() {
+		true => serde_json::from_reader(std::fs::File::open(config_path)?)?,
+		false => TrackingConfig::default(),
+	})
+}
+
+/// Rebuild the visualisation for a single project (and the README) with the
+/// percentages in `format`, committed with `client`
 pub async fn rebuild(
+	client: &Octocrab,
 	records: &Path,
 	coverage_repo: &str,
 	target_repo: &str,
 	branch: &str,
+	format: PercentageFormat,
 ) -> Result<(), MeowCoverageError> {
 	let branch = branch.trim_start_matches("refs/heads/");
+	sync_records(records).await?;
+	remove_stale_branches(client, records, coverage_repo).await?;
+
 	let (coverage_repo_owner, coverage_repo) =
 		coverage_repo.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
 	let (target_repo_owner, target_repo) =
@@ -54,80 +162,119 @@ pub async fn rebuild(
 		path.push(target_repo);
 		path.push(format!("{}.meowcov.json", branch));
 
-		serde_json::from_reader(std::fs::File::open(path)?)?
+		visualisation::read_record_collection(&path)?
 	};
 
-	let Some(coverage_report) = visualisation::build_coverage_report(target_repo_owner, target_repo, branch, &record_collection) else {
-		return Ok(())
+	let Some(coverage_report) = visualisation::build_coverage_report(
+		target_repo_owner,
+		target_repo,
+		branch,
+		&record_collection,
+		format,
+	) else {
+		return Ok(());
 	};
-	let readme = visualisation::build_readme(records, coverage_repo_owner, coverage_repo)?;
+	let readme = visualisation::build_readme(records, coverage_repo_owner, coverage_repo, format)?;
 
-	let report_path = format!("reports/{}/{}/{}.md", target_repo_owner, target_repo, branch);
+	let report_path = format!("reports/{}/{}/{}", target_repo_owner, target_repo, branch);
 
-	let readme_sha = get_file_sha(
+	let mut files = vec![
+		(String::from("README.md"), Some(readme)),
+		(format!("{}.md", report_path), Some(coverage_report)),
+	];
+	if let Some(heatmap) = visualisation::build_heatmap(&record_collection, format) {
+		files.push((format!("{}.svg", report_path), Some(heatmap)));
+	}
+	let threshold = Percentage::from_f64(read_config(records)?.threshold);
+	if let Some(badge) = visualisation::build_badge(&record_collection, threshold, format) {
+		files.push((make_badge_path(target_repo_owner, target_repo, branch), Some(badge)));
+	}
+	for (owner, repo, comparison) in visualisation::build_branch_comparisons(
+		records,
 		coverage_repo_owner,
 		coverage_repo,
-		octocrab::params::repos::Reference::Branch(String::from("main")),
-		"README.md",
-	)
-	.await?;
-	let other_sha = get_file_sha(
+		format,
+	)? {
+		if owner == target_repo_owner && repo == target_repo {
+			files.push((format!("reports/{}/{}/README.md", owner, repo), Some(comparison)));
+		}
+	}
+
+	commit_files(
+		client,
 		coverage_repo_owner,
 		coverage_repo,
-		octocrab::params::repos::Reference::Branch(String::from("main")),
-		report_path.as_str(),
+		"main",
+		&format!("Update report for {}/{}/{}", target_repo_owner, target_repo, branch),
+		&files,
+		&author(),
 	)
-	.await
-	.ok();
-
-	octocrab::instance()
-		.repos(coverage_repo_owner, coverage_repo)
-		.update_file("README.md", "Update README", readme.as_bytes(), readme_sha)
-		.branch("main")
-		.author(author())
-		.commiter(author())
-		.send()
-		.await?;
-	match other_sha {
-		Some(sha) => {
-			octocrab::instance()
-				.repos(coverage_repo_owner, coverage_repo)
-				.update_file(
-					report_path.as_str(),
-					&format!("Update report for {}/{}/{}", target_repo_owner, target_repo, branch),
-					coverage_report.as_bytes(),
-					sha,
-				)
-				.branch("main")
-				.author(author())
-				.commiter(author())
-				.send()
-				.await?;
-		}
-		None => {
-			octocrab::instance()
-				.repos(coverage_repo_owner, coverage_repo)
-				.create_file(
-					report_path.as_str(),
-					&format!("Create report for {}/{}/{}", target_repo_owner, target_repo, branch),
-					coverage_report.as_bytes(),
-				)
-				.branch("main")
-				.author(author())
-				.commiter(author())
-				.send()
-				.await?;
-		}
-	}
+	.await?;
 
 	Ok(())
 }
 
-/// Wrapper for displaying an i16 percent correctly
-struct PercentWrapper(i16);
+/// Rebuild the visualisations of all branches in the records (and the README)
+/// with the percentages in `format` in a single commit, committed with `client`
+pub async fn rebuild_all(
+	client: &Octocrab,
+	records: &Path,
+	coverage_repo: &str,
+	format: PercentageFormat,
+) -> Result<(), MeowCoverageError> {
+	sync_records(records).await?;
+	remove_stale_branches(client, records, coverage_repo).await?;
+
+	let (coverage_repo_owner, coverage_repo) =
+		coverage_repo.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
 
-impl Display for PercentWrapper {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		f.write_fmt(format_args!("{:.2}", f64::from(self.0) / 100_f64))
+	let mut files = vec![(
+		String::from("README.md"),
+		Some(visualisation::build_readme(records, coverage_repo_owner, coverage_repo, format)?),
+	)];
+	let threshold = Percentage::from_f64(read_config(records)?.threshold);
+	let mut reports = 0;
+	for branch in visualisation::walk_records(records)? {
+		if let Some(coverage_report) = visualisation::build_coverage_report(
+			&branch.owner,
+			&branch.repo,
+			&branch.branch,
+			&branch.collection,
+			format,
+		) {
+			let report_path = format!("reports/{}/{}/{}", branch.owner, branch.repo, branch.branch);
+			files.push((format!("{}.md", report_path), Some(coverage_report)));
+			if let Some(heatmap) = visualisation::build_heatmap(&branch.collection, format) {
+				files.push((format!("{}.svg", report_path), Some(heatmap)));
+			}
+			if let Some(badge) = visualisation::build_badge(&branch.collection, threshold, format) {
+				files.push((
+					make_badge_path(&branch.owner, &branch.repo, &branch.branch),
+					Some(badge),
+				));
+			}
+			reports += 1;
+		}
+	}
+	for (owner, repo, comparison) in visualisation::build_branch_comparisons(
+		records,
+		coverage_repo_owner,
+		coverage_repo,
+		format,
+	)? {
+		files.push((format!("reports/{}/{}/README.md", owner, repo), Some(comparison)));
 	}
+
+	commit_files(
+		client,
+		coverage_repo_owner,
+		coverage_repo,
+		"main",
+		&format!("Rebuild {} reports", reports),
+		&files,
+		&author(),
+	)
+	.await?;
+
+	Ok(())
 }
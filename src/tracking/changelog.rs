@@ -0,0 +1,237 @@
+//! Release-note style summaries of the coverage changes of a tracked branch
+//! between two releases
+
+use super::{fetch_stored_records, BranchCoverageRecord, RecordStorage};
+use crate::{
+	coverage::diff::CoverageDiff,
+	percentage::{Percentage, PercentageFormat},
+	provider::VcsProvider,
+	MeowCoverageError,
+};
+
+/// Number of files listed as the biggest movers
+const CHANGELOG_MOVERS: usize = 10;
+
+/// Summarise the coverage changes of `branch` of `repo_name` (`OWNER/REPO`)
+/// between the tags `from_tag` and `to_tag` as markdown with the percentages in
+/// `format`, from the records read from `storage`. A tag is matched to the
+/// record of its commit, or to the newest record before it if its commit was
+/// not recorded (or the record was dropped by the retention)
+pub async fn changelog(
+	provider: &dyn VcsProvider,
+	storage: &dyn RecordStorage,
+	repo_name: &str,
+	branch: &str,
+	from_tag: &str,
+	to_tag: &str,
+	format: PercentageFormat,
+) -> Result<String, MeowCoverageError> {
+	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+	let collection =
+		fetch_stored_records(storage, owner, repo, branch).await?.ok_or_else(|| {
+			MeowCoverageError::BranchNotTracked(format!("{} ({})", repo_name, branch))
+		})?;
+
+	let mut records = Vec::with_capacity(2);
+	for tag in [from_tag, to_tag] {
+		let commit = provider
+			.resolve_commit(owner, repo, tag)
+			.await?
+			.ok_or_else(|| MeowCoverageError::RefNotFound(format!("{} ({})", repo_name, tag)))?;
+		let record = collection
+			.record_of_commit(&commit.sha)
+			.or_else(|| collection.record_at(commit.timestamp))
+			.ok_or_else(|| {
+				MeowCoverageError::CommitNotRecorded(format!("{} ({}@{})", repo_name, branch, tag))
+			})?;
+		records.push(record);
+	}
+
+	Ok(build_changelog(repo_name, branch, (from_tag, records[0]), (to_tag, records[1]), format))
+}
+
+/// Format an optional change of percentage coverage in `format`
+fn format_delta(delta: Option<f64>, format: PercentageFormat) -> String {
+	match delta {
+		Some(delta) => format!("{:+}%", Percentage::from_f64(delta).display_with(format)),
+		None => String::from("N/A"),
+	}
+}
+
+/// Build the markdown changelog between the records of the `from` and `to`
+/// tags with the percentages in `format`
+fn build_changelog(
+	repo_name: &str,
+	branch: &str,
+	(from_tag, from): (&str, &BranchCoverageRecord),
+	(to_tag, to): (&str, &BranchCoverageRecord),
+	format: PercentageFormat,
+) -> String {
+	let diff = CoverageDiff::from_records(from, to);
+	let mut markdown = format!(
+		"## Coverage Changes From {} to {}\n\nCoverage of `{}` (`{}`): {}% → {}% ({})\n",
+		from_tag,
+		to_tag,
+		repo_name,
+		branch,
+		from.percentage.display_with(format),
+		to.percentage.display_with(format),
+		format_delta(diff.delta(), format)
+	);
+	if let (Some(from_lines), Some(to_lines)) = (from.lines, to.lines) {
+		let delta = match to_lines.checked_sub(from_lines) {
+			Some(added) => format!("+{}", added),
+			None => format!("-{}", from_lines - to_lines),
+		};
+		markdown.push_str(&format!(
+			"\nInstrumented lines: {} → {} ({})\n",
+			from_lines, to_lines, delta
+		));
+	}
+
+	if from.files.is_none() || to.files.is_none() {
+		markdown.push_str(
+			"\nPer-file changes are unavailable, the records of the releases keep no files.\n",
+		);
+		return markdown;
+	}
+
+	let added = diff.files.iter().filter(|file| file.old_percentage.is_none()).collect::<Vec<_>>();
+	if !added.is_empty() {
+		markdown.push_str("\n### Added Files\n\n");
+		for file in added {
+			markdown.push_str(&format!(
+				"- `{}` ({}%)\n",
+				file.filename,
+				Percentage::from_f64(file.new_percentage.unwrap_or_default()).display_with(format)
+			));
+		}
+	}
+
+	let removed =
+		diff.files.iter().filter(|file| file.new_percentage.is_none()).collect::<Vec<_>>();
+	if !removed.is_empty() {
+		markdown.push_str("\n### Removed Files\n\n");
+		for file in removed {
+			markdown.push_str(&format!(
+				"- `{}` (was {}%)\n",
+				file.filename,
+				Percentage::from_f64(file.old_percentage.unwrap_or_default()).display_with(format)
+			));
+		}
+	}
+
+	let mut movers = diff
+		.files
+		.iter()
+		.filter_map(|file| Some((file, file.delta().filter(|delta| *delta != 0_f64)?)))
+		.collect::<Vec<_>>();
+	movers.sort_by(|(l, l_delta), (r, r_delta)| {
+		r_delta.abs().total_cmp(&l_delta.abs()).then_with(|| l.filename.cmp(&r.filename))
+	});
+	if !movers.is_empty() {
+		markdown.push_str(&format!(
+			"\n### Biggest Movers\n\n| File | {} | {} | Delta |\n| --- | --- | --- | --- |\n",
+			from_tag, to_tag
+		));
+		for (file, delta) in movers.into_iter().take(CHANGELOG_MOVERS) {
+			markdown.push_str(&format!(
+				"| `{}` | {}% | {}% | {} |\n",
+				file.filename,
+				Percentage::from_f64(file.old_percentage.unwrap_or_default()).display_with(format),
+				Percentage::from_f64(file.new_percentage.unwrap_or_default()).display_with(format),
+				format_delta(Some(delta), format)
+			));
+		}
+	}
+
+	markdown
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::{BTreeMap, HashMap};
+
+	use super::changelog;
+	use crate::{
+		percentage::{Percentage, PercentageFormat},
+		provider::{fake::FakeProvider, CommitInfo, RepoFile},
+		tracking::{
+			BranchCoverageRecord, BranchCoverageRecordCollection, FileCoverageRecord, GitStorage,
+			Team,
+		},
+	};
+
+	#[tokio::test]
+	async fn test_changelog() {
+		let record =
+			|timestamp, basis_points, commit: &str, files: Vec<(&str, f64)>| BranchCoverageRecord {
+				timestamp,
+				percentage: Percentage::from_basis_points(basis_points),
+				commit: Some(String::from(commit)),
+				lines: Some(100),
+				files: Some(
+					files
+						.into_iter()
+						.map(|(path, percentage)| {
+							(
+								String::from(path),
+								FileCoverageRecord::new(percentage, 10, Vec::new()),
+							)
+						})
+						.collect(),
+				),
+				files_gz: None,
+				labels: BTreeMap::new(),
+				metadata: None,
+			};
+		let mut collection = BranchCoverageRecordCollection::new(Team::Workflow);
+		collection.records = vec![
+			record(1_000, 5000, "aaaa", vec![("src/lib.rs", 50_f64), ("src/old.rs", 40_f64)]),
+			record(2_000, 6000, "bbbb", vec![("src/lib.rs", 60_f64)]),
+			record(
+				3_000,
+				7000,
+				"cccc",
+				vec![("src/lib.rs", 80_f64), ("src/new.rs", 90_f64), ("src/main.rs", 70_f64)],
+			),
+		];
+		collection.records[0]
+			.files
+			.as_mut()
+			.unwrap()
+			.insert(String::from("src/main.rs"), FileCoverageRecord::new(75_f64, 10, Vec::new()));
+
+		let provider = FakeProvider {
+			refs: HashMap::from([
+				(String::from("v1.0"), CommitInfo { sha: String::from("aaaa"), timestamp: 1_000 }),
+				// Not recorded, matched to the newest record before it
+				(String::from("v1.1"), CommitInfo { sha: String::from("dddd"), timestamp: 3_500 }),
+			]),
+			..Default::default()
+		};
+		provider.files.lock().unwrap().insert(
+			String::from("famedly/coverage/records/famedly/meow/main.meowcov.json"),
+			RepoFile { content: serde_json::to_vec(&collection).unwrap(), sha: String::from("1") },
+		);
+		let storage = GitStorage::new(&provider, "famedly/coverage").unwrap();
+		let format = PercentageFormat::default();
+
+		let markdown =
+			changelog(&provider, &storage, "famedly/meow", "main", "v1.0", "v1.1", format)
+				.await
+				.unwrap();
+		assert!(markdown.starts_with(
+			"## Coverage Changes From v1.0 to v1.1\n\nCoverage of `famedly/meow` (`main`): 50.00% → 70.00% (+20.00%)\n"
+		));
+		assert!(markdown.contains("\n### Added Files\n\n- `src/new.rs` (90.00%)\n"));
+		assert!(markdown.contains("\n### Removed Files\n\n- `src/old.rs` (was 40.00%)\n"));
+		assert!(markdown.contains(
+			"| File | v1.0 | v1.1 | Delta |\n| --- | --- | --- | --- |\n| `src/lib.rs` | 50.00% | 80.00% | +30.00% |\n| `src/main.rs` | 75.00% | 70.00% | -5.00% |\n"
+		));
+
+		assert!(changelog(&provider, &storage, "famedly/meow", "main", "v0.9", "v1.1", format)
+			.await
+			.is_err());
+	}
+}
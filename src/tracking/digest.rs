@@ -0,0 +1,338 @@
+//! Module for building periodic digests of the coverage changes of all
+//! tracked branches
+
+use std::path::Path;
+
+use itertools::Itertools;
+use octocrab::Octocrab;
+use time::OffsetDateTime;
+
+use super::{
+	author, make_report_link, read_config, sync_records,
+	visualisation::{walk_records, BranchRecords},
+	BranchCoverageRecordCollection,
+};
+use crate::{
+	github_api::commit_files,
+	percentage::{Percentage, PercentageFormat},
+	MeowCoverageError,
+};
+
+/// Number of branches listed as the biggest gains and regressions
+const DIGEST_LIMIT: usize = 5;
+
+/// Period covered by a digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DigestPeriod {
+	/// The last 7 days
+	Weekly,
+	/// The last 30 days
+	Monthly,
+}
+
+impl DigestPeriod {
+	/// Duration of the period
+	#[must_use]
+	pub fn duration(self) -> time::Duration {
+		match self {
+			Self::Weekly => time::Duration::days(7),
+			Self::Monthly => time::Duration::days(30),
+		}
+	}
+
+	/// Name of the period
+	#[must_use]
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Weekly => "weekly",
+			Self::Monthly => "monthly",
+		}
+	}
+}
+
+/// Where a digest is published
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DigestTarget {
+	/// Commit it to `digests/` on the `main` branch of the tracking repository
+	Commit,
+	/// Open an issue in the tracking repository
+	Issue,
+}
+
+/// Coverage change of a branch over the period of a digest
+struct BranchChange<'a> {
+	/// Records of the branch
+	branch: &'a BranchRecords,
+	/// Coverage at the start of the period
+	before: Percentage,
+	/// Coverage at the end of the period
+	after: Percentage,
+}
+
+impl BranchChange<'_> {
+	/// Change of coverage over the period
+	fn delta(&self) -> Percentage {
+		self.after - self.before
+	}
+}
+
+/// Coverage of a collection at the start and end of the period starting at
+/// `period_start_ts`, the start being the newest record before the period (or
+/// the oldest within it). [None] if nothing was recorded in the period
+fn period_change(
+	collection: &BranchCoverageRecordCollection,
+	period_start_ts: i64,
+) -> Option<(Percentage, Percentage)> {
+	let latest = collection.latest()?;
+	if latest.timestamp < period_start_ts {
+		return None;
+	}
+
+	let before = collection
+		.records
+		.iter()
+		.filter(|record| record.timestamp <= period_start_ts)
+		.max_by_key(|record| record.timestamp)
+		.or_else(|| collection.records.iter().min_by_key(|record| record.timestamp))?;
+
+	Some((before.percentage, latest.percentage))
+}
+
+/// Build the list of branches of a digest section with the percentages in
+/// `format`
+fn build_section(
+	title: &str,
+	coverage_repo_owner: &str,
+	coverage_repo: &str,
+	changes: &[&BranchChange<'_>],
+	format: PercentageFormat,
+) -> String {
+	let list = match changes.is_empty() {
+		true => String::from("None\n"),
+		false => changes
+			.iter()
+			.map(|change| {
+				format!(
+					"- [{}/{} ({})]({}): {}% → {}% ({}%)\n",
+					change.branch.owner,
+					change.branch.repo,
+					change.branch.branch,
+					make_report_link(
+						coverage_repo_owner,
+						coverage_repo,
+						&change.branch.owner,
+						&change.branch.repo,
+						&change.branch.branch
+					),
+					change.before.display_with(format),
+					change.after.display_with(format),
+					change.delta().display_with(format)
+				)
+			})
+			.collect(),
+	};
+
+	format!("## {}\n\n{}\n", title, list)
+}
+
+/// Build the markdown digest of the coverage changes of `branches` over the
+/// `period` ending at `now`: the biggest gains and regressions, and branches
+/// crossing the `threshold`, with the percentages in `format`
+fn build_digest(
+	branches: &[BranchRecords],
+	period: DigestPeriod,
+	threshold: Percentage,
+	now: OffsetDateTime,
+	coverage_repo_owner: &str,
+	coverage_repo: &str,
+	format: PercentageFormat,
+) -> String {
+	let period_start = now - period.duration();
+	let changes = branches
+		.iter()
+		.filter_map(|branch| {
+			let (before, after) = period_change(&branch.collection, period_start.unix_timestamp())?;
+			Some(BranchChange { branch, before, after })
+		})
+		.sorted_by(|l, r| {
+			(&l.branch.owner, &l.branch.repo, &l.branch.branch).cmp(&(
+				&r.branch.owner,
+				&r.branch.repo,
+				&r.branch.branch,
+			))
+		})
+		.collect::<Vec<_>>();
+
+	let gains = changes
+		.iter()
+		.filter(|change| change.delta() > Percentage::ZERO)
+		.sorted_by_key(|change| std::cmp::Reverse(change.delta()))
+		.take(DIGEST_LIMIT)
+		.collect::<Vec<_>>();
+	let regressions = changes
+		.iter()
+		.filter(|change| change.delta() < Percentage::ZERO)
+		.sorted_by_key(|change| change.delta())
+		.take(DIGEST_LIMIT)
+		.collect::<Vec<_>>();
+	let above = changes
+		.iter()
+		.filter(|change| change.before <= threshold && change.after > threshold)
+		.collect::<Vec<_>>();
+	let below = changes
+		.iter()
+		.filter(|change| change.before > threshold && change.after <= threshold)
+		.collect::<Vec<_>>();
+
+	format!(
+		"# Coverage Digest {} to {}\n\n{} of {} tracked branches were updated in this period.\n\n{}{}{}{}",
+		period_start.date(),
+		now.date(),
+		changes.len(),
+		branches.len(),
+		build_section("Biggest Gains", coverage_repo_owner, coverage_repo, &gains, format),
+		build_section(
+			"Biggest Regressions",
+			coverage_repo_owner,
+			coverage_repo,
+			&regressions,
+			format
+		),
+		build_section(
+			&format!("Newly Above {}%", threshold.display_with(format)),
+			coverage_repo_owner,
+			coverage_repo,
+			&above,
+			format
+		),
+		build_section(
+			&format!("Newly At or Below {}%", threshold.display_with(format)),
+			coverage_repo_owner,
+			coverage_repo,
+			&below,
+			format
+		),
+	)
+}
+
+/// Build a digest of the coverage changes of the records at `records` over
+/// `period` with the percentages in `format`, and publish it to the tracking
+/// repository with `client`
+pub async fn digest(
+	client: &Octocrab,
+	records: &Path,
+	coverage_repo: &str,
+	period: DigestPeriod,
+	target: DigestTarget,
+	format: PercentageFormat,
+) -> Result<(), MeowCoverageError> {
+	let (coverage_repo_owner, coverage_repo) =
+		coverage_repo.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+	sync_records(records).await?;
+	let config = read_config(records)?;
+	let now = OffsetDateTime::now_utc();
+
+	let body = build_digest(
+		&walk_records(records)?,
+		period,
+		Percentage::from_f64(config.threshold),
+		now,
+		coverage_repo_owner,
+		coverage_repo,
+		format,
+	);
+	let title = format!("Coverage digest ({}, {})", period.name(), now.date());
+
+	match target {
+		DigestTarget::Commit => {
+			commit_files(
+				client,
+				coverage_repo_owner,
+				coverage_repo,
+				"main",
+				&format!("Add {}", title.to_lowercase()),
+				&[(format!("digests/{}-{}.md", now.date(), period.name()), Some(body))],
+				&author(),
+			)
+			.await?;
+		}
+		DigestTarget::Issue => {
+			client
+				.issues(coverage_repo_owner, coverage_repo)
+				.create(title)
+				.body(body)
+				.send()
+				.await?;
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+
+	use time::OffsetDateTime;
+
+	use super::{build_digest, DigestPeriod};
+	use crate::{
+		percentage::{Percentage, PercentageFormat},
+		tracking::{
+			visualisation::BranchRecords, BranchCoverageRecord, BranchCoverageRecordCollection,
+			Team, RECORD_FORMAT_VERSION,
+		},
+	};
+
+	#[test]
+	fn test_build_digest() {
+		let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+		let day = 60 * 60 * 24;
+		let branch = |name: &str, history: &[(i64, i16)]| BranchRecords {
+			owner: String::from("famedly"),
+			repo: String::from("meow"),
+			branch: String::from(name),
+			collection: BranchCoverageRecordCollection {
+				team: Team::Other,
+				records: history
+					.iter()
+					.map(|(days_ago, percentage)| BranchCoverageRecord {
+						timestamp: now.unix_timestamp() - days_ago * day,
+						percentage: Percentage::from_basis_points(*percentage),
+						commit: None,
+						lines: None,
+						files: None,
+						files_gz: None,
+						labels: BTreeMap::new(),
+						metadata: None,
+					})
+					.collect(),
+				retention: None,
+				flags: BTreeMap::new(),
+				format_version: RECORD_FORMAT_VERSION,
+			},
+		};
+		let branches = [
+			branch("gain", &[(10, 7000), (1, 8500)]),
+			branch("drop", &[(8, 8200), (6, 8100), (2, 7500)]),
+			branch("new", &[(3, 6000), (1, 6500)]),
+			branch("old", &[(20, 9000)]),
+		];
+
+		let digest = build_digest(
+			&branches,
+			DigestPeriod::Weekly,
+			Percentage::from_basis_points(8000),
+			now,
+			"famedly",
+			"coverage",
+			PercentageFormat::default(),
+		);
+		assert!(digest.starts_with("# Coverage Digest 2023-11-07 to 2023-11-14\n\n3 of 4 tracked"));
+		assert!(digest.contains("## Biggest Gains\n\n- [famedly/meow (gain)](https://github.com/famedly/coverage/blob/main/reports/famedly/meow/gain.md): 70.00% → 85.00% (15.00%)\n- [famedly/meow (new)]"));
+		assert!(digest.contains("## Biggest Regressions\n\n- [famedly/meow (drop)](https://github.com/famedly/coverage/blob/main/reports/famedly/meow/drop.md): 82.00% → 75.00% (-7.00%)\n\n"));
+		assert!(digest.contains("## Newly Above 80.00%\n\n- [famedly/meow (gain)]"));
+		assert!(digest.contains("## Newly At or Below 80.00%\n\n- [famedly/meow (drop)]"));
+		assert!(!digest.contains("(old)"));
+	}
+}
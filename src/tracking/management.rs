@@ -1,49 +1,695 @@
 //! This module contains functions for managing repositories in the centralised
 //! coverage tracking records
 
-use octocrab::params::repos::Reference;
+use std::{
+	collections::{BTreeMap, HashSet},
+	path::Path,
+};
 
-use super::{author, make_report_path, RECORDS_BRANCH};
-use crate::{github_api::get_file_sha, MeowCoverageError};
+use globset::GlobSet;
+use itertools::Itertools;
+use octocrab::Octocrab;
+use time::OffsetDateTime;
 
-/// Remove a branch of a repository from the centralised coverage tracking
-/// records
-pub async fn remove_branch_from_tracking(
+use super::{
+	author, fetch_stored_records, fetch_tracking_config, make_badge_path, make_report_path,
+	open_storage, read_config,
+	visualisation::{build_readme_from_entries, walk_records},
+	BranchCoverageRecord, BranchCoverageRecordCollection, RecordMetadata, RecordStorage,
+	StorageConfig, Team, TrackingConfig, RECORDS_BRANCH, TRACKING_CONFIG_PATH,
+};
+use crate::{
+	artifacts,
+	coverage::{lcov::LcovWrapper, options::build_globs},
+	github_api::{
+		commit_files, create_orphan_branch, file_exists, get_branch_head, get_recursive_tree,
+	},
+	metrics,
+	percentage::{Percentage, PercentageFormat},
+	provider::VcsProvider,
+	MeowCoverageError,
+};
+
+/// Path of the workflow rebuilding the reports, dispatched after every
+/// recorded push
+const REBUILD_WORKFLOW_PATH: &str = ".github/workflows/main.yml";
+
+/// Workflow rebuilding the report of a branch from the `records` branch
+const REBUILD_WORKFLOW: &str = r#"name: Rebuild coverage reports
+
+on:
+  workflow_dispatch:
+    inputs:
+      repo-name:
+        description: "Repository of the updated records (OWNER/REPO)"
+        required: true
+      branch:
+        description: "Branch of the updated records"
+        required: true
+
+concurrency:
+  group: rebuild
+
+permissions:
+  contents: write
+
+jobs:
+  rebuild:
+    runs-on: ubuntu-latest
+    steps:
+      - name: Checkout records
+        uses: actions/checkout@v3
+        with:
+          ref: records
+          path: records
+      - name: Rebuild reports
+        uses: famedly/meow-coverage@main
+        with:
+          repo-name: ${{ inputs.repo-name }}
+          branch: ${{ inputs.branch }}
+          coverage-repo: ${{ github.repository }}
+          rebuild-records: records
+"#;
+
+/// Description of the tracking repository, linked from the README
+const DESCRIPTION: &str = "\
+# Coverage Tracking
+
+This repository tracks the code coverage of branches of repositories using
+[Meow! Coverage](https://github.com/famedly/meow-coverage).
+
+- The `records` branch stores the coverage records of every tracked branch at
+  `OWNER/REPO/BRANCH.meowcov.json`, and the configuration of the tracking in
+  `meowcov.config.json`.
+- The `main` branch contains the README summarising all tracked branches and
+  a report of every branch in `reports/`, rebuilt by the workflow in
+  `.github/workflows/main.yml` whenever a record is added.
+
+Repositories record their coverage by running Meow! Coverage on pushes with
+`coverage-repo` set to this repository.
+";
+
+/// Owner, repo, and branch of the record file at `path` on the `records`
+/// branch (`OWNER/REPO/BRANCH.meowcov.json`), [None] for other files
+#[must_use]
+pub fn parse_record_path(path: &str) -> Option<(&str, &str, &str)> {
+	let (owner, rest) = path.split_once('/')?;
+	let (repo, branch) = rest.split_once('/')?;
+	Some((owner, repo, branch.strip_suffix(".meowcov.json")?))
+}
+
+/// Select the tracked branches (owner, repo, and branch) of the record files
+/// at `paths` on the `records` branch whose `OWNER/REPO` matches `repos` and
+/// whose branch matches `branches`, or any branch if [None]
+#[must_use]
+pub fn select_tracked_branches<'a>(
+	paths: impl IntoIterator<Item = &'a str>,
+	repos: &GlobSet,
+	branches: Option<&GlobSet>,
+) -> Vec<(String, String, String)> {
+	paths
+		.into_iter()
+		.filter_map(|path| {
+			let (owner, repo, branch) = parse_record_path(path)?;
+			(repos.is_match(format!("{}/{}", owner, repo))
+				&& branches.map_or(true, |branches| branches.is_match(branch)))
+			.then(|| (String::from(owner), String::from(repo), String::from(branch)))
+		})
+		.sorted()
+		.collect()
+}
+
+/// Paths of the files on `branch` of `owner`/`repo`, [None] if the branch
+/// does not exist
+async fn list_branch_files(
+	client: &Octocrab,
+	owner: &str,
+	repo: &str,
+	branch: &str,
+) -> Result<Option<HashSet<String>>, MeowCoverageError> {
+	let Some(head) = get_branch_head(client, owner, repo, branch).await? else {
+		return Ok(None);
+	};
+	let tree = get_recursive_tree(client, owner, repo, &head).await?;
+	#[allow(clippy::print_stderr)]
+	if tree.truncated {
+		eprintln!("Tree of {}/{}@{} is truncated, some files are missing", owner, repo, branch);
+	}
+
+	Ok(Some(
+		tree.tree
+			.into_iter()
+			.filter(|entry| entry.kind == "blob")
+			.map(|entry| entry.path)
+			.collect(),
+	))
+}
+
+/// Apply `changes` (paths and their new content, [None] to delete them) to the
+/// records in the configured `storage` of `coverage_repo_name` (`OWNER/REPO`):
+/// in a single commit on the `records` branch with `client`, or one by one in
+/// the bucket
+async fn change_records(
+	client: &Octocrab,
+	storage: &StorageConfig,
 	coverage_repo_name: &str,
-	remove_target_repo_name: &str,
-	remove_target_branch: &str,
+	message: &str,
+	changes: &[(String, Option<String>)],
 ) -> Result<(), MeowCoverageError> {
+	match storage {
+		StorageConfig::Git => {
+			let (coverage_repo_owner, coverage_repo) = coverage_repo_name
+				.split_once('/')
+				.ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+			commit_files(
+				client,
+				coverage_repo_owner,
+				coverage_repo,
+				RECORDS_BRANCH,
+				message,
+				changes,
+				&author(),
+			)
+			.await?;
+		}
+		StorageConfig::S3(s3_config) => {
+			let storage = s3_config.open()?;
+			for (path, content) in changes {
+				match content {
+					Some(content) => {
+						let version = storage.get(path).await?.map(|file| file.sha);
+						storage.put(path, message, content.clone().into_bytes(), version).await?;
+					}
+					None => storage.delete(path).await?,
+				}
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Remove the branches of the repositories matching the `repos` globs
+/// (`OWNER/REPO`) whose names match the `branches` globs, or all their
+/// branches if no `branches` are passed, from the centralised coverage
+/// tracking records and their reports and badges from the `main` branch, in a
+/// single commit on each with `client` (or from the configured bucket). The
+/// records are listed with `provider`, returns the removed branches (owner,
+/// repo, and branch)
+pub async fn remove_from_tracking(
+	client: &Octocrab,
+	provider: &dyn VcsProvider,
+	coverage_repo_name: &str,
+	repos: &[String],
+	branches: &[String],
+) -> Result<Vec<(String, String, String)>, MeowCoverageError> {
 	let (coverage_repo_owner, coverage_repo) =
 		coverage_repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
-	let (remove_target_repo_owner, remove_target_repo) =
-		remove_target_repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+	let Some(repos) = build_globs(repos)? else {
+		return Ok(Vec::new());
+	};
+	let branches = build_globs(branches)?;
 
-	let report_path =
-		make_report_path(remove_target_repo_owner, remove_target_repo, remove_target_branch);
+	let config = fetch_tracking_config(provider, coverage_repo_name).await?;
+	let record_files = config.storage.open(provider, coverage_repo_name)?.list().await?;
+	let removed =
+		select_tracked_branches(record_files.iter().map(String::as_str), &repos, branches.as_ref());
+	let message = match removed.as_slice() {
+		[] => return Ok(removed),
+		[(owner, repo, branch)] => format!("Delete report for {}/{} ({})", owner, repo, branch),
+		removed => format!("Delete reports of {} branches", removed.len()),
+	};
 
-	let sha = get_file_sha(
-		coverage_repo_owner,
-		coverage_repo,
-		Reference::Branch(String::from(RECORDS_BRANCH)),
-		&report_path,
+	change_records(
+		client,
+		&config.storage,
+		coverage_repo_name,
+		&message,
+		&removed
+			.iter()
+			.map(|(owner, repo, branch)| (make_report_path(owner, repo, branch), None))
+			.collect::<Vec<_>>(),
 	)
 	.await?;
 
-	octocrab::instance()
-		.repos(coverage_repo_owner, coverage_repo)
-		.delete_file(
-			report_path,
-			format!(
-				"Delete report for {}/{} ({})",
-				remove_target_repo_owner, remove_target_repo, remove_target_branch
-			),
+	let reports = list_branch_files(client, coverage_repo_owner, coverage_repo, "main")
+		.await?
+		.unwrap_or_default();
+	let removed_reports = removed
+		.iter()
+		.flat_map(|(owner, repo, branch)| {
+			[
+				format!("reports/{}/{}/{}.md", owner, repo, branch),
+				make_badge_path(owner, repo, branch),
+			]
+		})
+		.filter(|path| reports.contains(path))
+		.map(|path| (path, None))
+		.collect::<Vec<_>>();
+	if !removed_reports.is_empty() {
+		commit_files(
+			client,
+			coverage_repo_owner,
+			coverage_repo,
+			"main",
+			&message,
+			&removed_reports,
+			&author(),
+		)
+		.await?;
+	}
+
+	Ok(removed)
+}
+
+/// Move the records of `from` of `repo_name` (`OWNER/REPO`) to `to` in a
+/// single commit (or in the configured bucket), merging them with the
+/// records `to` may already have, and move the report and badge of `from` on
+/// the `main` branch until the next rebuild replaces it. The records are read
+/// with `provider` and committed with `client`, returns whether `from` was
+/// tracked
+pub async fn rename_tracked_branch(
+	client: &Octocrab,
+	provider: &dyn VcsProvider,
+	coverage_repo_name: &str,
+	repo_name: &str,
+	from: &str,
+	to: &str,
+) -> Result<bool, MeowCoverageError> {
+	let (coverage_repo_owner, coverage_repo) =
+		coverage_repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+
+	let config = fetch_tracking_config(provider, coverage_repo_name).await?;
+	let storage = config.storage.open(provider, coverage_repo_name)?;
+	let Some(renamed) = fetch_stored_records(storage.as_ref(), owner, repo, from).await? else {
+		return Ok(false);
+	};
+	let collection = match fetch_stored_records(storage.as_ref(), owner, repo, to).await? {
+		Some(mut collection) => {
+			collection.merge_renamed(renamed);
+			collection
+		}
+		None => renamed,
+	};
+
+	let message = format!("Rename {} from {} to {}", repo_name, from, to);
+	change_records(
+		client,
+		&config.storage,
+		coverage_repo_name,
+		&message,
+		&[
+			(make_report_path(owner, repo, to), Some(serde_json::to_string(&collection)?)),
+			(make_report_path(owner, repo, from), None),
+		],
+	)
+	.await?;
+
+	let report_path = |branch| format!("reports/{}/{}/{}.md", owner, repo, branch);
+	let mut moved = Vec::new();
+	for (from_path, to_path) in [
+		(report_path(from), report_path(to)),
+		(make_badge_path(owner, repo, from), make_badge_path(owner, repo, to)),
+	] {
+		if let Some(file) =
+			provider.get_file(coverage_repo_owner, coverage_repo, "main", &from_path).await?
+		{
+			moved.push((to_path, Some(String::from_utf8_lossy(&file.content).into_owned())));
+			moved.push((from_path, None));
+		}
+	}
+	if !moved.is_empty() {
+		commit_files(
+			client,
+			coverage_repo_owner,
+			coverage_repo,
+			"main",
+			&message,
+			&moved,
+			&author(),
+		)
+		.await?;
+	}
+
+	Ok(true)
+}
+
+/// Reconstruct the coverage history of `branch` of `repo_name` (`OWNER/REPO`)
+/// since the unix timestamp `since` from the Lcov artifacts uploaded by its
+/// past push runs (`--upload-artifact`), adding a record on every day without
+/// one, and dispatch the rebuild of its report. Branches without records are
+/// tracked for `team`. The artifacts are downloaded with `client`, the tracking
+/// repository is accessed with `provider`. Returns the number of added records
+pub async fn backfill_branch(
+	client: &Octocrab,
+	provider: &dyn VcsProvider,
+	coverage_repo_name: &str,
+	repo_name: &str,
+	branch: &str,
+	since: i64,
+	team: Team,
+) -> Result<usize, MeowCoverageError> {
+	let (coverage_repo_owner, coverage_repo) =
+		coverage_repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+
+	let reports =
+		artifacts::download_lcov_artifacts_since(client, owner, repo, branch, since).await?;
+	let mut records = Vec::with_capacity(reports.len());
+	for report in reports {
+		let statistics = LcovWrapper::stream_reader(report.lcov.as_slice(), None)?;
+		let Some(percentage) = metrics::percentage(statistics.lines_hit, statistics.lines_found)
+		else {
+			continue;
+		};
+		records.push(BranchCoverageRecord {
+			timestamp: report.timestamp,
+			percentage: Percentage::from_f64(percentage),
+			commit: Some(report.commit.clone()),
+			lines: Some(statistics.lines_found),
+			files: None,
+			files_gz: None,
+			labels: BTreeMap::new(),
+			metadata: Some(RecordMetadata {
+				run_id: Some(report.run_id.to_string()),
+				..RecordMetadata::new(&report.commit)
+			}),
+		});
+	}
+
+	let config = fetch_tracking_config(provider, coverage_repo_name).await?;
+	let storage = config.storage.open(provider, coverage_repo_name)?;
+	let path = make_report_path(owner, repo, branch);
+	let (mut collection, sha) = match storage.get(&path).await? {
+		Some(file) => (BranchCoverageRecordCollection::from_slice(&file.content)?, Some(file.sha)),
+		None => (BranchCoverageRecordCollection::new(team), None),
+	};
+
+	let added = collection.backfill(records, OffsetDateTime::now_utc(), &config.retention);
+	if added == 0 {
+		return Ok(0);
+	}
+
+	storage
+		.put(
+			&path,
+			&format!("Backfill {} records of {} ({})", added, repo_name, branch),
+			serde_json::to_vec(&collection)?,
 			sha,
 		)
-		.branch(RECORDS_BRANCH)
-		.author(author())
-		.commiter(author())
-		.send()
 		.await?;
+	provider
+		.dispatch_workflow(
+			coverage_repo_owner,
+			coverage_repo,
+			"main.yml",
+			"main",
+			serde_json::json!({"repo-name": repo_name, "branch": branch}),
+		)
+		.await?;
+
+	Ok(added)
+}
+
+/// Find the tracked branch of `repo_name` (`OWNER/REPO`) that `branch` was
+/// renamed from: `branch` has no records yet and was pushed on top of
+/// `before`, the commit of the newest record of the other branch
+pub async fn detect_renamed_branch(
+	provider: &dyn VcsProvider,
+	coverage_repo_name: &str,
+	repo_name: &str,
+	branch: &str,
+	before: &str,
+) -> Result<Option<String>, MeowCoverageError> {
+	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+
+	let storage = open_storage(provider, coverage_repo_name).await?;
+	let record_files = storage.list().await?;
+	let tracked = record_files
+		.iter()
+		.filter_map(|path| parse_record_path(path))
+		.filter(|(record_owner, record_repo, _)| *record_owner == owner && *record_repo == repo)
+		.map(|(_, _, tracked_branch)| tracked_branch)
+		.sorted()
+		.collect_vec();
+	if tracked.contains(&branch) {
+		return Ok(None);
+	}
+
+	for candidate in tracked {
+		let Some(collection) =
+			fetch_stored_records(storage.as_ref(), owner, repo, candidate).await?
+		else {
+			continue;
+		};
+		if collection.latest().and_then(|record| record.commit.as_deref()) == Some(before) {
+			return Ok(Some(String::from(candidate)));
+		}
+	}
+
+	Ok(None)
+}
 
+/// Move the records of the branch `branch` of `repo_name` was renamed from
+/// (see [detect_renamed_branch]) to `branch`, before recording a push to it
+/// (see [rename_tracked_branch])
+pub async fn follow_renamed_branch(
+	client: &Octocrab,
+	provider: &dyn VcsProvider,
+	coverage_repo_name: &str,
+	repo_name: &str,
+	branch: &str,
+	before: &str,
+) -> Result<(), MeowCoverageError> {
+	let Some(from) =
+		detect_renamed_branch(provider, coverage_repo_name, repo_name, branch, before).await?
+	else {
+		return Ok(());
+	};
+
+	#[allow(clippy::print_stderr)]
+	{
+		eprintln!("{} was renamed from {} to {}, moving its records", repo_name, from, branch);
+	}
+	rename_tracked_branch(client, provider, coverage_repo_name, repo_name, &from, branch).await?;
 	Ok(())
 }
+
+/// Remove the records of branches without a record for
+/// [remove_stale_days](super::TrackingConfig::remove_stale_days) days from the
+/// centralised coverage tracking records, in a single commit with `client` (or
+/// from the configured bucket), and from the `records` checkout. Returns the
+/// number of removed branches
+pub async fn remove_stale_branches(
+	client: &Octocrab,
+	records: &Path,
+	coverage_repo_name: &str,
+) -> Result<usize, MeowCoverageError> {
+	let config = read_config(records)?;
+	let Some(days) = config.remove_stale_days else {
+		return Ok(0);
+	};
+	let now = OffsetDateTime::now_utc();
+	let stale_paths = walk_records(records)?
+		.into_iter()
+		.filter(|branch| branch.collection.is_stale(now, days))
+		.map(|branch| make_report_path(&branch.owner, &branch.repo, &branch.branch))
+		.collect::<Vec<_>>();
+
+	if stale_paths.is_empty() {
+		return Ok(0);
+	}
+
+	change_records(
+		client,
+		&config.storage,
+		coverage_repo_name,
+		&format!(
+			"Delete reports of {} branches without records for {} days",
+			stale_paths.len(),
+			days
+		),
+		&stale_paths.iter().map(|path| (path.clone(), None)).collect::<Vec<_>>(),
+	)
+	.await?;
+
+	for path in &stale_paths {
+		#[allow(clippy::print_stderr)]
+		{
+			eprintln!("Removed stale records {}", path);
+		}
+		std::fs::remove_file(records.join(path))?;
+	}
+
+	Ok(stale_paths.len())
+}
+
+/// Create the structure of a centralised coverage tracking repository: the
+/// `main` branch with a README, [DESCRIPTION], and the rebuild workflow, and
+/// the `records` branch with the default [TrackingConfig]. Existing files and
+/// branches are kept, returns the created files and branches. Percentages of
+/// the README are in `format`
+pub async fn init_tracking(
+	client: &Octocrab,
+	coverage_repo_name: &str,
+	format: PercentageFormat,
+) -> Result<Vec<String>, MeowCoverageError> {
+	let (coverage_repo_owner, coverage_repo) =
+		coverage_repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+	let mut created = Vec::new();
+
+	let readme = build_readme_from_entries(
+		Default::default(),
+		&TrackingConfig::default(),
+		coverage_repo_owner,
+		coverage_repo,
+		format,
+	);
+	let mut files = vec![
+		(String::from("README.md"), readme),
+		(String::from("Description.md"), String::from(DESCRIPTION)),
+		(String::from(REBUILD_WORKFLOW_PATH), String::from(REBUILD_WORKFLOW)),
+	];
+
+	// The Git Data API is unavailable on empty repositories, the contents API
+	// creates the first commit
+	if get_branch_head(client, coverage_repo_owner, coverage_repo, "main").await?.is_none() {
+		let (path, content) = files.remove(0);
+		client
+			.repos(coverage_repo_owner, coverage_repo)
+			.create_file(path.as_str(), "Initialise coverage tracking", content)
+			.branch("main")
+			.author(author())
+			.commiter(author())
+			.send()
+			.await?;
+		created.push(format!("main:{}", path));
+	}
+
+	let mut missing = Vec::new();
+	for (path, content) in files {
+		if !file_exists(client, coverage_repo_owner, coverage_repo, "main", &path).await? {
+			created.push(format!("main:{}", path));
+			missing.push((path, Some(content)));
+		}
+	}
+	if !missing.is_empty() {
+		commit_files(
+			client,
+			coverage_repo_owner,
+			coverage_repo,
+			"main",
+			"Add coverage tracking files",
+			&missing,
+			&author(),
+		)
+		.await?;
+	}
+
+	if get_branch_head(client, coverage_repo_owner, coverage_repo, RECORDS_BRANCH).await?.is_none()
+	{
+		create_orphan_branch(
+			client,
+			coverage_repo_owner,
+			coverage_repo,
+			RECORDS_BRANCH,
+			"Initialise coverage records",
+			&[(
+				String::from(TRACKING_CONFIG_PATH),
+				serde_json::to_string_pretty(&TrackingConfig::default())?,
+			)],
+			&author(),
+		)
+		.await?;
+		created.push(format!("{}:{}", RECORDS_BRANCH, TRACKING_CONFIG_PATH));
+	}
+
+	Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::{BTreeMap, HashSet};
+
+	use super::{detect_renamed_branch, select_tracked_branches};
+	use crate::{
+		coverage::options::build_globs,
+		percentage::Percentage,
+		provider::{fake::FakeProvider, RepoFile},
+		tracking::{BranchCoverageRecord, BranchCoverageRecordCollection, Team},
+	};
+
+	#[test]
+	fn test_select_tracked_branches() {
+		let paths = [
+			"meowcov.config.json",
+			"famedly/meow/main.meowcov.json",
+			"famedly/meow/feature/cats.meowcov.json",
+			"famedly/legacy-app/main.meowcov.json",
+			"famedly/legacy-app/notes.txt",
+			"other/meow/main.meowcov.json",
+		];
+		let globs = |globs: &[&str]| {
+			build_globs(&globs.iter().map(|glob| String::from(*glob)).collect::<Vec<_>>()).unwrap()
+		};
+		let branch = |owner: &str, repo: &str, branch: &str| {
+			(String::from(owner), String::from(repo), String::from(branch))
+		};
+
+		assert_eq!(
+			select_tracked_branches(paths, &globs(&["famedly/meow"]).unwrap(), None),
+			[branch("famedly", "meow", "feature/cats"), branch("famedly", "meow", "main")]
+		);
+		assert_eq!(
+			select_tracked_branches(
+				paths,
+				&globs(&["famedly/meow"]).unwrap(),
+				globs(&["feature/*"]).as_ref()
+			),
+			[branch("famedly", "meow", "feature/cats")]
+		);
+		assert_eq!(
+			select_tracked_branches(paths, &globs(&["famedly/legacy-*", "other/*"]).unwrap(), None),
+			[branch("famedly", "legacy-app", "main"), branch("other", "meow", "main")]
+		);
+		assert!(select_tracked_branches(paths, &globs(&["famedly/cat"]).unwrap(), None).is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_detect_renamed_branch() {
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		collection.records.push(BranchCoverageRecord {
+			timestamp: 1_700_000_000,
+			percentage: Percentage::from_basis_points(5000),
+			commit: Some(String::from("abcdef")),
+			lines: None,
+			files: None,
+			files_gz: None,
+			labels: BTreeMap::new(),
+			metadata: None,
+		});
+		let provider = FakeProvider {
+			tree: HashSet::from([
+				String::from("famedly/meow/master.meowcov.json"),
+				String::from("famedly/other/master.meowcov.json"),
+			]),
+			..FakeProvider::default()
+		};
+		for repo in ["meow", "other"] {
+			provider.files.lock().unwrap().insert(
+				format!("famedly/coverage/records/famedly/{}/master.meowcov.json", repo),
+				RepoFile {
+					content: serde_json::to_vec(&collection).unwrap(),
+					sha: String::from("1"),
+				},
+			);
+		}
+		let detect = |branch, before| {
+			detect_renamed_branch(&provider, "famedly/coverage", "famedly/meow", branch, before)
+		};
+
+		assert_eq!(detect("main", "abcdef").await.unwrap().as_deref(), Some("master"));
+		assert_eq!(detect("main", "fedcba").await.unwrap(), None);
+		assert_eq!(detect("master", "abcdef").await.unwrap(), None);
+	}
+}
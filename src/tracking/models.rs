@@ -1,11 +1,22 @@
 //! This module contains models for record storage
 
-use std::{collections::HashMap, str::FromStr};
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	io::{Read, Write},
+	str::FromStr,
+};
 
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
-use super::make_percent;
+use super::StorageConfig;
+use crate::{
+	metrics,
+	percentage::{Percentage, PercentageFormat},
+	MeowCoverageError,
+};
 
 /// Enum for all the teams a project can be owned by
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, Hash)]
@@ -37,6 +48,32 @@ impl std::fmt::Display for Team {
 	}
 }
 
+impl Team {
+	/// Match a CODEOWNERS owner or team name to a [Team], ignoring the `@org/`
+	/// prefix, case, and separators (so `@famedly/instant-messaging` is
+	/// [Team::InstantMessaging])
+	#[must_use]
+	pub fn from_name(name: &str) -> Option<Self> {
+		let name = name.trim_start_matches('@');
+		let name = name.rsplit_once('/').map_or(name, |(_, team)| team);
+		let name = name
+			.chars()
+			.filter(|char| !matches!(char, '-' | '_' | ' '))
+			.collect::<String>()
+			.to_lowercase();
+
+		match name.as_str() {
+			"instantmessaging" => Some(Self::InstantMessaging),
+			"workflow" => Some(Self::Workflow),
+			"infrastructure" => Some(Self::Infrastructure),
+			"product" => Some(Self::Product),
+			"security" => Some(Self::Security),
+			"other" => Some(Self::Other),
+			_ => None,
+		}
+	}
+}
+
 /// Wrapper for errors returned from [Team::from_str]
 #[derive(Debug)]
 pub struct TeamFromStrError;
@@ -69,29 +106,284 @@ impl FromStr for Team {
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct FileCoverageRecord {
 	/// File coverage percentage (lines_hit/lines_found)
-	pub percentage: i16,
+	pub percentage: Percentage,
+	/// Number of instrumented lines, absent on records produced before line
+	/// counts were recorded
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub lines: Option<u64>,
 	/// List of untested lines
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
 	pub untested_lines: Vec<u32>,
+	/// Untested lines as inclusive `[first, last]` ranges, replacing
+	/// [FileCoverageRecord::untested_lines] in compressed records until they
+	/// are decompressed
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub untested_ranges: Vec<(u32, u32)>,
 }
 
 impl FileCoverageRecord {
 	/// Create a new [FileCoverageRecord]
 	#[must_use]
-	pub fn new(percentage: f64, untested_lines: Vec<u32>) -> Self {
-		Self { percentage: make_percent(percentage), untested_lines }
+	pub fn new(percentage: f64, lines: u64, untested_lines: Vec<u32>) -> Self {
+		Self {
+			percentage: Percentage::from_f64(percentage),
+			lines: Some(lines),
+			untested_lines,
+			untested_ranges: Vec::new(),
+		}
+	}
+
+	/// Move the untested lines into ranges of consecutive lines
+	fn compress_lines(&mut self) {
+		let mut lines = std::mem::take(&mut self.untested_lines);
+		lines.sort_unstable();
+		lines.dedup();
+		for line in lines {
+			match self.untested_ranges.last_mut() {
+				Some((_, last)) if line == *last + 1 => *last = line,
+				_ => self.untested_ranges.push((line, line)),
+			}
+		}
+	}
+
+	/// Expand the ranges of untested lines back into the untested lines
+	fn decompress_lines(&mut self) {
+		for (first, last) in std::mem::take(&mut self.untested_ranges) {
+			self.untested_lines.extend(first..=last);
+		}
+	}
+}
+
+/// How the files of records are compressed in the tracking repository, the
+/// records are decompressed transparently when read
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordCompression {
+	/// Store untested lines as ranges of consecutive lines
+	Ranges,
+	/// Store untested lines as ranges and the files gzipped and base64
+	/// encoded
+	Gzip,
+}
+
+/// Settings for how long the records of a branch are kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionSettings {
+	/// Records older than this many days are removed (or downsampled), [None]
+	/// keeps records of any age
+	pub days: Option<u32>,
+	/// Maximum number of records, the oldest are removed first
+	pub max_records: Option<usize>,
+	/// Keep the newest record of every week of records older than `days`
+	/// instead of removing them
+	pub downsample: bool,
+}
+
+impl Default for RetentionSettings {
+	fn default() -> Self {
+		Self { days: Some(90), max_records: None, downsample: false }
+	}
+}
+
+/// Order of the branches in the README of the tracking repository
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadmeSort {
+	/// By repository owner, name, and branch
+	#[default]
+	Name,
+	/// Lowest coverage first
+	Coverage,
+	/// Biggest drop of coverage over the last 30 days first
+	Drop,
+}
+
+/// Configuration of the tracking repository, stored at
+/// [TRACKING_CONFIG_PATH](super::TRACKING_CONFIG_PATH) on the `records` branch
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackingConfig {
+	/// Retention of collections without their own settings
+	pub retention: RetentionSettings,
+	/// Coverage percentage at or below which branches are counted and listed
+	/// as needing attention in the README
+	pub threshold: f64,
+	/// Order of the branches in the README
+	pub sort: ReadmeSort,
+	/// Branches without a record for this many days are flagged as stale in
+	/// the README, [None] never flags branches
+	pub stale_days: Option<u32>,
+	/// Records of branches without a record for this many days are removed
+	/// when rebuilding, [None] keeps them
+	pub remove_stale_days: Option<u32>,
+	/// Where the records are stored
+	pub storage: StorageConfig,
+}
+
+impl Default for TrackingConfig {
+	fn default() -> Self {
+		Self {
+			retention: RetentionSettings::default(),
+			threshold: 80_f64,
+			sort: ReadmeSort::default(),
+			stale_days: Some(30),
+			remove_stale_days: None,
+			storage: StorageConfig::default(),
+		}
 	}
 }
 
+/// Options for adding a record to a collection
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecordOptions {
+	/// Keep a record for every commit instead of one per day
+	pub per_commit: bool,
+	/// Retention settings to store on the collection, replacing its previous
+	/// settings
+	pub retention: Option<RetentionSettings>,
+	/// Keep the file percentages (without untested lines) of older records
+	/// instead of only on the newest record
+	pub file_history: bool,
+	/// Compression of the files of the records when stored, [None] stores
+	/// them uncompressed
+	pub compression: Option<RecordCompression>,
+	/// Reject uploads whose number of files dropped by more than this
+	/// percentage compared with the previous record
+	pub max_file_drop: Option<Percentage>,
+	/// Reject uploads whose coverage changed by more than this many points
+	/// compared with the previous record
+	pub max_coverage_change: Option<Percentage>,
+}
+
 /// A coverage record for a branch
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct BranchCoverageRecord {
 	/// Timestamp the record was produced at
 	pub timestamp: i64,
-	/// Coverage percentage multiplied by 100 stored as an i16
-	pub percentage: i16,
-	/// List of file coverage records, only present on newest entry
+	/// Coverage percentage
+	pub percentage: Percentage,
+	/// SHA of the commit the record was produced for, absent on records
+	/// produced before commits were recorded
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub commit: Option<String>,
+	/// Number of instrumented lines, absent on records produced before line
+	/// counts were recorded
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub lines: Option<u64>,
+	/// List of file coverage records, only present on newest entry unless
+	/// [RecordOptions::file_history] is set, which keeps them without untested
+	/// lines on older entries
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub files: Option<HashMap<String, FileCoverageRecord>>,
+	/// [BranchCoverageRecord::files] gzipped and base64 encoded, replacing
+	/// them in records compressed with [RecordCompression::Gzip] until they
+	/// are decompressed
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub files_gz: Option<String>,
+	/// Coverage percentages of the labelled reports merged into the record,
+	/// by label
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub labels: BTreeMap<String, Percentage>,
+	/// Where the record was produced, absent on records produced before
+	/// metadata was recorded
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub metadata: Option<RecordMetadata>,
+}
+
+impl BranchCoverageRecord {
+	/// Compress the files of the record with `compression`
+	fn compress(&mut self, compression: RecordCompression) -> Result<(), MeowCoverageError> {
+		let Some(mut files) = self.files.take() else {
+			return Ok(());
+		};
+		for file in files.values_mut() {
+			file.compress_lines();
+		}
+		match compression {
+			RecordCompression::Ranges => self.files = Some(files),
+			RecordCompression::Gzip => {
+				let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+				encoder.write_all(&serde_json::to_vec(&files)?)?;
+				self.files_gz = Some(BASE64.encode(encoder.finish()?));
+			}
+		}
+		Ok(())
+	}
+
+	/// Decompress the files of a record compressed with any
+	/// [RecordCompression], records that aren't compressed are unchanged
+	fn decompress(&mut self) -> Result<(), MeowCoverageError> {
+		if let Some(files_gz) = self.files_gz.take() {
+			let compressed = BASE64
+				.decode(files_gz)
+				.map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+			let mut files = Vec::new();
+			GzDecoder::new(compressed.as_slice()).read_to_end(&mut files)?;
+			self.files = Some(serde_json::from_slice(&files)?);
+		}
+		for file in self.files.iter_mut().flat_map(HashMap::values_mut) {
+			file.decompress_lines();
+		}
+		Ok(())
+	}
+}
+
+/// Version of the record format written by this version of meow-coverage,
+/// records of newer formats are rejected when rebuilding. Format 1 stores the
+/// percentages of files in basis points like all other percentages, see
+/// [BranchCoverageRecordCollection::format_version] for older records
+pub const RECORD_FORMAT_VERSION: u32 = 1;
+
+/// Provenance of a record, to trace where a bogus record came from and to
+/// reject records produced by incompatible versions
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordMetadata {
+	/// Version of the record format, see [RECORD_FORMAT_VERSION]
+	pub format_version: u32,
+	/// Version of meow-coverage that produced the record
+	pub tool_version: String,
+	/// SHA of the commit the record was produced for
+	pub commit: String,
+	/// ID of the GitHub Actions workflow run that produced the record
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub run_id: Option<String>,
+	/// SHA-256 of the Lcov reports the record was produced from
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub lcov_digest: Option<String>,
+}
+
+impl RecordMetadata {
+	/// Metadata of a record for `commit` produced by this version, in the
+	/// workflow run of `GITHUB_RUN_ID` if set
+	#[must_use]
+	pub fn new(commit: &str) -> Self {
+		Self {
+			format_version: RECORD_FORMAT_VERSION,
+			tool_version: String::from(env!("CARGO_PKG_VERSION")),
+			commit: String::from(commit),
+			run_id: std::env::var("GITHUB_RUN_ID").ok().filter(|run_id| !run_id.is_empty()),
+			lcov_digest: None,
+		}
+	}
+
+	/// Check that the metadata describes `record` and that its format is
+	/// supported, returning why not otherwise
+	pub fn verify(&self, record: &BranchCoverageRecord) -> Result<(), String> {
+		if self.format_version > RECORD_FORMAT_VERSION {
+			return Err(format!(
+				"produced by meow-coverage {} in record format {}, only format {} is supported",
+				self.tool_version, self.format_version, RECORD_FORMAT_VERSION
+			));
+		}
+		match record.commit.as_deref() {
+			Some(commit) if commit != self.commit => Err(format!(
+				"recorded for commit {} but its metadata names commit {}",
+				commit, self.commit
+			)),
+			_ => Ok(()),
+		}
+	}
 }
 
 /// A collection of the records for a branch of a file
@@ -102,59 +394,458 @@ pub struct BranchCoverageRecordCollection {
 	/// Branch records
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
 	pub records: Vec<BranchCoverageRecord>,
+	/// Retention settings of this branch, [TrackingConfig::retention] applies
+	/// if [None]
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub retention: Option<RetentionSettings>,
+	/// Records of the uploads tagged with a flag (for example `unit` or
+	/// `integration`) by flag, [BranchCoverageRecordCollection::records] then
+	/// holds the merged total of the latest record of every flag
+	#[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+	pub flags: BTreeMap<String, BranchCoverageRecordCollection>,
+	/// [RECORD_FORMAT_VERSION] the collection was written with, 0 for
+	/// collections written before it was stored. Their records without
+	/// metadata stored the percentages of partly covered files as their
+	/// covered fraction times 100 (0 to 100), which are converted to basis
+	/// points when the collection is read
+	#[serde(default)]
+	pub format_version: u32,
 }
 
 impl BranchCoverageRecordCollection {
-	/// Add a new record, purge old records
-	pub fn add_new_record(&mut self, percentage: f64, files: HashMap<String, FileCoverageRecord>) {
+	/// Create an empty collection
+	#[must_use]
+	pub fn new(team: Team) -> Self {
+		Self {
+			team,
+			records: Vec::new(),
+			retention: None,
+			flags: BTreeMap::new(),
+			format_version: RECORD_FORMAT_VERSION,
+		}
+	}
+
+	/// Add a new record for the upload tagged with `flag` like
+	/// [BranchCoverageRecordCollection::add_new_record], then add the merged
+	/// total of the latest record of every flag as a record of the branch. The
+	/// `labels` are only kept on the record of the flag
+	#[allow(clippy::too_many_arguments)]
+	pub fn add_new_flag_record(
+		&mut self,
+		flag: &str,
+		percentage: f64,
+		lines: u64,
+		files: HashMap<String, FileCoverageRecord>,
+		labels: &BTreeMap<String, f64>,
+		metadata: &RecordMetadata,
+		options: &RecordOptions,
+		default_retention: &RetentionSettings,
+	) {
+		if let Some(retention) = options.retention {
+			self.retention = Some(retention);
+		}
+		let retention = self.retention.unwrap_or(*default_retention);
+		let team = self.team;
+
+		self.flags.entry(String::from(flag)).or_insert_with(|| Self::new(team)).add_new_record(
+			percentage,
+			lines,
+			files,
+			labels,
+			metadata,
+			&RecordOptions { retention: None, ..*options },
+			&retention,
+		);
+
+		let (percentage, lines, files) = self.merge_flags();
+		self.add_new_record(
+			percentage,
+			lines,
+			files,
+			&BTreeMap::new(),
+			metadata,
+			options,
+			default_retention,
+		);
+	}
+
+	/// Merge the latest records of all flags into the total percentage, number
+	/// of instrumented lines, and file records: a line is untested if it is
+	/// untested in every flag covering its file
+	fn merge_flags(&self) -> (f64, u64, HashMap<String, FileCoverageRecord>) {
+		let mut merged: HashMap<&str, (u64, Option<HashSet<u32>>)> = HashMap::new();
+		for files in self.flags.values().filter_map(|flag| flag.latest()?.files.as_ref()) {
+			for (path, file) in files {
+				let (lines, untested) = merged.entry(path.as_str()).or_default();
+				*lines = (*lines).max(file.lines.unwrap_or_default());
+				let file_untested = file.untested_lines.iter().copied().collect::<HashSet<_>>();
+				*untested = Some(match untested.take() {
+					Some(untested) => untested.intersection(&file_untested).copied().collect(),
+					None => file_untested,
+				});
+			}
+		}
+
+		let (mut lines_found, mut lines_hit) = (0, 0);
+		let files = merged
+			.into_iter()
+			.map(|(path, (lines, untested))| {
+				let untested_lines =
+					untested.unwrap_or_default().into_iter().sorted().collect_vec();
+				let hit = lines.saturating_sub(untested_lines.len() as u64);
+				lines_found += lines;
+				lines_hit += hit;
+
+				let percentage = metrics::percentage(hit, lines).unwrap_or(100_f64);
+				(String::from(path), FileCoverageRecord::new(percentage, lines, untested_lines))
+			})
+			.collect();
+
+		let percentage = metrics::percentage(lines_hit, lines_found).unwrap_or(100_f64);
+		(percentage, lines_found, files)
+	}
+
+	/// Add a new record with the percentages of its labelled reports and the
+	/// `metadata` of where it was produced, purge old records according to
+	/// the retention settings of the collection, or `default_retention`.
+	/// Unless [RecordOptions::per_commit] is set, the new record replaces the
+	/// records of the same day
+	#[allow(clippy::too_many_arguments)]
+	pub fn add_new_record(
+		&mut self,
+		percentage: f64,
+		lines: u64,
+		files: HashMap<String, FileCoverageRecord>,
+		labels: &BTreeMap<String, f64>,
+		metadata: &RecordMetadata,
+		options: &RecordOptions,
+		default_retention: &RetentionSettings,
+	) {
 		let time: time::OffsetDateTime = time::OffsetDateTime::now_utc();
 		let timestamp = time.unix_timestamp();
 
+		if let Some(retention) = options.retention {
+			self.retention = Some(retention);
+		}
+
+		if !options.per_commit {
+			self.records.retain(|record| {
+				time::OffsetDateTime::from_unix_timestamp(record.timestamp)
+					.map_or(true, |record_time| record_time.date() != time.date())
+			});
+		}
+
 		self.records.push(BranchCoverageRecord {
 			timestamp,
-			percentage: make_percent(percentage),
+			percentage: Percentage::from_f64(percentage),
+			commit: Some(metadata.commit.clone()),
+			lines: Some(lines),
 			files: Some(files),
+			files_gz: None,
+			labels: labels
+				.iter()
+				.map(|(label, percentage)| (label.clone(), Percentage::from_f64(*percentage)))
+				.collect(),
+			metadata: Some(metadata.clone()),
 		});
 
-		self.remove_old_records(time);
+		let retention = self.retention.unwrap_or(*default_retention);
+		self.remove_old_records(time, &retention);
 
 		#[allow(clippy::expect_used)]
 		let highest_ts = self.latest_timestamp().expect("We pushed a record, there is always a timestamp");
 
-		// Remove the file info for old records
+		// Remove the file info (or only the untested lines) for old records
+		for record in self.records.iter_mut().filter(|record| record.timestamp != highest_ts) {
+			match (options.file_history, &mut record.files) {
+				(true, Some(files)) => {
+					for file in files.values_mut() {
+						file.untested_lines.clear();
+					}
+				}
+				_ => record.files = None,
+			}
+		}
+	}
+
+	/// Check an upload of `percentage` over `file_count` files against the
+	/// latest record (of the records of `flag` if passed) with the sanity
+	/// checks of `options`, returning why it looks broken otherwise (with the
+	/// percentages in `format`)
+	pub fn check_upload(
+		&self,
+		flag: Option<&str>,
+		percentage: f64,
+		file_count: usize,
+		options: &RecordOptions,
+		format: PercentageFormat,
+	) -> Result<(), String> {
+		let collection = match flag {
+			Some(flag) => match self.flags.get(flag) {
+				Some(collection) => collection,
+				None => return Ok(()),
+			},
+			None => self,
+		};
+		let Some(previous) = collection.latest() else {
+			return Ok(());
+		};
+
+		if let (Some(max_drop), Some(previous_files)) = (options.max_file_drop, &previous.files) {
+			let previous_count = previous_files.len() as u64;
+			let dropped = previous_count.saturating_sub(file_count as u64);
+			let drop = metrics::percentage(dropped, previous_count).map(Percentage::from_f64);
+			if drop.is_some_and(|drop| drop > max_drop) {
+				return Err(format!(
+					"the number of files dropped from {} to {}, more than {}%",
+					previous_count,
+					file_count,
+					max_drop.display_with(format)
+				));
+			}
+		}
+
+		if let Some(max_change) = options.max_coverage_change {
+			let change = Percentage::from_f64(percentage) - previous.percentage;
+			if change > max_change || Percentage::ZERO - change > max_change {
+				return Err(format!(
+					"the coverage changed by {:+}% from {}%, more than {}%",
+					change.display_with(format),
+					previous.percentage.display_with(format),
+					max_change.display_with(format)
+				));
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Merge the records of `renamed`, a branch renamed to the branch of this
+	/// collection, keeping the team and retention of this collection. Only
+	/// the newest record keeps its untested lines
+	pub fn merge_renamed(&mut self, renamed: Self) {
+		self.retention = self.retention.or(renamed.retention);
+		self.records.extend(renamed.records);
+		self.records.sort_by_key(|record| record.timestamp);
+		let newest = self.records.len().saturating_sub(1);
+		for record in &mut self.records[..newest] {
+			for file in record.files.iter_mut().flat_map(HashMap::values_mut) {
+				file.untested_lines.clear();
+			}
+		}
+
+		for (flag, collection) in renamed.flags {
+			match self.flags.get_mut(&flag) {
+				Some(existing) => existing.merge_renamed(collection),
+				None => {
+					self.flags.insert(flag, collection);
+				}
+			}
+		}
+	}
+
+	/// Add historical `records` (for example reconstructed from the artifacts
+	/// of past workflow runs) on days without a record, keeping the newest
+	/// historical record of every day, and purge them according to the
+	/// retention settings of the collection, or `default_retention`. Returns
+	/// the number of added records
+	pub fn backfill(
+		&mut self,
+		records: Vec<BranchCoverageRecord>,
+		current_time: time::OffsetDateTime,
+		default_retention: &RetentionSettings,
+	) -> usize {
+		let day = |timestamp: i64| timestamp.div_euclid(time::Duration::DAY.whole_seconds());
+		let mut days =
+			self.records.iter().map(|record| day(record.timestamp)).collect::<HashSet<_>>();
+
+		let previous = self.records.len();
+		for record in records.into_iter().sorted_by_key(|record| -record.timestamp) {
+			if days.insert(day(record.timestamp)) {
+				self.records.push(record);
+			}
+		}
+		self.records.sort_by_key(|record| record.timestamp);
+		let added = self.records.len() - previous;
+
+		let retention = self.retention.unwrap_or(*default_retention);
+		self.remove_old_records(current_time, &retention);
+		added
+	}
+
+	/// Parse a collection stored in the tracking repository, decompressing
+	/// its records
+	pub fn from_slice(content: &[u8]) -> Result<Self, MeowCoverageError> {
+		let mut collection: Self = serde_json::from_slice(content)?;
+		collection.decompress()?;
+		collection.normalise_file_percentages();
+		Ok(collection)
+	}
+
+	/// Convert the file percentages of the records of collections written
+	/// before [BranchCoverageRecordCollection::format_version] was stored to
+	/// basis points (also of the flags). Records with metadata were already
+	/// written in basis points, and fully covered files always were
+	fn normalise_file_percentages(&mut self) {
+		if self.format_version == 0 {
+			for record in self.records.iter_mut().filter(|record| record.metadata.is_none()) {
+				for file in record.files.iter_mut().flat_map(HashMap::values_mut) {
+					if file.percentage < Percentage::FULL {
+						file.percentage = Percentage::from_basis_points(
+							file.percentage.basis_points().clamp(0, 100) * 100,
+						);
+					}
+				}
+			}
+		}
+		self.format_version = RECORD_FORMAT_VERSION;
+
+		for collection in self.flags.values_mut() {
+			collection.normalise_file_percentages();
+		}
+	}
+
+	/// Compress the files of the records (also of the flags) with
+	/// `compression` before storing the collection
+	pub fn compress(&mut self, compression: RecordCompression) -> Result<(), MeowCoverageError> {
+		for record in &mut self.records {
+			record.compress(compression)?;
+		}
+		for collection in self.flags.values_mut() {
+			collection.compress(compression)?;
+		}
+		Ok(())
+	}
+
+	/// Decompress the records (also of the flags) compressed with
+	/// [BranchCoverageRecordCollection::compress]
+	pub fn decompress(&mut self) -> Result<(), MeowCoverageError> {
 		for record in &mut self.records {
-			if record.timestamp != highest_ts {
-				record.files = None;
+			record.decompress()?;
+		}
+		for collection in self.flags.values_mut() {
+			collection.decompress()?;
+		}
+		Ok(())
+	}
+
+	/// Remove the records (also of the flags) whose metadata doesn't verify,
+	/// returning why each was rejected. Records without metadata are kept
+	pub fn reject_unverified(&mut self) -> Vec<String> {
+		let mut rejected = Vec::new();
+		self.records.retain(|record| {
+			match record.metadata.as_ref().map_or(Ok(()), |metadata| metadata.verify(record)) {
+				Ok(()) => true,
+				Err(reason) => {
+					rejected.push(format!("record of {}: {}", record.timestamp, reason));
+					false
+				}
 			}
+		});
+		for (flag, collection) in &mut self.flags {
+			rejected.extend(
+				collection
+					.reject_unverified()
+					.into_iter()
+					.map(|reason| format!("flag `{}` {}", flag, reason)),
+			);
 		}
+
+		rejected
 	}
 
-	/// Removes records over 90 days old
-	pub fn remove_old_records(&mut self, current_time: time::OffsetDateTime) {
-		let time_limit = (current_time - time::Duration::days(90)).unix_timestamp();
-		self.records.retain(|item| item.timestamp >= time_limit);
+	/// Removes records older than the retention period (keeping one per week
+	/// if downsampling) and the oldest records over the maximum count
+	pub fn remove_old_records(
+		&mut self,
+		current_time: time::OffsetDateTime,
+		retention: &RetentionSettings,
+	) {
+		self.records.sort_by_key(|record| record.timestamp);
+
+		if let Some(days) = retention.days {
+			let time_limit = (current_time - time::Duration::days(days.into())).unix_timestamp();
+			match retention.downsample {
+				true => {
+					let week = time::Duration::WEEK.whole_seconds();
+					let mut weeks = HashSet::new();
+					let mut records = std::mem::take(&mut self.records);
+					records.reverse();
+					records.retain(|item| {
+						item.timestamp >= time_limit
+							|| weeks.insert(item.timestamp.div_euclid(week))
+					});
+					records.reverse();
+					self.records = records;
+				}
+				false => self.records.retain(|item| item.timestamp >= time_limit),
+			}
+		}
+
+		if let Some(max_records) = retention.max_records {
+			let excess = self.records.len().saturating_sub(max_records.max(1));
+			self.records.drain(..excess);
+		}
 	}
 
-	/// Fetch the timestamp of the latest change
+	/// Fetch the latest record, the last added one if several share a
+	/// timestamp
 	#[must_use]
 	pub fn latest(&self) -> Option<&BranchCoverageRecord> {
 		self.records.iter().fold(None, |oldest, record| {
-			Some(oldest.map_or(record, |oldest| match oldest.timestamp < record.timestamp {
+			Some(oldest.map_or(record, |oldest| match oldest.timestamp <= record.timestamp {
 				true => record,
 				false => oldest,
 			}))
 		})
 	}
 
+	/// Fetch the newest record of the commit `commit` (or a commit starting
+	/// with it, for abbreviated SHAs), [None] if no record is kept
+	#[must_use]
+	pub fn record_of_commit(&self, commit: &str) -> Option<&BranchCoverageRecord> {
+		self.records
+			.iter()
+			.filter(|record| record.commit.as_deref().is_some_and(|sha| sha.starts_with(commit)))
+			.max_by_key(|record| record.timestamp)
+	}
+
 	/// Fetch the timestamp of the latest change
 	#[must_use]
 	pub fn latest_timestamp(&self) -> Option<i64> {
 		self.records.iter().map(|entry| entry.timestamp).sorted_by(|l, r| Ord::cmp(r, l)).next()
 	}
 
+	/// Fetch the newest record at or before `timestamp`, [None] if there is
+	/// no such record
+	#[must_use]
+	pub fn record_at(&self, timestamp: i64) -> Option<&BranchCoverageRecord> {
+		self.records
+			.iter()
+			.filter(|record| record.timestamp <= timestamp)
+			.max_by_key(|record| record.timestamp)
+	}
+
+	/// Fetch the percentage of the newest record at or before `timestamp`,
+	/// [None] if there is no such record
+	#[must_use]
+	pub fn percentage_at(&self, timestamp: i64) -> Option<Percentage> {
+		self.record_at(timestamp).map(|record| record.percentage)
+	}
+
+	/// Whether the collection has no record newer than `days` days
+	#[must_use]
+	pub fn is_stale(&self, current_time: time::OffsetDateTime, days: u32) -> bool {
+		let time_limit = (current_time - time::Duration::days(days.into())).unix_timestamp();
+		match self.latest_timestamp() {
+			Some(timestamp) => timestamp < time_limit,
+			None => true,
+		}
+	}
+
 	/// Returns the delta of the previous two changes
 	#[must_use]
-	pub fn last_delta(&self) -> Option<i16> {
+	pub fn last_delta(&self) -> Option<Percentage> {
 		let (Some(newest), second_newest) = ({
 			let mut iter = self
 			.records
@@ -173,7 +864,7 @@ impl BranchCoverageRecordCollection {
 
 	/// Returns the delta of changes between the start and end timestamps
 	#[must_use]
-	pub fn delta(&self, period_start_ts: i64, period_end_ts: i64) -> Option<i16> {
+	pub fn delta(&self, period_start_ts: i64, period_end_ts: i64) -> Option<Percentage> {
 		let (Some(oldest), newest) = ({
 			let mut iter = self
 			.records
@@ -192,7 +883,7 @@ impl BranchCoverageRecordCollection {
 
 	/// Returns a delta with a given duration since the last edit
 	#[must_use]
-	pub fn delta_duration(&self, duration: time::Duration) -> Option<i16> {
+	pub fn delta_duration(&self, duration: time::Duration) -> Option<Percentage> {
 		let period_end_ts = self.latest_timestamp()?;
 		let period_start_ts = period_end_ts - duration.as_seconds_f64() as i64;
 
@@ -201,19 +892,560 @@ impl BranchCoverageRecordCollection {
 
 	/// Returns the delta in the past 7 days since the last edit
 	#[must_use]
-	pub fn delta_last_7_days(&self) -> Option<i16> {
+	pub fn delta_last_7_days(&self) -> Option<Percentage> {
 		self.delta_duration(time::Duration::days(7))
 	}
 
 	/// Returns the delta in the past 30 days since the last edit
 	#[must_use]
-	pub fn delta_last_30_days(&self) -> Option<i16> {
+	pub fn delta_last_30_days(&self) -> Option<Percentage> {
 		self.delta_duration(time::Duration::days(30))
 	}
 
 	/// Returns the delta in the past 90 days since the last edit
 	#[must_use]
-	pub fn delta_last_90_days(&self) -> Option<i16> {
+	pub fn delta_last_90_days(&self) -> Option<Percentage> {
 		self.delta_duration(time::Duration::days(90))
 	}
+
+	/// Returns the files whose coverage dropped within `duration` since the
+	/// last edit with their delta, biggest drop first. Compares the newest
+	/// record to the oldest record in the period with file percentages
+	#[must_use]
+	pub fn regressed_files(&self, duration: time::Duration) -> Vec<(&str, Percentage)> {
+		let (Some(newest), Some(period_end_ts)) = (self.latest(), self.latest_timestamp()) else {
+			return Vec::new();
+		};
+		let period_start_ts = period_end_ts - duration.whole_seconds();
+
+		let Some(oldest) = self
+			.records
+			.iter()
+			.filter(|record| {
+				record.timestamp >= period_start_ts
+					&& record.timestamp < period_end_ts
+					&& record.files.is_some()
+			})
+			.min_by_key(|record| record.timestamp)
+		else {
+			return Vec::new();
+		};
+		let (Some(newest_files), Some(oldest_files)) = (&newest.files, &oldest.files) else {
+			return Vec::new();
+		};
+
+		newest_files
+			.iter()
+			.filter_map(|(path, file)| {
+				let delta = file.percentage - oldest_files.get(path)?.percentage;
+				(delta < Percentage::ZERO).then_some((path.as_str(), delta))
+			})
+			.sorted_by(|(l_path, l), (r_path, r)| l.cmp(r).then_with(|| l_path.cmp(r_path)))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::{BTreeMap, HashMap};
+
+	use super::{
+		BranchCoverageRecord, BranchCoverageRecordCollection, FileCoverageRecord,
+		RecordCompression, RecordMetadata, RecordOptions, RetentionSettings, Team,
+		RECORD_FORMAT_VERSION,
+	};
+	use crate::percentage::{Percentage, PercentageFormat};
+
+	#[test]
+	fn test_add_new_record_per_day_and_per_commit() {
+		let now = time::OffsetDateTime::now_utc().unix_timestamp();
+		let yesterday = now - 60 * 60 * 24;
+		let mut collection = BranchCoverageRecordCollection {
+			team: Team::Other,
+			records: vec![BranchCoverageRecord {
+				timestamp: yesterday,
+				percentage: Percentage::from_basis_points(5000),
+				commit: None,
+				lines: None,
+				files: None,
+				files_gz: None,
+				labels: BTreeMap::new(),
+				metadata: None,
+			}],
+			retention: None,
+			flags: BTreeMap::new(),
+			format_version: RECORD_FORMAT_VERSION,
+		};
+		let per_day = RecordOptions::default();
+		let per_commit = RecordOptions { per_commit: true, ..Default::default() };
+		let retention = RetentionSettings::default();
+
+		collection.add_new_record(
+			60_f64,
+			10,
+			HashMap::new(),
+			&BTreeMap::new(),
+			&RecordMetadata::new("aaaaaaa"),
+			&per_day,
+			&retention,
+		);
+		collection.add_new_record(
+			70_f64,
+			10,
+			HashMap::new(),
+			&BTreeMap::new(),
+			&RecordMetadata::new("bbbbbbb"),
+			&per_day,
+			&retention,
+		);
+		assert_eq!(
+			collection
+				.records
+				.iter()
+				.map(|record| (record.commit.as_deref(), record.percentage))
+				.collect::<Vec<_>>(),
+			vec![
+				(None, Percentage::from_basis_points(5000)),
+				(Some("bbbbbbb"), Percentage::from_basis_points(7000))
+			]
+		);
+
+		collection.add_new_record(
+			80_f64,
+			10,
+			HashMap::new(),
+			&BTreeMap::new(),
+			&RecordMetadata::new("ccccccc"),
+			&per_commit,
+			&retention,
+		);
+		assert_eq!(collection.records.len(), 3);
+		assert_eq!(
+			collection.latest().and_then(|record| record.commit.as_deref()),
+			Some("ccccccc")
+		);
+	}
+
+	#[test]
+	fn test_remove_old_records_retention() {
+		let now = time::OffsetDateTime::now_utc();
+		let day = 60 * 60 * 24;
+		let collection = || BranchCoverageRecordCollection {
+			team: Team::Other,
+			// One record per day for the past 30 days
+			records: (0..30)
+				.rev()
+				.map(|days| BranchCoverageRecord {
+					timestamp: now.unix_timestamp() - days * day,
+					percentage: Percentage::from_basis_points(0),
+					commit: None,
+					lines: None,
+					files: None,
+					files_gz: None,
+					labels: BTreeMap::new(),
+					metadata: None,
+				})
+				.collect(),
+			retention: None,
+			flags: BTreeMap::new(),
+			format_version: RECORD_FORMAT_VERSION,
+		};
+
+		let mut removed = collection();
+		removed.remove_old_records(now, &RetentionSettings::default());
+		assert_eq!(removed.records.len(), 30);
+		removed.remove_old_records(
+			now,
+			&RetentionSettings { days: Some(7), max_records: None, downsample: false },
+		);
+		assert_eq!(removed.records.len(), 8);
+
+		let mut downsampled = collection();
+		downsampled.remove_old_records(
+			now,
+			&RetentionSettings { days: Some(7), max_records: None, downsample: true },
+		);
+		let old = downsampled
+			.records
+			.iter()
+			.filter(|record| record.timestamp < now.unix_timestamp() - 7 * day)
+			.collect::<Vec<_>>();
+		assert!((3..=4).contains(&old.len()));
+		assert_eq!(downsampled.records.len(), 8 + old.len());
+
+		let mut capped = collection();
+		capped.remove_old_records(
+			now,
+			&RetentionSettings { days: None, max_records: Some(5), downsample: false },
+		);
+		assert_eq!(capped.records.len(), 5);
+		assert_eq!(capped.latest().map(|record| record.timestamp), Some(now.unix_timestamp()));
+	}
+
+	#[test]
+	fn test_is_stale() {
+		let now = time::OffsetDateTime::now_utc();
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		assert!(collection.is_stale(now, 30));
+
+		collection.records.push(BranchCoverageRecord {
+			timestamp: (now - time::Duration::days(10)).unix_timestamp(),
+			percentage: Percentage::from_basis_points(0),
+			commit: None,
+			lines: None,
+			files: None,
+			files_gz: None,
+			labels: BTreeMap::new(),
+			metadata: None,
+		});
+		assert!(!collection.is_stale(now, 30));
+		assert!(collection.is_stale(now, 7));
+	}
+
+	#[test]
+	fn test_file_history_regressed_files() {
+		let files = |lib: f64, main: f64| {
+			HashMap::from([
+				(String::from("src/lib.rs"), FileCoverageRecord::new(lib, 4, vec![1, 2])),
+				(String::from("src/main.rs"), FileCoverageRecord::new(main, 4, vec![3])),
+			])
+		};
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		let options = RecordOptions { per_commit: true, file_history: true, ..Default::default() };
+		let retention = RetentionSettings::default();
+
+		collection.add_new_record(
+			80_f64,
+			10,
+			files(90_f64, 50_f64),
+			&BTreeMap::new(),
+			&RecordMetadata::new("aaaaaaa"),
+			&options,
+			&retention,
+		);
+		collection.records[0].timestamp -= 60 * 60 * 24;
+		collection.add_new_record(
+			70_f64,
+			10,
+			files(60_f64, 55_f64),
+			&BTreeMap::new(),
+			&RecordMetadata::new("bbbbbbb"),
+			&options,
+			&retention,
+		);
+
+		let oldest = collection.records[0].files.as_ref().unwrap();
+		assert_eq!(oldest["src/lib.rs"].percentage, Percentage::from_basis_points(9000));
+		assert!(oldest["src/lib.rs"].untested_lines.is_empty());
+		assert_eq!(
+			collection.regressed_files(time::Duration::days(30)),
+			vec![("src/lib.rs", Percentage::from_basis_points(-3000))]
+		);
+
+		let options = RecordOptions { per_commit: true, ..Default::default() };
+		collection.records[1].timestamp -= 60;
+		collection.add_new_record(
+			70_f64,
+			10,
+			files(60_f64, 55_f64),
+			&BTreeMap::new(),
+			&RecordMetadata::new("ccccccc"),
+			&options,
+			&retention,
+		);
+		assert_eq!(collection.records.iter().filter(|record| record.files.is_some()).count(), 1);
+		assert!(collection.regressed_files(time::Duration::days(30)).is_empty());
+	}
+
+	#[test]
+	fn test_add_new_flag_record() {
+		let options = RecordOptions {
+			per_commit: false,
+			retention: None,
+			file_history: false,
+			compression: None,
+			..Default::default()
+		};
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		collection.add_new_flag_record(
+			"unit",
+			50_f64,
+			4,
+			HashMap::from([(
+				String::from("src/lib.rs"),
+				FileCoverageRecord::new(50_f64, 4, vec![3, 4]),
+			)]),
+			&BTreeMap::from([(String::from("backend"), 50_f64)]),
+			&RecordMetadata::new("abc"),
+			&options,
+			&RetentionSettings::default(),
+		);
+		collection.add_new_flag_record(
+			"integration",
+			50_f64,
+			6,
+			HashMap::from([
+				(String::from("src/lib.rs"), FileCoverageRecord::new(75_f64, 4, vec![1])),
+				(String::from("src/main.rs"), FileCoverageRecord::new(0_f64, 2, vec![1, 2])),
+			]),
+			&BTreeMap::new(),
+			&RecordMetadata::new("abc"),
+			&options,
+			&RetentionSettings::default(),
+		);
+
+		assert_eq!(collection.flags.keys().collect::<Vec<_>>(), ["integration", "unit"]);
+		assert_eq!(
+			collection.flags["unit"].latest().map(|record| record.percentage),
+			Some(Percentage::from_basis_points(5000))
+		);
+		assert_eq!(
+			collection.flags["unit"].latest().map(|record| record.labels.clone()),
+			Some(BTreeMap::from([(String::from("backend"), Percentage::from_basis_points(5000))]))
+		);
+		assert_eq!(collection.records.len(), 1);
+
+		let total = collection.latest().unwrap();
+		assert_eq!(total.percentage, Percentage::from_basis_points(6667));
+		assert!(total.labels.is_empty());
+		assert_eq!(total.lines, Some(6));
+		let files = total.files.as_ref().unwrap();
+		assert_eq!(files["src/lib.rs"], FileCoverageRecord::new(100_f64, 4, Vec::new()));
+		assert_eq!(files["src/main.rs"], FileCoverageRecord::new(0_f64, 2, vec![1, 2]));
+	}
+
+	#[test]
+	fn test_reject_unverified() {
+		let options = RecordOptions {
+			per_commit: true,
+			retention: None,
+			file_history: false,
+			compression: None,
+			..Default::default()
+		};
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		for commit in ["aaa", "bbb", "ccc"] {
+			collection.add_new_flag_record(
+				"unit",
+				50_f64,
+				4,
+				HashMap::new(),
+				&BTreeMap::new(),
+				&RecordMetadata::new(commit),
+				&options,
+				&RetentionSettings::default(),
+			);
+		}
+		let unit = collection.flags.get_mut("unit").unwrap();
+		unit.records[0].metadata.as_mut().unwrap().format_version = RECORD_FORMAT_VERSION + 1;
+		unit.records[1].metadata.as_mut().unwrap().commit = String::from("ddd");
+		unit.records[2].metadata = None;
+		let total = &collection.records[0];
+		assert!(total.metadata.as_ref().unwrap().verify(total).is_ok());
+
+		let rejected = collection.reject_unverified();
+		assert_eq!(rejected.len(), 2);
+		assert!(rejected[0].starts_with("flag `unit` record of"));
+		assert!(rejected[0].contains("only format 1 is supported"));
+		assert!(rejected[1].contains("recorded for commit bbb but its metadata names commit ddd"));
+		assert_eq!(collection.flags["unit"].records.len(), 1);
+		assert_eq!(collection.flags["unit"].records[0].commit.as_deref(), Some("ccc"));
+		assert_eq!(collection.records.len(), 3);
+	}
+
+	#[test]
+	fn test_compress_records() {
+		let options = RecordOptions::default();
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		collection.add_new_flag_record(
+			"unit",
+			50_f64,
+			8,
+			HashMap::from([(
+				String::from("src/lib.rs"),
+				FileCoverageRecord::new(50_f64, 8, vec![7, 2, 3, 4, 9]),
+			)]),
+			&BTreeMap::new(),
+			&RecordMetadata::new("abc"),
+			&options,
+			&RetentionSettings::default(),
+		);
+		let uncompressed = serde_json::to_vec(&collection).unwrap();
+
+		for compression in [RecordCompression::Ranges, RecordCompression::Gzip] {
+			let mut compressed = BranchCoverageRecordCollection::from_slice(&uncompressed).unwrap();
+			compressed.compress(compression).unwrap();
+			let latest = compressed.flags["unit"].latest().unwrap();
+			match compression {
+				RecordCompression::Ranges => {
+					let file = &latest.files.as_ref().unwrap()["src/lib.rs"];
+					assert!(file.untested_lines.is_empty());
+					assert_eq!(file.untested_ranges, [(2, 4), (7, 7), (9, 9)]);
+				}
+				RecordCompression::Gzip => {
+					assert!(latest.files.is_none());
+					assert!(latest.files_gz.is_some());
+				}
+			}
+
+			let content = serde_json::to_vec(&compressed).unwrap();
+			let decompressed = BranchCoverageRecordCollection::from_slice(&content).unwrap();
+			let file = &decompressed.latest().unwrap().files.as_ref().unwrap()["src/lib.rs"];
+			assert_eq!(file.untested_lines, [2, 3, 4, 7, 9]);
+			assert!(file.untested_ranges.is_empty());
+			assert_eq!(
+				decompressed.flags["unit"].latest().unwrap().files.as_ref().unwrap()["src/lib.rs"],
+				FileCoverageRecord::new(50_f64, 8, vec![2, 3, 4, 7, 9])
+			);
+		}
+	}
+
+	#[test]
+	fn test_normalise_legacy_file_percentages() {
+		let legacy = br#"{
+			"team": "Other",
+			"records": [
+				{
+					"timestamp": 1,
+					"percentage": 5000,
+					"files": {
+						"src/lib.rs": { "percentage": 50 },
+						"src/main.rs": { "percentage": 10000 }
+					}
+				},
+				{
+					"timestamp": 2,
+					"percentage": 5000,
+					"files": { "src/lib.rs": { "percentage": 5000 } },
+					"metadata": { "format_version": 1, "tool_version": "0.1.1", "commit": "abc" }
+				}
+			]
+		}"#;
+		let file_percentage = |collection: &BranchCoverageRecordCollection, index: usize, path| {
+			collection.records[index].files.as_ref().unwrap()[path].percentage.basis_points()
+		};
+
+		let collection = BranchCoverageRecordCollection::from_slice(legacy).unwrap();
+		assert_eq!(collection.format_version, RECORD_FORMAT_VERSION);
+		assert_eq!(file_percentage(&collection, 0, "src/lib.rs"), 5000);
+		assert_eq!(file_percentage(&collection, 0, "src/main.rs"), 10000);
+		assert_eq!(file_percentage(&collection, 1, "src/lib.rs"), 5000);
+
+		let content = serde_json::to_vec(&collection).unwrap();
+		let reread = BranchCoverageRecordCollection::from_slice(&content).unwrap();
+		assert_eq!(reread, collection);
+	}
+
+	#[test]
+	fn test_merge_renamed() {
+		let options = RecordOptions { per_commit: true, ..RecordOptions::default() };
+		let file = || {
+			let record = FileCoverageRecord::new(50_f64, 4, vec![1, 2]);
+			HashMap::from([(String::from("src/lib.rs"), record)])
+		};
+		let mut renamed = BranchCoverageRecordCollection::new(Team::Workflow);
+		renamed.add_new_flag_record(
+			"unit",
+			50_f64,
+			4,
+			file(),
+			&BTreeMap::new(),
+			&RecordMetadata::new("aaa"),
+			&options,
+			&RetentionSettings::default(),
+		);
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		collection.add_new_record(
+			75_f64,
+			4,
+			file(),
+			&BTreeMap::new(),
+			&RecordMetadata::new("bbb"),
+			&options,
+			&RetentionSettings::default(),
+		);
+		collection.records[0].timestamp += 1;
+
+		collection.merge_renamed(renamed);
+		assert_eq!(collection.team, Team::Other);
+		assert_eq!(
+			collection.records.iter().map(|record| record.commit.as_deref()).collect::<Vec<_>>(),
+			[Some("aaa"), Some("bbb")]
+		);
+		let untested_lines = |record: &BranchCoverageRecord| {
+			record.files.as_ref().unwrap()["src/lib.rs"].untested_lines.clone()
+		};
+		assert!(untested_lines(&collection.records[0]).is_empty());
+		assert_eq!(untested_lines(collection.latest().unwrap()), [1, 2]);
+		assert_eq!(collection.flags["unit"].records.len(), 1);
+	}
+
+	#[test]
+	fn test_backfill() {
+		let day = time::Duration::DAY.whole_seconds();
+		let now = time::OffsetDateTime::from_unix_timestamp(100 * day).unwrap();
+		let record = |timestamp: i64, commit: &str| BranchCoverageRecord {
+			timestamp,
+			percentage: Percentage::from_basis_points(5000),
+			commit: Some(String::from(commit)),
+			lines: Some(10),
+			files: None,
+			files_gz: None,
+			labels: BTreeMap::new(),
+			metadata: Some(RecordMetadata::new(commit)),
+		};
+
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		collection.records.push(record(99 * day, "latest"));
+		let added = collection.backfill(
+			vec![
+				record(99 * day + 60, "same-day"),
+				record(98 * day, "early"),
+				record(98 * day + 60, "late"),
+				record(10 * day, "expired"),
+			],
+			now,
+			&RetentionSettings { days: Some(30), ..RetentionSettings::default() },
+		);
+		assert_eq!(added, 2);
+		assert_eq!(
+			collection.records.iter().map(|record| record.commit.as_deref()).collect::<Vec<_>>(),
+			[Some("late"), Some("latest")]
+		);
+	}
+
+	#[test]
+	fn test_check_upload() {
+		let files = |count: usize| {
+			(0..count)
+				.map(|file| {
+					(format!("src/{}.rs", file), FileCoverageRecord::new(50_f64, 4, vec![1]))
+				})
+				.collect::<HashMap<_, _>>()
+		};
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		let options = RecordOptions {
+			max_file_drop: Some(Percentage::from_basis_points(2000)),
+			max_coverage_change: Some(Percentage::from_basis_points(1000)),
+			..Default::default()
+		};
+		let format = PercentageFormat::default();
+		assert!(collection.check_upload(None, 0_f64, 0, &options, format).is_ok());
+
+		collection.add_new_record(
+			50_f64,
+			40,
+			files(10),
+			&BTreeMap::new(),
+			&RecordMetadata::new("aaa"),
+			&options,
+			&RetentionSettings::default(),
+		);
+		assert!(collection.check_upload(None, 55_f64, 8, &options, format).is_ok());
+		assert!(collection.check_upload(None, 55_f64, 7, &options, format).is_err());
+		assert!(collection.check_upload(None, 39_f64, 10, &options, format).is_err());
+		assert!(collection.check_upload(None, 61_f64, 10, &options, format).is_err());
+		assert!(collection.check_upload(Some("unit"), 0_f64, 0, &options, format).is_ok());
+		assert!(collection.check_upload(None, 0_f64, 0, &RecordOptions::default(), format).is_ok());
+	}
 }
@@ -0,0 +1,388 @@
+//! Read-only queries of the centralised coverage tracking records, for
+//! looking up branches without opening the README
+
+use std::path::Path;
+
+use futures::{StreamExt, TryStreamExt};
+use itertools::Itertools;
+use time::OffsetDateTime;
+
+use super::{
+	fetch_stored_records, make_report_path, open_storage, parse_record_path, sync_records,
+	visualisation::{read_record_collection, walk_records, BranchRecords},
+	BranchCoverageRecord, BranchCoverageRecordCollection, RecordStorage,
+};
+use crate::{
+	coverage::diff::CoverageDiff,
+	percentage::{Percentage, PercentageFormat},
+	provider::VcsProvider,
+	MeowCoverageError,
+};
+
+/// Number of record files fetched concurrently when listing via the API
+const FETCH_CONCURRENCY: usize = 8;
+
+/// Number of records in the history of `tracking show`
+const SHOW_HISTORY: usize = 10;
+
+/// Number of files in the worst covered files of `tracking show`
+const SHOW_WORST_FILES: usize = 10;
+
+/// List all tracked branches with their latest coverage in `format`, read from
+/// the `records` checkout if passed (synced with [sync_records]), from the
+/// storage of the tracking repository via the API otherwise
+pub async fn list_tracked(
+	provider: &dyn VcsProvider,
+	coverage_repo_name: &str,
+	records: Option<&Path>,
+	format: PercentageFormat,
+) -> Result<String, MeowCoverageError> {
+	let branches = match records {
+		Some(records) => {
+			sync_records(records).await?;
+			walk_records(records)?
+		}
+		None => {
+			let storage = open_storage(provider, coverage_repo_name).await?;
+			let storage = storage.as_ref();
+			let paths = storage.list().await?;
+
+			futures::stream::iter(paths.iter().filter_map(|path| parse_record_path(path)))
+				.map(|(owner, repo, branch)| async move {
+					let collection = fetch_stored_records(storage, owner, repo, branch).await?;
+					Ok::<_, MeowCoverageError>(collection.map(|collection| BranchRecords {
+						owner: String::from(owner),
+						repo: String::from(repo),
+						branch: String::from(branch),
+						collection,
+					}))
+				})
+				.buffer_unordered(FETCH_CONCURRENCY)
+				.try_filter_map(|branch| async move { Ok(branch) })
+				.try_collect()
+				.await?
+		}
+	};
+
+	Ok(format_branch_list(&branches, format))
+}
+
+/// Show the history, deltas, and worst covered files of `branch` of
+/// `repo_name` (`OWNER/REPO`) with the percentages in `format`, read like
+/// [list_tracked]
+pub async fn show_tracked(
+	provider: &dyn VcsProvider,
+	coverage_repo_name: &str,
+	records: Option<&Path>,
+	repo_name: &str,
+	branch: &str,
+	format: PercentageFormat,
+) -> Result<String, MeowCoverageError> {
+	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+	let collection = match records {
+		Some(records) => {
+			sync_records(records).await?;
+			let path = records.join(make_report_path(owner, repo, branch));
+			match path.is_file() {
+				true => Some(read_record_collection(&path)?),
+				false => None,
+			}
+		}
+		None => {
+			let storage = open_storage(provider, coverage_repo_name).await?;
+			fetch_stored_records(storage.as_ref(), owner, repo, branch).await?
+		}
+	};
+
+	match collection {
+		Some(collection) => Ok(format_branch_details(owner, repo, branch, &collection, format)),
+		None => Err(MeowCoverageError::BranchNotTracked(format!("{} ({})", repo_name, branch))),
+	}
+}
+
+/// Compare the records of the refs `base` and `head` of `repo_name`
+/// (`OWNER/REPO`) read from `storage`. A ref is a tracked branch for its
+/// latest record, or `BRANCH@COMMIT` for the record of an earlier commit of
+/// the branch
+pub async fn compare_tracked(
+	storage: &dyn RecordStorage,
+	repo_name: &str,
+	base: &str,
+	head: &str,
+) -> Result<CoverageDiff, MeowCoverageError> {
+	let (owner, repo) = repo_name.split_once('/').ok_or(MeowCoverageError::RepoNameMissingSlash)?;
+
+	let mut collections = Vec::with_capacity(2);
+	for git_ref in [base, head] {
+		let (branch, _) = split_ref(git_ref);
+		let collection =
+			fetch_stored_records(storage, owner, repo, branch).await?.ok_or_else(|| {
+				MeowCoverageError::BranchNotTracked(format!("{} ({})", repo_name, branch))
+			})?;
+		collections.push(collection);
+	}
+
+	Ok(CoverageDiff::from_records(
+		ref_record(&collections[0], repo_name, base)?,
+		ref_record(&collections[1], repo_name, head)?,
+	))
+}
+
+/// Split a ref into its branch and, for `BRANCH@COMMIT`, its commit
+fn split_ref(git_ref: &str) -> (&str, Option<&str>) {
+	match git_ref.rsplit_once('@') {
+		Some((branch, commit)) => (branch, Some(commit)),
+		None => (git_ref, None),
+	}
+}
+
+/// Pick the record of `git_ref` from the `collection` of its branch
+fn ref_record<'a>(
+	collection: &'a BranchCoverageRecordCollection,
+	repo_name: &str,
+	git_ref: &str,
+) -> Result<&'a BranchCoverageRecord, MeowCoverageError> {
+	let record = match split_ref(git_ref) {
+		(_, Some(commit)) => collection.record_of_commit(commit),
+		(_, None) => collection.latest(),
+	};
+	record
+		.ok_or_else(|| MeowCoverageError::CommitNotRecorded(format!("{} ({})", repo_name, git_ref)))
+}
+
+/// Date of a record timestamp, the raw timestamp if it is out of range
+fn format_date(timestamp: i64) -> String {
+	OffsetDateTime::from_unix_timestamp(timestamp)
+		.map_or_else(|_| timestamp.to_string(), |time| time.date().to_string())
+}
+
+/// Format a delta with its sign in `format`, `-` if there is none
+fn format_delta(delta: Option<Percentage>, format: PercentageFormat) -> String {
+	delta.map_or_else(|| String::from("-"), |delta| format!("{:+}%", delta.display_with(format)))
+}
+
+/// Tab separated list of the branches (repository, branch, latest coverage,
+/// and date of the latest record), sorted by repository and branch
+fn format_branch_list(branches: &[BranchRecords], format: PercentageFormat) -> String {
+	branches
+		.iter()
+		.sorted_by(|l, r| (&l.owner, &l.repo, &l.branch).cmp(&(&r.owner, &r.repo, &r.branch)))
+		.map(|branch| {
+			let (coverage, updated) = match branch.collection.latest() {
+				Some(latest) => (
+					format!("{}%", latest.percentage.display_with(format)),
+					format_date(latest.timestamp),
+				),
+				None => (String::from("-"), String::from("-")),
+			};
+			format!(
+				"{}/{}\t{}\t{}\t{}\n",
+				branch.owner, branch.repo, branch.branch, coverage, updated
+			)
+		})
+		.fold(String::from("REPOSITORY\tBRANCH\tCOVERAGE\tUPDATED\n"), |l, r| l + r.as_str())
+}
+
+/// Latest coverage, deltas, the [SHOW_HISTORY] newest records, and the
+/// [SHOW_WORST_FILES] worst covered files of a branch, with the percentages
+/// in `format`
+fn format_branch_details(
+	owner: &str,
+	repo: &str,
+	branch: &str,
+	collection: &BranchCoverageRecordCollection,
+	format: PercentageFormat,
+) -> String {
+	let mut details = format!("{}/{} ({}), team {}\n", owner, repo, branch, collection.team);
+	let Some(latest) = collection.latest() else {
+		details.push_str("No records\n");
+		return details;
+	};
+
+	details.push_str(&format!(
+		"Coverage: {}% on {}\nDeltas: last {}, 7 days {}, 30 days {}, 90 days {}\n",
+		latest.percentage.display_with(format),
+		format_date(latest.timestamp),
+		format_delta(collection.last_delta(), format),
+		format_delta(collection.delta_last_7_days(), format),
+		format_delta(collection.delta_last_30_days(), format),
+		format_delta(collection.delta_last_90_days(), format),
+	));
+
+	details.push_str("\nHistory:\n");
+	for record in
+		collection.records.iter().sorted_by_key(|record| record.timestamp).rev().take(SHOW_HISTORY)
+	{
+		let commit =
+			record.commit.as_deref().map_or("-", |commit| commit.get(..7).unwrap_or(commit));
+		details.push_str(&format!(
+			"  {}  {}  {}%\n",
+			format_date(record.timestamp),
+			commit,
+			record.percentage.display_with(format)
+		));
+	}
+
+	let worst_files = latest
+		.files
+		.iter()
+		.flatten()
+		.sorted_by(|(l_path, l), (r_path, r)| (l.percentage, l_path).cmp(&(r.percentage, r_path)))
+		.take(SHOW_WORST_FILES)
+		.collect_vec();
+	if !worst_files.is_empty() {
+		details.push_str("\nWorst covered files:\n");
+		for (path, file) in worst_files {
+			details.push_str(&format!("  {}%  {}\n", file.percentage.display_with(format), path));
+		}
+	}
+
+	details
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::{BTreeMap, HashMap, HashSet};
+
+	use super::{compare_tracked, list_tracked, show_tracked};
+	use crate::{
+		percentage::{Percentage, PercentageFormat},
+		provider::{fake::FakeProvider, RepoFile},
+		tracking::{
+			BranchCoverageRecord, BranchCoverageRecordCollection, FileCoverageRecord, GitStorage,
+			Team,
+		},
+		MeowCoverageError,
+	};
+
+	#[tokio::test]
+	async fn test_list_and_show_tracked() {
+		let record = |timestamp, basis_points, commit: &str| BranchCoverageRecord {
+			timestamp,
+			percentage: Percentage::from_basis_points(basis_points),
+			commit: Some(String::from(commit)),
+			lines: None,
+			files: None,
+			files_gz: None,
+			labels: BTreeMap::new(),
+			metadata: None,
+		};
+		let mut collection = BranchCoverageRecordCollection::new(Team::Workflow);
+		collection.records.push(record(1_700_000_000, 5000, "aaaaaaaaaa"));
+		collection.records.push(record(1_700_086_400, 6250, "bbbbbbbbbb"));
+		collection.records[1].files = Some(HashMap::from([
+			(String::from("src/lib.rs"), FileCoverageRecord::new(90_f64, 10, vec![3])),
+			(String::from("src/main.rs"), FileCoverageRecord::new(12.5, 8, vec![1, 2])),
+		]));
+
+		let provider = FakeProvider {
+			tree: HashSet::from([
+				String::from("meowcov.config.json"),
+				String::from("famedly/meow/main.meowcov.json"),
+			]),
+			..FakeProvider::default()
+		};
+		provider.files.lock().unwrap().insert(
+			String::from("famedly/coverage/records/famedly/meow/main.meowcov.json"),
+			RepoFile { content: serde_json::to_vec(&collection).unwrap(), sha: String::from("1") },
+		);
+		let format = PercentageFormat::default();
+
+		assert_eq!(
+			list_tracked(&provider, "famedly/coverage", None, format).await.unwrap(),
+			"REPOSITORY\tBRANCH\tCOVERAGE\tUPDATED\nfamedly/meow\tmain\t62.50%\t2023-11-15\n"
+		);
+
+		let details =
+			show_tracked(&provider, "famedly/coverage", None, "famedly/meow", "main", format)
+				.await
+				.unwrap();
+		assert!(details
+			.starts_with("famedly/meow (main), team Workflow\nCoverage: 62.50% on 2023-11-15\n"));
+		assert!(details.contains("Deltas: last +12.50%, 7 days"));
+		assert!(details
+			.contains("History:\n  2023-11-15  bbbbbbb  62.50%\n  2023-11-14  aaaaaaa  50.00%\n"));
+		assert!(details
+			.ends_with("Worst covered files:\n  12.50%  src/main.rs\n  90.00%  src/lib.rs\n"));
+
+		assert!(matches!(
+			show_tracked(&provider, "famedly/coverage", None, "famedly/meow", "dev", format).await,
+			Err(MeowCoverageError::BranchNotTracked(branch)) if branch == "famedly/meow (dev)"
+		));
+	}
+
+	#[tokio::test]
+	async fn test_compare_tracked() {
+		let record = |timestamp, basis_points, commit: &str, files: Vec<(&str, f64, Vec<u32>)>| {
+			BranchCoverageRecord {
+				timestamp,
+				percentage: Percentage::from_basis_points(basis_points),
+				commit: Some(String::from(commit)),
+				lines: None,
+				files: Some(
+					files
+						.into_iter()
+						.map(|(path, percentage, untested_lines)| {
+							(
+								String::from(path),
+								FileCoverageRecord::new(percentage, 4, untested_lines),
+							)
+						})
+						.collect(),
+				),
+				files_gz: None,
+				labels: BTreeMap::new(),
+				metadata: None,
+			}
+		};
+		let provider = FakeProvider::default();
+		let store = |branch: &str, records| {
+			let mut collection = BranchCoverageRecordCollection::new(Team::Workflow);
+			collection.records = records;
+			provider.files.lock().unwrap().insert(
+				format!("famedly/coverage/records/famedly/meow/{}.meowcov.json", branch),
+				RepoFile {
+					content: serde_json::to_vec(&collection).unwrap(),
+					sha: String::from("1"),
+				},
+			);
+		};
+		store(
+			"main",
+			vec![
+				record(1_700_000_000, 5000, "aaaaaaaaaa", vec![("src/lib.rs", 50_f64, vec![1, 2])]),
+				record(1_700_086_400, 7500, "bbbbbbbbbb", vec![("src/lib.rs", 75_f64, vec![2])]),
+			],
+		);
+		store(
+			"release",
+			vec![record(
+				1_700_172_800,
+				6250,
+				"cccccccccc",
+				vec![("src/lib.rs", 50_f64, vec![2, 4]), ("src/new.rs", 75_f64, vec![1])],
+			)],
+		);
+		let storage = GitStorage::new(&provider, "famedly/coverage").unwrap();
+
+		let diff = compare_tracked(&storage, "famedly/meow", "main", "release").await.unwrap();
+		assert_eq!((diff.old_percentage, diff.new_percentage), (Some(75_f64), Some(62.5)));
+		assert_eq!(diff.files.len(), 2);
+		assert_eq!(diff.files[0].filename, "src/lib.rs");
+		assert_eq!(diff.files[0].newly_uncovered, [4]);
+		assert_eq!(diff.files[0].delta(), Some(-25_f64));
+		assert_eq!(diff.files[1].newly_uncovered, [1]);
+
+		let diff = compare_tracked(&storage, "famedly/meow", "main@aaaa", "main").await.unwrap();
+		assert_eq!(diff.files[0].newly_covered, [1]);
+
+		assert!(matches!(
+			compare_tracked(&storage, "famedly/meow", "main@dddd", "main").await,
+			Err(MeowCoverageError::CommitNotRecorded(git_ref)) if git_ref == "famedly/meow (main@dddd)"
+		));
+		assert!(matches!(
+			compare_tracked(&storage, "famedly/meow", "main", "dev").await,
+			Err(MeowCoverageError::BranchNotTracked(branch)) if branch == "famedly/meow (dev)"
+		));
+	}
+}
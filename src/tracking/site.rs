@@ -0,0 +1,498 @@
+//! Module for building a static HTML site of the centralised coverage tracking
+//! records, as an alternative to the README for publishing via GitHub Pages
+
+use std::{fmt::Write, path::Path};
+
+use itertools::Itertools;
+use time::OffsetDateTime;
+
+use super::{
+	read_config, sync_records,
+	visualisation::{
+		aggregate, build_heatmap, health_indicator, sort_entries, trend_arrow, walk_records,
+		ReadmeCoverageEntry,
+	},
+	BranchCoverageRecordCollection, Team,
+};
+use crate::{
+	percentage::{Percentage, PercentageFormat},
+	MeowCoverageError,
+};
+
+/// Stylesheet shared by all pages
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em auto; max-width: 1100px; padding: 0 1em; color: #222; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 2em; }
+th, td { border-bottom: 1px solid #ddd; padding: 0.4em; text-align: left; }
+th { background: #f4f4f4; }
+input, select { padding: 0.4em; margin-right: 1em; }
+.chart { width: 100%; height: auto; }
+.muted { color: #777; }
+";
+
+/// Script filtering the branches of the index by the search text and team
+const FILTER_SCRIPT: &str = "\
+function filterBranches() {
+	const search = document.getElementById('search').value.toLowerCase();
+	const team = document.getElementById('team').value;
+	for (const section of document.querySelectorAll('section[data-team]')) {
+		let visible = 0;
+		for (const row of section.querySelectorAll('tr[data-search]')) {
+			const shown = row.dataset.search.includes(search);
+			row.hidden = !shown;
+			visible += shown;
+		}
+		section.hidden = (team !== '' && section.dataset.team !== team) || visible === 0;
+	}
+}
+";
+
+/// Teams in the order they are listed on the index
+const TEAMS: [Team; 6] = [
+	Team::InstantMessaging,
+	Team::Workflow,
+	Team::Infrastructure,
+	Team::Product,
+	Team::Security,
+	Team::Other,
+];
+
+/// Escape text for use in HTML content and attributes
+fn escape_html(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&#39;")
+}
+
+/// Wrap a page body in an HTML document
+fn build_page(title: &str, body: &str, script: &str) -> String {
+	format!(
+		"<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{title}</title>
+<style>
+{style}</style>
+</head>
+<body>
+{body}<script>
+{script}</script>
+</body>
+</html>
+",
+		title = escape_html(title),
+		style = STYLE,
+		body = body,
+		script = script
+	)
+}
+
+/// Path of the page of a branch relative to the site root
+fn branch_page_path(owner: &str, repo: &str, branch: &str) -> String {
+	format!("{}/{}/{}.html", owner, repo, branch)
+}
+
+/// Builds an SVG line chart of the coverage of a branch over time, with the
+/// percentages of its tooltips in `format`
+fn build_chart(
+	record_collection: &BranchCoverageRecordCollection,
+	format: PercentageFormat,
+) -> String {
+	/// Width of the chart
+	const WIDTH: f64 = 800_f64;
+	/// Height of the chart
+	const HEIGHT: f64 = 240_f64;
+	/// Space for the axis labels
+	const MARGIN: f64 = 40_f64;
+
+	let records = record_collection
+		.records
+		.iter()
+		.sorted_by_key(|record| record.timestamp)
+		.collect::<Vec<_>>();
+	let (Some(first), Some(last)) = (records.first(), records.last()) else {
+		return String::new();
+	};
+	let span = (last.timestamp - first.timestamp).max(1) as f64;
+
+	let x = |timestamp: i64| {
+		((timestamp - first.timestamp) as f64 / span).mul_add(WIDTH - MARGIN, MARGIN)
+	};
+	let y = |percentage: Percentage| {
+		(percentage.as_f64() / 100_f64).mul_add(-(HEIGHT - MARGIN), HEIGHT - MARGIN / 2_f64)
+	};
+
+	let grid = [0_i16, 25, 50, 75, 100]
+		.into_iter()
+		.map(|label| {
+			format!(
+				"<line x1=\"{x1}\" y1=\"{y:.1}\" x2=\"{x2}\" y2=\"{y:.1}\" stroke=\"#ddd\"/><text x=\"0\" y=\"{y:.1}\" font-size=\"12\" fill=\"#777\">{label}%</text>",
+				x1 = MARGIN,
+				x2 = WIDTH,
+				y = y(Percentage::from_basis_points(label * 100)),
+				label = label
+			)
+		})
+		.collect::<String>();
+	let points = records
+		.iter()
+		.map(|record| format!("{:.1},{:.1}", x(record.timestamp), y(record.percentage)))
+		.join(" ");
+	let markers = records
+		.iter()
+		.map(|record| {
+			format!(
+				"<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"#2a7ae2\"><title>{}: {}%</title></circle>",
+				x(record.timestamp),
+				y(record.percentage),
+				format_date(record.timestamp),
+				record.percentage.display_with(format)
+			)
+		})
+		.collect::<String>();
+
+	format!(
+		"<svg class=\"chart\" viewBox=\"0 0 {} {}\" role=\"img\" aria-label=\"Coverage over time\">{}<polyline points=\"{}\" fill=\"none\" stroke=\"#2a7ae2\" stroke-width=\"2\"/>{}</svg>\n",
+		WIDTH, HEIGHT, grid, points, markers
+	)
+}
+
+/// Format the date of a timestamp
+fn format_date(timestamp: i64) -> String {
+	OffsetDateTime::from_unix_timestamp(timestamp)
+		.map_or_else(|_| timestamp.to_string(), |time| time.date().to_string())
+}
+
+/// Builds the page of a branch with its coverage chart, history, and the
+/// coverage of its files, with the percentages in `format`
+fn build_branch_page(
+	owner: &str,
+	repo: &str,
+	branch: &str,
+	record_collection: &BranchCoverageRecordCollection,
+	format: PercentageFormat,
+) -> String {
+	let root = "../".repeat(branch_page_path(owner, repo, branch).matches('/').count());
+
+	let mut history = String::new();
+	let records = record_collection
+		.records
+		.iter()
+		.sorted_by(|l, r| Ord::cmp(&r.timestamp, &l.timestamp))
+		.collect::<Vec<_>>();
+	for (idx, record) in records.iter().enumerate() {
+		let commit = match &record.commit {
+			Some(commit) => format!(
+				"<a href=\"https://github.com/{}/{}/commit/{}\"><code>{}</code></a>",
+				escape_html(owner),
+				escape_html(repo),
+				escape_html(commit),
+				escape_html(commit.get(..7).unwrap_or(commit))
+			),
+			None => String::from("<span class=\"muted\">Unknown</span>"),
+		};
+		let delta = match records.get(idx + 1) {
+			Some(previous) => {
+				format!("{}%", (record.percentage - previous.percentage).display_with(format))
+			}
+			None => String::from("-"),
+		};
+		let _ = writeln!(
+			history,
+			"<tr><td>{}</td><td>{}</td><td>{}%</td><td>{}</td></tr>",
+			format_date(record.timestamp),
+			commit,
+			record.percentage.display_with(format),
+			delta
+		);
+	}
+
+	let files = match record_collection.latest().and_then(|record| record.files.as_ref()) {
+		Some(files) => {
+			let rows = files
+				.iter()
+				.sorted_by(|(l_path, l), (r_path, r)| {
+					l.percentage.cmp(&r.percentage).then_with(|| l_path.cmp(r_path))
+				})
+				.map(|(path, file)| {
+					format!(
+						"<tr><td>{}</td><td>{}%</td><td>{}</td></tr>\n",
+						escape_html(path),
+						file.percentage.display_with(format),
+						file.untested_lines.len()
+					)
+				})
+				.collect::<String>();
+			format!(
+				"<h2>Files</h2>\n{}<table>\n<tr><th>File</th><th>Coverage</th><th>Untested Lines</th></tr>\n{}</table>\n",
+				build_heatmap(record_collection, format).unwrap_or_default(),
+				rows
+			)
+		}
+		None => String::new(),
+	};
+
+	let title = format!("{}/{} ({})", owner, repo, branch);
+	build_page(
+		&title,
+		&format!(
+			"<p><a href=\"{root}index.html\">All branches</a></p>
+<h1>{title}</h1>
+<p>Team: {team}</p>
+{chart}<h2>History</h2>
+<table>
+<tr><th>Date</th><th>Commit</th><th>Coverage</th><th>Delta</th></tr>
+{history}</table>
+{files}",
+			root = root,
+			title = escape_html(&title),
+			team = record_collection.team,
+			chart = build_chart(record_collection, format),
+			history = history,
+			files = files
+		),
+		"",
+	)
+}
+
+/// Builds the summary paragraph of the aggregate coverage of a group of
+/// branches with the percentages in `format`
+fn build_summary<'a>(
+	branches: impl Iterator<Item = &'a ReadmeCoverageEntry> + Clone,
+	format: PercentageFormat,
+) -> String {
+	let Some((coverage, delta)) = aggregate(branches.clone()) else {
+		return String::new();
+	};
+
+	format!(
+		"<p><strong>Coverage: {}%</strong> {} {}% in 30 days, {} branches</p>\n",
+		coverage.display_with(format),
+		trend_arrow(delta),
+		delta.display_with(format),
+		branches.count()
+	)
+}
+
+/// Builds the index listing the branches of every team, with a search field
+/// and team filter, and the percentages in `format`
+fn build_index(
+	coverage_repo: &str,
+	teams: &[Vec<ReadmeCoverageEntry>; 6],
+	threshold: Percentage,
+	format: PercentageFormat,
+) -> String {
+	let mut sections = String::new();
+
+	for team in TEAMS {
+		let branches = &teams[team as usize];
+		if branches.is_empty() {
+			continue;
+		}
+
+		let rows = branches
+			.iter()
+			.map(|entry| {
+				let name = format!("{}/{} ({})", entry.owner, entry.repo, entry.branch);
+				format!(
+					"<tr data-search=\"{search}\"><td>{health}</td><td><a href=\"{page}\">{name}</a></td><td>{coverage}%</td><td>{delta7}%</td><td>{delta30}%</td><td>{updated}{stale}</td></tr>\n",
+					search = escape_html(&name.to_lowercase()),
+					health = health_indicator(entry, threshold),
+					page = escape_html(&branch_page_path(&entry.owner, &entry.repo, &entry.branch)),
+					name = escape_html(&name),
+					coverage = entry.coverage.display_with(format),
+					delta7 = entry.delta_7_days.display_with(format),
+					delta30 = entry.delta_30_days.display_with(format),
+					updated = entry.last_update.date(),
+					stale = match entry.stale {
+						true => " ⚠️",
+						false => "",
+					}
+				)
+			})
+			.collect::<String>();
+
+		let _ = write!(
+			sections,
+			"<section data-team=\"{team:?}\">
+<h2>{team}</h2>
+{summary}<table>
+<tr><th></th><th>Repository (Branch)</th><th>Coverage</th><th>Delta (7 Days)</th><th>Delta (30 Days)</th><th>Last Updated</th></tr>
+{rows}</table>
+</section>
+",
+			team = team,
+			summary = build_summary(branches.iter(), format),
+			rows = rows
+		);
+	}
+
+	let options = TEAMS
+		.iter()
+		.map(|team| format!("<option value=\"{:?}\">{}</option>", team, team))
+		.collect::<String>();
+	let title = format!("Coverage Reports of {}", coverage_repo);
+
+	build_page(
+		&title,
+		&format!(
+			"<h1>{title}</h1>
+{summary}<p>
+<input id=\"search\" type=\"search\" placeholder=\"Search repositories\" oninput=\"filterBranches()\">
+<select id=\"team\" onchange=\"filterBranches()\"><option value=\"\">All teams</option>{options}</select>
+</p>
+{sections}",
+			title = escape_html(&title),
+			summary = build_summary(teams.iter().flatten(), format),
+			options = options,
+			sections = sections
+		),
+		FILTER_SCRIPT,
+	)
+}
+
+/// Build a static HTML site of the records at `records` into `output`: an
+/// index of all branches by team and a page for every branch with the
+/// percentages in `format`, returns the number of branch pages
+pub async fn build_site(
+	records: &Path,
+	coverage_repo: &str,
+	output: &Path,
+	format: PercentageFormat,
+) -> Result<usize, MeowCoverageError> {
+	sync_records(records).await?;
+	let config = read_config(records)?;
+	let threshold = Percentage::from_f64(config.threshold);
+	let now = OffsetDateTime::now_utc();
+
+	let mut teams: [Vec<ReadmeCoverageEntry>; 6] =
+		[Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+	let mut pages = 0;
+
+	for branch in walk_records(records)? {
+		let page_path = output.join(branch_page_path(&branch.owner, &branch.repo, &branch.branch));
+		if let Some(parent) = page_path.parent() {
+			std::fs::create_dir_all(parent)?;
+		}
+		std::fs::write(
+			page_path,
+			build_branch_page(
+				&branch.owner,
+				&branch.repo,
+				&branch.branch,
+				&branch.collection,
+				format,
+			),
+		)?;
+		pages += 1;
+
+		let team = branch.collection.team as usize;
+		let stale = config.stale_days.is_some_and(|days| branch.collection.is_stale(now, days));
+		if let Some(entry) = ReadmeCoverageEntry::from_collection(
+			&branch.owner,
+			&branch.repo,
+			&branch.branch,
+			branch.collection,
+			stale,
+		) {
+			teams[team].push(entry);
+		}
+	}
+
+	for branches in &mut teams {
+		sort_entries(branches, config.sort);
+	}
+
+	std::fs::create_dir_all(output)?;
+	std::fs::write(
+		output.join("index.html"),
+		build_index(coverage_repo, &teams, threshold, format),
+	)?;
+
+	Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+
+	use super::{build_chart, build_site, escape_html};
+	use crate::{
+		percentage::{Percentage, PercentageFormat},
+		tracking::{
+			BranchCoverageRecord, BranchCoverageRecordCollection, Team, RECORD_FORMAT_VERSION,
+		},
+	};
+
+	#[tokio::test]
+	async fn test_build_site() {
+		let root = std::env::temp_dir().join("meow-coverage-test-build-site");
+		let _ = std::fs::remove_dir_all(&root);
+		let records = root.join("records");
+		let output = root.join("site");
+		std::fs::create_dir_all(records.join("famedly/meow")).unwrap();
+
+		let collection = BranchCoverageRecordCollection {
+			team: Team::Workflow,
+			records: vec![
+				BranchCoverageRecord {
+					timestamp: 1_700_000_000,
+					percentage: Percentage::from_basis_points(5000),
+					commit: Some(String::from("abcdef123")),
+					lines: None,
+					files: None,
+					files_gz: None,
+					labels: BTreeMap::new(),
+					metadata: None,
+				},
+				BranchCoverageRecord {
+					timestamp: 1_700_086_400,
+					percentage: Percentage::from_basis_points(7500),
+					commit: None,
+					lines: None,
+					files: None,
+					files_gz: None,
+					labels: BTreeMap::new(),
+					metadata: None,
+				},
+			],
+			retention: None,
+			flags: BTreeMap::new(),
+			format_version: RECORD_FORMAT_VERSION,
+		};
+		std::fs::write(
+			records.join("famedly/meow/<script>.meowcov.json"),
+			serde_json::to_vec(&collection).unwrap(),
+		)
+		.unwrap();
+
+		assert_eq!(
+			build_site(&records, "famedly/coverage", &output, PercentageFormat::default())
+				.await
+				.unwrap(),
+			1
+		);
+
+		let index = std::fs::read_to_string(output.join("index.html")).unwrap();
+		assert!(index.contains("<section data-team=\"Workflow\">"));
+		assert!(index.contains("href=\"famedly/meow/&lt;script&gt;.html\""));
+		assert!(!index.contains("<script>.html"));
+
+		let page = std::fs::read_to_string(output.join("famedly/meow/<script>.html")).unwrap();
+		assert!(page.contains("<a href=\"../../index.html\">"));
+		assert!(page.contains("https://github.com/famedly/meow/commit/abcdef123"));
+		assert!(page.contains("<td>25.00%</td>"));
+		assert!(page.contains("<polyline points=\"40.0,120.0 800.0,70.0\""));
+	}
+
+	#[test]
+	fn test_build_chart_empty() {
+		let collection = BranchCoverageRecordCollection::new(Team::Other);
+		assert_eq!(build_chart(&collection, PercentageFormat::default()), "");
+		assert_eq!(escape_html("<a href=\"'&'\">"), "&lt;a href=&quot;&#39;&amp;&#39;&quot;&gt;");
+	}
+}
@@ -0,0 +1,140 @@
+//! This module contains helpers for deriving the team responsible for a
+//! repository from its CODEOWNERS file or the teams with access to it
+
+use super::Team;
+use crate::{provider::VcsProvider, MeowCoverageError};
+
+/// Locations of the CODEOWNERS file, in order of precedence
+pub const CODEOWNERS_PATHS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Source to derive the [Team] responsible for a repository from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TeamSource {
+	/// Owners of the whole repository (the `*` rule) in the CODEOWNERS file
+	Codeowners,
+	/// Teams with access to the repository
+	RepositoryTeams,
+}
+
+/// A rule of a CODEOWNERS file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeownersRule {
+	/// Path pattern
+	pub pattern: String,
+	/// Owners (`@user`, `@org/team`, or email addresses)
+	pub owners: Vec<String>,
+}
+
+/// Parse the rules of a CODEOWNERS file, in file order
+#[must_use]
+pub fn parse_codeowners(content: &str) -> Vec<CodeownersRule> {
+	content
+		.lines()
+		.map(|line| line.split_once('#').map_or(line, |(rule, _)| rule))
+		.filter_map(|line| {
+			let mut parts = line.split_whitespace();
+			Some(CodeownersRule {
+				pattern: String::from(parts.next()?),
+				owners: parts.map(String::from).collect(),
+			})
+		})
+		.collect()
+}
+
+/// Owners of the whole repository, of the last rule matching every file
+#[must_use]
+pub fn repository_owners(rules: &[CodeownersRule]) -> &[String] {
+	rules
+		.iter()
+		.rev()
+		.find(|rule| matches!(rule.pattern.as_str(), "*" | "/*" | "/" | "**" | "/**"))
+		.map_or(&[], |rule| rule.owners.as_slice())
+}
+
+/// Fetch the CODEOWNERS file of a repository at a commit, returns [None] if
+/// there is none
+pub async fn get_codeowners(
+	provider: &dyn VcsProvider,
+	owner: &str,
+	repo: &str,
+	commit_sha: &str,
+) -> Result<Option<Vec<CodeownersRule>>, MeowCoverageError> {
+	for path in CODEOWNERS_PATHS {
+		if let Some(content) = provider.get_file_at_commit(owner, repo, commit_sha, path).await? {
+			return Ok(Some(parse_codeowners(&String::from_utf8_lossy(&content))));
+		}
+	}
+
+	Ok(None)
+}
+
+/// Derive the team responsible for a repository, the first owner (or team
+/// with access) named like a [Team], returns [None] if there is none
+pub async fn derive_team(
+	provider: &dyn VcsProvider,
+	owner: &str,
+	repo: &str,
+	commit_sha: &str,
+	source: TeamSource,
+) -> Result<Option<Team>, MeowCoverageError> {
+	let names = match source {
+		TeamSource::Codeowners => get_codeowners(provider, owner, repo, commit_sha)
+			.await?
+			.map(|rules| repository_owners(&rules).to_vec())
+			.unwrap_or_default(),
+		TeamSource::RepositoryTeams => provider.list_repository_teams(owner, repo).await?,
+	};
+
+	Ok(names.iter().find_map(|name| Team::from_name(name)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{derive_team, parse_codeowners, repository_owners, TeamSource};
+	use crate::{
+		provider::{fake::FakeProvider, RepoFile},
+		tracking::Team,
+	};
+
+	#[test]
+	fn test_parse_codeowners() {
+		let rules = parse_codeowners(
+			"# Owners\n* @famedly/workflow @alice # default\n\n/docs/ docs@famedly.com\n/* @famedly/instant-messaging\n",
+		);
+		assert_eq!(rules.len(), 3);
+		assert_eq!(rules[1].pattern, "/docs/");
+		assert_eq!(repository_owners(&rules), ["@famedly/instant-messaging"]);
+		assert!(repository_owners(&rules[1..2]).is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_derive_team() {
+		let provider = FakeProvider {
+			teams: vec![String::from("admins"), String::from("infrastructure")],
+			..Default::default()
+		};
+		provider.files.lock().unwrap().insert(
+			String::from("famedly/meow/abcdef/.github/CODEOWNERS"),
+			RepoFile { content: b"* @alice @famedly/security\n".to_vec(), sha: String::new() },
+		);
+
+		assert_eq!(
+			derive_team(&provider, "famedly", "meow", "abcdef", TeamSource::Codeowners)
+				.await
+				.unwrap(),
+			Some(Team::Security)
+		);
+		assert_eq!(
+			derive_team(&provider, "famedly", "meow", "abcdef", TeamSource::RepositoryTeams)
+				.await
+				.unwrap(),
+			Some(Team::Infrastructure)
+		);
+		assert_eq!(
+			derive_team(&provider, "famedly", "meow", "fedcba", TeamSource::Codeowners)
+				.await
+				.unwrap(),
+			None
+		);
+	}
+}
@@ -1,16 +1,124 @@
 //! Module for building the centralised visualisation resources
 
-use std::{borrow::Cow, path::Path};
+use std::{
+	borrow::Cow,
+	num::NonZeroUsize,
+	path::{Path, PathBuf},
+	sync::atomic::{AtomicUsize, Ordering},
+};
 
+use itertools::Itertools;
 use time::OffsetDateTime;
 
-use super::{BranchCoverageRecordCollection, Team};
-use crate::{tracking::PercentWrapper, MeowCoverageError};
+use super::{
+	make_report_link, read_config, BranchCoverageRecordCollection, ReadmeSort, Team, TrackingConfig,
+};
+use crate::{
+	coverage::{heatmap::Heatmap, helpers::gather_lines},
+	percentage::{Percentage, PercentageFormat},
+	MeowCoverageError,
+};
 
-/// Try and collect records
-fn try_collect_records(records: &Path) -> Result<[Vec<ReadmeCoverageEntry>; 6], MeowCoverageError> {
-	let mut teams: [Vec<ReadmeCoverageEntry>; 6] =
-		[Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+/// Width and height of the heatmaps of the branch reports
+const HEATMAP_SIZE: (u32, u32) = (800, 400);
+
+/// Record collection of a branch in the records directory
+pub struct BranchRecords {
+	/// Repo Owner
+	pub owner: String,
+	/// Repo Name
+	pub repo: String,
+	/// Repo Branch
+	pub branch: String,
+	/// Records of the branch
+	pub collection: BranchCoverageRecordCollection,
+}
+
+/// Read the record collection at `path`, dropping the records whose
+/// metadata doesn't verify (for example written by a newer version)
+pub fn read_record_collection(
+	path: &Path,
+) -> Result<BranchCoverageRecordCollection, MeowCoverageError> {
+	let mut collection = BranchCoverageRecordCollection::from_slice(&std::fs::read(path)?)?;
+	for reason in collection.reject_unverified() {
+		#[allow(clippy::print_stderr)]
+		{
+			eprintln!("Rejecting {} in {}", reason, path.display());
+		}
+	}
+	Ok(collection)
+}
+
+/// Record file of a branch in the records directory
+struct BranchPath {
+	/// Repo Owner
+	owner: String,
+	/// Repo Name
+	repo: String,
+	/// Repo Branch
+	branch: String,
+	/// Path of the record file
+	path: PathBuf,
+}
+
+/// Read the record collections of all branches in the records directory
+pub fn walk_records(records: &Path) -> Result<Vec<BranchRecords>, MeowCoverageError> {
+	map_records(records, |branch| branch)
+}
+
+/// Read the record collections of all branches in the records directory in
+/// parallel, mapping each with `map` as soon as it is read so only the
+/// mapped values are kept in memory. The results are in the order of
+/// [walk_records]
+pub fn map_records<T, F>(records: &Path, map: F) -> Result<Vec<T>, MeowCoverageError>
+where
+	T: Send,
+	F: Fn(BranchRecords) -> T + Sync,
+{
+	let paths = branch_paths(records)?;
+	let next = AtomicUsize::new(0);
+	let workers =
+		std::thread::available_parallelism().map_or(1, NonZeroUsize::get).min(paths.len());
+
+	let read_branches = || -> Result<Vec<(usize, T)>, MeowCoverageError> {
+		let mut mapped = Vec::new();
+		loop {
+			let index = next.fetch_add(1, Ordering::Relaxed);
+			let Some(branch) = paths.get(index) else {
+				return Ok(mapped);
+			};
+			let collection = read_record_collection(&branch.path)?;
+			mapped.push((
+				index,
+				map(BranchRecords {
+					owner: branch.owner.clone(),
+					repo: branch.repo.clone(),
+					branch: branch.branch.clone(),
+					collection,
+				}),
+			));
+		}
+	};
+
+	let mut mapped = Vec::with_capacity(paths.len());
+	std::thread::scope(|scope| {
+		let workers = (0..workers).map(|_| scope.spawn(read_branches)).collect_vec();
+		for worker in workers {
+			match worker.join() {
+				Ok(result) => mapped.extend(result?),
+				Err(panic) => std::panic::resume_unwind(panic),
+			}
+		}
+		Ok::<_, MeowCoverageError>(())
+	})?;
+
+	mapped.sort_by_key(|(index, _)| *index);
+	Ok(mapped.into_iter().map(|(_, value)| value).collect())
+}
+
+/// List the record files of all branches in the records directory
+fn branch_paths(records: &Path) -> Result<Vec<BranchPath>, MeowCoverageError> {
+	let mut branches = Vec::new();
 
 	let records_dir = std::fs::read_dir(records)?;
 
@@ -68,28 +176,53 @@ fn try_collect_records(records: &Path) -> Result<[Vec<ReadmeCoverageEntry>; 6],
                     continue;
                 };
 
-				let record_collection: BranchCoverageRecordCollection =
-					serde_json::from_reader(std::fs::File::open(branch.path())?)?;
-
-				let idx = record_collection.team as usize;
-				if let Some(entry) = ReadmeCoverageEntry::from_collection(
-					owner_name,
-					repo_name,
-					branch_name,
-					record_collection,
-				) {
-					teams[idx].push(entry);
-				}
+				branches.push(BranchPath {
+					owner: String::from(owner_name),
+					repo: String::from(repo_name),
+					branch: String::from(branch_name),
+					path: branch.path(),
+				});
 			}
 		}
 	}
 
+	Ok(branches)
+}
+
+/// Try and collect records, flagging branches without a record for
+/// `stale_days` days as stale
+fn try_collect_records(
+	records: &Path,
+	stale_days: Option<u32>,
+) -> Result<[Vec<ReadmeCoverageEntry>; 6], MeowCoverageError> {
+	let mut teams: [Vec<ReadmeCoverageEntry>; 6] =
+		[Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+	let now = OffsetDateTime::now_utc();
+
+	let entries = map_records(records, |branch| {
+		let idx = branch.collection.team as usize;
+		let stale = stale_days.is_some_and(|days| branch.collection.is_stale(now, days));
+		let entry = ReadmeCoverageEntry::from_collection(
+			&branch.owner,
+			&branch.repo,
+			&branch.branch,
+			branch.collection,
+			stale,
+		);
+		(idx, entry)
+	})?;
+	for (idx, entry) in entries {
+		if let Some(entry) = entry {
+			teams[idx].push(entry);
+		}
+	}
+
 	Ok(teams)
 }
 
 /// Data needed for each table entry in the README
 #[derive(Debug, Clone, PartialEq, Eq)]
-struct ReadmeCoverageEntry {
+pub(super) struct ReadmeCoverageEntry {
 	/// Repo Owner
 	pub owner: String,
 	/// Repo Name
@@ -97,27 +230,32 @@ struct ReadmeCoverageEntry {
 	/// Repo Branch
 	pub branch: String,
 	/// Coverage
-	pub coverage: i16,
+	pub coverage: Percentage,
 	/// Last delta
-	pub last_delta: i16,
+	pub last_delta: Percentage,
 	/// 7 day delta
-	pub delta_7_days: i16,
+	pub delta_7_days: Percentage,
 	/// 30 day delta
-	pub delta_30_days: i16,
+	pub delta_30_days: Percentage,
 	/// 90 day delta
-	pub delta_90_days: i16,
+	pub delta_90_days: Percentage,
+	/// Number of instrumented lines of the latest record, if recorded
+	pub lines: Option<u64>,
 	/// Latest update date
 	pub last_update: OffsetDateTime,
+	/// Whether the branch has no recent record
+	pub stale: bool,
 }
 
 impl ReadmeCoverageEntry {
-	/// Build [Self] from an `owner`, `repo`, `branch`, and
-	/// [BranchCoverageRecordCollection]
+	/// Build [Self] from an `owner`, `repo`, `branch`,
+	/// [BranchCoverageRecordCollection], and whether it is stale
 	pub fn from_collection(
 		owner: &str,
 		repo: &str,
 		branch: &str,
 		record: BranchCoverageRecordCollection,
+		stale: bool,
 	) -> Option<Self> {
 		Some(Self {
 			owner: String::from(owner),
@@ -128,31 +266,167 @@ impl ReadmeCoverageEntry {
 			delta_7_days: record.delta_last_7_days()?,
 			delta_30_days: record.delta_last_30_days()?,
 			delta_90_days: record.delta_last_90_days()?,
+			lines: record.latest()?.lines,
 			last_update: OffsetDateTime::from_unix_timestamp(record.latest_timestamp()?).ok()?,
+			stale,
 		})
 	}
 }
 
-/// Builds the table for a team in the README
+/// Coverage and 30 day delta of a group of branches, weighted by their
+/// instrumented lines (unweighted if a branch has no line count recorded),
+/// returns [None] if there are no branches
+pub(super) fn aggregate<'a>(
+	branches: impl Iterator<Item = &'a ReadmeCoverageEntry> + Clone,
+) -> Option<(Percentage, Percentage)> {
+	let weighted = branches.clone().all(|entry| entry.lines.is_some());
+	let weight = |entry: &ReadmeCoverageEntry| match weighted {
+		true => entry.lines.unwrap_or_default() as f64,
+		false => 1_f64,
+	};
+
+	let total_weight = branches.clone().map(weight).sum::<f64>();
+	if total_weight <= 0_f64 {
+		return None;
+	}
+
+	let weighted_average = |value: fn(&ReadmeCoverageEntry) -> Percentage| {
+		Percentage::from_f64(
+			branches.clone().map(|entry| weight(entry) * value(entry).as_f64()).sum::<f64>()
+				/ total_weight,
+		)
+	};
+
+	Some((weighted_average(|entry| entry.coverage), weighted_average(|entry| entry.delta_30_days)))
+}
+
+/// Arrow showing the direction of a delta
+pub(super) fn trend_arrow(delta: Percentage) -> &'static str {
+	match delta.cmp(&Percentage::ZERO) {
+		std::cmp::Ordering::Greater => "↗",
+		std::cmp::Ordering::Less => "↘",
+		std::cmp::Ordering::Equal => "→",
+	}
+}
+
+/// Indicator of the health of a branch: red at or below the `threshold`,
+/// yellow if coverage dropped over the last 30 days, green otherwise
+pub(super) fn health_indicator(entry: &ReadmeCoverageEntry, threshold: Percentage) -> &'static str {
+	if entry.coverage <= threshold {
+		"🔴"
+	} else if entry.delta_30_days < Percentage::ZERO {
+		"🟡"
+	} else {
+		"🟢"
+	}
+}
+
+/// Sort the branches in the order configured for the README
+pub(super) fn sort_entries(branches: &mut [ReadmeCoverageEntry], sort: ReadmeSort) {
+	branches.sort_by(|a, b| {
+		let name = (&a.owner, &a.repo, &a.branch).cmp(&(&b.owner, &b.repo, &b.branch));
+		match sort {
+			ReadmeSort::Name => name,
+			ReadmeSort::Coverage => a.coverage.cmp(&b.coverage).then(name),
+			ReadmeSort::Drop => a.delta_30_days.cmp(&b.delta_30_days).then(name),
+		}
+	});
+}
+
+/// Builds the section listing the branches at or below the `threshold` with
+/// the percentages in `format`, empty if there are none
+fn build_attention_section(
+	coverage_repo_owner: &str,
+	coverage_repo: &str,
+	branches: &[ReadmeCoverageEntry],
+	threshold: Percentage,
+	format: PercentageFormat,
+) -> String {
+	if branches.is_empty() {
+		return String::new();
+	}
+
+	let list = branches
+		.iter()
+		.map(|entry| {
+			format!(
+				"- {} [{}/{} ({})]({}): {}% ({}% in 30 days)\n",
+				health_indicator(entry, threshold),
+				entry.owner,
+				entry.repo,
+				entry.branch,
+				make_report_link(
+					coverage_repo_owner,
+					coverage_repo,
+					&entry.owner,
+					&entry.repo,
+					&entry.branch
+				),
+				entry.coverage.display_with(format),
+				entry.delta_30_days.display_with(format)
+			)
+		})
+		.collect::<String>();
+
+	format!(
+		"## Needs Attention\n\n{} branches are at or below {}% coverage\n\n{}\n",
+		branches.len(),
+		threshold.display_with(format),
+		list
+	)
+}
+
+/// Builds the summary line of the aggregate coverage of a group of branches
+/// with the percentages in `format`
+fn build_aggregate_line<'a>(
+	branches: impl Iterator<Item = &'a ReadmeCoverageEntry> + Clone,
+	threshold: Percentage,
+	format: PercentageFormat,
+) -> String {
+	let Some((coverage, delta)) = aggregate(branches.clone()) else {
+		return String::new();
+	};
+	let below = branches.clone().filter(|entry| entry.coverage <= threshold).count();
+
+	format!(
+		"**Coverage: {}%** {} {}% in 30 days, {} of {} branches at or below {}%\n\n",
+		coverage.display_with(format),
+		trend_arrow(delta),
+		delta.display_with(format),
+		below,
+		branches.count(),
+		threshold.display_with(format)
+	)
+}
+
+/// Builds the table for a team in the README with the percentages in `format`
 fn build_team_readme(
 	coverage_repo_owner: &str,
 	coverage_repo: &str,
 	team: Team,
 	branches: &[ReadmeCoverageEntry],
+	threshold: Percentage,
+	format: PercentageFormat,
 ) -> String {
 	let count = branches.len();
+	let summary = build_aggregate_line(branches.iter(), threshold, format);
 
 	let table_entries = branches.iter().map(|entry| {
-        format!("| [{owner}/{repo} ({branch})](https://github.com/{owner}/{repo}/tree/{branch}) | {cov}% | [Report](https://github.com/{cov_owner}/{cov_repo}/blob/main/reports/{owner}/{repo}/{branch}.md) | {last_delta}%         | {delta7}%         | {delta30}%          | {delta90}%          | {time}   |\n",
+        format!("| [{owner}/{repo} ({branch})](https://github.com/{owner}/{repo}/tree/{branch}) | {health} {cov}% | [Report](https://github.com/{cov_owner}/{cov_repo}/blob/main/reports/{owner}/{repo}/{branch}.md) | {last_delta}%         | {delta7}%         | {delta30}%          | {delta90}%          | {time}{stale}   |\n",
             owner = entry.owner,
             repo = entry.repo,
             branch = entry.branch,
-            cov = PercentWrapper(entry.coverage),
-            last_delta = PercentWrapper(entry.last_delta),
-            delta7 = PercentWrapper(entry.delta_7_days),
-            delta30 = PercentWrapper(entry.delta_30_days),
-            delta90 = PercentWrapper(entry.delta_90_days),
+            health = health_indicator(entry, threshold),
+            cov = entry.coverage.display_with(format),
+            last_delta = entry.last_delta.display_with(format),
+            delta7 = entry.delta_7_days.display_with(format),
+            delta30 = entry.delta_30_days.display_with(format),
+            delta90 = entry.delta_90_days.display_with(format),
             time = entry.last_update,
+            stale = match entry.stale {
+                true => " ⚠️",
+                false => "",
+            },
             cov_owner = coverage_repo_owner,
             cov_repo = coverage_repo
         )
@@ -163,22 +437,50 @@ fn build_team_readme(
 
 Tracking coverage of {} branches of repositories in this group
 
-| Repository (Branch)                | Coverage  | Report         | Delta (Last) | Delta (7 Days) | Delta (30 Days) | Delta (90 Days) | Last Updated |
+{}| Repository (Branch)                | Coverage  | Report         | Delta (Last) | Delta (7 Days) | Delta (30 Days) | Delta (90 Days) | Last Updated |
 |------------------------------------|-----------|----------------|--------------|----------------|-----------------|-----------------|--------------|
 {}\n",
     team,
     count,
+    summary,
     table_entries
     )
 }
 
-/// Builds a new `README.md` into a string
+/// Builds a new `README.md` with the percentages in `format` into a string
 pub fn build_readme(
 	records: &Path,
 	coverage_repo_owner: &str,
 	coverage_repo: &str,
+	format: PercentageFormat,
 ) -> Result<String, MeowCoverageError> {
-	let team_records = try_collect_records(records)?;
+	let config = read_config(records)?;
+	let team_records = try_collect_records(records, config.stale_days)?;
+
+	Ok(build_readme_from_entries(team_records, &config, coverage_repo_owner, coverage_repo, format))
+}
+
+/// Builds a new `README.md` of the branches of every team with the
+/// percentages in `format` into a string
+pub(super) fn build_readme_from_entries(
+	mut team_records: [Vec<ReadmeCoverageEntry>; 6],
+	config: &TrackingConfig,
+	coverage_repo_owner: &str,
+	coverage_repo: &str,
+	format: PercentageFormat,
+) -> String {
+	let threshold = Percentage::from_f64(config.threshold);
+
+	for branches in &mut team_records {
+		sort_entries(branches, config.sort);
+	}
+	let mut attention = team_records
+		.iter()
+		.flatten()
+		.filter(|entry| entry.coverage <= threshold)
+		.cloned()
+		.collect::<Vec<_>>();
+	sort_entries(&mut attention, config.sort);
 
 	let total_count = team_records[Team::InstantMessaging as usize].len()
 		+ team_records[Team::Workflow as usize].len()
@@ -187,7 +489,7 @@ pub fn build_readme(
 		+ team_records[Team::Security as usize].len()
 		+ team_records[Team::Other as usize].len();
 
-	Ok(format!(
+	format!(
 		"\
 # Coverage Reports
 
@@ -195,7 +497,7 @@ For a description of this repository please [read here](./Description.md).
 
 Tracking coverage of {} branches of repositories
 
-## Teams
+{}{}## Teams
 
 - [Instant Messaging](#instant-messaging)
 - [Workflow](#workflow)
@@ -217,45 +519,161 @@ Tracking coverage of {} branches of repositories
 {other}
     ",
 		total_count,
+		build_aggregate_line(team_records.iter().flatten(), threshold, format),
+		build_attention_section(coverage_repo_owner, coverage_repo, &attention, threshold, format),
 		im = build_team_readme(
 			coverage_repo_owner,
 			coverage_repo,
 			Team::InstantMessaging,
-			&team_records[Team::InstantMessaging as usize]
+			&team_records[Team::InstantMessaging as usize],
+			threshold,
+			format
 		),
 		workflow = build_team_readme(
 			coverage_repo_owner,
 			coverage_repo,
 			Team::Workflow,
-			&team_records[Team::Workflow as usize]
+			&team_records[Team::Workflow as usize],
+			threshold,
+			format
 		),
 		infra = build_team_readme(
 			coverage_repo_owner,
 			coverage_repo,
 			Team::Infrastructure,
-			&team_records[Team::Infrastructure as usize]
+			&team_records[Team::Infrastructure as usize],
+			threshold,
+			format
 		),
 		product = build_team_readme(
 			coverage_repo_owner,
 			coverage_repo,
 			Team::Product,
-			&team_records[Team::Product as usize]
+			&team_records[Team::Product as usize],
+			threshold,
+			format
 		),
 		security = build_team_readme(
 			coverage_repo_owner,
 			coverage_repo,
 			Team::Security,
-			&team_records[Team::Security as usize]
+			&team_records[Team::Security as usize],
+			threshold,
+			format
 		),
 		other = build_team_readme(
 			coverage_repo_owner,
 			coverage_repo,
 			Team::Other,
-			&team_records[Team::Other as usize]
+			&team_records[Team::Other as usize],
+			threshold,
+			format
 		)
+	)
+}
+
+/// Branch the other branches of a repository are compared against: `main`,
+/// `master`, or else the best covered branch
+fn comparison_base(branches: &[ReadmeCoverageEntry]) -> Option<&ReadmeCoverageEntry> {
+	branches
+		.iter()
+		.find(|entry| entry.branch == "main")
+		.or_else(|| branches.iter().find(|entry| entry.branch == "master"))
+		.or_else(|| branches.iter().max_by_key(|entry| entry.coverage))
+}
+
+/// Builds the page comparing the coverage of the `branches` of a single
+/// repository, with the divergence of every branch from the
+/// [comparison_base], with the percentages in `format`
+pub(super) fn build_branch_comparison(
+	coverage_repo_owner: &str,
+	coverage_repo: &str,
+	branches: &[ReadmeCoverageEntry],
+	threshold: Percentage,
+	format: PercentageFormat,
+) -> Option<String> {
+	let base = comparison_base(branches)?;
+
+	let rows = branches
+		.iter()
+		.map(|entry| {
+			format!(
+				"| [{branch}](https://github.com/{owner}/{repo}/tree/{branch}) | {health} {cov}% | {divergence} | {delta30}% | {lines} | {time}{stale} | [Report]({report}) |\n",
+				owner = entry.owner,
+				repo = entry.repo,
+				branch = entry.branch,
+				health = health_indicator(entry, threshold),
+				cov = entry.coverage.display_with(format),
+				divergence = match entry.branch == base.branch {
+					true => String::from("base"),
+					false => format!("{}%", (entry.coverage - base.coverage).display_with(format)),
+				},
+				delta30 = entry.delta_30_days.display_with(format),
+				lines = entry.lines.map_or_else(|| String::from("-"), |lines| lines.to_string()),
+				time = entry.last_update,
+				stale = match entry.stale {
+					true => " ⚠️",
+					false => "",
+				},
+				report = make_report_link(
+					coverage_repo_owner,
+					coverage_repo,
+					&entry.owner,
+					&entry.repo,
+					&entry.branch
+				),
+			)
+		})
+		.collect::<String>();
+
+	Some(format!(
+		"\
+# [{owner}/{repo}](https://github.com/{owner}/{repo}/) Branches
+
+Comparing the coverage of {count} branches, divergence is relative to `{base}`
+
+| Branch | Coverage | Divergence | Delta (30 Days) | Lines | Last Updated | Report |
+|--------|----------|------------|-----------------|-------|--------------|--------|
+{rows}",
+		owner = base.owner,
+		repo = base.repo,
+		count = branches.len(),
+		base = base.branch,
+		rows = rows
 	))
 }
 
+/// Builds the branch comparison pages of every repository in the records
+/// directory with the percentages in `format`, as `(owner, repo, page)`
+pub fn build_branch_comparisons(
+	records: &Path,
+	coverage_repo_owner: &str,
+	coverage_repo: &str,
+	format: PercentageFormat,
+) -> Result<Vec<(String, String, String)>, MeowCoverageError> {
+	let config = read_config(records)?;
+	let threshold = Percentage::from_f64(config.threshold);
+
+	Ok(try_collect_records(records, config.stale_days)?
+		.into_iter()
+		.flatten()
+		.into_group_map_by(|entry| (entry.owner.clone(), entry.repo.clone()))
+		.into_iter()
+		.sorted_by(|(l, _), (r, _)| l.cmp(r))
+		.filter_map(|((owner, repo), mut branches)| {
+			sort_entries(&mut branches, config.sort);
+			let page = build_branch_comparison(
+				coverage_repo_owner,
+				coverage_repo,
+				&branches,
+				threshold,
+				format,
+			)?;
+			Some((owner, repo, page))
+		})
+		.collect())
+}
+
 /// Build a list of lines
 fn build_lines(
 	repo_owner: &str,
@@ -264,20 +682,7 @@ fn build_lines(
 	file_path: &str,
 	lines: &[u32],
 ) -> String {
-	itertools::intersperse(lines.iter().copied().fold(Vec::<(u32, u32)>::new(), |mut acc, val| {
-        match acc.last_mut() {
-            Some(last) => {
-                if last.1 == val - 1 {
-                    last.1 = val;
-                } else {
-                    acc.push((val, val));
-                }
-            },
-            None => acc.push((val, val))
-        }
-
-        acc
-    }).into_iter().map(|(start_line, end_line)| {
+	itertools::intersperse(gather_lines(lines).into_iter().map(|(start_line, end_line)| {
         match start_line == end_line {
             true => Cow::Owned(format!("[{line}](https://github.com/{repo_owner}/{repo}/blob/{branch}/{file_path}#L{line})", repo_owner = repo_owner, repo = repo, branch = branch, file_path = file_path, line = start_line)),
             false => Cow::Owned(format!("[{start_line}-{end_line}](https://github.com/{repo_owner}/{repo}/blob/{branch}/{file_path}#L{start_line}-L{end_line})", repo_owner = repo_owner, repo = repo, branch = branch, file_path = file_path, start_line = start_line, end_line = end_line)),
@@ -286,12 +691,202 @@ fn build_lines(
     .collect()
 }
 
-/// Builds individual coverage report markdown files to a string
+/// Build the table of the records of a branch with the percentages in
+/// `format`, newest first, linking the commit of each record
+fn build_history(
+	target_repo_owner: &str,
+	target_repo: &str,
+	record_collection: &BranchCoverageRecordCollection,
+	format: PercentageFormat,
+) -> String {
+	let records = record_collection
+		.records
+		.iter()
+		.sorted_by(|l, r| Ord::cmp(&r.timestamp, &l.timestamp))
+		.collect::<Vec<_>>();
+
+	records
+		.iter()
+		.enumerate()
+		.map(|(idx, record)| {
+			let commit = match &record.commit {
+				Some(commit) => format!(
+					"[`{short}`](https://github.com/{repo_owner}/{repo}/commit/{commit})",
+					short = commit.get(..7).unwrap_or(commit),
+					repo_owner = target_repo_owner,
+					repo = target_repo,
+					commit = commit
+				),
+				None => String::from("Unknown"),
+			};
+			let delta = match records.get(idx + 1) {
+				Some(previous) => {
+					format!("{}%", (record.percentage - previous.percentage).display_with(format))
+				}
+				None => String::from("-"),
+			};
+			let time = OffsetDateTime::from_unix_timestamp(record.timestamp)
+				.map_or_else(|_| record.timestamp.to_string(), |time| time.to_string());
+
+			format!(
+				"| {} | {} | {}% | {} |\n",
+				time,
+				commit,
+				record.percentage.display_with(format),
+				delta
+			)
+		})
+		.fold(
+			String::from(
+				"| Date | Commit | Coverage | Delta |\n|------|--------|----------|-------|\n",
+			),
+			|l, r| l + r.as_str(),
+		)
+}
+
+/// Number of files listed as the most regressed in a branch report
+const REGRESSED_FILES_LIMIT: usize = 10;
+
+/// Builds the table of the files whose coverage regressed the most over the
+/// last 30 days with the deltas in `format`, empty if there are none (or no
+/// file history is recorded)
+fn build_regressed_files(
+	target_repo_owner: &str,
+	target_repo: &str,
+	branch: &str,
+	record_collection: &BranchCoverageRecordCollection,
+	format: PercentageFormat,
+) -> String {
+	let regressed = record_collection.regressed_files(time::Duration::days(30));
+	if regressed.is_empty() {
+		return String::new();
+	}
+
+	regressed
+		.into_iter()
+		.take(REGRESSED_FILES_LIMIT)
+		.map(|(file_name, delta)| {
+			format!(
+				"| [{file_name}](https://github.com/{repo_owner}/{repo}/blob/{branch}/{file_name}) | {delta}% |\n",
+				file_name = file_name,
+				repo_owner = target_repo_owner,
+				repo = target_repo,
+				branch = branch,
+				delta = delta.display_with(format)
+			)
+		})
+		.fold(
+			String::from("\n### Most Regressed Files (30 Days)\n\n| File Name | Delta |\n|-----------|-------|\n"),
+			|l, r| l + r.as_str(),
+		)
+}
+
+/// Builds the SVG heatmap of the files of the latest record of a branch with
+/// the percentages of its tooltips in `format`, [None] if the latest record
+/// has no files
+#[must_use]
+pub fn build_heatmap(
+	record_collection: &BranchCoverageRecordCollection,
+	format: PercentageFormat,
+) -> Option<String> {
+	let heatmap = Heatmap::from_record(record_collection.latest()?);
+	match heatmap.is_empty() {
+		true => None,
+		false => heatmap.render_svg(HEATMAP_SIZE.0, HEATMAP_SIZE.1, format).ok(),
+	}
+}
+
+/// Shields.io endpoint badge, see <https://shields.io/badges/endpoint-badge>
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EndpointBadge {
+	/// Version of the endpoint schema, always 1
+	schema_version: u8,
+	/// Left hand side text
+	label: &'static str,
+	/// Right hand side text
+	message: String,
+	/// Colour of the right hand side
+	color: &'static str,
+}
+
+/// Builds the shields.io endpoint JSON of the coverage badge of a branch,
+/// coloured like the health indicator of the README (red at or below the
+/// `threshold`, yellow if coverage dropped over the last 30 days, green
+/// otherwise) showing the coverage in `format`, [None] if the branch has no
+/// records
+#[must_use]
+pub fn build_badge(
+	record_collection: &BranchCoverageRecordCollection,
+	threshold: Percentage,
+	format: PercentageFormat,
+) -> Option<String> {
+	let coverage = record_collection.latest()?.percentage;
+	let color = if coverage <= threshold {
+		"red"
+	} else if record_collection.delta_last_30_days().unwrap_or_default() < Percentage::ZERO {
+		"yellow"
+	} else {
+		"brightgreen"
+	};
+
+	serde_json::to_string(&EndpointBadge {
+		schema_version: 1,
+		label: "coverage",
+		message: format!("{}%", coverage.display_with(format)),
+		color,
+	})
+	.ok()
+}
+
+/// Builds the link to the heatmap stored next to the report of a branch,
+/// empty if the branch has no heatmap
+fn build_heatmap_link(branch: &str, record_collection: &BranchCoverageRecordCollection) -> String {
+	let has_files =
+		record_collection.latest().is_some_and(|latest| !Heatmap::from_record(latest).is_empty());
+	match has_files {
+		true => format!(
+			"\n![Coverage heatmap](./{}.svg)\n",
+			branch.rsplit('/').next().unwrap_or(branch)
+		),
+		false => String::new(),
+	}
+}
+
+/// Builds the table of the latest coverage of every flag of a branch with the
+/// percentages in `format`, empty if no uploads were flagged
+fn build_flags(
+	record_collection: &BranchCoverageRecordCollection,
+	format: PercentageFormat,
+) -> String {
+	record_collection
+		.flags
+		.iter()
+		.filter_map(|(flag, collection)| {
+			Some(format!(
+				"| `{}` | {}% | {}% |\n",
+				flag,
+				collection.latest()?.percentage.display_with(format),
+				collection.delta_last_7_days().unwrap_or_default().display_with(format)
+			))
+		})
+		.fold(String::new(), |table, row| match table.is_empty() {
+			true => format!(
+				"\n### Flags\n\nThe coverage above merges the latest upload of every flag.\n\n| Flag | Coverage | 7 Day Delta |\n|------|----------|-------------|\n{}",
+				row
+			),
+			false => table + row.as_str(),
+		})
+}
+
+/// Builds individual coverage report markdown files with the percentages in
+/// `format` to a string
 pub fn build_coverage_report(
 	target_repo_owner: &str,
 	target_repo: &str,
 	branch: &str,
 	record_collection: &BranchCoverageRecordCollection,
+	format: PercentageFormat,
 ) -> Option<String> {
 	let latest = record_collection.latest()?;
 	let time = OffsetDateTime::from_unix_timestamp(latest.timestamp).ok()?;
@@ -299,7 +894,7 @@ pub fn build_coverage_report(
 	let file_cov = latest.files.iter().map(|map| {
             map
                 .iter()
-                .map(|(file_name, value)| format!("| [{file_name}](https://github.com/{repo_owner}/{repo}/blob/{branch}/{file_name}) | {cov}% | {lines} |\n", file_name = file_name, repo_owner = target_repo_owner, repo = target_repo, branch = branch, cov = PercentWrapper(value.percentage), lines = build_lines(target_repo_owner, target_repo, branch, file_name, &value.untested_lines))).collect::<String>()
+                .map(|(file_name, value)| format!("| [{file_name}](https://github.com/{repo_owner}/{repo}/blob/{branch}/{file_name}) | {cov}% | {lines} |\n", file_name = file_name, repo_owner = target_repo_owner, repo = target_repo, branch = branch, cov = value.percentage.display_with(format), lines = build_lines(target_repo_owner, target_repo, branch, file_name, &value.untested_lines))).collect::<String>()
     }).fold(String::from("| File Name | Coverage  | Untested Lines  |\n|-----------|-----------|-----------------|\n"), |l, r| l + r.as_ref());
 
 	Some(format!(
@@ -315,17 +910,297 @@ pub fn build_coverage_report(
 #### 7 Day Delta: {delta7}%
 #### 30 Day Delta: {delta30}%
 #### 90 Day Delta: {delta90}%
+{flags}{heatmap}
+{file_cov}
+{regressed}
+### Coverage by Commit
 
-{file_cov}\n",
+{history}\n",
 		repo_owner = target_repo_owner,
 		repo_name = target_repo,
 		branch_name = branch,
 		team = record_collection.team,
-		coverage = PercentWrapper(latest.percentage),
+		coverage = latest.percentage.display_with(format),
 		timestamp = time,
-		last_delta = record_collection.last_delta()?,
-		delta7 = record_collection.delta_last_7_days()?,
-		delta30 = record_collection.delta_last_30_days()?,
-		delta90 = record_collection.delta_last_90_days()?
+		last_delta = record_collection.last_delta()?.display_with(format),
+		delta7 = record_collection.delta_last_7_days()?.display_with(format),
+		delta30 = record_collection.delta_last_30_days()?.display_with(format),
+		delta90 = record_collection.delta_last_90_days()?.display_with(format),
+		flags = build_flags(record_collection, format),
+		heatmap = build_heatmap_link(branch, record_collection),
+		regressed = build_regressed_files(
+			target_repo_owner,
+			target_repo,
+			branch,
+			record_collection,
+			format
+		),
+		history = build_history(target_repo_owner, target_repo, record_collection, format)
 	))
 }
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+
+	use itertools::Itertools;
+	use time::OffsetDateTime;
+
+	use super::{
+		aggregate, build_aggregate_line, build_attention_section, build_badge,
+		build_branch_comparison, build_readme, build_readme_from_entries, health_indicator,
+		map_records, sort_entries, walk_records, ReadmeCoverageEntry,
+	};
+	use crate::{
+		percentage::{Percentage, PercentageFormat, Rounding},
+		tracking::{
+			BranchCoverageRecord, BranchCoverageRecordCollection, ReadmeSort, Team, TrackingConfig,
+			RECORD_FORMAT_VERSION,
+		},
+	};
+
+	#[test]
+	fn test_walk_records() {
+		let records = std::env::temp_dir().join("meow-coverage-test-walk-records");
+		let _ = std::fs::remove_dir_all(&records);
+		std::fs::create_dir_all(records.join("famedly/meow")).unwrap();
+		let collection = BranchCoverageRecordCollection {
+			team: Team::Workflow,
+			records: vec![BranchCoverageRecord {
+				timestamp: 1_700_000_000,
+				percentage: Percentage::from_basis_points(5000),
+				commit: Some(String::from("abcdef")),
+				lines: None,
+				files: None,
+				files_gz: None,
+				labels: BTreeMap::new(),
+				metadata: None,
+			}],
+			retention: None,
+			flags: BTreeMap::new(),
+			format_version: RECORD_FORMAT_VERSION,
+		};
+		std::fs::write(
+			records.join("famedly/meow/main.meowcov.json"),
+			serde_json::to_vec(&collection).unwrap(),
+		)
+		.unwrap();
+		std::fs::write(records.join("famedly/meow/notes.txt"), "").unwrap();
+
+		let branches = walk_records(&records).unwrap();
+		assert_eq!(branches.len(), 1);
+		assert_eq!(
+			(branches[0].owner.as_str(), branches[0].repo.as_str(), branches[0].branch.as_str()),
+			("famedly", "meow", "main")
+		);
+		assert_eq!(branches[0].collection, collection);
+
+		let format = PercentageFormat::default();
+		let readme = build_readme(&records, "famedly", "coverage", format).unwrap();
+		assert!(readme.contains("Tracking coverage of 1 branches of repositories\n"));
+		assert!(readme.contains("## Needs Attention\n\n1 branches are at or below 80.00% coverage"));
+		assert!(readme.contains(" ⚠️   |"));
+
+		let skeleton = build_readme_from_entries(
+			Default::default(),
+			&TrackingConfig::default(),
+			"famedly",
+			"coverage",
+			format,
+		);
+		assert!(skeleton.contains("Tracking coverage of 0 branches of repositories\n\n## Teams"));
+		assert!(!skeleton.contains("Needs Attention"));
+	}
+
+	#[test]
+	fn test_map_records() {
+		let records = std::env::temp_dir().join("meow-coverage-test-map-records");
+		let _ = std::fs::remove_dir_all(&records);
+		std::fs::create_dir_all(records.join("famedly/meow")).unwrap();
+		let collection =
+			serde_json::to_vec(&BranchCoverageRecordCollection::new(Team::Other)).unwrap();
+		for branch in 0..40 {
+			std::fs::write(
+				records.join(format!("famedly/meow/branch-{}.meowcov.json", branch)),
+				&collection,
+			)
+			.unwrap();
+		}
+
+		let branches = map_records(&records, |branch| branch.branch).unwrap();
+		assert_eq!(
+			branches,
+			walk_records(&records).unwrap().into_iter().map(|branch| branch.branch).collect_vec()
+		);
+		assert_eq!(
+			branches.iter().cloned().sorted().collect_vec(),
+			(0..40).map(|branch| format!("branch-{}", branch)).sorted().collect_vec()
+		);
+
+		std::fs::write(records.join("famedly/meow/broken.meowcov.json"), "{").unwrap();
+		assert!(map_records(&records, |branch| branch.branch).is_err());
+	}
+
+	#[test]
+	fn test_aggregate() {
+		let entry = |coverage, delta_30_days, lines| ReadmeCoverageEntry {
+			owner: String::from("famedly"),
+			repo: String::from("meow"),
+			branch: String::from("main"),
+			coverage: Percentage::from_basis_points(coverage),
+			last_delta: Percentage::ZERO,
+			delta_7_days: Percentage::ZERO,
+			delta_30_days: Percentage::from_basis_points(delta_30_days),
+			delta_90_days: Percentage::ZERO,
+			lines,
+			last_update: OffsetDateTime::UNIX_EPOCH,
+			stale: false,
+		};
+
+		let weighted = [entry(9000, 100, Some(300)), entry(5000, -300, Some(100))];
+		let threshold = Percentage::from_basis_points(8000);
+		let format = PercentageFormat::default();
+		assert_eq!(aggregate(weighted.iter()), Some((threshold, Percentage::ZERO)));
+		assert_eq!(
+			build_aggregate_line(weighted.iter(), threshold, format),
+			"**Coverage: 80.00%** → 0.00% in 30 days, 1 of 2 branches at or below 80.00%\n\n"
+		);
+
+		let unweighted = [entry(9000, 100, Some(300)), entry(5000, 300, None)];
+		assert_eq!(
+			aggregate(unweighted.iter()),
+			Some((Percentage::from_basis_points(7000), Percentage::from_basis_points(200)))
+		);
+
+		assert_eq!(aggregate([].iter()), None);
+		assert_eq!(build_aggregate_line([].iter(), threshold, format), "");
+	}
+
+	#[test]
+	fn test_branch_comparison() {
+		let entry = |branch: &str, coverage, lines| ReadmeCoverageEntry {
+			owner: String::from("famedly"),
+			repo: String::from("meow"),
+			branch: String::from(branch),
+			coverage: Percentage::from_basis_points(coverage),
+			last_delta: Percentage::ZERO,
+			delta_7_days: Percentage::ZERO,
+			delta_30_days: Percentage::ZERO,
+			delta_90_days: Percentage::ZERO,
+			lines,
+			last_update: OffsetDateTime::UNIX_EPOCH,
+			stale: false,
+		};
+		let threshold = Percentage::from_basis_points(8000);
+		let format = PercentageFormat::default();
+
+		let page = build_branch_comparison(
+			"famedly",
+			"coverage",
+			&[entry("main", 9000, Some(100)), entry("release/1.0", 8500, None)],
+			threshold,
+			format,
+		)
+		.unwrap();
+		assert!(page.starts_with("# [famedly/meow](https://github.com/famedly/meow/) Branches\n\nComparing the coverage of 2 branches, divergence is relative to `main`\n"));
+		assert!(page.contains("| [main](https://github.com/famedly/meow/tree/main) | 🟢 90.00% | base | 0.00% | 100 |"));
+		assert!(page.contains("| [release/1.0](https://github.com/famedly/meow/tree/release/1.0) | 🟢 85.00% | -5.00% | 0.00% | - |"));
+		assert!(page.contains("[Report](https://github.com/famedly/coverage/blob/main/reports/famedly/meow/release/1.0.md)"));
+
+		let page = build_branch_comparison(
+			"famedly",
+			"coverage",
+			&[entry("develop", 7000, None), entry("feature", 7500, None)],
+			threshold,
+			format,
+		)
+		.unwrap();
+		assert!(page.contains("divergence is relative to `feature`"));
+		assert!(page.contains("| 🔴 70.00% | -5.00% |"));
+
+		assert_eq!(build_branch_comparison("famedly", "coverage", &[], threshold, format), None);
+	}
+
+	#[test]
+	fn test_sort_and_health() {
+		let entry = |branch: &str, coverage, delta_30_days| ReadmeCoverageEntry {
+			owner: String::from("famedly"),
+			repo: String::from("meow"),
+			branch: String::from(branch),
+			coverage: Percentage::from_basis_points(coverage),
+			last_delta: Percentage::ZERO,
+			delta_7_days: Percentage::ZERO,
+			delta_30_days: Percentage::from_basis_points(delta_30_days),
+			delta_90_days: Percentage::ZERO,
+			lines: None,
+			last_update: OffsetDateTime::UNIX_EPOCH,
+			stale: false,
+		};
+		let mut branches = vec![entry("c", 9000, -500), entry("a", 7000, 0), entry("b", 9500, 100)];
+		let order = |branches: &[ReadmeCoverageEntry]| {
+			branches.iter().map(|entry| entry.branch.clone()).collect::<Vec<_>>()
+		};
+
+		sort_entries(&mut branches, ReadmeSort::Name);
+		assert_eq!(order(&branches), ["a", "b", "c"]);
+		sort_entries(&mut branches, ReadmeSort::Coverage);
+		assert_eq!(order(&branches), ["a", "c", "b"]);
+		sort_entries(&mut branches, ReadmeSort::Drop);
+		assert_eq!(order(&branches), ["c", "a", "b"]);
+
+		let threshold = Percentage::from_basis_points(8000);
+		assert_eq!(health_indicator(&branches[0], threshold), "🟡");
+		assert_eq!(health_indicator(&branches[1], threshold), "🔴");
+		assert_eq!(health_indicator(&branches[2], threshold), "🟢");
+
+		let format = PercentageFormat::default();
+		assert_eq!(build_attention_section("famedly", "coverage", &[], threshold, format), "");
+		assert_eq!(
+			build_attention_section("famedly", "coverage", &branches[1..2], threshold, format),
+			"## Needs Attention\n\n1 branches are at or below 80.00% coverage\n\n- 🔴 [famedly/meow (a)](https://github.com/famedly/coverage/blob/main/reports/famedly/meow/a.md): 70.00% (0.00% in 30 days)\n\n"
+		);
+	}
+
+	#[test]
+	fn test_build_badge() {
+		let record = |timestamp, basis_points| BranchCoverageRecord {
+			timestamp,
+			percentage: Percentage::from_basis_points(basis_points),
+			commit: None,
+			lines: None,
+			files: None,
+			files_gz: None,
+			labels: BTreeMap::new(),
+			metadata: None,
+		};
+		let mut collection = BranchCoverageRecordCollection::new(Team::Other);
+		let threshold = Percentage::from_basis_points(8000);
+		let format = PercentageFormat::default();
+		assert_eq!(build_badge(&collection, threshold, format), None);
+
+		collection.records = vec![record(0, 8500)];
+		assert_eq!(
+			build_badge(&collection, threshold, format).unwrap(),
+			r#"{"schemaVersion":1,"label":"coverage","message":"85.00%","color":"brightgreen"}"#
+		);
+		collection.records.push(record(86_400, 8250));
+		assert_eq!(
+			build_badge(&collection, threshold, format).unwrap(),
+			r#"{"schemaVersion":1,"label":"coverage","message":"82.50%","color":"yellow"}"#
+		);
+		assert_eq!(
+			build_badge(
+				&collection,
+				threshold,
+				PercentageFormat { precision: 0, rounding: Rounding::Down }
+			)
+			.unwrap(),
+			r#"{"schemaVersion":1,"label":"coverage","message":"82%","color":"yellow"}"#
+		);
+		collection.records.push(record(2 * 86_400, 8000));
+		assert_eq!(
+			build_badge(&collection, threshold, format).unwrap(),
+			r#"{"schemaVersion":1,"label":"coverage","message":"80.00%","color":"red"}"#
+		);
+	}
+}